@@ -0,0 +1,102 @@
+use cookie_store::{Cookie, CookieStore};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use url::Url;
+
+const DOMAINS: usize = 50;
+const PATHS: usize = 4;
+const NAMES: usize = 5;
+
+fn url(s: &str) -> Url {
+    Url::parse(s).expect("unable to parse bench url")
+}
+
+/// The `Set-Cookie` values (and the URL each was received from) used to populate a store of
+/// `DOMAINS * PATHS * NAMES` cookies
+fn cookies() -> Vec<(String, Url)> {
+    let mut cookies = Vec::with_capacity(DOMAINS * PATHS * NAMES);
+    for d in 0..DOMAINS {
+        for p in 0..PATHS {
+            for n in 0..NAMES {
+                cookies.push((
+                    format!(
+                        "cookie{}=value{}; Domain=example{}.com; Path=/path{}; Max-Age=3600",
+                        n, n, d, p
+                    ),
+                    url(&format!("http://www.example{}.com/path{}/index.html", d, p)),
+                ));
+            }
+        }
+    }
+    cookies
+}
+
+fn populated_store() -> CookieStore {
+    let mut store = CookieStore::default();
+    for (cookie, url) in cookies() {
+        store
+            .parse(&cookie, &url)
+            .expect("unable to insert bench cookie");
+    }
+    store
+}
+
+fn insert(c: &mut Criterion) {
+    let cookies = cookies()
+        .into_iter()
+        .map(|(cookie, url)| {
+            let cookie = Cookie::parse(cookie, &url).expect("unable to parse bench cookie");
+            (cookie, url)
+        })
+        .collect::<Vec<_>>();
+    c.bench_function("insert", |b| {
+        b.iter_batched(
+            || (CookieStore::default(), cookies.clone()),
+            |(mut store, cookies)| {
+                for (cookie, url) in cookies {
+                    let _ = store.insert(cookie, &url);
+                }
+                store
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn matches(c: &mut Criterion) {
+    let store = populated_store();
+    let hit = url("http://www.example25.com/path2/foo/bar");
+    let miss = url("http://www.example.org/path2/foo/bar");
+    c.bench_function("matches (hit)", |b| {
+        b.iter(|| store.matches(black_box(&hit)).len())
+    });
+    c.bench_function("matches (miss)", |b| {
+        b.iter(|| store.matches(black_box(&miss)).len())
+    });
+}
+
+#[cfg(feature = "serde_json")]
+fn save_load(c: &mut Criterion) {
+    let store = populated_store();
+    let mut saved = Vec::new();
+    cookie_store::serde::json::save(&store, &mut saved).expect("unable to save bench store");
+    c.bench_function("save (json)", |b| {
+        b.iter(|| {
+            let mut writer = Vec::with_capacity(saved.len());
+            cookie_store::serde::json::save(black_box(&store), &mut writer)
+                .expect("unable to save bench store");
+            writer
+        })
+    });
+    c.bench_function("load (json)", |b| {
+        b.iter(|| {
+            cookie_store::serde::json::load(black_box(&saved[..]))
+                .expect("unable to load bench store")
+        })
+    });
+}
+
+#[cfg(not(feature = "serde_json"))]
+fn save_load(_: &mut Criterion) {}
+
+criterion_group!(benches, insert, matches, save_load);
+criterion_main!(benches);
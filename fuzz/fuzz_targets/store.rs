@@ -0,0 +1,53 @@
+//! Exercises `CookieStore::insert` and `CookieStore::matches` with arbitrary cookies and
+//! request-uris, and checks that the store round-trips through JSON serialization.
+//!
+//! Run with `cargo fuzz run store` from the repository root.
+#![no_main]
+
+use arbitrary::Arbitrary;
+use cookie_store::{Cookie, CookieStore};
+use libfuzzer_sys::fuzz_target;
+use url::Url;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    /// Cookies to insert, each with the scheme and host of the request-uri it is received from
+    inserts: Vec<(Cookie<'static>, bool, String)>,
+    /// `Set-Cookie` headers to parse and insert, with the request-uri they are received from
+    set_cookies: Vec<(String, String)>,
+    /// Request-uris to match against the store
+    requests: Vec<String>,
+}
+
+fuzz_target!(|input: Input| {
+    let mut store = CookieStore::default();
+    for (cookie, https, host) in input.inserts {
+        let scheme = if https { "https" } else { "http" };
+        let host = if host.is_empty() {
+            String::from(&cookie.domain)
+        } else {
+            host
+        };
+        if let Ok(url) = Url::parse(&format!("{}://{}{}", scheme, host, &*cookie.path)) {
+            let _ = store.insert(cookie, &url);
+        }
+    }
+    for (set_cookie, url) in input.set_cookies {
+        if let Ok(url) = Url::parse(&url) {
+            let _ = store.parse(&set_cookie, &url);
+        }
+    }
+    for url in input.requests {
+        if let Ok(url) = Url::parse(&url) {
+            for cookie in store.matches(&url) {
+                assert!(cookie.domain.matches(&url));
+                assert!(cookie.path.matches(&url));
+            }
+        }
+    }
+
+    let mut json = Vec::new();
+    cookie_store::serde::json::save_incl_expired_and_nonpersistent(&store, &mut json).unwrap();
+    let loaded = cookie_store::serde::json::load_all(&json[..]).unwrap();
+    assert_eq!(store.len(), loaded.len());
+});
@@ -0,0 +1,120 @@
+//! Export of cookies with their names and values anonymized, for analysis of cookie usage without
+//! collecting the (potentially sensitive) contents of cookies
+
+use hmac::{Hmac, Mac};
+#[cfg(feature = "serde")]
+use serde_derive::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::cookie::Cookie;
+use crate::cookie_domain::CookieDomain;
+use crate::cookie_expiration::CookieExpiration;
+use crate::cookie_store::CookieStore;
+
+/// A `Cookie` with its name and value replaced by keyed digests, as exported by
+/// [`CookieStore::export_anonymized`]. All other attributes of the cookie are preserved, so that
+/// e.g. the domains setting cookies, or the distribution of cookie sizes and lifetimes, may be
+/// analyzed; as the digests are deterministic for a given key, cookies sharing a name or value may
+/// also be correlated.
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AnonymizedCookie {
+    /// The hex-encoded HMAC-SHA256 digest of the cookie name
+    pub name: String,
+    /// The hex-encoded HMAC-SHA256 digest of the cookie value
+    pub value: String,
+    /// The length in bytes of the cookie name
+    pub name_len: usize,
+    /// The length in bytes of the cookie value
+    pub value_len: usize,
+    /// The domain of the cookie
+    pub domain: CookieDomain,
+    /// The path of the cookie
+    pub path: String,
+    /// The expiration of the cookie
+    pub expires: CookieExpiration,
+    /// Whether the cookie has the `Secure` attribute
+    pub secure: bool,
+    /// Whether the cookie has the `HttpOnly` attribute
+    pub http_only: bool,
+}
+
+/// The hex-encoded HMAC-SHA256 digest of `data` under `key`
+fn digest(key: &[u8], data: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl AnonymizedCookie {
+    /// Anonymize `cookie`, with digests keyed by `key`
+    pub fn new(cookie: &Cookie<'_>, key: &[u8]) -> AnonymizedCookie {
+        AnonymizedCookie {
+            name: digest(key, cookie.name()),
+            value: digest(key, cookie.value()),
+            name_len: cookie.name().len(),
+            value_len: cookie.value().len(),
+            domain: cookie.domain.clone(),
+            path: String::from(&cookie.path),
+            expires: cookie.expires.clone(),
+            secure: cookie.secure().unwrap_or(false),
+            http_only: cookie.http_only().unwrap_or(false),
+        }
+    }
+}
+
+impl CookieStore {
+    /// Export the __unexpired__ cookies in the store with their names and values replaced by
+    /// HMAC-SHA256 digests keyed by `key`. The same `key` should be used across exports to be
+    /// correlated (e.g. from a fleet of clients), and should be kept secret, as low-entropy names
+    /// and values may otherwise be recovered by brute force.
+    pub fn export_anonymized(&self, key: &[u8]) -> Vec<AnonymizedCookie> {
+        self.iter_unexpired()
+            .map(|cookie| AnonymizedCookie::new(cookie, key))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cookie_store::CookieStore;
+    use crate::test_util;
+
+    #[test]
+    fn export_anonymized() {
+        let url = test_util::url("https://www.example.com/foo/bar");
+        let mut store = CookieStore::default();
+        store
+            .parse(
+                "session=secret; Domain=example.com; Path=/; Secure; HttpOnly",
+                &url,
+            )
+            .unwrap();
+        store.parse("other=secret; Max-Age=3600", &url).unwrap();
+
+        let mut exported = store.export_anonymized(b"key");
+        exported.sort_by_key(|c| c.name_len);
+        assert_eq!(2, exported.len());
+        let (other, session) = (&exported[0], &exported[1]);
+        assert_eq!(64, session.name.len());
+        assert!(session.name.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(session.name, other.name);
+        assert_eq!(session.value, other.value);
+        assert!(!session.value.contains("secret"));
+        assert_eq!((7, 6), (session.name_len, session.value_len));
+        assert_eq!(Some("example.com"), session.domain.as_cow().as_deref());
+        assert_eq!("/", session.path);
+        assert_eq!("/foo", other.path);
+        assert!(session.secure && session.http_only);
+        assert!(!other.secure && !other.http_only);
+        assert!(other.expires.expires_by(&test_util::in_days(1)));
+
+        // digests depend on the key
+        let rekeyed = store.export_anonymized(b"other key");
+        assert!(rekeyed.iter().all(|c| c.value != session.value));
+    }
+}
@@ -0,0 +1,180 @@
+//! A [`CookieStore`] shared between async tasks behind an
+//! [async-lock](https://crates.io/crates/async-lock) `RwLock`
+
+use async_lock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use url::Url;
+
+use crate::cookie::Cookie;
+use crate::cookie_store::{CookieStore, InsertResult, StoreResult};
+
+/// A [`CookieStore`] shared between async tasks, which may be locked without blocking the
+/// executor. Unlike the tokio-based APIs of the crate (e.g. `CookieStoreHandle::spawn_gc`), the
+/// lock is independent of any async runtime, and so may be used from e.g. async-std or smol.
+/// Requires feature `async-lock`.
+///
+/// Readers may hold the lock concurrently; as with any lock, guards should not be held across
+/// long-running `.await` points, as writers (e.g. storing the cookies of a response) would wait.
+#[derive(Debug, Default)]
+pub struct AsyncCookieStore {
+    cookie_store: RwLock<CookieStore>,
+}
+
+impl AsyncCookieStore {
+    /// Share `cookie_store`
+    pub fn new(cookie_store: CookieStore) -> AsyncCookieStore {
+        AsyncCookieStore {
+            cookie_store: RwLock::new(cookie_store),
+        }
+    }
+
+    /// Lock the store for shared access
+    pub async fn read(&self) -> RwLockReadGuard<'_, CookieStore> {
+        self.cookie_store.read().await
+    }
+
+    /// Lock the store for exclusive access
+    pub async fn write(&self) -> RwLockWriteGuard<'_, CookieStore> {
+        self.cookie_store.write().await
+    }
+
+    /// Unwrap the shared store
+    pub fn into_inner(self) -> CookieStore {
+        self.cookie_store.into_inner()
+    }
+
+    /// Insert the `cookie::Cookie` (from the `cookie` crate) `cookie`, received from
+    /// `request_url`, into the store; see [`CookieStore::insert_raw`].
+    pub async fn insert_raw(
+        &self,
+        cookie: &crate::RawCookie<'_>,
+        request_url: &Url,
+    ) -> InsertResult {
+        self.write().await.insert_raw(cookie, request_url)
+    }
+
+    /// Store the `cookies` received from `url`; see [`CookieStore::store_response_cookies`].
+    pub async fn store_response_cookies<I>(&self, cookies: I, url: &Url)
+    where
+        I: Iterator<Item = crate::RawCookie<'static>>,
+    {
+        self.write().await.store_response_cookies(cookies, url)
+    }
+
+    /// Copies of the cookies matching `request_url`; see [`CookieStore::matches`].
+    pub async fn matches(&self, request_url: &Url) -> Vec<Cookie<'static>> {
+        self.read()
+            .await
+            .matches(request_url)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Remove all __expired__ cookies from the store, returning the number removed; see
+    /// [`CookieStore::purge_expired`].
+    pub async fn purge_expired(&self) -> usize {
+        self.write().await.purge_expired()
+    }
+
+    /// Persist the store by running `save` against it, e.g. with one of the [`crate::serde`]
+    /// `save` functions. The store is locked for shared access while `save` runs.
+    pub async fn save<F>(&self, save: F) -> StoreResult<()>
+    where
+        F: FnOnce(&CookieStore) -> StoreResult<()>,
+    {
+        save(&*self.read().await)
+    }
+}
+
+impl From<CookieStore> for AsyncCookieStore {
+    fn from(cookie_store: CookieStore) -> Self {
+        AsyncCookieStore::new(cookie_store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake};
+    use std::thread::{self, Thread};
+
+    use super::AsyncCookieStore;
+    use crate::cookie_store::{CookieStore, StoreAction};
+    use crate::test_util;
+
+    /// A minimal executor, polling `future` on the current thread until it completes
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct Unpark(Thread);
+
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Arc::new(Unpark(thread::current())).into();
+        let mut context = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn async_cookie_store() {
+        let store = Arc::new(AsyncCookieStore::default());
+        let url = test_util::url("http://example.com/foo");
+        block_on(async {
+            assert_eq!(
+                Ok(StoreAction::Inserted),
+                store
+                    .insert_raw(&crate::RawCookie::parse("cookie1=1").unwrap(), &url)
+                    .await
+            );
+            store
+                .store_response_cookies(
+                    vec![crate::RawCookie::parse("cookie2=2; Max-Age=0").unwrap()].into_iter(),
+                    &url,
+                )
+                .await;
+        });
+
+        // a writer on another thread waits for the read guard to be released
+        let read = block_on(store.read());
+        let writer = {
+            let store = Arc::clone(&store);
+            let url = url.clone();
+            thread::spawn(move || {
+                block_on(async {
+                    store.write().await.parse("cookie3=3", &url).unwrap();
+                })
+            })
+        };
+        assert_eq!(1, read.len());
+        drop(read);
+        writer.join().unwrap();
+
+        let mut names = block_on(store.matches(&url))
+            .iter()
+            .map(|c| c.name().to_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(vec!["cookie1", "cookie3"], names);
+        assert_eq!(0, block_on(store.purge_expired()));
+        let store = Arc::try_unwrap(store).unwrap().into_inner();
+        assert_eq!(2, store.len());
+
+        let store = AsyncCookieStore::from(CookieStore::default());
+        let mut saved = None;
+        block_on(store.save(|cookie_store| {
+            saved = Some(cookie_store.len());
+            Ok(())
+        }))
+        .unwrap();
+        assert_eq!(Some(0), saved);
+    }
+}
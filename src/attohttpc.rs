@@ -0,0 +1,107 @@
+//! Helpers connecting [attohttpc](https://crates.io/crates/attohttpc) requests to a
+//! [`CookieStore`]. Requires feature `attohttpc`
+//!
+//! ```no_run
+//! # fn main() -> attohttpc::Result<()> {
+//! use cookie_store::CookieStore;
+//!
+//! let mut cookie_store = CookieStore::default();
+//! let session = attohttpc::Session::new();
+//! cookie_store::attohttpc::send(&mut cookie_store, session.get("https://example.com/"))?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! __NB__: attohttpc follows redirects internally, so only the cookies of the final response are
+//! stored; disable redirects via `follow_redirects(false)` to store the cookies of each response.
+
+use ::attohttpc::body::Body;
+use ::attohttpc::{RequestBuilder, Response};
+
+use crate::cookie_store::CookieStore;
+
+/// Add a `Cookie` header with the cookies in `cookie_store` matching the url of `request`,
+/// replacing any existing `Cookie` header; see [`CookieStore::add_cookie_header`]
+pub fn add_cookies<B>(
+    cookie_store: &CookieStore,
+    mut request: RequestBuilder<B>,
+) -> RequestBuilder<B> {
+    let url = request.inspect().url().clone();
+    cookie_store.add_cookie_header(request.headers_mut(), &url);
+    request
+}
+
+/// Store the cookies of the `Set-Cookie` headers of `response`; see
+/// [`CookieStore::store_response_headers`]
+pub fn store_cookies(cookie_store: &mut CookieStore, response: &Response) {
+    cookie_store.store_response_headers(response.headers(), response.url());
+}
+
+/// Send `request` with the cookies in `cookie_store` matching its url, storing the cookies of the
+/// response in `cookie_store`. If the store purges idle cookies (see
+/// [`CookieStore::with_max_idle`]), the cookies sent are recorded via [`CookieStore::touch`].
+pub fn send<B: Body>(
+    cookie_store: &mut CookieStore,
+    mut request: RequestBuilder<B>,
+) -> ::attohttpc::Result<Response> {
+    if cookie_store.tracks_access() {
+        let url = request.inspect().url().clone();
+        cookie_store.touch(&url);
+    }
+    let response = add_cookies(cookie_store, request).send()?;
+    store_cookies(cookie_store, &response);
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::send;
+    use crate::cookie_store::CookieStore;
+    use crate::test_util;
+
+    #[test]
+    fn send_with_cookies() {
+        let (url, request) = test_util::serve_once("Set-Cookie: cookie2=value2\r\n");
+        let mut cookie_store = CookieStore::default();
+        cookie_store.parse("cookie1=value1", &url).unwrap();
+        send(&mut cookie_store, ::attohttpc::get(url.as_str())).unwrap();
+        assert!(request
+            .join()
+            .unwrap()
+            .to_ascii_lowercase()
+            .contains("\r\ncookie: cookie1=value1\r\n"));
+        assert_eq!(2, cookie_store.len());
+        assert_eq!(
+            "value2",
+            cookie_store.get_for_url(&url, "cookie2").unwrap().value()
+        );
+    }
+
+    #[test]
+    fn send_records_access() {
+        use std::sync::{Arc, Mutex};
+        use time::Duration;
+
+        let (url, request) = test_util::serve_once("");
+        let start = time::OffsetDateTime::now_utc();
+        let now = Arc::new(Mutex::new(start));
+        let clock = {
+            let now = Arc::clone(&now);
+            move || *now.lock().unwrap()
+        };
+        let mut cookie_store = CookieStore::default()
+            .with_clock(clock)
+            .with_max_idle(Duration::days(30));
+        cookie_store.parse("cookie1=value1", &url).unwrap();
+        *now.lock().unwrap() += Duration::days(1);
+        send(&mut cookie_store, ::attohttpc::get(url.as_str())).unwrap();
+        request.join().unwrap();
+        assert_eq!(
+            Some(start + Duration::days(1)),
+            cookie_store
+                .get_for_url(&url, "cookie1")
+                .unwrap()
+                .last_access_time
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! An audit trail of the changes made to the contents of a `CookieStore`; see
+//! [`CookieStore::with_audit_log`](crate::CookieStore::with_audit_log)
+
+use std::sync::{Mutex, PoisonError};
+
+#[cfg(feature = "serde")]
+use serde_derive::{Deserialize, Serialize};
+use url::Url;
+
+use crate::clock::SharedClock;
+use crate::cookie::CookieIdentity;
+use crate::cookie_store::StoreEvent;
+
+/// The kind of change recorded by an [`AuditEntry`]
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AuditAction {
+    /// A `Cookie` was added to the store
+    Inserted,
+    /// A `Cookie` was added to the store, replacing an existing entry
+    Updated,
+    /// A `Cookie` already in the store was expired
+    Expired,
+    /// A `Cookie` was removed from the store
+    Removed,
+}
+
+/// A change made to the contents of a `CookieStore`, as recorded by the audit log of the store;
+/// see [`CookieStore::audit_log`](crate::CookieStore::audit_log)
+#[derive(PartialEq, Clone, Debug, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AuditEntry {
+    /// The time of the change, per the [`Clock`](crate::Clock) of the store
+    #[cfg_attr(feature = "serde", serde(with = "crate::rfc3339_fmt"))]
+    pub time: time::OffsetDateTime,
+    /// The kind of change
+    pub action: AuditAction,
+    /// The identity of the `Cookie` changed
+    pub cookie: CookieIdentity,
+    /// The request-uri from which the `Cookie` causing the change was received, for changes
+    /// made by [`CookieStore::insert`](crate::CookieStore::insert) and the methods implemented in
+    /// terms of it
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub source_url: Option<String>,
+}
+
+/// The audit log held by a `CookieStore`
+pub(crate) struct AuditLog {
+    clock: SharedClock,
+    entries: Mutex<Vec<AuditEntry>>,
+}
+
+impl AuditLog {
+    pub(crate) fn new(clock: SharedClock) -> AuditLog {
+        AuditLog {
+            clock,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Record `event`, caused by a `Cookie` received from `source_url` if any
+    pub(crate) fn record(&self, event: &StoreEvent<'_>, source_url: Option<&Url>) {
+        let (action, cookie) = match *event {
+            StoreEvent::Inserted(cookie) => (AuditAction::Inserted, cookie),
+            StoreEvent::Updated { new, .. } => (AuditAction::Updated, new),
+            StoreEvent::Expired(cookie) => (AuditAction::Expired, cookie),
+            StoreEvent::Removed(cookie) => (AuditAction::Removed, cookie),
+        };
+        let entry = AuditEntry {
+            time: self.clock.now_utc(),
+            action,
+            cookie: cookie.identity(),
+            source_url: source_url.map(Url::to_string),
+        };
+        self.lock().push(entry);
+    }
+
+    pub(crate) fn entries(&self) -> Vec<AuditEntry> {
+        self.lock().clone()
+    }
+
+    pub(crate) fn take(&self) -> Vec<AuditEntry> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<AuditEntry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clone for AuditLog {
+    fn clone(&self) -> Self {
+        AuditLog {
+            clock: self.clock.clone(),
+            entries: Mutex::new(self.entries()),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Tracking of the generation at which each `Cookie` of a `CookieStore` last changed; see
+//! [`CookieStore::with_change_tracking`](crate::CookieStore::with_change_tracking)
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+use crate::cookie::CookieIdentity;
+use crate::cookie_store::StoreEvent;
+
+/// The changes made to the `Cookie` of an identity
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Change {
+    /// The generation of the last change
+    pub(crate) changed: u64,
+    /// The generation at which the `Cookie` was last inserted, if since tracking began
+    pub(crate) inserted: Option<u64>,
+}
+
+/// The change tracking held by a `CookieStore`
+pub(crate) struct ChangeLog {
+    /// The earliest generation since which changes are known
+    since: u64,
+    changes: Mutex<HashMap<CookieIdentity, Change>>,
+}
+
+impl ChangeLog {
+    pub(crate) fn new(since: u64) -> ChangeLog {
+        ChangeLog {
+            since,
+            changes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn since(&self) -> u64 {
+        self.since
+    }
+
+    /// Record `event`, by which the store reached `generation`
+    pub(crate) fn record(&self, event: &StoreEvent<'_>, generation: u64) {
+        let (cookie, inserted) = match *event {
+            StoreEvent::Inserted(cookie) => (cookie, true),
+            StoreEvent::Updated { new, .. } => (new, false),
+            StoreEvent::Expired(cookie) | StoreEvent::Removed(cookie) => (cookie, false),
+        };
+        let mut changes = self.lock();
+        let change = changes.entry(cookie.identity()).or_insert(Change {
+            changed: generation,
+            inserted: None,
+        });
+        change.changed = generation;
+        if inserted {
+            change.inserted = Some(generation);
+        }
+    }
+
+    /// The changes made after `generation`
+    pub(crate) fn changes_since(&self, generation: u64) -> Vec<(CookieIdentity, Change)> {
+        self.lock()
+            .iter()
+            .filter(|(_, change)| change.changed > generation)
+            .map(|(id, change)| (id.clone(), *change))
+            .collect()
+    }
+
+    /// Discard the changes made up to and including `generation`
+    pub(crate) fn compact(&mut self, generation: u64) {
+        if generation > self.since {
+            self.since = generation;
+            self.changes
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .retain(|_, change| change.changed > generation);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<CookieIdentity, Change>> {
+        self.changes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clone for ChangeLog {
+    fn clone(&self) -> Self {
+        ChangeLog {
+            since: self.since,
+            changes: Mutex::new(self.lock().clone()),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt;
+use std::sync::Arc;
+
+use time::OffsetDateTime;
+
+/// A source of the current time, used by a [`CookieStore`](crate::CookieStore) when deciding
+/// whether cookies are __expired__ and when computing the expiry time of a cookie from its
+/// Max-Age attribute. Providing a `Clock` other than the default [`SystemClock`] (see
+/// [`CookieStore::with_clock`](crate::CookieStore::with_clock)) allows for deterministic
+/// testing, or the replay of previously recorded sessions.
+///
+/// `Clock` is implemented for any `Fn() -> OffsetDateTime + Send + Sync`.
+pub trait Clock: Send + Sync {
+    /// The current UTC time
+    fn now_utc(&self) -> OffsetDateTime;
+}
+
+/// A [`Clock`] reading the system time; the default for a `CookieStore`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_utc(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> OffsetDateTime + Send + Sync,
+{
+    fn now_utc(&self) -> OffsetDateTime {
+        self()
+    }
+}
+
+/// The `Clock` held by a `CookieStore`, allowing the store to remain `Clone` and `Debug`
+#[derive(Clone)]
+pub(crate) struct SharedClock(Arc<dyn Clock>);
+
+impl SharedClock {
+    pub(crate) fn new<C: Clock + 'static>(clock: C) -> SharedClock {
+        SharedClock(Arc::new(clock))
+    }
+
+    pub(crate) fn now_utc(&self) -> OffsetDateTime {
+        self.0.now_utc()
+    }
+}
+
+impl Default for SharedClock {
+    fn default() -> Self {
+        SharedClock::new(SystemClock)
+    }
+}
+
+impl fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Clock").finish()
+    }
+}
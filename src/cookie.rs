@@ -1,4 +1,5 @@
-use crate::cookie_domain::CookieDomain;
+use crate::cookie_date::DateParsing;
+use crate::cookie_domain::{is_domain_match, CookieDomain};
 use crate::cookie_expiration::CookieExpiration;
 use crate::cookie_path::CookiePath;
 
@@ -7,34 +8,135 @@ use cookie::{Cookie as RawCookie, CookieBuilder as RawCookieBuilder, ParseError}
 #[cfg(feature = "serde")]
 use serde_derive::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::fmt;
 use std::ops::Deref;
-use time;
 use url::Url;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// The cookie and request-uri involved in an [`Error`], where known
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// The name of the cookie
+    pub name: Option<String>,
+    /// The Domain attribute of the cookie, or the domain of the cookie if it had no Domain
+    /// attribute
+    pub domain: Option<String>,
+    /// The host of the request-uri from which the cookie was received
+    pub request_host: Option<String>,
+}
+
+impl ErrorContext {
+    /// The context of an error for `raw_cookie` received from `request_url`
+    pub(crate) fn of(raw_cookie: &RawCookie<'_>, request_url: &Url) -> ErrorContext {
+        ErrorContext {
+            name: Some(raw_cookie.name().to_owned()),
+            domain: raw_cookie.domain().map(str::to_owned),
+            request_host: request_url.host_str().map(str::to_owned),
+        }
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut separator = "";
+        for (label, value) in [
+            ("cookie", &self.name),
+            ("domain", &self.domain),
+            ("request-host", &self.request_host),
+        ] {
+            if let Some(value) = value {
+                write!(f, "{}{} '{}'", separator, label, value)?;
+                separator = ", ";
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An error inserting or creating a `Cookie`. Errors compare equal if they are the same variant,
+/// regardless of their [`ErrorContext`].
+#[derive(Debug, Clone)]
 pub enum Error {
     /// Cookie had attribute HttpOnly but was received from a request-uri which was not an http
     /// scheme
-    NonHttpScheme,
+    NonHttpScheme(ErrorContext),
     /// Cookie did not specify domain but was received from non-relative-scheme request-uri from
     /// which host could not be determined
-    NonRelativeScheme,
+    NonRelativeScheme(ErrorContext),
     /// Cookie received from a request-uri that does not domain-match
-    DomainMismatch,
+    DomainMismatch(ErrorContext),
     /// Cookie is Expired
-    Expired,
+    Expired(ErrorContext),
     /// `cookie::Cookie` Parse error
     Parse,
     #[cfg(feature = "public_suffix")]
     /// Cookie specified a public suffix domain-attribute that does not match the canonicalized
     /// request-uri host
-    PublicSuffix,
+    PublicSuffix(ErrorContext),
     /// Tried to use a CookieDomain variant of `Empty` or `NotPresent` in a context requiring a Domain value
     UnspecifiedDomain,
+    /// Cookie domain is not permitted by the domain allowlist or denylist of the `CookieStore`
+    DomainNotPermitted(ErrorContext),
+    /// Cookie had attribute Secure but was received from a request-uri which is not trustworthy
+    /// per the `TrustworthyOrigins` of the `CookieStore`
+    NonSecureOrigin(ErrorContext),
+    /// Cookie would exceed the capacity limits of the `CookieStore`, and its `OverflowPolicy` is
+    /// to reject such cookies
+    StoreFull(ErrorContext),
+    /// Cookie did not have attribute Secure, was received from a request-uri which is not
+    /// trustworthy, and would overwrite or shadow a Secure cookie in the `CookieStore`
+    ShadowsSecure(ErrorContext),
 }
 
+impl Error {
+    /// The cookie and request-uri involved in this error, if the variant carries any context
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::NonHttpScheme(context)
+            | Error::NonRelativeScheme(context)
+            | Error::DomainMismatch(context)
+            | Error::Expired(context)
+            | Error::DomainNotPermitted(context)
+            | Error::NonSecureOrigin(context)
+            | Error::StoreFull(context)
+            | Error::ShadowsSecure(context) => Some(context),
+            #[cfg(feature = "public_suffix")]
+            Error::PublicSuffix(context) => Some(context),
+            Error::Parse | Error::UnspecifiedDomain => None,
+        }
+    }
+
+    /// Fill in any context of this error not already known from `context`
+    pub(crate) fn in_context(mut self, context: ErrorContext) -> Error {
+        let existing = match self {
+            Error::NonHttpScheme(ref mut existing)
+            | Error::NonRelativeScheme(ref mut existing)
+            | Error::DomainMismatch(ref mut existing)
+            | Error::Expired(ref mut existing)
+            | Error::DomainNotPermitted(ref mut existing)
+            | Error::NonSecureOrigin(ref mut existing)
+            | Error::StoreFull(ref mut existing)
+            | Error::ShadowsSecure(ref mut existing) => existing,
+            #[cfg(feature = "public_suffix")]
+            Error::PublicSuffix(ref mut existing) => existing,
+            Error::Parse | Error::UnspecifiedDomain => return self,
+        };
+        existing.name = existing.name.take().or(context.name);
+        existing.domain = existing.domain.take().or(context.domain);
+        existing.request_host = existing.request_host.take().or(context.request_host);
+        self
+    }
+}
+
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+impl Eq for Error {}
+
 impl std::error::Error for Error {}
 
 impl fmt::Display for Error {
@@ -43,19 +145,31 @@ impl fmt::Display for Error {
             f,
             "{}",
             match *self {
-                Error::NonHttpScheme =>
+                Error::NonHttpScheme(_) =>
                     "request-uri is not an http scheme but HttpOnly attribute set",
-                Error::NonRelativeScheme => {
+                Error::NonRelativeScheme(_) => {
                     "request-uri is not a relative scheme; cannot determine host"
                 }
-                Error::DomainMismatch => "request-uri does not domain-match the cookie",
-                Error::Expired => "attempted to utilize an Expired Cookie",
+                Error::DomainMismatch(_) => "request-uri does not domain-match the cookie",
+                Error::Expired(_) => "attempted to utilize an Expired Cookie",
                 Error::Parse => "unable to parse string as cookie::Cookie",
                 #[cfg(feature = "public_suffix")]
-                Error::PublicSuffix => "domain-attribute value is a public suffix",
+                Error::PublicSuffix(_) => "domain-attribute value is a public suffix",
                 Error::UnspecifiedDomain => "domain-attribute is not specified",
+                Error::DomainNotPermitted(_) =>
+                    "cookie domain is not permitted by the domain allowlist or denylist",
+                Error::NonSecureOrigin(_) =>
+                    "request-uri is not trustworthy but Secure attribute set",
+                Error::StoreFull(_) => "cookie store is full",
+                Error::ShadowsSecure(_) => {
+                    "request-uri is not trustworthy but cookie would shadow a Secure cookie"
+                }
             }
-        )
+        )?;
+        match self.context() {
+            Some(context) if *context != ErrorContext::default() => write!(f, " ({})", context),
+            _ => Ok(()),
+        }
     }
 }
 
@@ -71,10 +185,12 @@ pub type CookieResult<'a> = Result<Cookie<'a>, Error>;
 /// A cookie conforming more closely to [IETF RFC6265](https://datatracker.ietf.org/doc/html/rfc6265)
 #[derive(PartialEq, Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Cookie<'a> {
     /// The parsed Set-Cookie data
     #[cfg_attr(feature = "serde", serde(serialize_with = "serde_raw_cookie::serialize"))]
     #[cfg_attr(feature = "serde", serde(deserialize_with = "serde_raw_cookie::deserialize"))]
+    #[cfg_attr(feature = "schemars", schemars(with = "String"))]
     raw_cookie: RawCookie<'a>,
     /// The Path attribute from a Set-Cookie header or the default-path as
     /// determined from
@@ -91,6 +207,175 @@ pub struct Cookie<'a> {
     /// indicating a non-persistent `Cookie` that should expire at the end of the
     /// session
     pub expires: CookieExpiration,
+    /// The scheme of the request-uri from which the `Cookie` was received, if recorded by a
+    /// scheme-bound `CookieStore` (see
+    /// [`CookieStore::with_scheme_bound`](crate::CookieStore::with_scheme_bound))
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub source_scheme: Option<SourceScheme>,
+    /// The port of the request-uri from which the `Cookie` was received, if recorded by a
+    /// port-bound `CookieStore` (see
+    /// [`CookieStore::with_port_bound`](crate::CookieStore::with_port_bound))
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub source_port: Option<u16>,
+    /// The site of the top-level document under which the `Cookie` was received (its registrable
+    /// domain, or its host if the `CookieStore` has no public suffix list), if recorded by e.g.
+    /// [`CookieStore::insert_for_site`](crate::CookieStore::insert_for_site); see
+    /// [`Cookie::is_first_party`]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub site_for_cookies: Option<String>,
+    /// Arbitrary application metadata attached to the `Cookie`, e.g. to record its provenance;
+    /// this is never sent to servers, but is preserved through serialization
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub extensions: BTreeMap<String, String>,
+    /// The time at which the `Cookie` was first stored, per [IETF RFC6265 Section
+    /// 5.3](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3); retained when the `Cookie`
+    /// is replaced by one with the same domain, path, and name. `None` until the `Cookie` is
+    /// inserted into a `CookieStore`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "crate::rfc3339_fmt::option"
+        )
+    )]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "Option<crate::rfc3339_fmt::Timestamp>")
+    )]
+    pub creation_time: Option<time::OffsetDateTime>,
+    /// The time at which the `Cookie` was last stored, or last sent in a request as recorded by
+    /// [`CookieStore::touch`](crate::CookieStore::touch), per [IETF RFC6265 Section
+    /// 5.3](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3). `None` until the `Cookie`
+    /// is inserted into a `CookieStore`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "crate::rfc3339_fmt::option"
+        )
+    )]
+    #[cfg_attr(
+        feature = "schemars",
+        schemars(with = "Option<crate::rfc3339_fmt::Timestamp>")
+    )]
+    pub last_access_time: Option<time::OffsetDateTime>,
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Cookie<'static> {
+    /// Generates cookies with valid names and values, and arbitrary domain, path, expiry,
+    /// Secure, HttpOnly, and SameSite attributes; the underlying `RawCookie` is consistent with the
+    /// domain, path, and expiry of the `Cookie`
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        use crate::utils::arbitrary_string;
+        use cookie::SameSite;
+
+        const NAME: &[u8] =
+            b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!#$%&'*+-.^_`|~";
+        const VALUE: &[u8] =
+            b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!#$%&'()*+-./:<=>?@[]^_`{|}~";
+        let domain = CookieDomain::arbitrary(u)?;
+        let path = CookiePath::arbitrary(u)?;
+        let expires = CookieExpiration::arbitrary(u)?;
+        let mut raw_cookie = RawCookie::build((
+            arbitrary_string(u, NAME, 1..=16)?,
+            arbitrary_string(u, VALUE, 0..=32)?,
+        ))
+        .secure(u.arbitrary()?)
+        .http_only(u.arbitrary()?);
+        if let Some(same_site) = u.choose(&[
+            None,
+            Some(SameSite::Strict),
+            Some(SameSite::Lax),
+            Some(SameSite::None),
+        ])? {
+            raw_cookie = raw_cookie.same_site(*same_site);
+        }
+        if path.is_from_path_attr() {
+            raw_cookie = raw_cookie.path(String::from(&path));
+        }
+        if let CookieDomain::Suffix(ref suffix) = domain {
+            raw_cookie = raw_cookie.domain(suffix.clone());
+        }
+        if let CookieExpiration::AtUtc(expires) = expires {
+            raw_cookie = raw_cookie.expires(expires);
+        }
+        Ok(Cookie {
+            raw_cookie: raw_cookie.build(),
+            path,
+            domain,
+            expires,
+            source_scheme: None,
+            source_port: None,
+            site_for_cookies: None,
+            extensions: BTreeMap::new(),
+            creation_time: None,
+            last_access_time: None,
+        })
+    }
+}
+
+/// The identity of a `Cookie` within a `CookieStore`: the domain, path, and name under which it is
+/// stored. A `Cookie` received later with the same identity replaces the existing `Cookie`, so a
+/// `CookieIdentity` serves as a stable handle to a specific cookie across store mutations; see
+/// [`Cookie::identity`] and [`CookieStore::get_by_id`](crate::CookieStore::get_by_id).
+#[derive(PartialEq, Eq, Clone, Debug, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct CookieIdentity {
+    /// The (normalized) domain of the `Cookie`
+    pub domain: String,
+    /// The (normalized) path of the `Cookie`
+    pub path: String,
+    /// The name of the `Cookie`
+    pub name: String,
+}
+
+impl CookieIdentity {
+    /// A `CookieIdentity` for the specified `domain`, `path`, and `name`
+    pub fn new(domain: &str, path: &str, name: &str) -> CookieIdentity {
+        CookieIdentity {
+            domain: domain.to_owned(),
+            path: path.to_owned(),
+            name: name.to_owned(),
+        }
+    }
+}
+
+/// Whether a `Cookie` was received from a secure (`https`, `wss`) or non-secure request-uri, per
+/// the [Origin-Bound Cookies](https://github.com/sbingler/origin-bound-cookies) proposal
+#[derive(PartialEq, Clone, Copy, Debug, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum SourceScheme {
+    /// Received from an `https` or `wss` request-uri
+    Secure,
+    /// Received from a request-uri of any other scheme
+    NonSecure,
+}
+
+impl SourceScheme {
+    /// The `SourceScheme` of `url`
+    pub fn of(url: &Url) -> SourceScheme {
+        match url.scheme() {
+            "https" | "wss" => SourceScheme::Secure,
+            _ => SourceScheme::NonSecure,
+        }
+    }
 }
 
 #[cfg(feature = "serde")]
@@ -132,11 +417,55 @@ impl<'a> Cookie<'a> {
             && (!self.raw_cookie.http_only().unwrap_or(false) || is_http_scheme(request_url))
     }
 
+    /// The value of this `Cookie`, percent-decoded. Fails if the decoded value is not valid UTF-8.
+    pub fn value_decoded(&self) -> Result<Cow<'_, str>, std::str::Utf8Error> {
+        percent_encoding::percent_decode_str(self.value()).decode_utf8()
+    }
+
+    /// The value of the extension `key` attached to this `Cookie`, if any
+    pub fn extension(&self, key: &str) -> Option<&str> {
+        self.extensions.get(key).map(String::as_str)
+    }
+
+    /// Attach the extension `key` with `value` to this `Cookie`, returning any previous value
+    pub fn set_extension<K: Into<String>, V: Into<String>>(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Option<String> {
+        self.extensions.insert(key.into(), value.into())
+    }
+
+    /// The [`CookieIdentity`] of this `Cookie`
+    pub fn identity(&self) -> CookieIdentity {
+        CookieIdentity {
+            domain: String::from(&self.domain),
+            path: String::from(&self.path),
+            name: self.name().to_owned(),
+        }
+    }
+
+    /// The size in bytes of this `Cookie`, as counted against the per-domain byte budget of a
+    /// `CookieStore`: the combined length of its name and value, as browsers count it
+    pub fn byte_size(&self) -> usize {
+        self.name().len() + self.value().len()
+    }
+
     /// Should this `Cookie` be persisted across sessions?
     pub fn is_persistent(&self) -> bool {
-        match self.expires {
-            CookieExpiration::AtUtc(_) => true,
-            CookieExpiration::SessionEnd => false,
+        self.expires.is_persistent()
+    }
+
+    /// Was this `Cookie` received in a first-party context, i.e. is its domain the same as, a
+    /// subdomain of, or a parent domain of its recorded
+    /// [`site_for_cookies`](Cookie::site_for_cookies)? A `Cookie` with no recorded site is
+    /// considered first-party.
+    pub fn is_first_party(&self) -> bool {
+        match (self.site_for_cookies.as_deref(), self.domain.as_str()) {
+            (Some(site), Some(domain)) => {
+                is_domain_match(domain, site) || is_domain_match(site, domain)
+            }
+            _ => true,
         }
     }
 
@@ -163,14 +492,46 @@ impl<'a> Cookie<'a> {
         Cookie::try_from_raw_cookie(&RawCookie::parse(cookie_str)?, request_url)
     }
 
+    /// Parses a new `cookie_store::Cookie` from `cookie_str`, parsing any `Expires` attribute per
+    /// `date_parsing`.
+    pub fn parse_with<S>(
+        cookie_str: S,
+        request_url: &Url,
+        date_parsing: DateParsing,
+    ) -> CookieResult<'a>
+    where
+        S: Into<Cow<'a, str>>,
+    {
+        Cookie::try_from_raw_cookie(
+            &crate::cookie_date::parse(cookie_str, date_parsing)?,
+            request_url,
+        )
+    }
+
     /// Create a new `cookie_store::Cookie` from a `cookie::Cookie` (from the `cookie` crate)
-    /// received from `request_url`.
+    /// received from `request_url`. Any Max-Age attribute is taken relative to the system time;
+    /// see [`Cookie::try_from_raw_cookie_at`] for a `Cookie` to be inserted into a `CookieStore`
+    /// with a custom [`Clock`](crate::Clock).
     pub fn try_from_raw_cookie(raw_cookie: &RawCookie<'a>, request_url: &Url) -> CookieResult<'a> {
+        Cookie::try_from_raw_cookie_at(raw_cookie, request_url, time::OffsetDateTime::now_utc())
+    }
+
+    /// As [`Cookie::try_from_raw_cookie`], with any Max-Age attribute taken relative to `now_utc`,
+    /// e.g. the [`CookieStore::now_utc`](crate::CookieStore::now_utc) of the store the `Cookie`
+    /// is to be inserted into.
+    pub fn try_from_raw_cookie_at(
+        raw_cookie: &RawCookie<'a>,
+        request_url: &Url,
+        now_utc: time::OffsetDateTime,
+    ) -> CookieResult<'a> {
         if raw_cookie.http_only().unwrap_or(false) && !is_http_scheme(request_url) {
             // If the cookie was received from a "non-HTTP" API and the
             // cookie's http-only-flag is set, abort these steps and ignore the
             // cookie entirely.
-            return Err(Error::NonHttpScheme);
+            return Err(Error::NonHttpScheme(ErrorContext::of(
+                raw_cookie,
+                request_url,
+            )));
         }
 
         let domain = match CookieDomain::try_from(raw_cookie) {
@@ -180,7 +541,7 @@ impl<'a> Cookie<'a> {
                     //    If the canonicalized request-host does not domain-match the
                     //    domain-attribute:
                     //       Ignore the cookie entirely and abort these steps.
-                    Err(Error::DomainMismatch)
+                    Err(Error::DomainMismatch(ErrorContext::default()))
                 } else {
                     //    Otherwise:
                     //       Set the cookie's host-only-flag to false.
@@ -193,7 +554,8 @@ impl<'a> Cookie<'a> {
             //    Set the cookie's host-only-flag to true.
             //    Set the cookie's domain to the canonicalized request-host.
             _ => CookieDomain::host_only(request_url),
-        }?;
+        }
+        .map_err(|e| e.in_context(ErrorContext::of(raw_cookie, request_url)))?;
 
         let path = raw_cookie
             .path()
@@ -204,7 +566,7 @@ impl<'a> Cookie<'a> {
         // per RFC6265, Max-Age takes precedence, then Expires, otherwise is Session
         // only
         let expires = if let Some(max_age) = raw_cookie.max_age() {
-            CookieExpiration::from(max_age)
+            CookieExpiration::from_max_age_at(max_age, now_utc)
         } else if let Some(expiration) = raw_cookie.expires() {
             CookieExpiration::from(expiration)
         } else {
@@ -216,6 +578,12 @@ impl<'a> Cookie<'a> {
             path,
             expires,
             domain,
+            source_scheme: None,
+            source_port: None,
+            site_for_cookies: None,
+            extensions: BTreeMap::new(),
+            creation_time: None,
+            last_access_time: None,
         })
     }
 
@@ -225,8 +593,19 @@ impl<'a> Cookie<'a> {
             path: self.path,
             domain: self.domain,
             expires: self.expires,
+            source_scheme: self.source_scheme,
+            source_port: self.source_port,
+            site_for_cookies: self.site_for_cookies,
+            extensions: self.extensions,
+            creation_time: self.creation_time,
+            last_access_time: self.last_access_time,
         }
     }
+
+    /// Set the SameSite attribute of the underlying `RawCookie`
+    pub(crate) fn set_same_site(&mut self, same_site: cookie::SameSite) {
+        self.raw_cookie.set_same_site(same_site);
+    }
 }
 
 impl<'a> Deref for Cookie<'a> {
@@ -270,7 +649,7 @@ mod tests {
     use time::{Duration, OffsetDateTime};
     use url::Url;
 
-    use crate::utils::test as test_utils;
+    use crate::test_util as test_utils;
 
     fn cmp_domain(cookie: &str, url: &str, exp: CookieDomain) {
         let ua = test_utils::make_cookie(cookie, url, None, None);
@@ -464,10 +843,7 @@ mod tests {
             None,
             Some(9223372036854776),
         );
-        assert!(match ua.expires {
-            CookieExpiration::AtUtc(_) => true,
-            _ => false,
-        });
+        assert!(matches!(ua.expires, CookieExpiration::AtUtc(_)));
     }
 
     #[test]
@@ -514,10 +890,7 @@ mod tests {
     fn session_end() {
         let ua =
             test_utils::make_cookie("cookie1=value1", "http://example.com/foo/bar", None, None);
-        assert!(match ua.expires {
-            CookieExpiration::SessionEnd => true,
-            _ => false,
-        });
+        assert!(matches!(ua.expires, CookieExpiration::SessionEnd));
         assert!(!ua.is_expired());
         assert!(!ua.expires_by(&in_days(1)));
         assert!(!ua.expires_by(&in_days(-1)));
@@ -724,16 +1097,87 @@ mod tests {
             Some("data:nonrelativescheme"),
         );
     }
+
+    #[test]
+    fn error_context() {
+        use super::{Error, ErrorContext};
+
+        let url = test_utils::url("http://www.example.com/foo/bar");
+        let raw_cookie = RawCookie::parse("cookie1=value1; Domain=example.org").unwrap();
+        let err = Cookie::try_from_raw_cookie(&raw_cookie, &url).unwrap_err();
+        assert_eq!(Error::DomainMismatch(Default::default()), err);
+        assert_eq!(
+            Some(&ErrorContext {
+                name: Some("cookie1".to_owned()),
+                domain: Some("example.org".to_owned()),
+                request_host: Some("www.example.com".to_owned()),
+            }),
+            err.context()
+        );
+        assert_eq!(
+            "request-uri does not domain-match the cookie (cookie 'cookie1', domain \
+             'example.org', request-host 'www.example.com')",
+            err.to_string()
+        );
+
+        let url = test_utils::url("data:nonrelativescheme");
+        let raw_cookie = RawCookie::parse("cookie1=value1").unwrap();
+        let err = Cookie::try_from_raw_cookie(&raw_cookie, &url).unwrap_err();
+        assert_eq!(Error::NonRelativeScheme(Default::default()), err);
+        assert_eq!(
+            "request-uri is not a relative scheme; cannot determine host (cookie 'cookie1')",
+            err.to_string()
+        );
+
+        assert_eq!(None, Error::Parse.context());
+        assert_eq!(
+            "unable to parse string as cookie::Cookie",
+            Error::Parse.to_string()
+        );
+    }
+}
+
+#[cfg(all(test, feature = "arbitrary"))]
+mod arbitrary_tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use cookie::Cookie as RawCookie;
+
+    use crate::cookie::Cookie;
+    use crate::cookie_domain::CookieDomain;
+    use crate::test_util as test_utils;
+
+    #[test]
+    fn arbitrary() {
+        let data = (0..8192u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect::<Vec<_>>();
+        let mut u = Unstructured::new(&data);
+        for _ in 0..64 {
+            let cookie = Cookie::arbitrary(&mut u).unwrap();
+            // the underlying RawCookie round-trips through a Set-Cookie header...
+            let raw_cookie = RawCookie::parse(cookie.to_string()).unwrap();
+            assert_eq!(cookie.name_value(), raw_cookie.name_value());
+            // ...and is parsed to an equivalent Cookie when received from a matching request-uri
+            let host = match cookie.domain {
+                CookieDomain::HostOnly(ref host) | CookieDomain::Suffix(ref host) => host,
+                CookieDomain::NotPresent | CookieDomain::Empty => continue,
+            };
+            let url = test_utils::url(&format!("http://{}{}/", host, &*cookie.path));
+            let parsed = Cookie::try_from_raw_cookie(&raw_cookie, &url).unwrap();
+            assert_eq!(cookie.domain, parsed.domain);
+            assert_eq!(&*cookie.path, &*parsed.path);
+            assert_eq!(cookie.expires, parsed.expires);
+        }
+    }
 }
 
 #[cfg(all(test, feature = "serde_json"))]
 mod serde_json_tests {
     use crate::cookie::Cookie;
     use crate::cookie_expiration::CookieExpiration;
-    use crate::utils::test as test_utils;
-    use crate::utils::test::*;
+    use crate::test_util as test_utils;
+    use crate::test_util::*;
     use serde_json::json;
-    use time;
 
     fn encode_decode(c: &Cookie<'_>, expected: serde_json::Value) {
         let encoded = serde_json::to_value(c).unwrap();
@@ -741,16 +1185,16 @@ mod serde_json_tests {
             expected,
             encoded,
             "\nexpected: '{}'\n encoded: '{}'",
-            expected.to_string(),
-            encoded.to_string()
+            expected,
+            encoded
         );
         let decoded: Cookie<'_> = serde_json::from_value(encoded).unwrap();
         assert_eq!(
             *c,
             decoded,
             "\nexpected: '{}'\n decoded: '{}'",
-            c.to_string(),
-            decoded.to_string()
+            **c,
+            *decoded
         );
     }
 
@@ -887,5 +1331,21 @@ mod serde_json_tests {
                 "expires": { "AtUtc": utc_tm.format(crate::rfc3339_fmt::RFC3339_FORMAT).unwrap().to_string() },
             }),
         );
+
+        let mut extensions =
+            test_utils::make_cookie("cookie8=value8", "http://example.com/foo/bar", None, None);
+        assert_eq!(None, extensions.set_extension("account", "alice"));
+        extensions.set_extension("job", "42");
+        assert_eq!(Some("alice"), extensions.extension("account"));
+        encode_decode(
+            &extensions,
+            json!({
+                "raw_cookie": "cookie8=value8",
+                "path":["/foo", false],
+                "domain": { "HostOnly": "example.com" },
+                "expires": "SessionEnd",
+                "extensions": { "account": "alice", "job": "42" },
+            }),
+        );
     }
 }
@@ -0,0 +1,202 @@
+use std::borrow::Cow;
+
+use cookie::{Cookie as RawCookie, ParseError};
+#[cfg(feature = "serde")]
+use serde_derive::{Deserialize, Serialize};
+use time::{Date, Month, OffsetDateTime, Time};
+
+/// How the dates of `Expires` attributes are parsed; see
+/// [`CookieStore::with_date_parsing`](crate::CookieStore::with_date_parsing) and
+/// [`Cookie::parse_with`](crate::Cookie::parse_with).
+#[derive(PartialEq, Clone, Copy, Debug, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum DateParsing {
+    /// Dates are parsed by the [cookie](https://crates.io/crates/cookie) crate, which accepts the
+    /// common HTTP date formats; an `Expires` attribute it cannot parse is ignored
+    #[default]
+    Standard,
+    /// Dates are parsed per the
+    /// [RFC6265 cookie-date algorithm](https://datatracker.ietf.org/doc/html/rfc6265#section-5.1.1),
+    /// as user agents do, tolerating e.g. 2-digit years, a missing `GMT`, and dashes or other
+    /// delimiters between the date components. Dates the algorithm rejects are parsed as per
+    /// `DateParsing::Standard`.
+    Lenient,
+}
+
+/// Parse `cookie_str` as a `cookie::Cookie`, parsing any `Expires` attribute per `date_parsing`
+pub(crate) fn parse<'c, S>(
+    cookie_str: S,
+    date_parsing: DateParsing,
+) -> Result<RawCookie<'c>, ParseError>
+where
+    S: Into<Cow<'c, str>>,
+{
+    let cookie_str = cookie_str.into();
+    let expires = match date_parsing {
+        DateParsing::Standard => None,
+        DateParsing::Lenient => expires_attribute(&cookie_str).and_then(parse_cookie_date),
+    };
+    let mut raw_cookie = RawCookie::parse(cookie_str)?;
+    if let Some(expires) = expires {
+        raw_cookie.set_expires(expires);
+    }
+    Ok(raw_cookie)
+}
+
+/// The value of the last `Expires` attribute of `cookie_str`, if any
+fn expires_attribute(cookie_str: &str) -> Option<&str> {
+    cookie_str
+        .split(';')
+        .skip(1)
+        .filter_map(|attribute| attribute.split_once('='))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case("expires"))
+        .map(|(_, value)| value.trim())
+        .last()
+}
+
+/// Returns true if `c` is a delimiter of the cookie-date grammar
+fn is_delimiter(c: char) -> bool {
+    matches!(c, '\x09' | '\x20'..='\x2F' | '\x3B'..='\x40' | '\x5B'..='\x60' | '\x7B'..='\x7E')
+}
+
+/// Parse the leading 1 to `max` digits of `token`, which must be followed by either the end of
+/// the token or a non-digit, returning the value and the remainder of `token`
+fn leading_digits(token: &str, min: usize, max: usize) -> Option<(u32, &str)> {
+    let len = token
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(token.len());
+    if len < min || len > max {
+        return None;
+    }
+    Some((token[..len].parse().ok()?, &token[len..]))
+}
+
+/// Parse the time production, `hms-time ( non-digit *OCTET )`
+fn parse_time(token: &str) -> Option<(u8, u8, u8)> {
+    let (hour, rest) = leading_digits(token, 1, 2)?;
+    let (minute, rest) = leading_digits(rest.strip_prefix(':')?, 1, 2)?;
+    let (second, _) = leading_digits(rest.strip_prefix(':')?, 1, 2)?;
+    Some((hour as u8, minute as u8, second as u8))
+}
+
+/// Parse the month production, the first three characters of a month name
+fn parse_month(token: &str) -> Option<Month> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let prefix = token.get(..3)?;
+    MONTHS
+        .iter()
+        .position(|month| prefix.eq_ignore_ascii_case(month))
+        .and_then(|i| Month::try_from(i as u8 + 1).ok())
+}
+
+/// Parse `date` per the
+/// [RFC6265 cookie-date algorithm](https://datatracker.ietf.org/doc/html/rfc6265#section-5.1.1)
+pub(crate) fn parse_cookie_date(date: &str) -> Option<OffsetDateTime> {
+    let mut time = None;
+    let mut day_of_month = None;
+    let mut month = None;
+    let mut year = None;
+    for token in date.split(is_delimiter).filter(|t| !t.is_empty()) {
+        if time.is_none() {
+            if let Some(t) = parse_time(token) {
+                time = Some(t);
+                continue;
+            }
+        }
+        if day_of_month.is_none() {
+            if let Some((day, _)) = leading_digits(token, 1, 2) {
+                day_of_month = Some(day);
+                continue;
+            }
+        }
+        if month.is_none() {
+            if let Some(m) = parse_month(token) {
+                month = Some(m);
+                continue;
+            }
+        }
+        if year.is_none() {
+            if let Some((y, _)) = leading_digits(token, 2, 4) {
+                year = Some(y);
+            }
+        }
+    }
+    let year = match year? {
+        year @ 70..=99 => year + 1900,
+        year @ 0..=69 => year + 2000,
+        year => year,
+    };
+    if year < 1601 {
+        return None;
+    }
+    let (hour, minute, second) = time?;
+    let date =
+        Date::from_calendar_date(year as i32, month?, u8::try_from(day_of_month?).ok()?).ok()?;
+    let time = Time::from_hms(hour, minute, second).ok()?;
+    Some(date.with_time(time).assume_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::{parse, parse_cookie_date, DateParsing};
+
+    #[test]
+    fn parse_cookie_date_formats() {
+        let expected = Some(datetime!(2030-08-03 00:38:37 UTC));
+        for date in [
+            "Sat, 03 Aug 2030 00:38:37 GMT",
+            "Saturday, 03-Aug-30 00:38:37 GMT",
+            "Sat Aug  3 00:38:37 2030",
+            "Sat, 03 Aug 2030 00:38:37",
+            "03-aug-2030 00:38:37",
+            "2030 August 3, 0:38:37 UTC",
+            "Sat, 03/Aug/2030 00:38:37 +0000",
+        ] {
+            assert_eq!(expected, parse_cookie_date(date), "{}", date);
+        }
+        assert_eq!(
+            Some(datetime!(1999-01-01 12:00:00 UTC)),
+            parse_cookie_date("1 Jan 99 12:00:00")
+        );
+        for date in [
+            "",
+            "Sat, 03 Aug 2030",
+            "03 Aug 2030 24:00:00",
+            "03 Foo 2030 00:38:37",
+            "31 Feb 2030 00:38:37",
+            "03 Aug 1600 00:38:37",
+            "03 Aug 20301 00:38:37",
+        ] {
+            assert_eq!(None, parse_cookie_date(date), "{}", date);
+        }
+    }
+
+    #[test]
+    fn parse_expires() {
+        let cookie_str = "cookie1=value1; expires=03-Aug-30 00:38:37; Path=/";
+        let standard = parse(cookie_str, DateParsing::Standard).unwrap();
+        assert!(standard.expires().is_none());
+        let lenient = parse(cookie_str, DateParsing::Lenient).unwrap();
+        assert_eq!(
+            Some(datetime!(2030-08-03 00:38:37 UTC)),
+            lenient.expires_datetime()
+        );
+        assert_eq!(Some("/"), lenient.path());
+
+        // dates in the standard formats are parsed identically
+        let cookie_str = "cookie1=value1; Expires=Sat, 03 Aug 2030 00:38:37 GMT";
+        assert_eq!(
+            parse(cookie_str, DateParsing::Standard)
+                .unwrap()
+                .expires_datetime(),
+            parse(cookie_str, DateParsing::Lenient)
+                .unwrap()
+                .expires_datetime()
+        );
+    }
+}
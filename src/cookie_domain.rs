@@ -1,26 +1,76 @@
-use std;
-
 use cookie::Cookie as RawCookie;
-use idna;
 #[cfg(feature = "public_suffix")]
-use publicsuffix::{List, Psl, Suffix};
+use publicsuffix::{Psl, Suffix};
 #[cfg(feature = "serde")]
 use serde_derive::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::convert::TryFrom;
 use url::{Host, Url};
 
 use crate::utils::is_host_name;
 use crate::CookieError;
 
-pub fn is_match(domain: &str, request_url: &Url) -> bool {
-    CookieDomain::try_from(domain)
-        .map(|domain| domain.matches(request_url))
-        .unwrap_or(false)
+/// The domain strings which may domain-match the request-host of `request_url`: the
+/// request-host itself and, if the request-host is a host name, each of its parent domains (e.g.
+/// for `a.example.com`: `a.example.com`, `example.com`, and `com`).
+pub(crate) fn match_candidates(request_url: &Url) -> impl Iterator<Item = &str> {
+    request_url
+        .host_str()
+        .map(strip_trailing_dot)
+        .into_iter()
+        .flat_map(host_match_candidates)
+}
+
+/// The domain strings which `host` may domain-match: `host` itself and, if `host` is a host
+/// name, each of its parent domains
+pub(crate) fn host_match_candidates(host: &str) -> impl Iterator<Item = &str> {
+    let parents = if is_host_name(host) {
+        Some(host.match_indices('.').map(move |(i, _)| &host[i + 1..]))
+    } else {
+        None
+    };
+    std::iter::once(host).chain(parents.into_iter().flatten())
+}
+
+/// Returns true if `domain` domain-matches `suffix`, i.e. is identical to, or a subdomain of,
+/// `suffix`
+pub(crate) fn is_domain_match(domain: &str, suffix: &str) -> bool {
+    suffix == domain
+        || (is_host_name(domain)
+            && domain.ends_with(suffix)
+            && domain[(domain.len() - suffix.len() - 1)..].starts_with('.'))
+}
+
+/// Normalize `domain`, as passed to the lookup APIs of a `CookieStore` (e.g.
+/// [`CookieStore::get`](crate::CookieStore::get)), to the (IDNA ASCII, lowercase) form in which
+/// cookie domains are stored; e.g. `Bücher.example` becomes `xn--bcher-kva.example`. If `domain`
+/// cannot be normalized, it is returned unchanged (and so will not match any stored cookie
+/// domain).
+pub(crate) fn normalize(domain: &str) -> Cow<'_, str> {
+    let domain = strip_trailing_dot(domain);
+    if domain
+        .bytes()
+        .all(|b| b.is_ascii() && !b.is_ascii_uppercase())
+    {
+        Cow::Borrowed(domain)
+    } else {
+        idna::domain_to_ascii(domain).map_or(Cow::Borrowed(domain), Cow::Owned)
+    }
+}
+
+/// Strip the trailing '.' of an absolute (fully-qualified) domain name, e.g. `example.com.`, so
+/// that it is treated identically to the relative form `example.com`, as browsers do
+fn strip_trailing_dot(domain: &str) -> &str {
+    match domain.strip_suffix('.') {
+        Some(stripped) if !stripped.is_empty() => stripped,
+        _ => domain,
+    }
 }
 
 /// The domain of a `Cookie`
 #[derive(PartialEq, Eq, Clone, Debug, Hash, PartialOrd, Ord)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum CookieDomain {
     /// No Domain attribute in Set-Cookie header
     HostOnly(String),
@@ -57,35 +107,53 @@ impl CookieDomain {
     pub fn host_only(request_url: &Url) -> Result<CookieDomain, CookieError> {
         request_url
             .host()
-            .ok_or(CookieError::NonRelativeScheme)
+            .ok_or_else(|| CookieError::NonRelativeScheme(Default::default()))
             .map(|h| match h {
-                Host::Domain(d) => CookieDomain::HostOnly(d.into()),
+                Host::Domain(d) => CookieDomain::HostOnly(strip_trailing_dot(d).into()),
                 Host::Ipv4(addr) => CookieDomain::HostOnly(format!("{}", addr)),
                 Host::Ipv6(addr) => CookieDomain::HostOnly(format!("[{}]", addr)),
             })
     }
 
+    /// Get the `CookieDomain` for a Domain attribute-value `domain` of a cookie received from
+    /// `request_url`, per [IETF RFC6265 Section 5.3](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3):
+    /// an empty `domain` yields the HostOnly variant for `request_url`, otherwise `domain` must
+    /// domain-match `request_url`.
+    pub fn from_str_with_url(domain: &str, request_url: &Url) -> Result<CookieDomain, CookieError> {
+        match CookieDomain::try_from(domain).map_err(|_| CookieError::Parse)? {
+            CookieDomain::Empty | CookieDomain::NotPresent => CookieDomain::host_only(request_url),
+            d if d.matches(request_url) => Ok(d),
+            d => Err(CookieError::DomainMismatch(crate::cookie::ErrorContext {
+                name: None,
+                domain: d.as_str().map(str::to_owned),
+                request_host: request_url.host_str().map(str::to_owned),
+            })),
+        }
+    }
+
     /// Tests if the given `url::Url` meets the domain-match criteria
     pub fn matches(&self, request_url: &Url) -> bool {
-        if let Some(url_host) = request_url.host_str() {
-            match *self {
-                CookieDomain::HostOnly(ref host) => host == url_host,
-                CookieDomain::Suffix(ref suffix) => {
-                    suffix == url_host
-                        || (is_host_name(url_host)
-                            && url_host.ends_with(suffix)
-                            && url_host[(url_host.len() - suffix.len() - 1)..].starts_with('.'))
-                }
-                CookieDomain::NotPresent | CookieDomain::Empty => false, // nothing can match the Empty case
-            }
-        } else {
-            false // not a matchable scheme
+        match request_url.host_str() {
+            Some(url_host) => self.is_suffix_of(url_host),
+            None => false, // not a matchable scheme
+        }
+    }
+
+    /// Tests if the host `domain` meets the domain-match criteria; i.e. for the Suffix variant,
+    /// if `domain` is identical to, or a subdomain of, the domain-attribute, and for the HostOnly
+    /// variant, if `domain` is identical to the host.
+    pub fn is_suffix_of(&self, domain: &str) -> bool {
+        let domain = strip_trailing_dot(domain);
+        match *self {
+            CookieDomain::HostOnly(ref host) => host == domain,
+            CookieDomain::Suffix(ref suffix) => is_domain_match(domain, suffix),
+            CookieDomain::NotPresent | CookieDomain::Empty => false, // nothing can match the Empty case
         }
     }
 
     /// Tests if the given `url::Url` has a request-host identical to the domain attribute
     pub fn host_is_identical(&self, request_url: &Url) -> bool {
-        if let Some(url_host) = request_url.host_str() {
+        if let Some(url_host) = request_url.host_str().map(strip_trailing_dot) {
             match *self {
                 CookieDomain::HostOnly(ref host) => host == url_host,
                 CookieDomain::Suffix(ref suffix) => suffix == url_host,
@@ -97,9 +165,9 @@ impl CookieDomain {
     }
 
     /// Tests if the domain-attribute is a public suffix as indicated by the provided
-    /// `publicsuffix::List`.
+    /// `publicsuffix::List` (or other implementation of `publicsuffix::Psl`).
     #[cfg(feature = "public_suffix")]
-    pub fn is_public_suffix(&self, psl: &List) -> bool {
+    pub fn is_public_suffix<L: Psl + ?Sized>(&self, psl: &L) -> bool {
         if let Some(domain) = self.as_cow().as_ref().map(|d| d.as_bytes()) {
             psl.suffix(domain)
                 // Only consider suffixes explicitly listed in the public suffix list
@@ -112,6 +180,14 @@ impl CookieDomain {
         }
     }
 
+    /// The domain, or `None` for the `Empty` and `NotPresent` variants
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            CookieDomain::HostOnly(ref s) | CookieDomain::Suffix(ref s) => Some(s),
+            CookieDomain::Empty | CookieDomain::NotPresent => None,
+        }
+    }
+
     /// Get a borrowed string representation of the domain. For `Empty` and `NotPresent` variants,
     /// `None` shall be returned;
     pub fn as_cow(&self) -> Option<std::borrow::Cow<'_, str>> {
@@ -126,7 +202,7 @@ impl CookieDomain {
 
 /// Construct a `CookieDomain::Suffix` from a string, stripping a single leading '.' if present.
 /// If the source string is empty, returns the `CookieDomain::Empty` variant.
-impl<'a> TryFrom<&'a str> for CookieDomain {
+impl TryFrom<&str> for CookieDomain {
     type Error = crate::Error;
     fn try_from(value: &str) -> Result<CookieDomain, Self::Error> {
         idna::domain_to_ascii(value.trim())
@@ -135,10 +211,9 @@ impl<'a> TryFrom<&'a str> for CookieDomain {
             .map(|domain| {
                 if domain.is_empty() || "." == domain {
                     CookieDomain::Empty
-                } else if domain.starts_with('.') {
-                    CookieDomain::Suffix(String::from(&domain[1..]))
                 } else {
-                    CookieDomain::Suffix(domain)
+                    let domain = domain.strip_prefix('.').unwrap_or(&domain);
+                    CookieDomain::Suffix(String::from(strip_trailing_dot(domain)))
                 }
             })
     }
@@ -161,7 +236,7 @@ impl<'a, 'c> TryFrom<&'a RawCookie<'c>> for CookieDomain {
                     if domain.is_empty() {
                         CookieDomain::Empty
                     } else {
-                        CookieDomain::Suffix(domain)
+                        CookieDomain::Suffix(String::from(strip_trailing_dot(&domain)))
                     }
                 })
         } else {
@@ -170,6 +245,30 @@ impl<'a, 'c> TryFrom<&'a RawCookie<'c>> for CookieDomain {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for CookieDomain {
+    /// Generates `HostOnly` and `Suffix` variants of (normalized) host names of up to 3 labels,
+    /// or loopback IP addresses, as well as the `NotPresent` and `Empty` variants
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        const LABEL: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+        let domain = if u.ratio(1, 8)? {
+            String::from(*u.choose(&["127.0.0.1", "[::1]", "localhost"])?)
+        } else {
+            let labels = u.int_in_range(1..=3)?;
+            (0..labels)
+                .map(|_| crate::utils::arbitrary_string(u, LABEL, 1..=8))
+                .collect::<arbitrary::Result<Vec<_>>>()?
+                .join(".")
+        };
+        Ok(match u.int_in_range(0..=9)? {
+            0 => CookieDomain::NotPresent,
+            1 => CookieDomain::Empty,
+            2..=5 => CookieDomain::HostOnly(domain),
+            _ => CookieDomain::Suffix(domain),
+        })
+    }
+}
+
 impl<'a> From<&'a CookieDomain> for String {
     fn from(c: &'a CookieDomain) -> String {
         match *c {
@@ -187,7 +286,7 @@ mod tests {
     use url::Url;
 
     use super::CookieDomain;
-    use crate::utils::test::*;
+    use crate::test_util::*;
 
     #[inline]
     fn matches(expected: bool, cookie_domain: &CookieDomain, url: &str) {
@@ -248,6 +347,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn match_candidates() {
+        fn candidates(url: &str) -> Vec<String> {
+            super::match_candidates(&Url::parse(url).unwrap())
+                .map(String::from)
+                .collect()
+        }
+        assert_eq!(
+            candidates("http://a.b.example.com/foo"),
+            vec!["a.b.example.com", "b.example.com", "example.com", "com"]
+        );
+        assert_eq!(
+            candidates("http://foo..example.com"),
+            vec!["foo..example.com", ".example.com", "example.com", "com"]
+        );
+        assert_eq!(candidates("http://localhost:8080"), vec!["localhost"]);
+        assert_eq!(candidates("http://127.0.0.1"), vec!["127.0.0.1"]);
+        assert_eq!(candidates("http://[::1]"), vec!["[::1]"]);
+        assert!(candidates("data:nonrelative").is_empty());
+    }
+
     #[test]
     fn from_strs() {
         assert_eq!(
@@ -281,6 +401,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn from_str_with_url() {
+        let request_url = url("http://www.example.com/foo");
+        assert_eq!(
+            Ok(CookieDomain::HostOnly(String::from("www.example.com"))),
+            CookieDomain::from_str_with_url("", &request_url)
+        );
+        assert_eq!(
+            Ok(CookieDomain::Suffix(String::from("example.com"))),
+            CookieDomain::from_str_with_url(".Example.com", &request_url)
+        );
+        assert_eq!(
+            Err(crate::CookieError::DomainMismatch(Default::default())),
+            CookieDomain::from_str_with_url("example.org", &request_url)
+        );
+        assert_eq!(
+            Err(crate::CookieError::DomainMismatch(Default::default())),
+            CookieDomain::from_str_with_url("foo.www.example.com", &request_url)
+        );
+    }
+
+    #[test]
+    fn is_suffix_of() {
+        let suffix = CookieDomain::Suffix(String::from("example.com"));
+        assert!(suffix.is_suffix_of("example.com"));
+        assert!(suffix.is_suffix_of("www.example.com"));
+        assert!(suffix.is_suffix_of("www.example.com."));
+        assert!(!suffix.is_suffix_of("fooexample.com"));
+        assert!(!suffix.is_suffix_of("com"));
+        let host_only = CookieDomain::HostOnly(String::from("example.com"));
+        assert!(host_only.is_suffix_of("example.com"));
+        assert!(!host_only.is_suffix_of("www.example.com"));
+        assert!(!CookieDomain::Empty.is_suffix_of(""));
+        assert_eq!(Some("example.com"), suffix.as_str());
+        assert_eq!(None, CookieDomain::NotPresent.as_str());
+    }
+
+    #[test]
+    fn trailing_dot() {
+        assert_eq!(
+            CookieDomain::Suffix(String::from("example.com")),
+            CookieDomain::try_from("example.com.").expect("unable to parse domain")
+        );
+        assert_eq!(
+            CookieDomain::HostOnly(String::from("example.com")),
+            CookieDomain::host_only(&url("http://example.com./")).expect("unable to parse domain")
+        );
+        let host_only = CookieDomain::host_only(&url("http://example.com")).unwrap();
+        variants(true, &host_only, "http://example.com.");
+        let suffix = CookieDomain::try_from("example.com").unwrap();
+        variants(true, &suffix, "http://www.example.com.");
+        assert!(suffix.host_is_identical(&url("http://example.com.")));
+        assert_eq!(
+            super::match_candidates(&url("http://www.example.com./")).collect::<Vec<_>>(),
+            vec!["www.example.com", "example.com", "com"]
+        );
+    }
+
     #[test]
     fn from_raw_cookie() {
         fn raw_cookie(s: &str) -> RawCookie<'_> {
@@ -369,11 +547,10 @@ mod tests {
 
 #[cfg(all(test, feature = "serde_json"))]
 mod serde_json_tests {
-    use serde_json;
     use std::convert::TryFrom;
 
     use crate::cookie_domain::CookieDomain;
-    use crate::utils::test::*;
+    use crate::test_util::*;
 
     fn encode_decode(cd: &CookieDomain, exp_json: &str) {
         let encoded = serde_json::to_string(cd).unwrap();
@@ -1,5 +1,3 @@
-use std;
-
 #[cfg(feature = "serde")]
 use serde_derive::{Deserialize, Serialize};
 use time::{self, OffsetDateTime};
@@ -7,16 +5,34 @@ use time::{self, OffsetDateTime};
 /// When a given `Cookie` expires
 #[derive(Eq, Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum CookieExpiration {
     /// `Cookie` expires at the given UTC time, as set from either the Max-Age
     /// or Expires attribute of a Set-Cookie header
     #[cfg_attr(feature = "serde", serde(with = "crate::rfc3339_fmt"))]
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::rfc3339_fmt::Timestamp"))]
     AtUtc(OffsetDateTime),
     /// `Cookie` expires at the end of the current `Session`; this means the cookie
     /// is not persistent
     SessionEnd,
 }
 
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for CookieExpiration {
+    /// Generates `SessionEnd`, or `AtUtc` with a whole-second time between the Unix epoch and the
+    /// end of the year 9999
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        if u.arbitrary()? {
+            Ok(CookieExpiration::SessionEnd)
+        } else {
+            let timestamp = u.int_in_range(0..=253_402_300_799)?;
+            OffsetDateTime::from_unix_timestamp(timestamp)
+                .map(CookieExpiration::AtUtc)
+                .map_err(|_| arbitrary::Error::IncorrectFormat)
+        }
+    }
+}
+
 // We directly impl `PartialEq` as the cookie Expires attribute does not include nanosecond precision
 impl std::cmp::PartialEq for CookieExpiration {
     fn eq(&self, other: &Self) -> bool {
@@ -47,9 +63,29 @@ impl CookieExpiration {
             CookieExpiration::SessionEnd => false,
         }
     }
+
+    /// The time at which the `Cookie` expires, or `None` for a non-persistent `Cookie`
+    pub fn as_datetime(&self) -> Option<OffsetDateTime> {
+        match *self {
+            CookieExpiration::AtUtc(expire_tm) => Some(expire_tm),
+            CookieExpiration::SessionEnd => None,
+        }
+    }
+
+    /// The time remaining as of `now` until the `Cookie` expires (zero if already expired), or
+    /// `None` for a non-persistent `Cookie`
+    pub fn remaining(&self, now: OffsetDateTime) -> Option<time::Duration> {
+        self.as_datetime()
+            .map(|expire_tm| (expire_tm - now).max(time::Duration::ZERO))
+    }
+
+    /// Indicates if the `Cookie` persists beyond the end of the current session
+    pub fn is_persistent(&self) -> bool {
+        matches!(*self, CookieExpiration::AtUtc(_))
+    }
 }
 
-const MAX_RFC3339: time::OffsetDateTime = time::macros::date!(9999 - 12 - 31)
+pub(crate) const MAX_RFC3339: time::OffsetDateTime = time::macros::date!(9999 - 12 - 31)
     .with_time(time::macros::time!(23:59:59))
     .assume_utc();
 impl From<u64> for CookieExpiration {
@@ -78,15 +114,27 @@ impl From<cookie::Expiration> for CookieExpiration {
     }
 }
 
+/// The expiration for a Max-Age of `duration`, relative to the system time; see
+/// [`CookieExpiration::from_max_age_at`] to take it relative to e.g. the [`Clock`](crate::Clock)
+/// of a `CookieStore`.
 impl From<time::Duration> for CookieExpiration {
     fn from(duration: time::Duration) -> Self {
+        CookieExpiration::from_max_age_at(duration, time::OffsetDateTime::now_utc())
+    }
+}
+
+impl CookieExpiration {
+    /// The expiration for a Max-Age of `duration`, received at `now_utc`
+    pub fn from_max_age_at(
+        duration: time::Duration,
+        now_utc: time::OffsetDateTime,
+    ) -> CookieExpiration {
         // If delta-seconds is less than or equal to zero (0), let expiry-time
         //    be the earliest representable date and time.  Otherwise, let the
         //    expiry-time be the current date and time plus delta-seconds seconds.
         let utc_tm = if duration.is_zero() {
             time::OffsetDateTime::UNIX_EPOCH
         } else {
-            let now_utc = time::OffsetDateTime::now_utc();
             let d = (MAX_RFC3339 - now_utc).min(duration);
             now_utc + d
         };
@@ -97,16 +145,15 @@ impl From<time::Duration> for CookieExpiration {
 #[cfg(test)]
 mod tests {
     use super::CookieExpiration;
-    use time;
 
-    use crate::utils::test::*;
+    use crate::test_util::*;
 
     #[test]
     fn max_age_bounds() {
-        match CookieExpiration::from(time::Duration::MAX.whole_seconds() as u64 + 1) {
-            CookieExpiration::AtUtc(_) => assert!(true),
-            _ => assert!(false),
-        }
+        assert!(matches!(
+            CookieExpiration::from(time::Duration::MAX.whole_seconds() as u64 + 1),
+            CookieExpiration::AtUtc(_)
+        ));
     }
 
     #[test]
@@ -123,6 +170,15 @@ mod tests {
         assert!(ma.expires_by(&in_minutes(2)));
     }
 
+    #[test]
+    fn max_age_at() {
+        let received = in_days(-1);
+        let ma = CookieExpiration::from_max_age_at(time::Duration::minutes(1), received);
+        assert!(ma.is_expired());
+        assert!(!ma.expires_by(&received));
+        assert!(ma.expires_by(&(received + time::Duration::minutes(1))));
+    }
+
     #[test]
     fn session_end() {
         // SessionEnd never "expires"; lives until end of session
@@ -132,6 +188,24 @@ mod tests {
         assert!(!se.expires_by(&in_days(-1)));
     }
 
+    #[test]
+    fn helpers() {
+        let now = in_days(0);
+        let tmrw = CookieExpiration::from(now + time::Duration::days(1));
+        assert!(tmrw.is_persistent());
+        assert_eq!(Some(now + time::Duration::days(1)), tmrw.as_datetime());
+        assert_eq!(Some(time::Duration::days(1)), tmrw.remaining(now));
+        assert_eq!(
+            Some(time::Duration::ZERO),
+            tmrw.remaining(now + time::Duration::days(2))
+        );
+
+        let se = CookieExpiration::SessionEnd;
+        assert!(!se.is_persistent());
+        assert_eq!(None, se.as_datetime());
+        assert_eq!(None, se.remaining(now));
+    }
+
     #[test]
     fn at_utc() {
         {
@@ -2,33 +2,47 @@
 use serde_derive::{Deserialize, Serialize};
 use std::cmp::max;
 use std::ops::Deref;
+use std::sync::Arc;
 use url::Url;
 
 /// Returns true if `request_url` path-matches `path` per
 /// [IETF RFC6265 Section 5.1.4](https://datatracker.ietf.org/doc/html/rfc6265#section-5.1.4)
 pub fn is_match(path: &str, request_url: &Url) -> bool {
-    CookiePath::parse(path).map_or(false, |cp| cp.matches(request_url))
+    path.starts_with('/') && path_matches(path, request_url)
+}
+
+/// The path-match algorithm, comparing the paths bytewise; as both paths are known to be valid
+/// UTF-8, there is no need to re-validate (or check for char boundaries in) any sub-slices.
+fn path_matches(cookie_path: &str, request_url: &Url) -> bool {
+    !request_url.cannot_be_a_base() && is_path_match(cookie_path, request_url.path())
+}
+
+/// Returns true if `request_path` path-matches `cookie_path`
+pub(crate) fn is_path_match(cookie_path: &str, request_path: &str) -> bool {
+    let request_path = request_path.as_bytes();
+    let cookie_path = cookie_path.as_bytes();
+    // o  The cookie-path and the request-path are identical.
+    // o  The cookie-path is a prefix of the request-path, and the last
+    //    character of the cookie-path is %x2F ("/").
+    // o  The cookie-path is a prefix of the request-path, and the first
+    //    character of the request-path that is not included in the cookie-
+    //    path is a %x2F ("/") character.
+    request_path.starts_with(cookie_path)
+        && (request_path.len() == cookie_path.len()
+            || cookie_path.ends_with(b"/")
+            || request_path[cookie_path.len()] == b'/')
 }
 
 /// The path of a `Cookie`
 #[derive(PartialEq, Eq, Clone, Debug, Hash, PartialOrd, Ord)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct CookiePath(String, bool);
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct CookiePath(Arc<str>, bool);
 impl CookiePath {
     /// Determine if `request_url` path-matches this `CookiePath` per
     /// [IETF RFC6265 Section 5.1.4](https://datatracker.ietf.org/doc/html/rfc6265#section-5.1.4)
     pub fn matches(&self, request_url: &Url) -> bool {
-        if request_url.cannot_be_a_base() {
-            false
-        } else {
-            let request_path = request_url.path();
-            let cookie_path = &*self.0;
-            // o  The cookie-path and the request-path are identical.
-            cookie_path == request_path
-                || (request_path.starts_with(cookie_path)
-                    && (cookie_path.ends_with('/')
-                        || &request_path[cookie_path.len()..=cookie_path.len()] == "/"))
-        }
+        path_matches(&self.0, request_url)
     }
 
     /// Returns true if this `CookiePath` was set from a Path attribute; this allows us to
@@ -61,7 +75,7 @@ impl CookiePath {
     pub fn default_path(request_url: &Url) -> CookiePath {
         let cp = if request_url.cannot_be_a_base() {
             // non-relative path scheme, default to "/" (uri-path "empty", case 2)
-            "/".into()
+            String::from("/")
         } else {
             let path = request_url.path();
             match path.rfind('/') {
@@ -69,7 +83,7 @@ impl CookiePath {
                 Some(i) => path[0..max(i, 1)].into(), // case 4 (subsumes case 3)
             }
         };
-        CookiePath(cp, false)
+        CookiePath(Arc::from(cp), false)
     }
 
     /// Attempt to parse `path` as a `CookiePath`; if unsuccessful, the default-path of
@@ -85,11 +99,36 @@ impl CookiePath {
     /// `None` is returned.
     pub fn parse(path: &str) -> Option<CookiePath> {
         if path.starts_with('/') {
-            Some(CookiePath(String::from(path), true))
+            Some(CookiePath(Arc::from(path), true))
         } else {
             None
         }
     }
+
+    /// Replace the path string with the equivalent `interned` instance, so that it may be shared
+    /// with the keys of a `CookieStore`
+    pub(crate) fn with_interned(self, interned: Arc<str>) -> CookiePath {
+        debug_assert_eq!(*self.0, *interned);
+        CookiePath(interned, self.1)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for CookiePath {
+    /// Generates paths of up to 3 segments, either as if from a Path attribute or as the
+    /// default-path of a request-uri
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        const SEGMENT: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789-._~";
+        let segments = u.int_in_range(0..=3)?;
+        let mut path = String::from("/");
+        for i in 0..segments {
+            if i > 0 {
+                path.push('/');
+            }
+            path.push_str(&crate::utils::arbitrary_string(u, SEGMENT, 1..=8)?);
+        }
+        Ok(CookiePath(Arc::from(path), u.arbitrary()?))
+    }
 }
 
 impl AsRef<str> for CookiePath {
@@ -105,15 +144,15 @@ impl Deref for CookiePath {
     }
 }
 
-impl<'a> From<&'a CookiePath> for String {
+impl From<&CookiePath> for String {
     fn from(cp: &CookiePath) -> String {
-        cp.0.clone()
+        String::from(&*cp.0)
     }
 }
 
 impl From<CookiePath> for String {
     fn from(cp: CookiePath) -> String {
-        cp.0
+        String::from(&*cp.0)
     }
 }
 
@@ -143,6 +182,7 @@ mod tests {
     fn do_match(exp: bool, cp: &str, rp: &str) {
         let url = Url::parse(&format!("http://example.com{}", rp))
             .expect("unable to parse url in do_match");
+        assert_eq!(exp, super::is_match(cp, &url));
         let cp = CookiePath::parse(cp).expect("unable to parse CookiePath in do_match");
         assert!(
             exp == cp.matches(&url),
@@ -164,6 +204,9 @@ mod tests {
     fn bad_paths() {
         assert!(CookiePath::parse("").is_none());
         assert!(CookiePath::parse("a/foo").is_none());
+        let url = Url::parse("http://example.com/a/foo").unwrap();
+        assert!(!super::is_match("", &url));
+        assert!(!super::is_match("a/foo", &url));
     }
 
     #[test]
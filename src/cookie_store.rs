@@ -1,14 +1,34 @@
+use std::borrow::Cow;
 use std::io::{BufRead, Write};
+use std::mem::size_of;
 use std::ops::Deref;
+use std::sync::{Arc, Mutex, PoisonError};
 
-use cookie::Cookie as RawCookie;
-use log::debug;
+use cookie::{Cookie as RawCookie, CookieJar, SameSite};
+#[cfg(feature = "serde")]
+use serde_derive::{Deserialize, Serialize};
 use url::Url;
 
-use crate::cookie::Cookie;
-use crate::cookie_domain::is_match as domain_match;
+use crate::audit::{AuditEntry, AuditLog};
+use crate::change_log::ChangeLog;
+use crate::clock::{Clock, SharedClock};
+use crate::cookie::{Cookie, CookieIdentity, ErrorContext, SourceScheme};
+use crate::cookie_date::DateParsing;
+use crate::cookie_domain::host_match_candidates;
+use crate::cookie_domain::match_candidates as domain_match_candidates;
+use crate::cookie_domain::normalize as normalize_domain;
+use crate::cookie_expiration::CookieExpiration;
 use crate::cookie_path::is_match as path_match;
-use crate::utils::{is_http_scheme, is_secure};
+use crate::cookie_path::is_path_match;
+use crate::cookie_transform::{CookieTransform, SharedTransform};
+use crate::domain_index::DomainIndex;
+use crate::domain_rule::DomainRule;
+use crate::frozen::FrozenCookieStore;
+use crate::interner::Interner;
+use crate::request_context::RequestContext;
+use crate::tombstone::{Tombstone, Tombstones};
+use crate::trustworthy_origins::TrustworthyOrigins;
+use crate::utils::{is_glob_match, is_http_scheme, Buffer};
 use crate::CookieError;
 
 #[cfg(feature = "preserve_order")]
@@ -20,9 +40,11 @@ type Map<K, V> = IndexMap<K, V>;
 #[cfg(not(feature = "preserve_order"))]
 type Map<K, V> = HashMap<K, V>;
 
-type NameMap = Map<String, Cookie<'static>>;
-type PathMap = Map<String, NameMap>;
-type DomainMap = Map<String, PathMap>;
+// Keys are shared via `CookieStore::interned`. The `PathMap` of each domain is shared between a
+// store and its snapshots, and copied on write.
+type NameMap = Map<Arc<str>, Cookie<'static>>;
+type PathMap = Map<Arc<str>, NameMap>;
+type DomainMap = Map<Arc<str>, Arc<PathMap>>;
 
 #[derive(PartialEq, Clone, Debug, Eq)]
 pub enum StoreAction {
@@ -32,10 +54,448 @@ pub enum StoreAction {
     ExpiredExisting,
     /// The `Cookie` was added to the store, replacing an existing entry
     UpdatedExisting,
+    /// The `Cookie` was not added to the store, as the store is full and its [`OverflowPolicy`]
+    /// is [`OverflowPolicy::Ignore`], or as it would replace an existing `Cookie` when inserted
+    /// via [`CookieStore::insert_if_absent`]
+    Ignored,
+}
+
+/// Take ownership of the contents of `shared`, cloning them only if shared with a snapshot
+fn unshare<T: Clone>(shared: Arc<T>) -> T {
+    Arc::try_unwrap(shared).unwrap_or_else(|shared| T::clone(&shared))
+}
+
+/// The number of cookies in `path_map`
+fn domain_len(path_map: &Arc<PathMap>) -> usize {
+    path_map.values().map(NameMap::len).sum()
+}
+
+/// Replace the (interned) path of a `Cookie` being removed from the store with an unshared
+/// instance, so that the interned keys may be released
+fn unshare_path(mut cookie: Cookie<'static>) -> Cookie<'static> {
+    let unshared = Arc::from(&*cookie.path);
+    cookie.path = cookie.path.with_interned(unshared);
+    cookie
+}
+
+/// A change to the contents of a [`CookieStore`], as reported to an observer registered via
+/// [`CookieStore::set_observer`]
+#[derive(Debug)]
+pub enum StoreEvent<'a> {
+    /// The `Cookie` was added to the store
+    Inserted(&'a Cookie<'static>),
+    /// The `new` `Cookie` was added to the store, replacing the `old` entry
+    Updated {
+        old: &'a Cookie<'static>,
+        new: &'a Cookie<'static>,
+    },
+    /// The `Cookie` already in the store was expired by the receipt of an __expired__ `Cookie`
+    Expired(&'a Cookie<'static>),
+    /// The `Cookie` was removed from the store
+    Removed(&'a Cookie<'static>),
+}
+
+/// An owned [`StoreEvent`], as received from the channels returned by
+/// [`CookieStore::subscribe`]
+#[derive(PartialEq, Clone, Debug)]
+pub enum OwnedStoreEvent {
+    /// The `Cookie` was added to the store
+    Inserted(Cookie<'static>),
+    /// The `new` `Cookie` was added to the store, replacing the `old` entry
+    Updated {
+        old: Box<Cookie<'static>>,
+        new: Box<Cookie<'static>>,
+    },
+    /// The `Cookie` already in the store was expired by the receipt of an __expired__ `Cookie`
+    Expired(Cookie<'static>),
+    /// The `Cookie` was removed from the store
+    Removed(Cookie<'static>),
+}
+
+impl From<&StoreEvent<'_>> for OwnedStoreEvent {
+    fn from(event: &StoreEvent<'_>) -> Self {
+        match *event {
+            StoreEvent::Inserted(cookie) => OwnedStoreEvent::Inserted(cookie.clone()),
+            StoreEvent::Updated { old, new } => OwnedStoreEvent::Updated {
+                old: Box::new(old.clone()),
+                new: Box::new(new.clone()),
+            },
+            StoreEvent::Expired(cookie) => OwnedStoreEvent::Expired(cookie.clone()),
+            StoreEvent::Removed(cookie) => OwnedStoreEvent::Removed(cookie.clone()),
+        }
+    }
+}
+
+/// The sending half of a channel returned by [`CookieStore::subscribe`]
+#[derive(Clone)]
+enum Subscriber {
+    Channel(std::sync::mpsc::Sender<OwnedStoreEvent>),
+    #[cfg(feature = "tokio")]
+    Tokio(tokio::sync::mpsc::UnboundedSender<OwnedStoreEvent>),
+}
+
+impl Subscriber {
+    /// Send `event`, returning false if the receiving half of the channel has been dropped
+    fn send(&self, event: OwnedStoreEvent) -> bool {
+        match self {
+            Subscriber::Channel(sender) => sender.send(event).is_ok(),
+            #[cfg(feature = "tokio")]
+            Subscriber::Tokio(sender) => sender.send(event).is_ok(),
+        }
+    }
+}
+
+type Callback = Arc<dyn Fn(&StoreEvent<'_>) + Send + Sync>;
+
+/// The observer of the changes made to a `CookieStore`, and any subscribers to those changes;
+/// see [`CookieStore::set_observer`] and [`CookieStore::subscribe`]
+#[derive(Default)]
+struct Observer {
+    callback: Option<Callback>,
+    // subscribers whose receivers have been dropped are removed on the next notification
+    subscribers: Mutex<Vec<Subscriber>>,
+    audit_log: Option<AuditLog>,
+    tombstones: Option<Tombstones>,
+    change_log: Option<ChangeLog>,
+}
+
+impl Observer {
+    /// Notify of `event`, by which the store reached `generation`
+    fn notify(&self, event: StoreEvent<'_>, generation: u64) {
+        self.notify_from(event, None, generation)
+    }
+
+    /// As `notify`, for an `event` caused by a `Cookie` received from `source_url` if any
+    fn notify_from(&self, event: StoreEvent<'_>, source_url: Option<&Url>, generation: u64) {
+        if let Some(audit_log) = &self.audit_log {
+            audit_log.record(&event, source_url);
+        }
+        if let Some(tombstones) = &self.tombstones {
+            tombstones.record(&event);
+        }
+        if let Some(change_log) = &self.change_log {
+            change_log.record(&event, generation);
+        }
+        if let Some(callback) = &self.callback {
+            callback(&event)
+        }
+        let mut subscribers = self
+            .subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !subscribers.is_empty() {
+            let event = OwnedStoreEvent::from(&event);
+            subscribers.retain(|subscriber| subscriber.send(event.clone()));
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.callback.is_none()
+            && self.audit_log.is_none()
+            && self.tombstones.is_none()
+            && self.change_log.is_none()
+            && self
+                .subscribers
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .is_empty()
+    }
+}
+
+impl Clone for Observer {
+    fn clone(&self) -> Self {
+        Observer {
+            callback: self.callback.clone(),
+            subscribers: Mutex::new(
+                self.subscribers
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone(),
+            ),
+            audit_log: self.audit_log.clone(),
+            tombstones: self.tombstones.clone(),
+            change_log: self.change_log.clone(),
+        }
+    }
+}
+
+impl std::fmt::Debug for Observer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Observer").finish()
+    }
+}
+
+/// How [`CookieStore::merge`] resolves a conflict between two cookies with the same domain, path,
+/// and name
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum MergeStrategy {
+    /// Keep whichever `Cookie` expires later; a non-persistent `Cookie` (expiring at the end of
+    /// the session) is considered to expire later than any persistent `Cookie`. If the cookies
+    /// expire at the same time, the existing `Cookie` is kept.
+    PreferNewerExpiry,
+    /// Keep the existing `Cookie` in the store being merged into
+    PreferSelf,
+    /// Keep the `Cookie` from the store being merged from
+    PreferOther,
+}
+
+/// Which cookies are evicted to make room for a new `Cookie` in a full [`CookieStore`]; see
+/// [`OverflowPolicy::Evict`]. __Expired__ cookies are always evicted first.
+#[derive(PartialEq, Clone, Copy, Debug, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum EvictionPolicy {
+    /// Evict the `Cookie` created earliest; cookies without a recorded creation-time (e.g. those
+    /// loaded from data saved by earlier versions) are evicted before any others
+    #[default]
+    OldestCreated,
+    /// Evict the `Cookie` which expires soonest; a non-persistent `Cookie` (expiring at the end
+    /// of the session) is considered to expire later than any persistent `Cookie`
+    SoonestExpiring,
+}
+
+/// How [`CookieStore::insert`] handles a new `Cookie` which would exceed the capacity limits of
+/// the store, as set via [`CookieStore::with_max_cookies`] and
+/// [`CookieStore::with_max_cookies_per_domain`], or which would exceed the byte budget set via
+/// [`CookieStore::with_max_bytes_per_domain`]. Updates to cookies already in the store are
+/// always permitted by the limits on the number of cookies.
+///
+/// Before the policy is applied, any __expired__ cookies are purged from the store (as per
+/// [`CookieStore::purge_expired`]) to make room; as with any removal, an observer is sent a
+/// [`StoreEvent::Removed`] for each, and tombstones are recorded for them if enabled.
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum OverflowPolicy {
+    /// Evict cookies per the `EvictionPolicy` until the new `Cookie` fits
+    Evict(EvictionPolicy),
+    /// Reject the new `Cookie` with `CookieError::StoreFull`
+    Reject,
+    /// Discard the new `Cookie`, returning `Ok(StoreAction::Ignored)`
+    Ignore,
+}
+
+/// Bounds on the lifetime of the cookies accepted by [`CookieStore::insert`], overriding the
+/// expiry requested by the server, e.g. to bound the retention of credentials; see
+/// [`CookieStore::with_ttl_policy`]. The default policy leaves the expiry of cookies unchanged.
+#[derive(PartialEq, Clone, Copy, Debug, Eq, Default)]
+pub struct TtlPolicy {
+    /// If set, cookies expire at most this long after being received
+    pub max_lifetime: Option<time::Duration>,
+    /// If set, __non-persistent__ cookies are instead made __persistent__, expiring this long
+    /// after being received (subject to `max_lifetime`). Otherwise, __non-persistent__ cookies
+    /// are unaffected by `max_lifetime`, as they expire at the end of the session.
+    pub session_lifetime: Option<time::Duration>,
+}
+
+impl TtlPolicy {
+    /// Cap the lifetime of cookies to `max_lifetime`
+    pub fn with_max_lifetime(self, max_lifetime: time::Duration) -> TtlPolicy {
+        TtlPolicy {
+            max_lifetime: Some(max_lifetime),
+            ..self
+        }
+    }
+
+    /// Make __non-persistent__ cookies expire `session_lifetime` after being received
+    pub fn with_session_lifetime(self, session_lifetime: time::Duration) -> TtlPolicy {
+        TtlPolicy {
+            session_lifetime: Some(session_lifetime),
+            ..self
+        }
+    }
+
+    /// Apply the policy to `cookie`, received at `now_utc`
+    fn apply(&self, cookie: &mut Cookie<'_>, now_utc: time::OffsetDateTime) {
+        if let (CookieExpiration::SessionEnd, Some(lifetime)) =
+            (&cookie.expires, self.session_lifetime)
+        {
+            cookie.expires = TtlPolicy::expiry(now_utc, lifetime);
+        }
+        if let (CookieExpiration::AtUtc(expires), Some(lifetime)) =
+            (&cookie.expires, self.max_lifetime)
+        {
+            let latest = TtlPolicy::expiry(now_utc, lifetime);
+            if latest.expires_by(expires) {
+                cookie.expires = latest;
+            }
+        }
+    }
+
+    /// The expiry `lifetime` after `now_utc`, saturating at the latest (or earliest)
+    /// representable expiry rather than overflowing, e.g. for a `lifetime` of `Duration::MAX`
+    fn expiry(now_utc: time::OffsetDateTime, lifetime: time::Duration) -> CookieExpiration {
+        match now_utc.checked_add(lifetime) {
+            Some(expires) => CookieExpiration::from(expires),
+            None if lifetime.is_negative() => {
+                CookieExpiration::AtUtc(time::OffsetDateTime::UNIX_EPOCH)
+            }
+            None => CookieExpiration::from(crate::cookie_expiration::MAX_RFC3339),
+        }
+    }
+}
+
+impl Default for OverflowPolicy {
+    fn default() -> Self {
+        OverflowPolicy::Evict(EvictionPolicy::default())
+    }
+}
+
+/// Whether room could be made for a new `Cookie` per the [`OverflowPolicy`] of the store
+enum Room {
+    /// The `Cookie` may be stored
+    Available,
+    /// The `Cookie` is discarded, per `OverflowPolicy::Ignore` or as no room can be made
+    Discard,
+    /// The `Cookie` is rejected, per `OverflowPolicy::Reject`
+    Reject,
+}
+
+/// The differences between two [`CookieStore`]s, as returned by [`CookieStore::diff`]
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct StoreDiff<'a> {
+    /// Cookies present only in the other store
+    pub added: Vec<&'a Cookie<'static>>,
+    /// Cookies present only in this store
+    pub removed: Vec<&'a Cookie<'static>>,
+    /// Cookies present in both stores (i.e. with the same domain, path, and name) which differ,
+    /// as (`Cookie` in this store, `Cookie` in the other store) pairs
+    pub changed: Vec<(&'a Cookie<'static>, &'a Cookie<'static>)>,
+}
+
+impl<'a> StoreDiff<'a> {
+    /// Returns true if there are no differences between the stores
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The changes made to the contents of a [`CookieStore`] since a given generation, as returned
+/// by [`CookieStore::export_changes_since`]
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct StoreChanges<'a> {
+    /// The generation of the store as of the export, from which the next export may continue
+    pub generation: u64,
+    /// Cookies added to the store since the given generation
+    pub added: Vec<&'a Cookie<'static>>,
+    /// Cookies in the store as of the given generation which have since been replaced or
+    /// expired
+    pub updated: Vec<&'a Cookie<'static>>,
+    /// The identities of cookies in the store as of the given generation which have since been
+    /// removed
+    pub removed: Vec<CookieIdentity>,
+}
+
+impl<'a> StoreChanges<'a> {
+    /// Returns true if no changes have been made since the given generation
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// A `Cookie` to be sent in a request, along with its rendering for the `Cookie` header, as
+/// yielded by [`CookieStore::get_request_cookies_detailed`]
+#[derive(PartialEq, Clone, Debug)]
+pub struct RequestCookie<'a> {
+    /// The `Cookie`, for access to its attributes
+    pub cookie: &'a Cookie<'static>,
+    /// The `name=value` pair of the `Cookie`, as rendered in the `Cookie` header
+    pub pair: String,
+}
+
+/// The reason a `Cookie` is not sent in a request, as reported by [`CookieStore::match_report`]
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum MatchExclusion {
+    /// The `Cookie` has expired
+    Expired,
+    /// The domain of the `Cookie` does not domain-match the request-uri
+    DomainMismatch,
+    /// The path of the `Cookie` does not path-match the request-uri
+    PathMismatch,
+    /// The `Cookie` is HttpOnly and the request-uri is not HTTP(S)
+    HttpOnly,
+    /// The `Cookie` is Secure and the request-uri is not trustworthy
+    Secure,
+    /// The `Cookie` was set over a different scheme, and the store is scheme-bound; see
+    /// [`CookieStore::with_scheme_bound`]
+    SchemeBound,
+    /// The `Cookie` was set from a different port, and the store is port-bound; see
+    /// [`CookieStore::with_port_bound`]
+    PortBound,
+    /// The SameSite attribute of the `Cookie` does not permit the request context
+    SameSite,
+}
+
+/// Whether a `Cookie` is sent in a request, as reported by [`CookieStore::match_report`]
+#[derive(PartialEq, Clone, Debug)]
+pub struct CookieMatch<'a> {
+    /// The `Cookie`
+    pub cookie: &'a Cookie<'static>,
+    /// The reason the `Cookie` is not sent, or `None` if it is sent
+    pub exclusion: Option<MatchExclusion>,
+}
+
+impl<'a> CookieMatch<'a> {
+    /// Returns true if the `Cookie` is sent in the request
+    pub fn is_match(&self) -> bool {
+        self.exclusion.is_none()
+    }
+}
+
+/// Summary statistics of the contents of a [`CookieStore`], as returned by
+/// [`CookieStore::stats`]
+#[derive(PartialEq, Clone, Debug, Eq, Default)]
+pub struct StoreStats {
+    /// The number of cookies (including __expired__) in the store
+    pub cookies: usize,
+    /// The number of __expired__ cookies in the store
+    pub expired: usize,
+    /// The number of cookies (including __expired__) stored under each domain
+    pub cookies_per_domain: Vec<(String, usize)>,
+    /// The estimated number of heap bytes used by the store; see
+    /// [`CookieStore::memory_footprint`]
+    pub heap_bytes: usize,
+}
+
+/// A violation of the internal invariants of a [`CookieStore`], as reported by
+/// [`CookieStore::validate`]
+#[derive(PartialEq, Clone, Debug, Eq)]
+pub enum StoreViolation {
+    /// A `Cookie` is stored under keys which do not agree with its own domain, path, and name
+    KeyMismatch {
+        /// The keys under which the `Cookie` is stored
+        key: CookieIdentity,
+        /// The identity of the `Cookie` itself
+        cookie: CookieIdentity,
+    },
+    /// A domain key is not in normalized (lowercase, ASCII) form, and so will never be matched
+    UnnormalizedDomain(String),
+    /// No cookies are stored under the domain key
+    EmptyDomain(String),
+    /// No cookies are stored under the (domain, path) keys
+    EmptyPath(String, String),
+    /// A `Cookie` has neither a HostOnly nor Suffix domain
+    UnspecifiedDomain(CookieIdentity),
+    /// A `Cookie` has a path which does not begin with `/`
+    InvalidPath(CookieIdentity),
+    /// A `Cookie` has an expiry time which cannot be represented in an Expires attribute
+    InvalidExpiration(CookieIdentity),
 }
 
 pub type StoreResult<T> = Result<T, crate::Error>;
+
+/// The location of the latest public suffix list, as fetched by
+/// [`CookieStore::download_suffix_list`]
+#[cfg(feature = "psl_download")]
+pub const PUBLIC_SUFFIX_LIST_URL: &str = "https://publicsuffix.org/list/public_suffix_list.dat";
 pub type InsertResult = Result<StoreAction, CookieError>;
+/// The result of [`CookieStore::upsert`]: the [`StoreAction`] taken, along with any `Cookie`
+/// replaced
+pub type UpsertResult = Result<(StoreAction, Option<Cookie<'static>>), CookieError>;
+
+/// A typical server limit on the size of a request header, suitable as the `max_len` of
+/// [`CookieStore::cookie_header_values`]
+pub const DEFAULT_MAX_COOKIE_HEADER_LEN: usize = 8 * 1024;
 
 #[derive(Debug, Default, Clone)]
 /// An implementation for storing and retrieving [`Cookie`]s per the path and domain matching
@@ -43,9 +503,97 @@ pub type InsertResult = Result<StoreAction, CookieError>;
 pub struct CookieStore {
     /// Cookies stored by domain, path, then name
     cookies: DomainMap,
+    /// The domain keys of `cookies`, ordered such that subdomains may be looked up by range
+    domain_index: DomainIndex,
+    /// Shared instances of the domain, path, and name keys of `cookies`
+    interned: Interner,
+    /// The source of the current time for all expiry decisions
+    clock: SharedClock,
+    /// If set, notified of each change to the contents of `cookies`
+    observer: Option<Observer>,
+    /// If set, only cookies with a domain matching one of these rules are stored
+    domain_allowlist: Option<Vec<DomainRule>>,
+    /// Cookies with a domain matching any of these rules are not stored
+    domain_denylist: Vec<DomainRule>,
+    /// Incremented on each change to the contents of `cookies`
+    generation: u64,
+    /// The `generation` as of the last call to `mark_clean`
+    clean_generation: u64,
+    /// If set, cookies lacking a SameSite attribute are treated as `SameSite=Lax`
+    same_site_lax_by_default: bool,
+    /// If set, cookies are only returned for requests with the scheme they were received from
+    scheme_bound: bool,
+    /// If set, cookies are only returned for requests to the port they were received from
+    port_bound: bool,
+    /// The request-uris to and from which Secure cookies may be sent and received
+    trustworthy_origins: TrustworthyOrigins,
+    /// If set, the maximum number of cookies (including __expired__) in the store
+    max_cookies: Option<usize>,
+    /// If set, the maximum number of cookies (including __expired__) stored under each domain
+    max_cookies_per_domain: Option<usize>,
+    /// If set, the maximum total size in bytes of the cookies stored under each domain
+    max_bytes_per_domain: Option<usize>,
+    /// How a new cookie exceeding `max_cookies` or `max_cookies_per_domain` is handled
+    overflow_policy: OverflowPolicy,
+    /// If set, __non-persistent__ cookies are saved along with __persistent__ cookies
+    persist_session_cookies: bool,
+    /// If set, only first-party cookies (see `Cookie::is_first_party`) are saved
+    first_party_only_persistence: bool,
+    /// How the dates of `Expires` attributes are parsed by `parse` and friends
+    date_parsing: DateParsing,
+    /// If set, rewrites each cookie received by `insert` before it is stored
+    transform: Option<SharedTransform>,
+    /// Bounds on the lifetime of each cookie received by `insert`
+    ttl_policy: TtlPolicy,
+    /// If set, cookies not accessed for this long are purged by `insert`
+    max_idle: Option<time::Duration>,
+    /// The time at which `insert` last purged idle cookies
+    idle_purged_at: Option<time::OffsetDateTime>,
     #[cfg(feature = "public_suffix")]
-    /// If set, enables [public suffix](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3) rejection based on the provided `publicsuffix::List`
-    public_suffix_list: Option<publicsuffix::List>,
+    /// If set, enables [public suffix](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3) rejection based on the provided `SuffixList`
+    public_suffix_list: Option<SuffixList>,
+}
+
+/// Parse `list`, in the public suffix list format, as a `publicsuffix::List`
+#[cfg(feature = "public_suffix")]
+fn parse_suffix_list(list: &str) -> StoreResult<publicsuffix::List> {
+    list.parse()
+        .map_err(|e| crate::Error::from(format!("unable to parse public suffix list: {:?}", e)))
+}
+
+/// The source of public suffix data for a `CookieStore`
+#[cfg(feature = "public_suffix")]
+#[derive(Debug, Clone)]
+enum SuffixList {
+    /// A list provided by the user
+    List(publicsuffix::List),
+    /// The list compiled into the [psl](https://crates.io/crates/psl) crate
+    #[cfg(feature = "psl")]
+    Embedded,
+}
+
+#[cfg(feature = "public_suffix")]
+impl SuffixList {
+    fn is_public_suffix(&self, domain: &crate::CookieDomain) -> bool {
+        match self {
+            SuffixList::List(psl) => domain.is_public_suffix(psl),
+            #[cfg(feature = "psl")]
+            SuffixList::Embedded => domain.is_public_suffix(&psl::List),
+        }
+    }
+
+    /// The registrable domain of `host`, i.e. its public suffix plus one label
+    fn registrable_domain(&self, host: &str) -> Option<String> {
+        use publicsuffix::Psl;
+        let domain = match self {
+            SuffixList::List(psl) => psl.domain(host.as_bytes()),
+            #[cfg(feature = "psl")]
+            SuffixList::Embedded => psl::List.domain(host.as_bytes()),
+        }?;
+        std::str::from_utf8(domain.as_bytes())
+            .ok()
+            .map(str::to_owned)
+    }
 }
 
 impl CookieStore {
@@ -69,6 +617,92 @@ impl CookieStore {
         self.matches(url).into_iter().map(|c| c.name_value())
     }
 
+    /// As [`CookieStore::get_request_values`], but with each name and value percent-encoded, for
+    /// stores holding decoded values (e.g. inserted via [`CookieStore::insert_raw`]) which may
+    /// contain characters not permitted in a `Cookie` header. Values already percent-encoded are
+    /// encoded again; see [`Cookie::value_decoded`].
+    pub fn get_request_values_encoded(
+        &self,
+        url: &Url,
+    ) -> impl Iterator<Item = (Cow<'_, str>, Cow<'_, str>)> {
+        self.matches(url).into_iter().map(|c| {
+            (
+                crate::utils::percent_encode_cookie(c.name()),
+                crate::utils::percent_encode_cookie(c.value()),
+            )
+        })
+    }
+
+    /// As [`CookieStore::get_request_values`], but yielding only the most specific `Cookie` for
+    /// each name when several cookies of the same name match `url` (e.g. from differing paths or
+    /// domains), since many servers mishandle repeated names. The most specific `Cookie` is that
+    /// with the longest path, then with the longest domain, preferring a host-only `Cookie`.
+    pub fn get_request_values_deduped(&self, url: &Url) -> impl Iterator<Item = (&str, &str)> {
+        fn specificity(c: &Cookie<'_>) -> (usize, usize, bool) {
+            let domain_len = c.domain.as_cow().map_or(0, |d| d.len());
+            let host_only = matches!(c.domain, crate::CookieDomain::HostOnly(_));
+            (c.path.len(), domain_len, host_only)
+        }
+        let mut deduped: Vec<&Cookie<'static>> = Vec::new();
+        let mut index_by_name = std::collections::HashMap::new();
+        for cookie in self.matches_iter(url) {
+            match index_by_name.get(cookie.name()) {
+                Some(&i) => {
+                    if specificity(cookie) > specificity(deduped[i]) {
+                        deduped[i] = cookie;
+                    }
+                }
+                None => {
+                    index_by_name.insert(cookie.name(), deduped.len());
+                    deduped.push(cookie);
+                }
+            }
+        }
+        deduped.into_iter().map(|c| c.name_value())
+    }
+
+    /// As [`CookieStore::get_request_values`], but yielding each matching `Cookie` along with its
+    /// rendered `name=value` pair, for callers needing both the pair and the attributes of the
+    /// `Cookie` (e.g. to annotate metrics by SameSite)
+    pub fn get_request_cookies_detailed(
+        &self,
+        url: &Url,
+    ) -> impl Iterator<Item = RequestCookie<'_>> {
+        self.matches(url).into_iter().map(|cookie| {
+            let (name, value) = cookie.name_value();
+            RequestCookie {
+                cookie,
+                pair: format!("{}={}", name, value),
+            }
+        })
+    }
+
+    /// Render the cookies for `url` in the store as one or more values for `Cookie` headers of an
+    /// HTTP request, each at most `max_len` bytes (see [`DEFAULT_MAX_COOKIE_HEADER_LEN`]), for
+    /// servers which reject oversized headers. A single cookie whose `name=value` exceeds
+    /// `max_len` is rendered as a value of its own. Returns an empty `Vec` if no cookies match.
+    pub fn cookie_header_values(&self, url: &Url, max_len: usize) -> Vec<String> {
+        const SEPARATOR: &str = "; ";
+        let mut values = Vec::new();
+        let mut current = String::new();
+        for (name, value) in self.get_request_values(url) {
+            let len = name.len() + 1 + value.len();
+            if !current.is_empty() && current.len() + SEPARATOR.len() + len > max_len {
+                values.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push_str(SEPARATOR);
+            }
+            current.push_str(name);
+            current.push('=');
+            current.push_str(value);
+        }
+        if !current.is_empty() {
+            values.push(current);
+        }
+        values
+    }
+
     /// Store the `cookies` received from `url`
     pub fn store_response_cookies<I: Iterator<Item = RawCookie<'static>>>(
         &mut self,
@@ -88,859 +722,4918 @@ impl CookieStore {
         }
     }
 
+    /// As [`CookieStore::store_response_cookies`], recording `site_for_cookies`, the URL of the
+    /// top-level document under which the response was received, with each `Cookie` as per
+    /// [`CookieStore::insert_for_site`]
+    pub fn store_response_cookies_for_site<I: Iterator<Item = RawCookie<'static>>>(
+        &mut self,
+        cookies: I,
+        url: &Url,
+        site_for_cookies: &Url,
+    ) {
+        for cookie in cookies {
+            if cookie.secure() != Some(true) || cfg!(feature = "log_secure_cookie_values") {
+                debug!("inserting Set-Cookie '{:?}'", cookie);
+            } else {
+                debug!("inserting secure cookie '{}'", cookie.name());
+            }
+
+            if let Err(e) = self.insert_raw_for_site(&cookie, url, site_for_cookies) {
+                debug!("unable to store Set-Cookie: {:?}", e);
+            }
+        }
+    }
+
+    /// Store the cookies of the `Set-Cookie` header values `headers`, received from `url`,
+    /// returning the `InsertResult` of each header value in order. A header value which fails to
+    /// parse results in `Err(CookieError::Parse)`.
+    pub fn store_response_headers_str<'a, I>(&mut self, headers: I, url: &Url) -> Vec<InsertResult>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        headers
+            .map(|header| {
+                let result = self.parse(header, url);
+                if let Err(ref e) = result {
+                    debug!("unable to store Set-Cookie: {:?}", e);
+                }
+                result
+            })
+            .collect()
+    }
+
+    /// Create a `cookie::CookieJar` (from the `cookie` crate) containing the cookies for `url` in
+    /// the store, as returned by [`CookieStore::matches`]. The cookies are added to the jar as
+    /// _original_ cookies, so any subsequent modifications made to the jar are available via
+    /// `CookieJar::delta`; see [`CookieStore::store_cookie_jar_delta`].
+    pub fn to_cookie_jar(&self, url: &Url) -> CookieJar {
+        let mut jar = CookieJar::new();
+        for cookie in self.matches(url) {
+            let mut raw_cookie = cookie.deref().clone();
+            // explicitly set the path, so that removal cookies generated by the jar target
+            // the stored cookie rather than the default-path of `url`
+            raw_cookie.set_path(String::from(&cookie.path));
+            jar.add_original(raw_cookie);
+        }
+        jar
+    }
+
+    /// Store the changes made to `jar` (i.e. the contents of `CookieJar::delta`), treating them
+    /// as cookies received from `url`. Cookies removed from the jar are expired in the store.
+    pub fn store_cookie_jar_delta(&mut self, jar: &CookieJar, url: &Url) {
+        self.store_response_cookies(jar.delta().cloned(), url);
+    }
+
     /// Specify a `publicsuffix::List` for the `CookieStore` to allow [public suffix
     /// matching](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3)
     #[cfg(feature = "public_suffix")]
     pub fn with_suffix_list(self, psl: publicsuffix::List) -> CookieStore {
         CookieStore {
-            cookies: self.cookies,
-            public_suffix_list: Some(psl),
+            public_suffix_list: Some(SuffixList::List(psl)),
+            ..self
         }
     }
 
-    /// Returns true if the `CookieStore` contains an __unexpired__ `Cookie` corresponding to the
-    /// specified `domain`, `path`, and `name`.
-    pub fn contains(&self, domain: &str, path: &str, name: &str) -> bool {
-        self.get(domain, path, name).is_some()
+    /// Create an empty `CookieStore` performing [public suffix
+    /// matching](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3) against the snapshot
+    /// of the list compiled into the [psl](https://crates.io/crates/psl) crate, requiring no I/O
+    /// to obtain a list. The list may be overridden with [`CookieStore::with_suffix_list`] or
+    /// [`CookieStore::update_suffix_list`]. Requires feature `psl`
+    #[cfg(feature = "psl")]
+    pub fn new_with_public_suffix() -> CookieStore {
+        CookieStore {
+            public_suffix_list: Some(SuffixList::Embedded),
+            ..CookieStore::default()
+        }
     }
 
-    /// Returns true if the `CookieStore` contains any (even an __expired__) `Cookie` corresponding
-    /// to the specified `domain`, `path`, and `name`.
-    pub fn contains_any(&self, domain: &str, path: &str, name: &str) -> bool {
-        self.get_any(domain, path, name).is_some()
+    /// Replace the `publicsuffix::List` used by the `CookieStore` for [public suffix
+    /// matching](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3), e.g. to refresh the
+    /// list of a long-lived store. Cookies already in the store are unaffected.
+    #[cfg(feature = "public_suffix")]
+    pub fn update_suffix_list(&mut self, psl: publicsuffix::List) {
+        self.public_suffix_list = Some(SuffixList::List(psl));
     }
 
-    /// Returns a reference to the __unexpired__ `Cookie` corresponding to the specified `domain`,
-    /// `path`, and `name`.
-    pub fn get(&self, domain: &str, path: &str, name: &str) -> Option<&Cookie<'_>> {
-        self.get_any(domain, path, name).and_then(|cookie| {
-            if cookie.is_expired() {
-                None
-            } else {
-                Some(cookie)
-            }
-        })
+    /// Download and parse the latest public suffix list from [`PUBLIC_SUFFIX_LIST_URL`], for use
+    /// with [`CookieStore::with_suffix_list`] or [`CookieStore::update_suffix_list`]. Requires
+    /// feature `psl_download`
+    #[cfg(feature = "psl_download")]
+    pub fn download_suffix_list() -> StoreResult<publicsuffix::List> {
+        let list = ureq::get(PUBLIC_SUFFIX_LIST_URL)
+            .call()?
+            .body_mut()
+            .read_to_string()?;
+        parse_suffix_list(&list)
     }
 
-    /// Returns a mutable reference to the __unexpired__ `Cookie` corresponding to the specified
-    /// `domain`, `path`, and `name`.
-    fn get_mut(&mut self, domain: &str, path: &str, name: &str) -> Option<&mut Cookie<'static>> {
-        self.get_mut_any(domain, path, name).and_then(|cookie| {
-            if cookie.is_expired() {
-                None
-            } else {
-                Some(cookie)
-            }
-        })
+    /// Specify a public suffix list for the `CookieStore`, as with
+    /// [`CookieStore::with_suffix_list`], parsed from `reader` in the [public suffix list
+    /// format](https://github.com/publicsuffix/list/wiki/Format)
+    #[cfg(feature = "public_suffix")]
+    pub fn with_suffix_list_from_reader<R: std::io::Read>(
+        self,
+        mut reader: R,
+    ) -> StoreResult<CookieStore> {
+        let mut list = String::new();
+        reader.read_to_string(&mut list)?;
+        Ok(self.with_suffix_list(parse_suffix_list(&list)?))
     }
 
-    /// Returns a reference to the (possibly __expired__) `Cookie` corresponding to the specified
-    /// `domain`, `path`, and `name`.
-    pub fn get_any(&self, domain: &str, path: &str, name: &str) -> Option<&Cookie<'static>> {
-        self.cookies.get(domain).and_then(|domain_cookies| {
-            domain_cookies
-                .get(path)
-                .and_then(|path_cookies| path_cookies.get(name))
-        })
+    /// Specify a public suffix list for the `CookieStore`, as with
+    /// [`CookieStore::with_suffix_list`], parsed from the file at `path` in the [public suffix
+    /// list format](https://github.com/publicsuffix/list/wiki/Format), e.g. a copy of
+    /// `public_suffix_list.dat`
+    #[cfg(feature = "public_suffix")]
+    pub fn with_suffix_list_from_path<P: AsRef<std::path::Path>>(
+        self,
+        path: P,
+    ) -> StoreResult<CookieStore> {
+        self.with_suffix_list_from_reader(std::fs::File::open(path)?)
     }
 
-    /// Returns a mutable reference to the (possibly __expired__) `Cookie` corresponding to the
-    /// specified `domain`, `path`, and `name`.
-    fn get_mut_any(
-        &mut self,
-        domain: &str,
-        path: &str,
-        name: &str,
-    ) -> Option<&mut Cookie<'static>> {
-        self.cookies.get_mut(domain).and_then(|domain_cookies| {
-            domain_cookies
-                .get_mut(path)
-                .and_then(|path_cookies| path_cookies.get_mut(name))
-        })
+    /// Specify the [`Clock`] used by the `CookieStore` for all expiry decisions, in lieu of the
+    /// default [`SystemClock`](crate::SystemClock)
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> CookieStore {
+        let clock = SharedClock::new(clock);
+        if let Some(audit_log) = self.observer.as_mut().and_then(|o| o.audit_log.as_mut()) {
+            audit_log.set_clock(clock.clone());
+        }
+        if let Some(tombstones) = self.observer.as_mut().and_then(|o| o.tombstones.as_mut()) {
+            tombstones.set_clock(clock.clone());
+        }
+        CookieStore { clock, ..self }
     }
 
-    /// Removes a `Cookie` from the store, returning the `Cookie` if it was in the store
-    pub fn remove(&mut self, domain: &str, path: &str, name: &str) -> Option<Cookie<'static>> {
-        #[cfg(not(feature = "preserve_order"))]
-        fn map_remove<K, V, Q>(map: &mut Map<K, V>, key: &Q) -> Option<V>
-        where
-            K: std::borrow::Borrow<Q> + std::cmp::Eq + std::hash::Hash,
-            Q: std::cmp::Eq + std::hash::Hash + ?Sized,
-        {
-            map.remove(key)
+    /// Only store cookies whose domain matches one of `rules`; any other cookies are rejected
+    /// by [`CookieStore::insert`] with `CookieError::DomainNotPermitted`. Cookies already in the
+    /// store are unaffected.
+    pub fn with_domain_allowlist<I>(self, rules: I) -> CookieStore
+    where
+        I: IntoIterator<Item = DomainRule>,
+    {
+        CookieStore {
+            domain_allowlist: Some(rules.into_iter().collect()),
+            ..self
         }
-        #[cfg(feature = "preserve_order")]
-        fn map_remove<K, V, Q>(map: &mut Map<K, V>, key: &Q) -> Option<V>
-        where
-            K: std::borrow::Borrow<Q> + std::cmp::Eq + std::hash::Hash,
-            Q: std::cmp::Eq + std::hash::Hash + ?Sized,
-        {
-            map.shift_remove(key)
+    }
+
+    /// Never store cookies whose domain matches any of `rules`; such cookies are rejected by
+    /// [`CookieStore::insert`] with `CookieError::DomainNotPermitted`, even if permitted by the
+    /// domain allowlist. Cookies already in the store are unaffected.
+    pub fn with_domain_denylist<I>(self, rules: I) -> CookieStore
+    where
+        I: IntoIterator<Item = DomainRule>,
+    {
+        CookieStore {
+            domain_denylist: rules.into_iter().collect(),
+            ..self
         }
+    }
 
-        let (removed, remove_domain) = match self.cookies.get_mut(domain) {
-            None => (None, false),
-            Some(domain_cookies) => {
-                let (removed, remove_path) = match domain_cookies.get_mut(path) {
-                    None => (None, false),
-                    Some(path_cookies) => {
-                        let removed = map_remove(path_cookies, name);
-                        (removed, path_cookies.is_empty())
-                    }
-                };
+    /// If `lax_by_default` is true, treat cookies lacking a SameSite attribute as
+    /// `SameSite=Lax`, as modern browsers do: such cookies are stored by [`CookieStore::insert`]
+    /// with an explicit `SameSite=Lax` attribute, and cookies already in the store lacking the
+    /// attribute are treated as `SameSite=Lax` by [`CookieStore::matches_with_context`].
+    pub fn with_same_site_lax_by_default(self, lax_by_default: bool) -> CookieStore {
+        CookieStore {
+            same_site_lax_by_default: lax_by_default,
+            ..self
+        }
+    }
 
-                if remove_path {
-                    map_remove(domain_cookies, path);
-                    (removed, domain_cookies.is_empty())
-                } else {
-                    (removed, false)
-                }
-            }
-        };
-
-        if remove_domain {
-            map_remove(&mut self.cookies, domain);
+    /// If `scheme_bound` is true, bind cookies to the scheme of the request-uri they were
+    /// received from, per the [Origin-Bound Cookies](https://github.com/sbingler/origin-bound-cookies)
+    /// proposal: cookies stored by [`CookieStore::insert`] record their
+    /// [`SourceScheme`](crate::SourceScheme), and [`CookieStore::matches`] only returns a cookie
+    /// received from e.g. an `http` request-uri for `http` (and not `https`) requests. Cookies
+    /// without a recorded scheme (e.g. those inserted before enabling this mode) are unaffected.
+    pub fn with_scheme_bound(self, scheme_bound: bool) -> CookieStore {
+        CookieStore {
+            scheme_bound,
+            ..self
         }
+    }
 
-        removed
+    /// If `port_bound` is true, bind cookies to the port of the request-uri they were received
+    /// from: cookies stored by [`CookieStore::insert`] record the port (or the default port of
+    /// the scheme), and [`CookieStore::matches`] only returns a cookie for requests to that same
+    /// port. This departs from RFC6265, under which cookies do not provide isolation by port, but
+    /// is useful e.g. in test environments where many services share `localhost`. Cookies without
+    /// a recorded port (e.g. those inserted before enabling this mode) are unaffected.
+    pub fn with_port_bound(self, port_bound: bool) -> CookieStore {
+        CookieStore { port_bound, ..self }
     }
 
-    /// Returns a collection of references to __unexpired__ cookies that path- and domain-match
-    /// `request_url`, as well as having HttpOnly and Secure attributes compatible with the
-    /// `request_url`.
-    pub fn matches(&self, request_url: &Url) -> Vec<&Cookie<'static>> {
-        // although we domain_match and path_match as we descend through the tree, we
-        // still need to
-        // do a full Cookie::matches() check in the last filter. Otherwise, we cannot
-        // properly deal
-        // with HostOnly Cookies.
-        let cookies = self
-            .cookies
-            .iter()
-            .filter(|&(d, _)| domain_match(d, request_url))
-            .flat_map(|(_, dcs)| {
-                dcs.iter()
-                    .filter(|&(p, _)| path_match(p, request_url))
-                    .flat_map(|(_, pcs)| {
-                        pcs.values()
-                            .filter(|c| !c.is_expired() && c.matches(request_url))
-                    })
-            });
-        match (!is_http_scheme(request_url), !is_secure(request_url)) {
-            (true, true) => cookies
-                .filter(|c| !c.http_only().unwrap_or(false) && !c.secure().unwrap_or(false))
-                .collect(),
-            (true, false) => cookies
-                .filter(|c| !c.http_only().unwrap_or(false))
-                .collect(),
-            (false, true) => cookies.filter(|c| !c.secure().unwrap_or(false)).collect(),
-            (false, false) => cookies.collect(),
+    /// Specify the [`TrustworthyOrigins`](crate::TrustworthyOrigins) policy deciding to which
+    /// request-uris cookies with the Secure attribute are sent by [`CookieStore::matches`] and, if
+    /// enforced, from which they are accepted by [`CookieStore::insert`], in lieu of the default
+    /// policy trusting `https` request-uris and loopback hosts.
+    pub fn with_trustworthy_origins(self, trustworthy_origins: TrustworthyOrigins) -> CookieStore {
+        CookieStore {
+            trustworthy_origins,
+            ..self
         }
     }
 
-    /// Parses a new `Cookie` from `cookie_str` and inserts it into the store.
-    pub fn parse(&mut self, cookie_str: &str, request_url: &Url) -> InsertResult {
-        Cookie::parse(cookie_str, request_url)
-            .and_then(|cookie| self.insert(cookie.into_owned(), request_url))
+    /// Limit the store to at most `max_cookies` cookies (including __expired__), as RFC6265
+    /// [recommends](https://datatracker.ietf.org/doc/html/rfc6265#section-6.1) of user agents. A
+    /// new `Cookie` which would exceed the limit is handled per the [`OverflowPolicy`] of the
+    /// store. Cookies already in the store are unaffected until the next insertion.
+    pub fn with_max_cookies(self, max_cookies: usize) -> CookieStore {
+        CookieStore {
+            max_cookies: Some(max_cookies),
+            ..self
+        }
     }
 
-    /// Converts a `cookie::Cookie` (from the `cookie` crate) into a `cookie_store::Cookie` and
-    /// inserts it into the store.
-    pub fn insert_raw(&mut self, cookie: &RawCookie<'_>, request_url: &Url) -> InsertResult {
-        Cookie::try_from_raw_cookie(cookie, request_url)
-            .and_then(|cookie| self.insert(cookie.into_owned(), request_url))
+    /// Limit the store to at most `max_cookies_per_domain` cookies (including __expired__) under
+    /// each domain. A new `Cookie` which would exceed the limit is handled per the
+    /// [`OverflowPolicy`] of the store, evicting (if so configured) only cookies of the same
+    /// domain.
+    pub fn with_max_cookies_per_domain(self, max_cookies_per_domain: usize) -> CookieStore {
+        CookieStore {
+            max_cookies_per_domain: Some(max_cookies_per_domain),
+            ..self
+        }
     }
 
-    /// Inserts `cookie`, received from `request_url`, into the store, following the rules of the
-    /// [IETF RFC6265 Storage Model](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3). If the
-    /// `Cookie` is __unexpired__ and is successfully inserted, returns
-    /// `Ok(StoreAction::Inserted)`. If the `Cookie` is __expired__ *and* matches an existing
-    /// `Cookie` in the store, the existing `Cookie` wil be `expired()` and
-    /// `Ok(StoreAction::ExpiredExisting)` will be returned.
-    pub fn insert(&mut self, cookie: Cookie<'static>, request_url: &Url) -> InsertResult {
-        if cookie.http_only().unwrap_or(false) && !is_http_scheme(request_url) {
-            // If the cookie was received from a "non-HTTP" API and the
-            // cookie's http-only-flag is set, abort these steps and ignore the
-            // cookie entirely.
-            return Err(CookieError::NonHttpScheme);
+    /// Limit the total size of the cookies (including __expired__) under each domain to
+    /// `max_bytes_per_domain` bytes, as counted by [`Cookie::byte_size`], defending against
+    /// servers setting many or very large cookies; browsers typically enforce a similar budget. A
+    /// new or updated `Cookie` which would exceed the limit is handled per the [`OverflowPolicy`]
+    /// of the store, evicting (if so configured) only cookies of the same domain. A `Cookie`
+    /// which alone exceeds the limit is never stored.
+    pub fn with_max_bytes_per_domain(self, max_bytes_per_domain: usize) -> CookieStore {
+        CookieStore {
+            max_bytes_per_domain: Some(max_bytes_per_domain),
+            ..self
         }
-        #[cfg(feature = "public_suffix")]
-        let mut cookie = cookie;
-        #[cfg(feature = "public_suffix")]
-        if let Some(ref psl) = self.public_suffix_list {
-            // If the user agent is configured to reject "public suffixes"
-            if cookie.domain.is_public_suffix(psl) {
-                // and the domain-attribute is a public suffix:
-                if cookie.domain.host_is_identical(request_url) {
-                    //   If the domain-attribute is identical to the canonicalized
-                    //   request-host:
-                    //     Let the domain-attribute be the empty string.
-                    // (NB: at this point, an empty domain-attribute should be represented
-                    // as the HostOnly variant of CookieDomain)
-                    cookie.domain = crate::cookie_domain::CookieDomain::host_only(request_url)?;
-                } else {
-                    //   Otherwise:
-                    //     Ignore the cookie entirely and abort these steps.
-                    return Err(CookieError::PublicSuffix);
-                }
-            }
+    }
+
+    /// Specify the [`OverflowPolicy`] for new cookies which would exceed the capacity limits of
+    /// the store, in lieu of the default `OverflowPolicy::Evict(EvictionPolicy::OldestCreated)`
+    pub fn with_overflow_policy(self, overflow_policy: OverflowPolicy) -> CookieStore {
+        CookieStore {
+            overflow_policy,
+            ..self
         }
-        if !cookie.domain.matches(request_url) {
-            // If the canonicalized request-host does not domain-match the
-            // domain-attribute:
-            //    Ignore the cookie entirely and abort these steps.
-            return Err(CookieError::DomainMismatch);
+    }
+
+    /// Specify a [`TtlPolicy`] bounding the lifetime of the cookies accepted by
+    /// [`CookieStore::insert`] (and the methods implemented in terms of it), applied after any
+    /// [`CookieTransform`]. Cookies already in the store are unaffected.
+    pub fn with_ttl_policy(self, ttl_policy: TtlPolicy) -> CookieStore {
+        CookieStore { ttl_policy, ..self }
+    }
+
+    /// Purge cookies which have not been accessed for `max_idle`, as if by
+    /// [`CookieStore::purge_idle`], when inserting new cookies via [`CookieStore::insert`] (and
+    /// the methods implemented in terms of it). To bound the cost of inserting, idle cookies are
+    /// purged at most once per half of `max_idle`, so a `Cookie` may remain in the store for up to
+    /// half of `max_idle` after becoming idle.
+    ///
+    /// __NB__: the retrieval methods taking `&self` (e.g. [`CookieStore::matches`],
+    /// [`CookieStore::get_request_values`], or `CookieStore::add_cookie_header`) cannot record
+    /// that a `Cookie` was sent, so a `Cookie` is judged idle since it was last stored unless
+    /// [`CookieStore::touch`] is called for each request. With a max-idle policy, the request
+    /// helpers holding exclusive access to the store (`CookieStoreHandle::matches`,
+    /// `attohttpc::send`, and `isahc::CookieInterceptor`) call it on the caller's behalf.
+    pub fn with_max_idle(self, max_idle: time::Duration) -> CookieStore {
+        CookieStore {
+            max_idle: Some(max_idle),
+            ..self
         }
-        // NB: we do not bail out above on is_expired(), as servers can remove a cookie
-        // by sending
-        // an expired one, so we need to do the old_cookie check below before checking
-        // is_expired() on an incoming cookie
+    }
 
-        {
-            // At this point in parsing, any non-present Domain attribute should have been
-            // converted into a HostOnly variant
-            let cookie_domain = cookie
-                .domain
-                .as_cow()
-                .ok_or_else(|| CookieError::UnspecifiedDomain)?;
-            if let Some(old_cookie) = self.get_mut(&cookie_domain, &cookie.path, cookie.name()) {
-                if old_cookie.http_only().unwrap_or(false) && !is_http_scheme(request_url) {
-                    // 2.  If the newly created cookie was received from a "non-HTTP"
-                    //    API and the old-cookie's http-only-flag is set, abort these
-                    //    steps and ignore the newly created cookie entirely.
-                    return Err(CookieError::NonHttpScheme);
-                } else if cookie.is_expired() {
-                    old_cookie.expire();
-                    return Ok(StoreAction::ExpiredExisting);
-                }
-            }
+    /// If `persist_session_cookies` is true, the functions saving the __unexpired__ and
+    /// __persistent__ cookies of the store (e.g. [`CookieStore::save`] and the `save` functions
+    /// of the [`crate::serde`] modules) also save __unexpired__ __non-persistent__ (session)
+    /// cookies, e.g. for CLI tools which maintain a session across invocations. __Expired__
+    /// cookies are still not saved.
+    pub fn with_persist_session_cookies(self, persist_session_cookies: bool) -> CookieStore {
+        CookieStore {
+            persist_session_cookies,
+            ..self
         }
+    }
 
-        if !cookie.is_expired() {
-            Ok(
-                if self
-                    .cookies
-                    .entry(String::from(&cookie.domain))
-                    .or_insert_with(Map::new)
-                    .entry(String::from(&cookie.path))
-                    .or_insert_with(Map::new)
-                    .insert(cookie.name().to_owned(), cookie)
-                    .is_none()
-                {
-                    StoreAction::Inserted
-                } else {
-                    StoreAction::UpdatedExisting
-                },
-            )
-        } else {
-            Err(CookieError::Expired)
+    /// If `first_party_only` is true, the functions saving the cookies of the store (e.g.
+    /// [`CookieStore::save`] and the `save` functions of the [`crate::serde`] modules) save only
+    /// first-party cookies, per [`Cookie::is_first_party`]; third-party cookies, i.e. those
+    /// received via e.g. [`CookieStore::insert_for_site`] under the site of another top-level
+    /// document, are kept for the session only, as with the "clear third-party cookies on exit"
+    /// setting of browsers.
+    pub fn with_first_party_only_persistence(self, first_party_only: bool) -> CookieStore {
+        CookieStore {
+            first_party_only_persistence: first_party_only,
+            ..self
         }
     }
 
-    /// Clear the contents of the store
-    pub fn clear(&mut self) {
-        self.cookies.clear()
+    /// Specify the [`DateParsing`](crate::DateParsing) used to parse the dates of `Expires`
+    /// attributes by the methods parsing `Set-Cookie` header values, e.g. [`CookieStore::parse`]
+    /// and [`CookieStore::store_response_headers_str`]. Defaults to `DateParsing::Standard`;
+    /// `DateParsing::Lenient` accepts the malformed dates user agents do.
+    pub fn with_date_parsing(self, date_parsing: DateParsing) -> CookieStore {
+        CookieStore {
+            date_parsing,
+            ..self
+        }
     }
 
-    /// An iterator visiting all the __unexpired__ cookies in the store
-    pub fn iter_unexpired<'a>(&'a self) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
-        self.cookies
-            .values()
-            .flat_map(|dcs| dcs.values())
-            .flat_map(|pcs| pcs.values())
-            .filter(|c| !c.is_expired())
+    /// The total size in bytes of the cookies (including __expired__) stored under `domain`, as
+    /// counted against the limit set via [`CookieStore::with_max_bytes_per_domain`]; see
+    /// [`Cookie::byte_size`]
+    pub fn domain_bytes(&self, domain: &str) -> usize {
+        self.cookies.get(domain).map_or(0, |path_map| {
+            path_map
+                .values()
+                .flat_map(NameMap::values)
+                .map(Cookie::byte_size)
+                .sum()
+        })
     }
 
-    /// An iterator visiting all (including __expired__) cookies in the store
-    pub fn iter_any<'a>(&'a self) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
-        self.cookies
-            .values()
-            .flat_map(|dcs| dcs.values())
-            .flat_map(|pcs| pcs.values())
+    /// Returns the cookie limit exceeded by storing `cookie` under `domain`, if any: `true` for
+    /// the per-domain limits, `false` for the limit on the store as a whole
+    fn exceeded_limit(&self, domain: &str, cookie: &Cookie<'_>) -> Option<bool> {
+        // looked up under the same key as by `domain_bytes`
+        let domain_cookies = self.cookies.get(domain);
+        let existing = domain_cookies
+            .and_then(|path_map| path_map.get(&*cookie.path))
+            .and_then(|name_map| name_map.get(cookie.name()));
+        let is_new = existing.is_none();
+        let domain_full = is_new
+            && self.max_cookies_per_domain.map_or(false, |max| {
+                domain_cookies.map_or(0, domain_len) >= max
+            });
+        let domain_over_budget = self.max_bytes_per_domain.map_or(false, |max| {
+            self.domain_bytes(domain)
+                .saturating_sub(existing.map_or(0, Cookie::byte_size))
+                + cookie.byte_size()
+                > max
+        });
+        if domain_full || domain_over_budget {
+            Some(true)
+        } else if is_new && self.max_cookies.map_or(false, |max| self.len() >= max) {
+            Some(false)
+        } else {
+            None
+        }
     }
 
-    /// Serialize any __unexpired__ and __persistent__ cookies in the store with `cookie_to_string`
-    /// and write them to `writer`
-    pub fn save<W, E, F>(&self, writer: &mut W, cookie_to_string: F) -> StoreResult<()>
-    where
-        W: Write,
-        F: Fn(&Cookie<'static>) -> Result<String, E>,
-        crate::Error: From<E>,
-    {
-        for cookie in self.iter_unexpired().filter_map(|c| {
-            if c.is_persistent() {
-                Some(cookie_to_string(c))
+    /// Make room for storing `cookie` under `domain` per the capacity limits and
+    /// [`OverflowPolicy`] of the store
+    fn make_room(&mut self, domain: &str, cookie: &Cookie<'_>) -> Room {
+        if self.exceeded_limit(domain, cookie).is_none() {
+            return Room::Available;
+        }
+        self.purge_expired();
+        let policy = match self.overflow_policy {
+            _ if self.exceeded_limit(domain, cookie).is_none() => return Room::Available,
+            OverflowPolicy::Evict(policy) => policy,
+            OverflowPolicy::Reject => return Room::Reject,
+            OverflowPolicy::Ignore => return Room::Discard,
+        };
+        if self
+            .max_bytes_per_domain
+            .map_or(false, |max| cookie.byte_size() > max)
+        {
+            // no amount of eviction would make room for the cookie
+            return Room::Discard;
+        }
+        let identity = CookieIdentity::new(domain, &cookie.path, cookie.name());
+        while let Some(per_domain) = self.exceeded_limit(domain, cookie) {
+            let candidates: Box<dyn Iterator<Item = &Cookie<'static>>> = if per_domain {
+                Box::new(
+                    self.cookies
+                        .get(domain)
+                        .into_iter()
+                        .flat_map(|path_map| path_map.values().flat_map(NameMap::values)),
+                )
             } else {
-                None
+                Box::new(self.iter_any())
+            };
+            // a cookie being updated is never evicted to make room for its replacement
+            let candidates = candidates.filter(|c| c.identity() != identity);
+            let victim = match policy {
+                EvictionPolicy::OldestCreated => candidates.min_by_key(|c| c.creation_time),
+                EvictionPolicy::SoonestExpiring => candidates.min_by_key(|c| match c.expires {
+                    CookieExpiration::AtUtc(expires) => (false, Some(expires)),
+                    CookieExpiration::SessionEnd => (true, None),
+                }),
+            }
+            .map(Cookie::identity);
+            match victim {
+                Some(victim) => {
+                    self.remove_by_id(&victim);
+                }
+                // a limit of 0 cannot be satisfied by evicting any cookies
+                None => return Room::Discard,
             }
-        }) {
-            writeln!(writer, "{}", cookie?)?;
         }
-        Ok(())
+        Room::Available
     }
 
-    /// Serialize all (including __expired__ and __non-persistent__) cookies in the store with `cookie_to_string` and write them to `writer`
-    pub fn save_incl_expired_and_nonpersistent<W, E, F>(
-        &self,
-        writer: &mut W,
-        cookie_to_string: F,
-    ) -> StoreResult<()>
-    where
-        W: Write,
-        F: Fn(&Cookie<'static>) -> Result<String, E>,
-        crate::Error: From<E>,
-    {
-        for cookie in self.iter_any() {
-            writeln!(writer, "{}", cookie_to_string(cookie)?)?;
+    /// Specify a [`CookieTransform`] rewriting each `Cookie` received by [`CookieStore::insert`]
+    /// (and the methods implemented in terms of it) once it has been validated against the
+    /// request-uri it was received from, but before it is stored, e.g. to cap the expiry time of
+    /// all cookies. Replaces any previously specified transform.
+    pub fn with_transform<T: CookieTransform + 'static>(self, transform: T) -> CookieStore {
+        CookieStore {
+            transform: Some(SharedTransform::new(transform)),
+            ..self
         }
-        Ok(())
     }
 
-    /// Load cookies from `reader`, deserializing with `cookie_from_str`, skipping any __expired__
-    /// cookies
-    pub fn load<R, E, F>(reader: R, cookie_from_str: F) -> StoreResult<CookieStore>
-    where
-        R: BufRead,
-        F: Fn(&str) -> Result<Cookie<'static>, E>,
-        crate::Error: From<E>,
-    {
-        CookieStore::load_from(reader, cookie_from_str, false)
+    /// Returns true if a non-Secure cookie with `domain`, `path`, and `name` would overwrite or
+    /// shadow an __unexpired__ Secure cookie in the store, per
+    /// [draft-ietf-httpbis-rfc6265bis](https://datatracker.ietf.org/doc/html/draft-ietf-httpbis-rfc6265bis#section-5.7)
+    fn shadows_secure(
+        &self,
+        domain: &str,
+        path: &str,
+        name: &str,
+        now_utc: &time::OffsetDateTime,
+    ) -> bool {
+        // the domains domain-matched by `domain`, then those domain-matching it
+        host_match_candidates(domain)
+            .chain(self.domain_index.subdomains(domain))
+            .filter_map(|d| self.cookies.get(d))
+            .flat_map(|path_map| path_map.iter())
+            .filter(|(p, _)| is_path_match(p, path))
+            .filter_map(|(_, name_map)| name_map.get(name))
+            .any(|c| c.secure().unwrap_or(false) && !c.expires_by(now_utc))
     }
 
-    /// Load cookies from `reader`, deserializing with `cookie_from_str`, loading both __unexpired__
-    /// and __expired__ cookies
-    pub fn load_all<R, E, F>(reader: R, cookie_from_str: F) -> StoreResult<CookieStore>
-    where
-        R: BufRead,
-        F: Fn(&str) -> Result<Cookie<'static>, E>,
-        crate::Error: From<E>,
-    {
-        CookieStore::load_from(reader, cookie_from_str, true)
+    /// Returns true if cookies for `domain` are permitted by the domain allowlist and denylist
+    fn is_domain_permitted(&self, domain: &str) -> bool {
+        self.domain_allowlist.as_ref().map_or(true, |allowlist| {
+            allowlist.iter().any(|rule| rule.matches(domain))
+        }) && !self.domain_denylist.iter().any(|rule| rule.matches(domain))
     }
 
-    fn load_from<R, E, F>(
-        reader: R,
-        cookie_from_str: F,
-        include_expired: bool,
-    ) -> StoreResult<CookieStore>
+    /// Register `observer` to be called with a [`StoreEvent`] describing each change made to the
+    /// contents of the store, replacing any previously registered observer. This allows e.g.
+    /// persistence or cache invalidation to react to changes without polling the store. Clones
+    /// of the store share the observer registered at the time of cloning.
+    ///
+    /// __NB__: `observer` is called while the store is being mutated, so must not attempt to
+    /// access the store itself (e.g. via a `CookieStoreHandle` or a lock held by the caller).
+    pub fn set_observer<F>(&mut self, observer: F)
     where
-        R: BufRead,
-        F: Fn(&str) -> Result<Cookie<'static>, E>,
-        crate::Error: From<E>,
+        F: Fn(&StoreEvent<'_>) + Send + Sync + 'static,
     {
-        let cookies = reader.lines().map(|line_result| {
-            line_result
-                .map_err(Into::into)
-                .and_then(|line| cookie_from_str(&line).map_err(crate::Error::from))
-        });
-        Self::from_cookies(cookies, include_expired)
+        self.observer.get_or_insert_with(Observer::default).callback = Some(Arc::new(observer));
     }
 
-    /// Create a `CookieStore` from an iterator of `Cookie` values. When
-    /// `include_expired` is `true`, both __expired__ and __unexpired__ cookies in the incoming
-    /// iterator will be included in the produced `CookieStore`; otherwise, only
-    /// __unexpired__ cookies will be included, and __expired__ cookies filtered
-    /// out.
-    pub fn from_cookies<I, E>(iter: I, include_expired: bool) -> Result<Self, E>
-    where
-        I: IntoIterator<Item = Result<Cookie<'static>, E>>,
-    {
-        let mut cookies = Map::new();
-        for cookie in iter {
-            let cookie = cookie?;
-            if include_expired || !cookie.is_expired() {
-                cookies
-                    .entry(String::from(&cookie.domain))
-                    .or_insert_with(Map::new)
-                    .entry(String::from(&cookie.path))
-                    .or_insert_with(Map::new)
-                    .insert(cookie.name().to_owned(), cookie);
+    /// Unregister any observer registered via [`CookieStore::set_observer`]; any subscribers
+    /// registered via [`CookieStore::subscribe`] are unaffected
+    pub fn clear_observer(&mut self) {
+        if let Some(observer) = &mut self.observer {
+            observer.callback = None;
+            if observer.is_empty() {
+                self.observer = None;
             }
         }
-        Ok(Self {
-            cookies,
-            #[cfg(feature = "public_suffix")]
-            public_suffix_list: None,
-        })
     }
 
-    pub fn new(
-        #[cfg(feature = "public_suffix")] public_suffix_list: Option<publicsuffix::List>,
-    ) -> Self {
-        Self {
-            cookies: DomainMap::new(),
-            #[cfg(feature = "public_suffix")]
-            public_suffix_list,
+    /// Subscribe to the changes made to the contents of the store, returning a channel receiving
+    /// an [`OwnedStoreEvent`] for each change. Unlike an observer registered via
+    /// [`CookieStore::set_observer`], a receiver may be handed to another thread (e.g. to persist
+    /// the store, or refresh a UI) and any number of subscribers may be registered. Once the
+    /// receiver is dropped, the subscription is removed on the next change to the store. Clones
+    /// of the store share the subscribers registered at the time of cloning.
+    pub fn subscribe(&mut self) -> std::sync::mpsc::Receiver<OwnedStoreEvent> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.add_subscriber(Subscriber::Channel(sender));
+        receiver
+    }
+
+    /// As [`CookieStore::subscribe`], returning a [tokio](https://crates.io/crates/tokio) channel
+    /// which may be awaited upon (or wrapped as a `Stream` via e.g.
+    /// `tokio_stream::wrappers::UnboundedReceiverStream`). Requires feature `tokio`
+    #[cfg(feature = "tokio")]
+    pub fn subscribe_async(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<OwnedStoreEvent> {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        self.add_subscriber(Subscriber::Tokio(sender));
+        receiver
+    }
+
+    /// Record an [`AuditEntry`] for each change subsequently made to the contents of the store,
+    /// retrievable via [`CookieStore::audit_log`], e.g. for security reviews of automated
+    /// clients. The log is held in memory, and grows without bound until drained via
+    /// [`CookieStore::take_audit_log`]. Clones of the store receive a copy of the log.
+    pub fn with_audit_log(mut self) -> CookieStore {
+        let observer = self.observer.get_or_insert_with(Observer::default);
+        if observer.audit_log.is_none() {
+            observer.audit_log = Some(AuditLog::new(self.clock.clone()));
         }
+        self
     }
-}
 
+    /// The entries of the audit log enabled via [`CookieStore::with_audit_log`], oldest first;
+    /// empty if the audit log is not enabled
+    pub fn audit_log(&self) -> Vec<AuditEntry> {
+        self.observer
+            .as_ref()
+            .and_then(|observer| observer.audit_log.as_ref())
+            .map_or_else(Vec::new, AuditLog::entries)
+    }
 
-#[cfg(feature = "serde_json")]
-/// Legacy serialization implementations. These methods do **not** produce/consume valid JSON output compatible with
-/// typical JSON libraries/tools.
-impl CookieStore {
-    /// Serialize any __unexpired__ and __persistent__ cookies in the store to JSON format and
-    /// write them to `writer`
-    ///
-    /// __NB__: this method does not produce valid JSON which can be directly loaded; such output
-    /// must be loaded via the corresponding method [CookieStore::load_json]. For a more
-    /// robust/universal
-    /// JSON format, see [crate::serde::json], which produces output __incompatible__ with this
-    /// method.
-    #[deprecated(
-        since = "0.22.0",
-        note = "See `cookie_store::serde` modules for more robust de/serialization options"
-    )]
-    pub fn save_json<W: Write>(&self, writer: &mut W) -> StoreResult<()> {
-        self.save(writer, ::serde_json::to_string)
+    /// Remove and return the entries of the audit log enabled via
+    /// [`CookieStore::with_audit_log`], oldest first; the log remains enabled
+    pub fn take_audit_log(&mut self) -> Vec<AuditEntry> {
+        self.observer
+            .as_ref()
+            .and_then(|observer| observer.audit_log.as_ref())
+            .map_or_else(Vec::new, AuditLog::take)
     }
 
-    /// Serialize all (including __expired__ and __non-persistent__) cookies in the store to JSON format and write them to `writer`
-    ///
-    /// __NB__: this method does not produce valid JSON which can be directly loaded; such output
-    /// must be loaded via the corresponding method [CookieStore::load_json]. For a more
-    /// robust/universal
-    /// JSON format, see [crate::serde::json], which produces output __incompatible__ with this
-    /// method.
-    #[deprecated(
-        since = "0.22.0",
-        note = "See `cookie_store::serde` modules for more robust de/serialization options"
-    )]
-    pub fn save_incl_expired_and_nonpersistent_json<W: Write>(
-        &self,
-        writer: &mut W,
-    ) -> StoreResult<()> {
-        self.save_incl_expired_and_nonpersistent(writer, ::serde_json::to_string)
+    /// Retain a [`Tombstone`] for each `Cookie` subsequently expired or removed from the store,
+    /// for `retention` after its deletion. Tombstones are persisted by the `save_with_config`
+    /// functions of the [`serde`](crate::serde) modules, and are consulted by
+    /// [`CookieStore::merge`], so that merging stores (e.g. synchronized across machines)
+    /// propagates deletions rather than resurrecting deleted cookies. A tombstone is discarded
+    /// when a `Cookie` of the same identity is added to the store. If tombstones are already
+    /// enabled, only the retention is updated.
+    pub fn with_tombstones(mut self, retention: time::Duration) -> CookieStore {
+        let observer = self.observer.get_or_insert_with(Observer::default);
+        match observer.tombstones.as_mut() {
+            Some(tombstones) => tombstones.set_retention(retention),
+            None => observer.tombstones = Some(Tombstones::new(self.clock.clone(), retention)),
+        }
+        self
     }
 
-    /// Load JSON-formatted cookies from `reader`, skipping any __expired__ cookies
-    ///
-    /// __NB__: this method does not expect true valid JSON; it is designed to load output
-    /// from the corresponding method [CookieStore::save_json]. For a more robust/universal
-    /// JSON format, see [crate::serde::json], which produces output __incompatible__ with this
-    /// method.
-    #[deprecated(
-        since = "0.22.0",
-        note = "See `cookie_store::serde` modules for more robust de/serialization options"
-    )]
-    pub fn load_json<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
-        CookieStore::load(reader, |cookie| ::serde_json::from_str(cookie))
+    /// The tombstones of the cookies deleted from the store within the retention window set via
+    /// [`CookieStore::with_tombstones`], oldest first; empty if tombstones are not enabled
+    pub fn tombstones(&self) -> Vec<Tombstone> {
+        self.tombstone_list()
+            .map_or_else(Vec::new, Tombstones::entries)
     }
 
-    /// Load JSON-formatted cookies from `reader`, loading both __expired__ and __unexpired__ cookies
-    ///
-    /// __NB__: this method does not expect true valid JSON; it is designed to load output
-    /// from the corresponding method [CookieStore::save_json]. For a more robust/universal
-    /// JSON format, see [crate::serde::json], which produces output __incompatible__ with this
-    /// method.
-    #[deprecated(
-        since = "0.22.0",
-        note = "See `cookie_store::serde` modules for more robust de/serialization options"
-    )]
-    pub fn load_json_all<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
-        CookieStore::load_all(reader, |cookie| ::serde_json::from_str(cookie))
+    fn tombstone_list(&self) -> Option<&Tombstones> {
+        self.observer
+            .as_ref()
+            .and_then(|observer| observer.tombstones.as_ref())
     }
-}
 
-#[cfg(feature = "serde")]
-/// Legacy de/serialization implementation which elides the collection-nature of the contained
-/// cookies. Suitable for line-oriented cookie persistence, but prefer/consider
-/// `cookie_store::serde` modules for more universally consumable serialization formats.
-mod serde_legacy {
-    use serde::de::{SeqAccess, Visitor};
-    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    /// The retention of tombstones, if enabled via [`CookieStore::with_tombstones`]
+    #[cfg(feature = "serde")]
+    fn tombstone_retention(&self) -> Option<time::Duration> {
+        self.tombstone_list().map(Tombstones::retention)
+    }
 
-    impl Serialize for super::CookieStore {
-        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    /// Disable tombstones, discarding any held
+    #[cfg(feature = "serde")]
+    fn without_tombstones(mut self) -> CookieStore {
+        if let Some(observer) = &mut self.observer {
+            observer.tombstones = None;
+            if observer.is_empty() {
+                self.observer = None;
+            }
+        }
+        self
+    }
+
+    /// Add `tombstones` (e.g. as loaded alongside the cookies of the store), if tombstones are
+    /// enabled
+    pub(crate) fn restore_tombstones<I: IntoIterator<Item = Tombstone>>(&mut self, tombstones: I) {
+        if let Some(list) = self.tombstone_list() {
+            for tombstone in tombstones {
+                list.insert(tombstone);
+            }
+        }
+    }
+
+    /// Returns true if a tombstone postdating the creation of `cookie` is held for its identity,
+    /// i.e. `cookie` is a copy of a `Cookie` since deleted from the store
+    fn is_tombstoned(&self, cookie: &Cookie<'_>) -> bool {
+        self.tombstone_list()
+            .and_then(|tombstones| tombstones.get(&cookie.identity()))
+            .map_or(false, |deleted_at| {
+                cookie
+                    .creation_time
+                    .map_or(true, |created| created <= deleted_at)
+            })
+    }
+
+    fn add_subscriber(&mut self, subscriber: Subscriber) {
+        self.observer
+            .get_or_insert_with(Observer::default)
+            .subscribers
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(subscriber);
+    }
+
+    /// A counter incremented on every change made to the contents of the store, e.g. by
+    /// inserting, updating, expiring, or removing a `Cookie`
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Track the generation at which each `Cookie` subsequently changes, enabling
+    /// [`CookieStore::export_changes_since`]. Cookies removed from the store are tracked by
+    /// identity until discarded via [`CookieStore::compact_changes`].
+    pub fn with_change_tracking(mut self) -> CookieStore {
+        let generation = self.generation;
+        let observer = self.observer.get_or_insert_with(Observer::default);
+        if observer.change_log.is_none() {
+            observer.change_log = Some(ChangeLog::new(generation));
+        }
+        self
+    }
+
+    /// The cookies (including __expired__) added, updated, or removed since the store was at
+    /// `generation` (see [`CookieStore::generation`]), e.g. to incrementally synchronize the
+    /// store to a remote copy, continuing from [`StoreChanges::generation`] on the next export.
+    /// Returns `None` if change tracking is not enabled via
+    /// [`CookieStore::with_change_tracking`], or the changes since `generation` are not known
+    /// (i.e. `generation` precedes the enabling of change tracking, or was discarded via
+    /// [`CookieStore::compact_changes`]), in which case the full contents of the store should be
+    /// synchronized instead.
+    pub fn export_changes_since(&self, generation: u64) -> Option<StoreChanges<'_>> {
+        let change_log = self.observer.as_ref()?.change_log.as_ref()?;
+        if generation < change_log.since() || generation > self.generation {
+            return None;
+        }
+        let mut changes = StoreChanges {
+            generation: self.generation,
+            ..StoreChanges::default()
+        };
+        for (id, change) in change_log.changes_since(generation) {
+            let inserted = change
+                .inserted
+                .map_or(false, |inserted| inserted > generation);
+            match self.get_any(&id.domain, &id.path, &id.name) {
+                Some(cookie) if inserted => changes.added.push(cookie),
+                Some(cookie) => changes.updated.push(cookie),
+                None if inserted => {}
+                None => changes.removed.push(id),
+            }
+        }
+        Some(changes)
+    }
+
+    /// Discard the changes tracked up to and including `generation`, e.g. once exported via
+    /// [`CookieStore::export_changes_since`] and synchronized, bounding the memory used for
+    /// tracking removed cookies. Changes can subsequently only be exported since `generation`
+    /// or later.
+    pub fn compact_changes(&mut self, generation: u64) {
+        if let Some(change_log) = self.observer.as_mut().and_then(|o| o.change_log.as_mut()) {
+            change_log.compact(generation.min(self.generation));
+        }
+    }
+
+    /// Returns true if the contents of the store have changed since the last call to
+    /// [`CookieStore::mark_clean`], or since the store was created (or loaded) if it has not
+    /// been called. This allows persistence layers to skip saving an unchanged store.
+    pub fn is_dirty(&self) -> bool {
+        self.generation != self.clean_generation
+    }
+
+    /// Mark the current contents of the store as clean, e.g. after persisting the store
+    pub fn mark_clean(&mut self) {
+        self.clean_generation = self.generation;
+    }
+
+    /// Convert the store into a read-only [`FrozenCookieStore`], which may be cheaply cloned and
+    /// shared with code which must not modify the store.
+    pub fn freeze(self) -> FrozenCookieStore {
+        FrozenCookieStore::new(self)
+    }
+
+    /// A read-only [`FrozenCookieStore`] holding the current contents of the store, which is
+    /// unaffected by any subsequent changes to the store. The cookies of each domain are shared
+    /// between the store and its snapshots until modified, so taking a snapshot is proportional
+    /// to the number of domains rather than the number of cookies in the store. A snapshot may
+    /// be shared across threads, e.g. for request matching without holding a lock on the store
+    /// itself. Any observer set via [`CookieStore::set_observer`] is not carried over.
+    pub fn snapshot(&self) -> FrozenCookieStore {
+        FrozenCookieStore::new(self.clone_without_observer())
+    }
+
+    /// Returns true if the `CookieStore` contains an __unexpired__ `Cookie` corresponding to the
+    /// specified `domain`, `path`, and `name`.
+    pub fn contains(&self, domain: &str, path: &str, name: &str) -> bool {
+        self.get(domain, path, name).is_some()
+    }
+
+    /// Returns true if the `CookieStore` contains any (even an __expired__) `Cookie` corresponding
+    /// to the specified `domain`, `path`, and `name`.
+    pub fn contains_any(&self, domain: &str, path: &str, name: &str) -> bool {
+        self.get_any(domain, path, name).is_some()
+    }
+
+    /// Returns a reference to the __unexpired__ `Cookie` corresponding to the specified `domain`,
+    /// `path`, and `name`.
+    pub fn get(&self, domain: &str, path: &str, name: &str) -> Option<&Cookie<'_>> {
+        let now_utc = self.clock.now_utc();
+        self.get_any(domain, path, name).and_then(|cookie| {
+            if cookie.expires_by(&now_utc) {
+                None
+            } else {
+                Some(cookie)
+            }
+        })
+    }
+
+    /// Returns a reference to the __unexpired__ `Cookie` with the identity `id`
+    pub fn get_by_id(&self, id: &CookieIdentity) -> Option<&Cookie<'_>> {
+        self.get(&id.domain, &id.path, &id.name)
+    }
+
+    /// Returns a mutable reference to the __unexpired__ `Cookie` corresponding to the specified
+    /// `domain`, `path`, and `name`.
+    fn get_mut(&mut self, domain: &str, path: &str, name: &str) -> Option<&mut Cookie<'static>> {
+        let now_utc = self.clock.now_utc();
+        self.get_mut_any(domain, path, name).and_then(|cookie| {
+            if cookie.expires_by(&now_utc) {
+                None
+            } else {
+                Some(cookie)
+            }
+        })
+    }
+
+    /// Returns a reference to the (possibly __expired__) `Cookie` corresponding to the specified
+    /// `domain`, `path`, and `name`. As for all methods taking a `domain`, this may be given in
+    /// either Unicode (e.g. `bücher.example`) or IDNA ASCII (`xn--bcher-kva.example`) form.
+    pub fn get_any(&self, domain: &str, path: &str, name: &str) -> Option<&Cookie<'static>> {
+        self.cookies.get(&*normalize_domain(domain)).and_then(|domain_cookies| {
+            domain_cookies
+                .get(path)
+                .and_then(|path_cookies| path_cookies.get(name))
+        })
+    }
+
+    /// Returns a mutable reference to the (possibly __expired__) `Cookie` corresponding to the
+    /// specified `domain`, `path`, and `name`.
+    fn get_mut_any(
+        &mut self,
+        domain: &str,
+        path: &str,
+        name: &str,
+    ) -> Option<&mut Cookie<'static>> {
+        self.cookies.get_mut(domain).and_then(|domain_cookies| {
+            Arc::make_mut(domain_cookies)
+                .get_mut(path)
+                .and_then(|path_cookies| path_cookies.get_mut(name))
+        })
+    }
+
+    /// Removes a `Cookie` from the store, returning the `Cookie` if it was in the store. `domain`
+    /// may be given in either Unicode or IDNA ASCII form.
+    pub fn remove(&mut self, domain: &str, path: &str, name: &str) -> Option<Cookie<'static>> {
+        #[cfg(not(feature = "preserve_order"))]
+        fn map_remove<K, V, Q>(map: &mut Map<K, V>, key: &Q) -> Option<V>
         where
-            S: Serializer,
+            K: std::borrow::Borrow<Q> + std::cmp::Eq + std::hash::Hash,
+            Q: std::cmp::Eq + std::hash::Hash + ?Sized,
+        {
+            map.remove(key)
+        }
+        #[cfg(feature = "preserve_order")]
+        fn map_remove<K, V, Q>(map: &mut Map<K, V>, key: &Q) -> Option<V>
+        where
+            K: std::borrow::Borrow<Q> + std::cmp::Eq + std::hash::Hash,
+            Q: std::cmp::Eq + std::hash::Hash + ?Sized,
+        {
+            map.shift_remove(key)
+        }
+
+        let domain = &*normalize_domain(domain);
+        let (removed, remove_domain) = match self.cookies.get_mut(domain) {
+            None => (None, false),
+            Some(domain_cookies) => {
+                let domain_cookies = Arc::make_mut(domain_cookies);
+                let (removed, remove_path) = match domain_cookies.get_mut(path) {
+                    None => (None, false),
+                    Some(path_cookies) => {
+                        let removed = map_remove(path_cookies, name);
+                        (removed, path_cookies.is_empty())
+                    }
+                };
+
+                if remove_path {
+                    map_remove(domain_cookies, path);
+                    (removed, domain_cookies.is_empty())
+                } else {
+                    (removed, false)
+                }
+            }
+        };
+
+        if remove_domain {
+            map_remove(&mut self.cookies, domain);
+            self.domain_index.remove(domain);
+        }
+
+        // give the caller an unshared path, so that the interned keys may be released
+        let removed = removed.map(unshare_path);
+        if let Some(ref cookie) = removed {
+            self.generation += 1;
+            if let Some(observer) = &self.observer {
+                observer.notify(StoreEvent::Removed(cookie), self.generation);
+            }
+        }
+        self.interned.release(domain);
+        self.interned.release(path);
+        self.interned.release(name);
+
+        removed
+    }
+
+    /// Returns a reference to the __unexpired__ `Cookie` named `name` which would be included
+    /// for `request_url`, per the same rules as [`CookieStore::matches`]. If several such cookies
+    /// exist (e.g. with differing paths), the `Cookie` with the longest path is returned.
+    pub fn get_for_url(&self, request_url: &Url, name: &str) -> Option<&Cookie<'static>> {
+        self.matches_iter(request_url)
+            .filter(|c| c.name() == name)
+            .max_by_key(|c| c.path.len())
+    }
+
+    /// Removes the __unexpired__ cookies named `name` which would be included for `request_url`,
+    /// per the same rules as [`CookieStore::matches`], returning the removed cookies.
+    pub fn remove_for_url(&mut self, request_url: &Url, name: &str) -> Vec<Cookie<'static>> {
+        let keys = self
+            .matches_iter(request_url)
+            .filter(|c| c.name() == name)
+            .filter_map(|c| Some((c.domain.as_cow()?.into_owned(), String::from(&c.path))))
+            .collect::<Vec<_>>();
+        keys.into_iter()
+            .filter_map(|(domain, path)| self.remove(&domain, &path, name))
+            .collect()
+    }
+
+    /// Removes all __unexpired__ cookies which would be included for `request_url`, per the same
+    /// rules as [`CookieStore::matches`], returning the removed cookies; e.g. to "log out" of a
+    /// site. Cookies of the same domain not matching `request_url` (e.g. of other paths) are
+    /// retained.
+    pub fn clear_for_url(&mut self, request_url: &Url) -> Vec<Cookie<'static>> {
+        let ids = self
+            .matches_iter(request_url)
+            .map(Cookie::identity)
+            .collect::<Vec<_>>();
+        ids.iter().filter_map(|id| self.remove_by_id(id)).collect()
+    }
+
+    /// Removes the `Cookie` with the identity `id` from the store, returning the `Cookie` if it
+    /// was in the store
+    pub fn remove_by_id(&mut self, id: &CookieIdentity) -> Option<Cookie<'static>> {
+        self.remove(&id.domain, &id.path, &id.name)
+    }
+
+    /// Removes all cookies (including __expired__) stored under `domain`, returning the removed
+    /// cookies. Cookies stored under subdomains of `domain` are not removed; see
+    /// [`CookieStore::remove_domain_and_subdomains`].
+    pub fn remove_domain(&mut self, domain: &str) -> Vec<Cookie<'static>> {
+        let domain = normalize_domain(domain);
+        self.remove_domains_where(|d| d == domain)
+    }
+
+    /// Removes all cookies (including __expired__) stored under `domain` or any of its
+    /// subdomains (e.g. for `example.com`, both `example.com` and `www.example.com`), returning
+    /// the removed cookies.
+    pub fn remove_domain_and_subdomains(&mut self, domain: &str) -> Vec<Cookie<'static>> {
+        let domain = normalize_domain(domain);
+        self.remove_domains_where(|d| {
+            d.strip_suffix(&*domain)
+                .map_or(false, |sub| sub.is_empty() || sub.ends_with('.'))
+        })
+    }
+
+    fn remove_domains_where<F>(&mut self, f: F) -> Vec<Cookie<'static>>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut removed = Vec::new();
+        self.cookies.retain(|domain, path_map| {
+            if f(domain) {
+                removed.push(std::mem::take(path_map));
+                false
+            } else {
+                true
+            }
+        });
+        if removed.is_empty() {
+            return Vec::new();
+        }
+        let removed = removed
+            .into_iter()
+            .flat_map(|path_map| unshare(path_map).into_values())
+            .flat_map(|name_map| name_map.into_values())
+            .map(unshare_path)
+            .collect::<Vec<_>>();
+        self.generation += 1;
+        self.prune_domain_index();
+        self.interned.prune();
+        if let Some(observer) = &self.observer {
+            for cookie in removed.iter() {
+                observer.notify(StoreEvent::Removed(cookie), self.generation);
+            }
+        }
+        removed
+    }
+
+    /// Returns a collection of references to __unexpired__ cookies that path- and domain-match
+    /// `request_url`, as well as having HttpOnly and Secure attributes compatible with the
+    /// `request_url`. See [`CookieStore::matches_iter`] for a non-allocating alternative.
+    pub fn matches(&self, request_url: &Url) -> Vec<&Cookie<'static>> {
+        self.matches_iter(request_url).collect()
+    }
+
+    /// An iterator visiting the __unexpired__ cookies that path- and domain-match
+    /// `request_url`, as well as having HttpOnly and Secure attributes compatible with the
+    /// `request_url`; the same cookies as returned by [`CookieStore::matches`], without
+    /// allocating a collection.
+    pub fn matches_iter<'a, 'u>(
+        &'a self,
+        request_url: &'u Url,
+    ) -> impl Iterator<Item = &'a Cookie<'static>> + 'u
+    where
+        'a: 'u,
+    {
+        let is_http = is_http_scheme(request_url);
+        let is_secure = self.trustworthy_origins.is_trustworthy(request_url);
+        let source_scheme = Some(SourceScheme::of(request_url));
+        let scheme_bound = self.scheme_bound;
+        let port = request_url.port_or_known_default();
+        let port_bound = self.port_bound;
+        let now_utc = self.clock.now_utc();
+        // although we only consider candidate domains and path_match as we descend through the
+        // tree, we still need to do a full domain-match check in the last filter. Otherwise,
+        // we cannot properly deal with HostOnly Cookies. NB: Cookie::matches() is not used, as
+        // it applies the default TrustworthyOrigins for the Secure attribute.
+        self.domain_candidates(request_url)
+            .flat_map(move |dcs| {
+                dcs.iter()
+                    .filter(move |&(p, _)| path_match(p, request_url))
+                    .flat_map(move |(_, pcs)| {
+                        pcs.values()
+                            .filter(move |c| {
+                                !c.expires_by(&now_utc)
+                                    && c.domain.matches(request_url)
+                                    && c.path.matches(request_url)
+                            })
+                    })
+            })
+            .filter(move |c| {
+                (is_http || !c.http_only().unwrap_or(false))
+                    && (is_secure || !c.secure().unwrap_or(false))
+                    && (!scheme_bound
+                        || c.source_scheme.is_none()
+                        || c.source_scheme == source_scheme)
+                    && (!port_bound || c.source_port.is_none() || c.source_port == port)
+            })
+    }
+
+    /// Returns the cookies of [`CookieStore::matches`] which may also be sent in a request to
+    /// `request_url` made in `context`, per their SameSite attribute. Cookies lacking a SameSite
+    /// attribute are always included, unless the store treats them as `SameSite=Lax` (see
+    /// [`CookieStore::with_same_site_lax_by_default`]).
+    pub fn matches_with_context(
+        &self,
+        request_url: &Url,
+        context: &RequestContext,
+    ) -> Vec<&Cookie<'static>> {
+        self.matches_iter(request_url)
+            .filter(|c| {
+                let same_site = match c.same_site() {
+                    None if self.same_site_lax_by_default => Some(SameSite::Lax),
+                    same_site => same_site,
+                };
+                context.allows(same_site)
+            })
+            .collect()
+    }
+
+    /// Report, for every `Cookie` stored under a domain which may domain-match `request_url`,
+    /// whether it is sent in a request to `request_url` (i.e. is returned by
+    /// [`CookieStore::matches`]) and if not, why. Intended for debugging; the SameSite attribute
+    /// is not considered, see [`CookieStore::match_report_with_context`].
+    pub fn match_report(&self, request_url: &Url) -> Vec<CookieMatch<'_>> {
+        self.match_report_for(request_url, None)
+    }
+
+    /// As [`CookieStore::match_report`], but also reporting cookies excluded by their SameSite
+    /// attribute from a request made in `context`, per [`CookieStore::matches_with_context`]
+    pub fn match_report_with_context(
+        &self,
+        request_url: &Url,
+        context: &RequestContext,
+    ) -> Vec<CookieMatch<'_>> {
+        self.match_report_for(request_url, Some(context))
+    }
+
+    fn match_report_for(
+        &self,
+        request_url: &Url,
+        context: Option<&RequestContext>,
+    ) -> Vec<CookieMatch<'_>> {
+        let now_utc = self.clock.now_utc();
+        self.domain_candidates(request_url)
+            .flat_map(|path_map| path_map.values())
+            .flat_map(|name_map| name_map.values())
+            .map(|cookie| CookieMatch {
+                cookie,
+                exclusion: self.match_exclusion(cookie, request_url, &now_utc, context),
+            })
+            .collect()
+    }
+
+    /// The reason `cookie` is not sent in a request to `request_url`, if any; this mirrors the
+    /// checks of [`CookieStore::matches_iter`] and [`CookieStore::matches_with_context`]
+    fn match_exclusion(
+        &self,
+        cookie: &Cookie<'static>,
+        request_url: &Url,
+        now_utc: &time::OffsetDateTime,
+        context: Option<&RequestContext>,
+    ) -> Option<MatchExclusion> {
+        if cookie.expires_by(now_utc) {
+            Some(MatchExclusion::Expired)
+        } else if !cookie.domain.matches(request_url) {
+            Some(MatchExclusion::DomainMismatch)
+        } else if !cookie.path.matches(request_url) {
+            Some(MatchExclusion::PathMismatch)
+        } else if !is_http_scheme(request_url) && cookie.http_only().unwrap_or(false) {
+            Some(MatchExclusion::HttpOnly)
+        } else if !self.trustworthy_origins.is_trustworthy(request_url)
+            && cookie.secure().unwrap_or(false)
+        {
+            Some(MatchExclusion::Secure)
+        } else if self.scheme_bound
+            && cookie.source_scheme.is_some()
+            && cookie.source_scheme != Some(SourceScheme::of(request_url))
         {
-            serializer.collect_seq(self.iter_unexpired().filter(|c| c.is_persistent()))
+            Some(MatchExclusion::SchemeBound)
+        } else if self.port_bound
+            && cookie.source_port.is_some()
+            && cookie.source_port != request_url.port_or_known_default()
+        {
+            Some(MatchExclusion::PortBound)
+        } else if context.map_or(false, |context| {
+            let same_site = match cookie.same_site() {
+                None if self.same_site_lax_by_default => Some(SameSite::Lax),
+                same_site => same_site,
+            };
+            !context.allows(same_site)
+        }) {
+            Some(MatchExclusion::SameSite)
+        } else {
+            None
+        }
+    }
+
+    /// The `PathMap`s for the domain keys which may domain-match `request_url`, found by direct
+    /// lookup of the request-host and its parent domains rather than scanning every domain in
+    /// the store.
+    #[cfg(not(feature = "preserve_order"))]
+    fn domain_candidates<'a, 'u>(
+        &'a self,
+        request_url: &'u Url,
+    ) -> impl Iterator<Item = &'a PathMap> + 'u
+    where
+        'a: 'u,
+    {
+        domain_match_candidates(request_url)
+            .filter_map(move |domain| self.cookies.get(domain))
+            .map(|path_map| &**path_map)
+    }
+
+    /// The `PathMap`s for the domain keys which may domain-match `request_url`, found by direct
+    /// lookup of the request-host and its parent domains rather than scanning every domain in
+    /// the store. The `PathMap`s are returned in insertion order.
+    #[cfg(feature = "preserve_order")]
+    fn domain_candidates<'a, 'u>(
+        &'a self,
+        request_url: &'u Url,
+    ) -> impl Iterator<Item = &'a PathMap> + 'u
+    where
+        'a: 'u,
+    {
+        let mut candidates = domain_match_candidates(request_url)
+            .filter_map(|domain| self.cookies.get_full(domain))
+            .map(|(index, _, path_map)| (index, path_map))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|&(index, _)| index);
+        candidates.into_iter().map(|(_, path_map)| &**path_map)
+    }
+
+    /// Parses a new `Cookie` from `cookie_str` and inserts it into the store.
+    pub fn parse(&mut self, cookie_str: &str, request_url: &Url) -> InsertResult {
+        crate::cookie_date::parse(cookie_str, self.date_parsing)
+            .map_err(CookieError::from)
+            .and_then(|cookie| self.insert_raw(&cookie, request_url))
+    }
+
+    /// Determine the outcome of [`CookieStore::parse`] for `cookie_str` received from
+    /// `request_url`, i.e. whether it would be accepted and if not why, without modifying the
+    /// store. All of the validation of [`CookieStore::insert`] is applied, including the limits and
+    /// [`OverflowPolicy`] of the store; any [`CookieTransform`] is also applied, so should be free
+    /// of side effects. No events are sent to an observer set via [`CookieStore::set_observer`].
+    pub fn would_accept(&self, cookie_str: &str, request_url: &Url) -> InsertResult {
+        self.clone_without_observer().parse(cookie_str, request_url)
+    }
+
+    /// A copy of the cookies and settings of the store, without any observer (and so without the
+    /// audit log, tombstones, or change log held by the observer, which are not copied)
+    fn clone_without_observer(&self) -> CookieStore {
+        CookieStore {
+            cookies: self.cookies.clone(),
+            domain_index: self.domain_index.clone(),
+            interned: self.interned.clone(),
+            clock: self.clock.clone(),
+            observer: None,
+            domain_allowlist: self.domain_allowlist.clone(),
+            domain_denylist: self.domain_denylist.clone(),
+            generation: self.generation,
+            clean_generation: self.clean_generation,
+            same_site_lax_by_default: self.same_site_lax_by_default,
+            scheme_bound: self.scheme_bound,
+            port_bound: self.port_bound,
+            trustworthy_origins: self.trustworthy_origins.clone(),
+            max_cookies: self.max_cookies,
+            max_cookies_per_domain: self.max_cookies_per_domain,
+            max_bytes_per_domain: self.max_bytes_per_domain,
+            overflow_policy: self.overflow_policy,
+            persist_session_cookies: self.persist_session_cookies,
+            first_party_only_persistence: self.first_party_only_persistence,
+            date_parsing: self.date_parsing,
+            transform: self.transform.clone(),
+            ttl_policy: self.ttl_policy,
+            max_idle: self.max_idle,
+            idle_purged_at: self.idle_purged_at,
+            #[cfg(feature = "public_suffix")]
+            public_suffix_list: self.public_suffix_list.clone(),
+        }
+    }
+
+    /// Converts a `cookie::Cookie` (from the `cookie` crate) into a `cookie_store::Cookie` and
+    /// inserts it into the store.
+    pub fn insert_raw(&mut self, cookie: &RawCookie<'_>, request_url: &Url) -> InsertResult {
+        Cookie::try_from_raw_cookie_at(cookie, request_url, self.clock.now_utc())
+            .and_then(|cookie| self.insert(cookie.into_owned(), request_url))
+    }
+
+    /// As [`CookieStore::insert_raw`], recording `site_for_cookies` with the `Cookie` as per
+    /// [`CookieStore::insert_for_site`]
+    pub fn insert_raw_for_site(
+        &mut self,
+        cookie: &RawCookie<'_>,
+        request_url: &Url,
+        site_for_cookies: &Url,
+    ) -> InsertResult {
+        Cookie::try_from_raw_cookie_at(cookie, request_url, self.clock.now_utc()).and_then(
+            |cookie| self.insert_for_site(cookie.into_owned(), request_url, site_for_cookies),
+        )
+    }
+
+    /// Inserts a fully-specified `cookie` into the store without a request-uri, e.g. when loading
+    /// cookies from another source or constructing fixtures in tests. None of the checks of
+    /// [`CookieStore::insert`] against the request-uri (nor the domain allowlist or denylist of the
+    /// store) are applied, and the `Cookie` is stored even if __expired__; only its domain is
+    /// normalized. As with `insert`, a `Cookie` replacing an existing `Cookie` retains the
+    /// creation-time of the existing `Cookie`. Fails with `CookieError::UnspecifiedDomain` if the
+    /// `Cookie` has neither a HostOnly nor Suffix domain.
+    pub fn insert_unchecked(&mut self, mut cookie: Cookie<'static>) -> InsertResult {
+        cookie.domain = match cookie.domain {
+            crate::CookieDomain::HostOnly(ref domain) => {
+                crate::CookieDomain::HostOnly(normalize_domain(domain).into_owned())
+            }
+            crate::CookieDomain::Suffix(ref domain) => {
+                crate::CookieDomain::Suffix(normalize_domain(domain).into_owned())
+            }
+            crate::CookieDomain::Empty | crate::CookieDomain::NotPresent => {
+                return Err(CookieError::UnspecifiedDomain)
+            }
+        };
+        let old_creation_time = self
+            .get_any(&String::from(&cookie.domain), &cookie.path, cookie.name())
+            .and_then(|old_cookie| old_cookie.creation_time);
+        cookie.creation_time = old_creation_time
+            .or(cookie.creation_time)
+            .or_else(|| Some(self.clock.now_utc()));
+        Ok(if self.store_cookie(cookie).is_none() {
+            StoreAction::Inserted
+        } else {
+            StoreAction::UpdatedExisting
+        })
+    }
+
+    /// Inserts `cookie`, received from `request_url`, into the store, following the rules of the
+    /// [IETF RFC6265 Storage Model](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3). If the
+    /// `Cookie` is __unexpired__ and is successfully inserted, returns
+    /// `Ok(StoreAction::Inserted)`. If the `Cookie` is __expired__ *and* matches an existing
+    /// `Cookie` in the store, the existing `Cookie` wil be `expired()` and
+    /// `Ok(StoreAction::ExpiredExisting)` will be returned. A new `Cookie` exceeding the capacity
+    /// limits of the store is handled per its [`OverflowPolicy`].
+    ///
+    /// The expiry of `cookie` is taken as-is; in particular, any Max-Age attribute was resolved
+    /// to an expiry when `cookie` was created, so for a store with a custom [`Clock`] the `Cookie`
+    /// should be created relative to the time of the store (see [`CookieStore::now_utc`]), as
+    /// [`CookieStore::parse`] and [`CookieStore::insert_raw`] do.
+    pub fn insert(&mut self, cookie: Cookie<'static>, request_url: &Url) -> InsertResult {
+        self.insert_with(cookie, request_url, true)
+            .map(|(action, _)| action)
+    }
+
+    /// As [`CookieStore::insert`], recording the site of `site_for_cookies`, the URL of the
+    /// top-level document under which `cookie` was received (e.g. the page which loaded the
+    /// resource setting `cookie`), as the [`Cookie::site_for_cookies`] of the stored `Cookie`.
+    /// The site is the registrable domain of the host of `site_for_cookies` if the store has a
+    /// public suffix list, otherwise its host. See
+    /// [`CookieStore::with_first_party_only_persistence`].
+    pub fn insert_for_site(
+        &mut self,
+        mut cookie: Cookie<'static>,
+        request_url: &Url,
+        site_for_cookies: &Url,
+    ) -> InsertResult {
+        cookie.site_for_cookies = self.site_of(site_for_cookies);
+        self.insert(cookie, request_url)
+    }
+
+    /// The site of `url`: the registrable domain of its host per the public suffix list of the
+    /// store, if any, otherwise its host
+    fn site_of(&self, url: &Url) -> Option<String> {
+        #[cfg(feature = "public_suffix")]
+        if let (Some(psl), Some(domain)) = (&self.public_suffix_list, url.domain()) {
+            if let Some(site) = psl.registrable_domain(domain) {
+                return Some(site);
+            }
+        }
+        url.host_str().map(str::to_owned)
+    }
+
+    /// As [`CookieStore::insert`], but an __unexpired__ `Cookie` already in the store with the
+    /// same domain, path, and name is neither replaced nor expired by `cookie`, in which case
+    /// `Ok(StoreAction::Ignored)` is returned.
+    pub fn insert_if_absent(&mut self, cookie: Cookie<'static>, request_url: &Url) -> InsertResult {
+        self.insert_with(cookie, request_url, false)
+            .map(|(action, _)| action)
+    }
+
+    /// As [`CookieStore::insert`], but also returning the `Cookie` replaced by `cookie` when the
+    /// result is `StoreAction::UpdatedExisting`, e.g. to detect a rotated session id
+    pub fn upsert(&mut self, cookie: Cookie<'static>, request_url: &Url) -> UpsertResult {
+        self.insert_with(cookie, request_url, true)
+    }
+
+    fn insert_with(
+        &mut self,
+        cookie: Cookie<'static>,
+        request_url: &Url,
+        overwrite: bool,
+    ) -> UpsertResult {
+        let context = |cookie: &Cookie<'_>| {
+            let mut context = ErrorContext::of(cookie, request_url);
+            context.domain = context
+                .domain
+                .or_else(|| cookie.domain.as_str().map(str::to_owned));
+            context
+        };
+        if cookie.http_only().unwrap_or(false) && !is_http_scheme(request_url) {
+            // If the cookie was received from a "non-HTTP" API and the
+            // cookie's http-only-flag is set, abort these steps and ignore the
+            // cookie entirely.
+            return Err(CookieError::NonHttpScheme(context(&cookie)));
+        }
+        if cookie.secure().unwrap_or(false)
+            && self.trustworthy_origins.rejects_secure_from(request_url)
+        {
+            // If the scheme component of the request-uri does not denote a "secure" protocol
+            // (as defined by the user agent), and the cookie's secure-only-flag is true, then
+            // abort these steps and ignore the cookie entirely.
+            return Err(CookieError::NonSecureOrigin(context(&cookie)));
+        }
+        let mut cookie = cookie;
+        #[cfg(feature = "public_suffix")]
+        if let Some(ref psl) = self.public_suffix_list {
+            // If the user agent is configured to reject "public suffixes"
+            if psl.is_public_suffix(&cookie.domain) {
+                // and the domain-attribute is a public suffix:
+                if cookie.domain.host_is_identical(request_url) {
+                    //   If the domain-attribute is identical to the canonicalized
+                    //   request-host:
+                    //     Let the domain-attribute be the empty string.
+                    // (NB: at this point, an empty domain-attribute should be represented
+                    // as the HostOnly variant of CookieDomain)
+                    cookie.domain = crate::cookie_domain::CookieDomain::host_only(request_url)?;
+                } else {
+                    //   Otherwise:
+                    //     Ignore the cookie entirely and abort these steps.
+                    return Err(CookieError::PublicSuffix(context(&cookie)));
+                }
+            }
+        }
+        if !cookie.domain.matches(request_url) {
+            // If the canonicalized request-host does not domain-match the
+            // domain-attribute:
+            //    Ignore the cookie entirely and abort these steps.
+            return Err(CookieError::DomainMismatch(context(&cookie)));
+        }
+        if let Some(transform) = &self.transform {
+            transform.transform(&mut cookie, request_url);
+        }
+        self.ttl_policy.apply(&mut cookie, self.clock.now_utc());
+        // NB: we do not bail out above on is_expired(), as servers can remove a cookie
+        // by sending
+        // an expired one, so we need to do the old_cookie check below before checking
+        // is_expired() on an incoming cookie
+        let now_utc = self.clock.now_utc();
+        self.purge_idle_if_due(now_utc);
+        let is_expired = cookie.expires_by(&now_utc);
+        let mut creation_time = Some(now_utc);
+
+        {
+            // At this point in parsing, any non-present Domain attribute should have been
+            // converted into a HostOnly variant
+            let cookie_domain = cookie
+                .domain
+                .as_cow()
+                .ok_or(CookieError::UnspecifiedDomain)?;
+            if !self.is_domain_permitted(&cookie_domain) {
+                return Err(CookieError::DomainNotPermitted(context(&cookie)));
+            }
+            if !cookie.secure().unwrap_or(false)
+                && !self.trustworthy_origins.is_trustworthy(request_url)
+                && self.shadows_secure(&cookie_domain, &cookie.path, cookie.name(), &now_utc)
+            {
+                // If the cookie's secure-only-flag is false, and the scheme component of
+                // request-uri does not denote a "secure" protocol, then abort these steps and
+                // ignore the cookie entirely if the cookie store contains one or more cookies
+                // that meet all of the following criteria:
+                //   1. Their name matches the name of the newly-created cookie.
+                //   2. Their secure-only-flag is true.
+                //   3. Their domain domain-matches the domain of the newly-created cookie, or
+                //      vice-versa.
+                //   4. The path of the newly-created cookie path-matches the path of the
+                //      existing cookie.
+                return Err(CookieError::ShadowsSecure(context(&cookie)));
+            }
+            if let Some(old_cookie) = self.get_mut(&cookie_domain, &cookie.path, cookie.name()) {
+                if !overwrite {
+                    return Ok((StoreAction::Ignored, None));
+                } else if old_cookie.http_only().unwrap_or(false) && !is_http_scheme(request_url) {
+                    // 2.  If the newly created cookie was received from a "non-HTTP"
+                    //    API and the old-cookie's http-only-flag is set, abort these
+                    //    steps and ignore the newly created cookie entirely.
+                    return Err(CookieError::NonHttpScheme(context(&cookie)));
+                } else if is_expired {
+                    old_cookie.expire();
+                    self.generation += 1;
+                    if let Some(observer) = &self.observer {
+                        if let Some(expired) =
+                            self.get_any(&cookie_domain, &cookie.path, cookie.name())
+                        {
+                            observer.notify_from(
+                                StoreEvent::Expired(expired),
+                                Some(request_url),
+                                self.generation,
+                            );
+                        }
+                    }
+                    return Ok((StoreAction::ExpiredExisting, None));
+                }
+                // 3.  Update the creation-time of the newly created cookie to match the
+                //     creation-time of the old-cookie.
+                creation_time = old_cookie.creation_time.or(creation_time);
+            }
+            if !is_expired {
+                match self.make_room(&cookie_domain, &cookie) {
+                    Room::Available => {}
+                    Room::Discard => return Ok((StoreAction::Ignored, None)),
+                    Room::Reject => return Err(CookieError::StoreFull(context(&cookie))),
+                }
+            }
+        }
+
+        if !is_expired {
+            let mut cookie = cookie;
+            cookie.creation_time = creation_time;
+            cookie.last_access_time = Some(now_utc);
+            if self.same_site_lax_by_default && cookie.same_site().is_none() {
+                cookie.set_same_site(SameSite::Lax);
+            }
+            if self.scheme_bound {
+                cookie.source_scheme = Some(SourceScheme::of(request_url));
+            }
+            if self.port_bound {
+                cookie.source_port = request_url.port_or_known_default();
+            }
+            Ok(match self.store_cookie_from(cookie, Some(request_url)) {
+                None => (StoreAction::Inserted, None),
+                Some(old) => (StoreAction::UpdatedExisting, Some(unshare_path(old))),
+            })
+        } else {
+            Err(CookieError::Expired(context(&cookie)))
+        }
+    }
+
+    /// Add `cookie` to the store under its domain, path, and name keys, returning any `Cookie`
+    /// it replaced.
+    fn store_cookie(&mut self, cookie: Cookie<'static>) -> Option<Cookie<'static>> {
+        self.store_cookie_from(cookie, None)
+    }
+
+    /// As `store_cookie`, for a `cookie` received from `source_url` if any
+    fn store_cookie_from(
+        &mut self,
+        mut cookie: Cookie<'static>,
+        source_url: Option<&Url>,
+    ) -> Option<Cookie<'static>> {
+        let domain = self.interned.intern(&String::from(&cookie.domain));
+        let path = self.interned.intern(&cookie.path);
+        let name = self.interned.intern(cookie.name());
+        cookie.path = cookie.path.with_interned(Arc::clone(&path));
+        if !self.cookies.contains_key(&domain) {
+            self.domain_index.insert(&domain);
+        }
+        let name_map = Arc::make_mut(self.cookies.entry(domain).or_default())
+            .entry(path)
+            .or_default();
+        let old = name_map.insert(Arc::clone(&name), cookie);
+        self.generation += 1;
+        if let Some(observer) = &self.observer {
+            let new = &name_map[&name];
+            let event = match old {
+                Some(ref old) => StoreEvent::Updated { old, new },
+                None => StoreEvent::Inserted(new),
+            };
+            observer.notify_from(event, source_url, self.generation);
+        }
+        old
+    }
+
+    /// Merge the cookies (including __expired__) of `other` into the store, using `strategy` to
+    /// decide which `Cookie` to keep when both stores contain a `Cookie` with the same domain,
+    /// path, and name.
+    ///
+    /// If tombstones are enabled (see [`CookieStore::with_tombstones`]), deletions are propagated
+    /// between the stores: a `Cookie` of `other` created before its deletion from the store is
+    /// not merged, and a `Cookie` of the store created before its deletion from `other` is
+    /// removed. As per RFC6265, the creation-time of a `Cookie` is retained when it is updated.
+    /// The tombstones of `other` are added to those of the store.
+    ///
+    /// The cookies of `other` were validated when inserted into `other`, and are merged as-is:
+    /// unlike [`CookieStore::insert`], `merge` does not apply the capacity limits (e.g.
+    /// [`CookieStore::with_max_cookies`]), the domain allow- and denylists, or the check against
+    /// non-secure cookies shadowing __Secure__ cookies of the store.
+    pub fn merge(&mut self, other: CookieStore, strategy: MergeStrategy) {
+        let other_tombstones = other.tombstones();
+        if self.tombstone_list().is_some() {
+            for tombstone in other_tombstones.iter() {
+                let id = &tombstone.cookie;
+                let deleted =
+                    self.get_any(&id.domain, &id.path, &id.name)
+                        .map_or(false, |existing| {
+                            existing
+                                .creation_time
+                                .map_or(true, |created| created <= tombstone.deleted_at)
+                        });
+                if deleted {
+                    self.remove_by_id(id);
+                }
+            }
+        }
+        for mut cookie in other {
+            if self.is_tombstoned(&cookie) {
+                continue;
+            }
+            let existing = cookie
+                .domain
+                .as_cow()
+                .and_then(|domain| self.get_any(&domain, &cookie.path, cookie.name()));
+            let keep_existing = existing.map_or(false, |existing| match strategy {
+                    MergeStrategy::PreferSelf => true,
+                    MergeStrategy::PreferOther => false,
+                    MergeStrategy::PreferNewerExpiry => {
+                        match (&existing.expires, &cookie.expires) {
+                            (_, CookieExpiration::SessionEnd) => {
+                                existing.expires == CookieExpiration::SessionEnd
+                            }
+                            (CookieExpiration::SessionEnd, _) => true,
+                            (CookieExpiration::AtUtc(existing), CookieExpiration::AtUtc(other)) => {
+                                existing >= other
+                            }
+                        }
+                    }
+                });
+            if !keep_existing {
+                if let Some(existing) = existing {
+                    cookie.creation_time = existing.creation_time;
+                }
+                self.store_cookie(cookie);
+            }
+        }
+        let deleted = other_tombstones
+            .into_iter()
+            .filter(|tombstone| {
+                let id = &tombstone.cookie;
+                self.get_any(&id.domain, &id.path, &id.name).is_none()
+            })
+            .collect::<Vec<_>>();
+        self.restore_tombstones(deleted);
+    }
+
+    /// The differences between the cookies (including __expired__) in the store and those in
+    /// `other`, i.e. the cookies which would be added, removed, or changed in going from this
+    /// store to `other`.
+    pub fn diff<'a>(&'a self, other: &'a CookieStore) -> StoreDiff<'a> {
+        let mut diff = StoreDiff::default();
+        for (domain, path_map) in self.cookies.iter() {
+            for (path, name_map) in path_map.iter() {
+                for (name, cookie) in name_map.iter() {
+                    match other.get_any(domain, path, name) {
+                        None => diff.removed.push(cookie),
+                        Some(other_cookie) if other_cookie != cookie => {
+                            diff.changed.push((cookie, other_cookie))
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+        for (domain, path_map) in other.cookies.iter() {
+            for (path, name_map) in path_map.iter() {
+                for (name, cookie) in name_map.iter() {
+                    if self.get_any(domain, path, name).is_none() {
+                        diff.added.push(cookie);
+                    }
+                }
+            }
+        }
+        diff
+    }
+
+    /// Retain only the cookies (including __expired__) for which `f` returns `true`, removing
+    /// all others from the store.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Cookie<'static>) -> bool,
+    {
+        let observer = &self.observer;
+        let generation = self.generation + 1;
+        let mut removed = false;
+        self.cookies.retain(|_, path_map| {
+            // the cookies to remove are found through a shared borrow, so that a domain shared
+            // with a snapshot is only copied if any of its cookies are removed
+            let mut doomed = std::collections::HashSet::new();
+            for (path, name_map) in path_map.iter() {
+                for (name, cookie) in name_map {
+                    if !f(cookie) {
+                        if let Some(observer) = observer {
+                            observer.notify(StoreEvent::Removed(cookie), generation);
+                        }
+                        doomed.insert((Arc::clone(path), Arc::clone(name)));
+                    }
+                }
+            }
+            if doomed.is_empty() {
+                return true;
+            }
+            removed = true;
+            let path_map = Arc::make_mut(path_map);
+            path_map.retain(|path, name_map| {
+                name_map.retain(|name, _| {
+                    !doomed.contains(&(Arc::clone(path), Arc::clone(name)))
+                });
+                !name_map.is_empty()
+            });
+            !path_map.is_empty()
+        });
+        if removed {
+            self.generation += 1;
+            self.prune_domain_index();
+            self.interned.prune();
+        }
+    }
+
+    /// Drop any domains no longer in the store from `domain_index`
+    fn prune_domain_index(&mut self) {
+        let cookies = &self.cookies;
+        self.domain_index
+            .retain(|domain| cookies.contains_key(domain));
+    }
+
+    /// Expire the __unexpired__ cookies for which `f` returns `true`, returning the number of
+    /// cookies expired. Unlike [`CookieStore::retain`], the cookies are kept in the store (until
+    /// e.g. [`CookieStore::purge_expired`]), so that the expiry is reflected in any store the
+    /// __expired__ cookies are saved to or merged into; e.g. to force credentials to be
+    /// invalidated.
+    pub fn expire_matching<F>(&mut self, mut f: F) -> usize
+    where
+        F: FnMut(&Cookie<'static>) -> bool,
+    {
+        let ids = self
+            .iter_unexpired()
+            .filter(|c| f(c))
+            .map(Cookie::identity)
+            .collect::<Vec<_>>();
+        for id in &ids {
+            if let Some(cookie) = self.get_mut_any(&id.domain, &id.path, &id.name) {
+                cookie.expire();
+            }
+            if let Some(observer) = &self.observer {
+                if let Some(expired) = self.get_any(&id.domain, &id.path, &id.name) {
+                    observer.notify(StoreEvent::Expired(expired), self.generation + 1);
+                }
+            }
+        }
+        if !ids.is_empty() {
+            self.generation += 1;
+        }
+        ids.len()
+    }
+
+    /// Record that the cookies for `request_url` (i.e. those returned by
+    /// [`CookieStore::matches`]) have been sent in a request, updating their
+    /// [`last_access_time`](Cookie::last_access_time) as per
+    /// [IETF RFC6265 Section 5.4](https://datatracker.ietf.org/doc/html/rfc6265#section-5.4).
+    /// Returns the number of cookies updated. If any are updated, the store is marked dirty (so
+    /// that the access times judged by [`CookieStore::purge_idle`] are persisted), though no
+    /// observer is notified.
+    pub fn touch(&mut self, request_url: &Url) -> usize {
+        let now_utc = self.clock.now_utc();
+        let ids = self
+            .matches_iter(request_url)
+            .map(Cookie::identity)
+            .collect::<Vec<_>>();
+        for id in &ids {
+            if let Some(cookie) = self.get_mut_any(&id.domain, &id.path, &id.name) {
+                cookie.last_access_time = Some(now_utc);
+            }
+        }
+        if !ids.is_empty() {
+            self.generation += 1;
+        }
+        ids.len()
+    }
+
+    /// Whether the store purges idle cookies, and so the request helpers holding exclusive access
+    /// to the store should record the cookies sent via [`CookieStore::touch`]
+    pub(crate) fn tracks_access(&self) -> bool {
+        self.max_idle.is_some()
+    }
+
+    /// Remove the cookies (including __expired__) which have not been accessed for longer than
+    /// `max_idle`, i.e. those last stored, or last sent as recorded by [`CookieStore::touch`],
+    /// before then, returning the number of cookies removed. __NB__: sending a `Cookie` is only
+    /// recorded via [`CookieStore::touch`]; see [`CookieStore::with_max_idle`]. Cookies without a recorded
+    /// [`last_access_time`](Cookie::last_access_time) are judged by their
+    /// [`creation_time`](Cookie::creation_time), and are retained if that too is unknown.
+    pub fn purge_idle(&mut self, max_idle: time::Duration) -> usize {
+        let len = self.len();
+        // no cookie can have been idle for longer than representable
+        let idle_since = match self.clock.now_utc().checked_sub(max_idle) {
+            Some(idle_since) => idle_since,
+            None => return 0,
+        };
+        self.retain(|c| {
+            c.last_access_time
+                .or(c.creation_time)
+                .map_or(true, |accessed| accessed >= idle_since)
+        });
+        len - self.len()
+    }
+
+    /// Purge idle cookies per `max_idle`, if not done within the last half of `max_idle`
+    fn purge_idle_if_due(&mut self, now_utc: time::OffsetDateTime) {
+        if let Some(max_idle) = self.max_idle {
+            if self
+                .idle_purged_at
+                .map_or(true, |purged_at| now_utc - purged_at >= max_idle / 2)
+            {
+                self.purge_idle(max_idle);
+                self.idle_purged_at = Some(now_utc);
+            }
+        }
+    }
+
+    /// Remove all __expired__ cookies from the store, returning the number of cookies removed
+    pub fn purge_expired(&mut self) -> usize {
+        let len = self.len();
+        let now_utc = self.clock.now_utc();
+        self.retain(|c| !c.expires_by(&now_utc));
+        len - self.len()
+    }
+
+    /// Remove all cookies (including __expired__) from the store, returning them as an iterator
+    /// of owned `Cookie`s. Any cookies not consumed from the iterator are dropped.
+    pub fn drain(&mut self) -> IntoIter {
+        if !self.cookies.is_empty() {
+            self.generation += 1;
+        }
+        if let Some(observer) = &self.observer {
+            for cookie in self.iter_any() {
+                observer.notify(StoreEvent::Removed(cookie), self.generation);
+            }
+        }
+        self.domain_index.clear();
+        self.interned.clear();
+        IntoIter::new(std::mem::take(&mut self.cookies))
+    }
+
+    /// Clear the contents of the store
+    pub fn clear(&mut self) {
+        if !self.cookies.is_empty() {
+            self.generation += 1;
+        }
+        if let Some(observer) = &self.observer {
+            for cookie in self.iter_any() {
+                observer.notify(StoreEvent::Removed(cookie), self.generation);
+            }
+        }
+        self.cookies.clear();
+        self.domain_index.clear();
+        self.interned.clear();
+    }
+
+    /// An estimate of the number of heap bytes used by the store, including the cookies it
+    /// contains and the maps used to index them.
+    pub fn memory_footprint(&self) -> usize {
+        fn map_size<K, V>(map: &Map<K, V>) -> usize {
+            map.capacity() * (size_of::<K>() + size_of::<V>())
+        }
+        fn cookie_size(cookie: &Cookie<'_>) -> usize {
+            // the path is shared with the interned keys, so is not counted here
+            cookie.name().len()
+                + cookie.value().len()
+                + cookie.domain().map_or(0, str::len)
+                + cookie.path().map_or(0, str::len)
+                + cookie.domain.as_cow().map_or(0, |d| d.len())
+        }
+        let mut size =
+            map_size(&self.cookies) + self.domain_index.heap_size() + self.interned.heap_size();
+        for path_map in self.cookies.values() {
+            size += map_size(path_map);
+            for name_map in path_map.values() {
+                size += map_size(name_map) + name_map.values().map(cookie_size).sum::<usize>();
+            }
+        }
+        size
+    }
+
+    /// Verify the internal invariants of the store, returning any violations found; e.g. after
+    /// loading cookies from a hand-edited or third-party file. An empty result indicates a
+    /// consistent store.
+    pub fn validate(&self) -> Vec<StoreViolation> {
+        let mut violations = Vec::new();
+        for (domain, path_map) in self.cookies.iter() {
+            if normalize_domain(domain) != **domain || domain.is_empty() {
+                violations.push(StoreViolation::UnnormalizedDomain(String::from(&**domain)));
+            }
+            if path_map.is_empty() {
+                violations.push(StoreViolation::EmptyDomain(String::from(&**domain)));
+            }
+            for (path, name_map) in path_map.iter() {
+                if name_map.is_empty() {
+                    violations.push(StoreViolation::EmptyPath(
+                        String::from(&**domain),
+                        String::from(&**path),
+                    ));
+                }
+                for (name, cookie) in name_map.iter() {
+                    let id = cookie.identity();
+                    if id.domain != **domain || id.path != **path || id.name != **name {
+                        violations.push(StoreViolation::KeyMismatch {
+                            key: CookieIdentity::new(domain, path, name),
+                            cookie: id.clone(),
+                        });
+                    }
+                    if cookie.domain.as_str().is_none() {
+                        violations.push(StoreViolation::UnspecifiedDomain(id.clone()));
+                    }
+                    if !cookie.path.starts_with('/') {
+                        violations.push(StoreViolation::InvalidPath(id.clone()));
+                    }
+                    if let Some(expires) = cookie.expires.as_datetime() {
+                        if !(0..=9999).contains(&expires.year()) {
+                            violations.push(StoreViolation::InvalidExpiration(id));
+                        }
+                    }
+                }
+            }
+        }
+        violations
+    }
+
+    /// Summary statistics of the contents of the store
+    pub fn stats(&self) -> StoreStats {
+        let now_utc = self.clock.now_utc();
+        let mut stats = StoreStats {
+            heap_bytes: self.memory_footprint(),
+            ..StoreStats::default()
+        };
+        for (domain, path_map) in self.cookies.iter() {
+            let domain_cookies = domain_len(path_map);
+            stats.expired += path_map
+                .values()
+                .flat_map(|name_map| name_map.values())
+                .filter(|cookie| cookie.expires_by(&now_utc))
+                .count();
+            stats.cookies += domain_cookies;
+            stats
+                .cookies_per_domain
+                .push((String::from(&**domain), domain_cookies));
+        }
+        stats
+    }
+
+    /// Prune any empty path or name maps from the store, release interned keys which are no
+    /// longer in use, and shrink the capacity of the internal maps as much as possible. Note
+    /// that __expired__ cookies are retained in the store, and so are not pruned.
+    pub fn shrink_to_fit(&mut self) {
+        self.cookies.retain(|_, path_map| {
+            // a `PathMap` shared with a snapshot is left as is, rather than copied
+            if let Some(path_map) = Arc::get_mut(path_map) {
+                path_map.retain(|_, name_map| {
+                    name_map.shrink_to_fit();
+                    !name_map.is_empty()
+                });
+                path_map.shrink_to_fit();
+            }
+            !path_map.is_empty()
+        });
+        self.prune_domain_index();
+        self.cookies.shrink_to_fit();
+        self.interned.shrink_to_fit();
+    }
+
+    /// The number of cookies (including __expired__) in the store
+    pub fn len(&self) -> usize {
+        self.cookies.values().map(domain_len).sum()
+    }
+
+    /// Returns true if the store contains no cookies (including __expired__)
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+
+    /// The number of __unexpired__ cookies in the store
+    pub fn unexpired_len(&self) -> usize {
+        self.iter_unexpired().count()
+    }
+
+    /// The number of cookies (including __expired__) stored under `domain`
+    pub fn domain_len(&self, domain: &str) -> usize {
+        self.cookies
+            .get(&*normalize_domain(domain))
+            .map_or(0, domain_len)
+    }
+
+    /// An iterator visiting all the __unexpired__ cookies in the store
+    pub fn iter_unexpired<'a>(&'a self) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
+        let now_utc = self.clock.now_utc();
+        self.cookies
+            .values()
+            .flat_map(|dcs| dcs.values())
+            .flat_map(|pcs| pcs.values())
+            .filter(move |c| !c.expires_by(&now_utc))
+    }
+
+    /// The __unexpired__ __persistent__ cookies which expire before `deadline`, soonest
+    /// expiring first, e.g. to refresh authentication cookies before they lapse during a
+    /// long-running job. __Non-persistent__ cookies are not included, as they expire only at the
+    /// end of the session.
+    pub fn cookies_expiring_before(&self, deadline: time::OffsetDateTime) -> Vec<&Cookie<'static>> {
+        let mut expiring = self
+            .iter_unexpired()
+            .filter(|c| match c.expires {
+                CookieExpiration::AtUtc(expires) => expires < deadline,
+                CookieExpiration::SessionEnd => false,
+            })
+            .collect::<Vec<_>>();
+        expiring.sort_by_key(|c| match c.expires {
+            CookieExpiration::AtUtc(expires) => Some(expires),
+            CookieExpiration::SessionEnd => None,
+        });
+        expiring
+    }
+
+    /// An iterator visiting all the __expired__ cookies in the store; these are retained in the
+    /// store until removed, e.g. via [`CookieStore::retain`]
+    pub fn iter_expired<'a>(&'a self) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
+        let now_utc = self.clock.now_utc();
+        self.iter_any().filter(move |c| c.expires_by(&now_utc))
+    }
+
+    /// An iterator visiting all the __unexpired__ and __persistent__ cookies in the store, i.e.
+    /// those which are written by [`CookieStore::save`]
+    pub fn iter_persistent<'a>(&'a self) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
+        self.iter_unexpired().filter(|c| c.is_persistent())
+    }
+
+    /// The current time, per the [`Clock`] of the store; e.g. for building a `Cookie` to be
+    /// inserted via [`Cookie::try_from_raw_cookie_at`]
+    pub fn now_utc(&self) -> time::OffsetDateTime {
+        self.clock.now_utc()
+    }
+
+    /// An iterator visiting the cookies to be saved by the functions saving the __unexpired__ and
+    /// __persistent__ cookies of the store, including any __non-persistent__ cookies if so
+    /// configured via [`CookieStore::with_persist_session_cookies`], and excluding any
+    /// third-party cookies if so configured via
+    /// [`CookieStore::with_first_party_only_persistence`]
+    pub(crate) fn iter_saved<'a>(&'a self) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
+        self.iter_unexpired()
+            .filter(move |c| self.persist_session_cookies || c.is_persistent())
+            .filter(move |c| !self.first_party_only_persistence || c.is_first_party())
+    }
+
+    /// An iterator visiting all (including __expired__) cookies in the store
+    pub fn iter_any<'a>(&'a self) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
+        self.cookies
+            .values()
+            .flat_map(|dcs| dcs.values())
+            .flat_map(|pcs| pcs.values())
+    }
+
+    /// An iterator visiting the domains under which cookies are stored, i.e. the Domain attribute
+    /// of cookies (or the request-host, for HostOnly cookies)
+    pub fn domains(&self) -> impl Iterator<Item = &str> + '_ {
+        self.cookies.keys().map(|domain| &**domain)
+    }
+
+    /// An iterator visiting all (including __expired__) cookies stored under `domain`; see
+    /// [`CookieStore::domains`]
+    pub fn iter_domain<'a>(
+        &'a self,
+        domain: &str,
+    ) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
+        self.cookies
+            .get(&*normalize_domain(domain))
+            .into_iter()
+            .flat_map(|dcs| dcs.values())
+            .flat_map(|pcs| pcs.values())
+    }
+
+    /// An iterator visiting all (including __expired__) cookies stored under a domain matching
+    /// the glob `domain_pattern` and, if provided, with a path matching the glob `path_pattern`.
+    /// In a glob, `*` matches any (possibly empty) sequence of characters, and `?` any single
+    /// character; e.g. `*.example.com` selects the cookies of all subdomains of `example.com`
+    /// (but not those of `example.com` itself). Domains are matched case-insensitively.
+    pub fn query<'a>(
+        &'a self,
+        domain_pattern: &str,
+        path_pattern: Option<&'a str>,
+    ) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
+        let domain_pattern = domain_pattern.to_ascii_lowercase();
+        self.cookies
+            .iter()
+            .filter(move |&(domain, _)| is_glob_match(&domain_pattern, domain))
+            .flat_map(move |(_, dcs)| {
+                dcs.iter()
+                    .filter(move |&(path, _)| {
+                        path_pattern.map_or(true, |pattern| is_glob_match(pattern, path))
+                    })
+                    .flat_map(|(_, pcs)| pcs.values())
+            })
+    }
+
+    /// Serialize any __unexpired__ and __persistent__ cookies in the store with `cookie_to_string`
+    /// and write them to `writer`
+    pub fn save<W, E, F>(&self, writer: &mut W, cookie_to_string: F) -> StoreResult<()>
+    where
+        W: Write,
+        F: Fn(&Cookie<'static>) -> Result<String, E>,
+        crate::Error: From<E>,
+    {
+        for cookie in self.iter_saved() {
+            writeln!(
+                writer,
+                "{}",
+                Buffer::from(cookie_to_string(cookie)?).as_str()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Serialize all (including __expired__ and __non-persistent__) cookies in the store with `cookie_to_string` and write them to `writer`
+    pub fn save_incl_expired_and_nonpersistent<W, E, F>(
+        &self,
+        writer: &mut W,
+        cookie_to_string: F,
+    ) -> StoreResult<()>
+    where
+        W: Write,
+        F: Fn(&Cookie<'static>) -> Result<String, E>,
+        crate::Error: From<E>,
+    {
+        for cookie in self.iter_any() {
+            writeln!(
+                writer,
+                "{}",
+                Buffer::from(cookie_to_string(cookie)?).as_str()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Load cookies from `reader`, deserializing with `cookie_from_str`, skipping any __expired__
+    /// cookies
+    pub fn load<R, E, F>(reader: R, cookie_from_str: F) -> StoreResult<CookieStore>
+    where
+        R: BufRead,
+        F: Fn(&str) -> Result<Cookie<'static>, E>,
+        crate::Error: From<E>,
+    {
+        CookieStore::load_from(reader, cookie_from_str, false)
+    }
+
+    /// Load cookies from `reader`, deserializing with `cookie_from_str`, loading both __unexpired__
+    /// and __expired__ cookies
+    pub fn load_all<R, E, F>(reader: R, cookie_from_str: F) -> StoreResult<CookieStore>
+    where
+        R: BufRead,
+        F: Fn(&str) -> Result<Cookie<'static>, E>,
+        crate::Error: From<E>,
+    {
+        CookieStore::load_from(reader, cookie_from_str, true)
+    }
+
+    fn load_from<R, E, F>(
+        reader: R,
+        cookie_from_str: F,
+        include_expired: bool,
+    ) -> StoreResult<CookieStore>
+    where
+        R: BufRead,
+        F: Fn(&str) -> Result<Cookie<'static>, E>,
+        crate::Error: From<E>,
+    {
+        let cookies = reader.lines().map(|line_result| {
+            line_result
+                .map_err(Into::into)
+                .and_then(|line| cookie_from_str(&Buffer::from(line)).map_err(crate::Error::from))
+        });
+        Self::from_cookies(cookies, include_expired)
+    }
+
+    /// Create a `CookieStore` from an iterator of `Cookie` values. When
+    /// `include_expired` is `true`, both __expired__ and __unexpired__ cookies in the incoming
+    /// iterator will be included in the produced `CookieStore`; otherwise, only
+    /// __unexpired__ cookies will be included, and __expired__ cookies filtered
+    /// out.
+    pub fn from_cookies<I, E>(iter: I, include_expired: bool) -> Result<Self, E>
+    where
+        I: IntoIterator<Item = Result<Cookie<'static>, E>>,
+    {
+        let mut cookie_store = CookieStore::default();
+        let now_utc = cookie_store.clock.now_utc();
+        for cookie in iter {
+            let cookie = cookie?;
+            if include_expired || !cookie.expires_by(&now_utc) {
+                cookie_store.store_cookie(cookie);
+            }
+        }
+        cookie_store.mark_clean();
+        Ok(cookie_store)
+    }
+
+    /// As [`CookieStore::from_cookies`], additionally returning a [`LoadWarning`] for each
+    /// __expired__ cookie skipped, each cookie replaced by a later cookie of the same identity,
+    /// and each cookie with a clamped expiry
+    ///
+    /// [`LoadWarning`]: crate::serde::LoadWarning
+    #[cfg(feature = "serde")]
+    pub(crate) fn from_cookies_with_warnings<I, E>(
+        iter: I,
+        include_expired: bool,
+    ) -> Result<(Self, Vec<crate::serde::LoadWarning>), E>
+    where
+        I: IntoIterator<Item = Result<Cookie<'static>, E>>,
+    {
+        use crate::cookie_expiration::MAX_RFC3339;
+        use crate::serde::LoadWarning;
+
+        let mut cookie_store = CookieStore::default();
+        let mut warnings = Vec::new();
+        let now_utc = cookie_store.clock.now_utc();
+        for cookie in iter {
+            let cookie = cookie?;
+            if !include_expired && cookie.expires_by(&now_utc) {
+                warnings.push(LoadWarning::Expired(cookie.identity()));
+                continue;
+            }
+            if cookie.expires == CookieExpiration::AtUtc(MAX_RFC3339) {
+                warnings.push(LoadWarning::ClampedExpiry(cookie.identity()));
+            }
+            let identity = cookie.identity();
+            if cookie_store.store_cookie(cookie).is_some() {
+                warnings.push(LoadWarning::Duplicate(identity));
+            }
+        }
+        cookie_store.mark_clean();
+        Ok((cookie_store, warnings))
+    }
+
+    /// The settings of the store which are persisted alongside its cookies by e.g.
+    /// [`crate::serde::json::save_with_config`]
+    #[cfg(feature = "serde")]
+    pub fn config(&self) -> crate::serde::StoreConfig {
+        crate::serde::StoreConfig {
+            #[cfg(feature = "public_suffix")]
+            public_suffix: self.public_suffix_list.is_some(),
+            #[cfg(not(feature = "public_suffix"))]
+            public_suffix: false,
+            max_cookies: self.max_cookies,
+            max_cookies_per_domain: self.max_cookies_per_domain,
+            max_bytes_per_domain: self.max_bytes_per_domain,
+            overflow_policy: self.overflow_policy,
+            persist_session_cookies: self.persist_session_cookies,
+            first_party_only_persistence: self.first_party_only_persistence,
+            same_site_lax_by_default: self.same_site_lax_by_default,
+            scheme_bound: self.scheme_bound,
+            port_bound: self.port_bound,
+            date_parsing: self.date_parsing,
+            tombstone_retention: self
+                .tombstone_retention()
+                .map(|retention| retention.whole_seconds().max(0) as u64),
+        }
+    }
+
+    /// Apply the settings of `config` to the store. If `config` enables public suffix rejection
+    /// and the store has no public suffix list, the list compiled into the `psl` crate is used
+    /// with feature `psl` (see [`CookieStore::new_with_public_suffix`]), and otherwise a list
+    /// must be specified via [`CookieStore::with_suffix_list`]; without feature
+    /// `public_suffix`, the setting is ignored.
+    #[cfg(feature = "serde")]
+    pub fn with_config(self, config: &crate::serde::StoreConfig) -> CookieStore {
+        let store = CookieStore {
+            #[cfg(feature = "public_suffix")]
+            public_suffix_list: match self.public_suffix_list {
+                #[cfg(feature = "psl")]
+                None if config.public_suffix => Some(SuffixList::Embedded),
+                _ if !config.public_suffix => None,
+                public_suffix_list => public_suffix_list,
+            },
+            max_cookies: config.max_cookies,
+            max_cookies_per_domain: config.max_cookies_per_domain,
+            max_bytes_per_domain: config.max_bytes_per_domain,
+            overflow_policy: config.overflow_policy,
+            persist_session_cookies: config.persist_session_cookies,
+            first_party_only_persistence: config.first_party_only_persistence,
+            same_site_lax_by_default: config.same_site_lax_by_default,
+            scheme_bound: config.scheme_bound,
+            port_bound: config.port_bound,
+            date_parsing: config.date_parsing,
+            ..self
+        };
+        match config.tombstone_retention {
+            Some(retention) => store.with_tombstones(time::Duration::seconds(
+                retention.min(i64::MAX as u64) as i64,
+            )),
+            None => store.without_tombstones(),
+        }
+    }
+
+    pub fn new(
+        #[cfg(feature = "public_suffix")] public_suffix_list: Option<publicsuffix::List>,
+    ) -> Self {
+        Self {
+            #[cfg(feature = "public_suffix")]
+            public_suffix_list: public_suffix_list.map(SuffixList::List),
+            ..CookieStore::default()
+        }
+    }
+}
+
+/// An iterator of the owned cookies (including __expired__) of a [`CookieStore`], as returned by
+/// [`CookieStore::drain`] or [`CookieStore::into_iter`]
+#[derive(Debug)]
+pub struct IntoIter {
+    domains: <DomainMap as IntoIterator>::IntoIter,
+    paths: Option<<PathMap as IntoIterator>::IntoIter>,
+    names: Option<<NameMap as IntoIterator>::IntoIter>,
+}
+
+impl IntoIter {
+    fn new(cookies: DomainMap) -> IntoIter {
+        IntoIter {
+            domains: cookies.into_iter(),
+            paths: None,
+            names: None,
+        }
+    }
+}
+
+impl Iterator for IntoIter {
+    type Item = Cookie<'static>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((_, cookie)) = self.names.as_mut().and_then(Iterator::next) {
+                return Some(cookie);
+            }
+            if let Some((_, name_map)) = self.paths.as_mut().and_then(Iterator::next) {
+                self.names = Some(name_map.into_iter());
+                continue;
+            }
+            let (_, path_map) = self.domains.next()?;
+            self.paths = Some(unshare(path_map).into_iter());
+        }
+    }
+}
+
+impl IntoIterator for CookieStore {
+    type Item = Cookie<'static>;
+    type IntoIter = IntoIter;
+
+    /// Consume the store, returning an iterator of its cookies (including __expired__)
+    fn into_iter(self) -> IntoIter {
+        IntoIter::new(self.cookies)
+    }
+}
+
+#[cfg(feature = "http")]
+/// Conveniences for clients built on the [http](https://crates.io/crates/http) crate.
+impl CookieStore {
+    /// Store the cookies from the `Set-Cookie` header values `cookie_headers`, received from
+    /// `url`. Header values which are not valid UTF-8 or which fail to parse as a cookie are
+    /// skipped.
+    pub fn set_cookies<'h, I>(&mut self, cookie_headers: I, url: &Url)
+    where
+        I: IntoIterator<Item = &'h http::HeaderValue>,
+    {
+        let date_parsing = self.date_parsing;
+        let cookies = cookie_headers.into_iter().filter_map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|s| crate::cookie_date::parse(s, date_parsing).ok())
+                .map(RawCookie::into_owned)
+        });
+        self.store_response_cookies(cookies, url);
+    }
+
+    /// Render the cookies in the store matching `url` as the value of a `Cookie` request header.
+    /// Returns `None` if no cookies match.
+    pub fn cookies(&self, url: &Url) -> Option<http::HeaderValue> {
+        let pairs = self.get_request_values(url).collect::<Vec<_>>();
+        if pairs.is_empty() {
+            return None;
+        }
+        // allocated up front, so that no copies of the value are left behind by reallocation
+        let len = pairs
+            .iter()
+            .map(|(name, value)| name.len() + value.len() + 3)
+            .sum::<usize>();
+        let mut cookie_header = Buffer::from(String::with_capacity(len));
+        for (name, value) in pairs {
+            if !cookie_header.is_empty() {
+                cookie_header.push_str("; ");
+            }
+            cookie_header.push_str(name);
+            cookie_header.push('=');
+            cookie_header.push_str(value);
+        }
+        http::HeaderValue::from_str(&cookie_header)
+            .map_err(|e| debug!("unable to create Cookie header: {:?}", e))
+            .ok()
+    }
+
+    /// Store any cookies from the `Set-Cookie` headers in `headers`, received from `url`.
+    /// Header values which are not valid UTF-8 or which fail to parse as a cookie are skipped.
+    pub fn store_response_headers(&mut self, headers: &http::HeaderMap, url: &Url) {
+        self.set_cookies(headers.get_all(http::header::SET_COOKIE), url);
+    }
+
+    /// Set the `Cookie` header in `headers` to the cookies in the store matching `url`, replacing
+    /// any existing `Cookie` header. If no cookies match, `headers` is left unmodified.
+    pub fn add_cookie_header(&self, headers: &mut http::HeaderMap, url: &Url) {
+        if let Some(value) = self.cookies(url) {
+            headers.insert(http::header::COOKIE, value);
+        }
+    }
+
+    /// Append a `Set-Cookie` header to `headers` for each of the cookies in the store matching
+    /// `url`, as rendered by [`CookieStore::set_cookie_headers_for`]; e.g. for a test server to
+    /// replay the state of the store to a client. Cookies which cannot be rendered as a header
+    /// value are skipped.
+    pub fn add_set_cookie_headers(&self, headers: &mut http::HeaderMap, url: &Url) {
+        for set_cookie in self.set_cookie_headers_for(url) {
+            match http::HeaderValue::from_str(&set_cookie) {
+                Ok(value) => {
+                    headers.append(http::header::SET_COOKIE, value);
+                }
+                Err(e) => debug!("unable to create Set-Cookie header: {:?}", e),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde_json")]
+/// Legacy serialization implementations. The `save` methods do **not** produce valid JSON output
+/// compatible with typical JSON libraries/tools.
+impl CookieStore {
+    /// Serialize any __unexpired__ and __persistent__ cookies in the store to JSON format and
+    /// write them to `writer`
+    ///
+    /// __NB__: this method does not produce valid JSON which can be directly loaded by typical
+    /// JSON libraries/tools; such output may be loaded via [crate::serde::json::load], which
+    /// accepts this legacy layout as well as the canonical JSON format produced by
+    /// [crate::serde::json::save].
+    #[deprecated(
+        since = "0.22.0",
+        note = "See `cookie_store::serde` modules for more robust de/serialization options"
+    )]
+    pub fn save_json<W: Write>(&self, writer: &mut W) -> StoreResult<()> {
+        self.save(writer, ::serde_json::to_string)
+    }
+
+    /// Serialize all (including __expired__ and __non-persistent__) cookies in the store to JSON format and write them to `writer`
+    ///
+    /// __NB__: this method does not produce valid JSON which can be directly loaded by typical
+    /// JSON libraries/tools; such output may be loaded via [crate::serde::json::load], which
+    /// accepts this legacy layout as well as the canonical JSON format produced by
+    /// [crate::serde::json::save].
+    #[deprecated(
+        since = "0.22.0",
+        note = "See `cookie_store::serde` modules for more robust de/serialization options"
+    )]
+    pub fn save_incl_expired_and_nonpersistent_json<W: Write>(
+        &self,
+        writer: &mut W,
+    ) -> StoreResult<()> {
+        self.save_incl_expired_and_nonpersistent(writer, ::serde_json::to_string)
+    }
+
+    /// Load JSON-formatted cookies from `reader`, skipping any __expired__ cookies
+    ///
+    /// Equivalent to [crate::serde::json::load], which loads both the output of
+    /// [CookieStore::save_json] and the canonical JSON format produced by
+    /// [crate::serde::json::save].
+    #[deprecated(
+        since = "0.22.0",
+        note = "See `cookie_store::serde` modules for more robust de/serialization options"
+    )]
+    pub fn load_json<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
+        crate::serde::json::load(reader)
+    }
+
+    /// Load JSON-formatted cookies from `reader`, loading both __expired__ and __unexpired__ cookies
+    ///
+    /// Equivalent to [crate::serde::json::load_all], which loads both the output of
+    /// [CookieStore::save_json] and the canonical JSON format produced by
+    /// [crate::serde::json::save].
+    #[deprecated(
+        since = "0.22.0",
+        note = "See `cookie_store::serde` modules for more robust de/serialization options"
+    )]
+    pub fn load_json_all<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
+        crate::serde::json::load_all(reader)
+    }
+}
+
+#[cfg(feature = "serde")]
+/// Legacy de/serialization implementation which elides the collection-nature of the contained
+/// cookies. Suitable for line-oriented cookie persistence, but prefer/consider
+/// `cookie_store::serde` modules for more universally consumable serialization formats.
+mod serde_legacy {
+    use serde::de::{SeqAccess, Visitor};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    impl Serialize for super::CookieStore {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.collect_seq(self.iter_saved())
+        }
+    }
+
+    struct CookieStoreVisitor;
+
+    impl<'de> Visitor<'de> for CookieStoreVisitor {
+        type Value = super::CookieStore;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(formatter, "a sequence of cookies")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            super::CookieStore::from_cookies(std::iter::from_fn(|| seq.next_element().transpose()), false)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for super::CookieStore {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_seq(CookieStoreVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CookieStore;
+    use super::{EvictionPolicy, InsertResult, OverflowPolicy, StoreAction};
+    use crate::cookie::Cookie;
+    use crate::CookieError;
+    use ::cookie::Cookie as RawCookie;
+    use time::OffsetDateTime;
+
+    use crate::test_util as test_utils;
+
+    macro_rules! inserted {
+        ($e: expr) => {
+            assert_eq!(Ok(StoreAction::Inserted), $e)
+        };
+    }
+    macro_rules! updated {
+        ($e: expr) => {
+            assert_eq!(Ok(StoreAction::UpdatedExisting), $e)
+        };
+    }
+    macro_rules! expired_existing {
+        ($e: expr) => {
+            assert_eq!(Ok(StoreAction::ExpiredExisting), $e)
+        };
+    }
+    macro_rules! domain_mismatch {
+        ($e: expr) => {
+            assert_eq!(Err(CookieError::DomainMismatch(Default::default())), $e)
+        };
+    }
+    macro_rules! non_http_scheme {
+        ($e: expr) => {
+            assert_eq!(Err(CookieError::NonHttpScheme(Default::default())), $e)
+        };
+    }
+    macro_rules! non_rel_scheme {
+        ($e: expr) => {
+            assert_eq!(Err(CookieError::NonRelativeScheme(Default::default())), $e)
+        };
+    }
+    macro_rules! expired_err {
+        ($e: expr) => {
+            assert_eq!(Err(CookieError::Expired(Default::default())), $e)
+        };
+    }
+    macro_rules! values_are {
+        ($store: expr, $url: expr, $values: expr) => {{
+            let mut matched_values = $store
+                .matches(&test_utils::url($url))
+                .iter()
+                .map(|c| &c.value()[..])
+                .collect::<Vec<_>>();
+            matched_values.sort();
+
+            let mut values: Vec<&str> = $values;
+            values.sort();
+
+            assert!(
+                matched_values == values,
+                "\n{:?}\n!=\n{:?}\n",
+                matched_values,
+                values
+            );
+        }};
+    }
+
+    fn add_cookie(
+        store: &mut CookieStore,
+        cookie: &str,
+        url: &str,
+        expires: Option<OffsetDateTime>,
+        max_age: Option<u64>,
+    ) -> InsertResult {
+        store.insert(
+            test_utils::make_cookie(cookie, url, expires, max_age),
+            &test_utils::url(url),
+        )
+    }
+
+    fn make_match_store() -> CookieStore {
+        let mut store = CookieStore::default();
+        inserted!(add_cookie(
+            &mut store,
+            "cookie1=1",
+            "http://example.com/foo/bar",
+            None,
+            Some(60 * 5),
+        ));
+        inserted!(add_cookie(
+            &mut store,
+            "cookie2=2; Secure",
+            "https://example.com/sec/",
+            None,
+            Some(60 * 5),
+        ));
+        inserted!(add_cookie(
+            &mut store,
+            "cookie3=3; HttpOnly",
+            "https://example.com/sec/",
+            None,
+            Some(60 * 5),
+        ));
+        inserted!(add_cookie(
+            &mut store,
+            "cookie4=4; Secure; HttpOnly",
+            "https://example.com/sec/",
+            None,
+            Some(60 * 5),
+        ));
+        inserted!(add_cookie(
+            &mut store,
+            "cookie5=5",
+            "http://example.com/foo/",
+            None,
+            Some(60 * 5),
+        ));
+        inserted!(add_cookie(
+            &mut store,
+            "cookie6=6",
+            "http://example.com/",
+            None,
+            Some(60 * 5),
+        ));
+        inserted!(add_cookie(
+            &mut store,
+            "cookie7=7",
+            "http://bar.example.com/foo/",
+            None,
+            Some(60 * 5),
+        ));
+
+        inserted!(add_cookie(
+            &mut store,
+            "cookie8=8",
+            "http://example.org/foo/bar",
+            None,
+            Some(60 * 5),
+        ));
+        inserted!(add_cookie(
+            &mut store,
+            "cookie9=9",
+            "http://bar.example.org/foo/bar",
+            None,
+            Some(60 * 5),
+        ));
+        store
+    }
+
+    macro_rules! check_matches {
+        ($store: expr) => {{
+            values_are!($store, "http://unknowndomain.org/foo/bar", vec![]);
+            values_are!($store, "http://example.org/foo/bar", vec!["8"]);
+            values_are!($store, "http://example.org/bus/bar", vec![]);
+            values_are!($store, "http://bar.example.org/foo/bar", vec!["9"]);
+            values_are!($store, "http://bar.example.org/bus/bar", vec![]);
+            values_are!(
+                $store,
+                "https://example.com/sec/foo",
+                vec!["6", "4", "3", "2"]
+            );
+            values_are!($store, "http://example.com/sec/foo", vec!["6", "3"]);
+            values_are!($store, "ftp://example.com/sec/foo", vec!["6"]);
+            values_are!($store, "http://bar.example.com/foo/bar/bus", vec!["7"]);
+            values_are!(
+                $store,
+                "http://example.com/foo/bar/bus",
+                vec!["1", "5", "6"]
+            );
+        }};
+    }
+
+    #[test]
+    fn insert_raw() {
+        let mut store = CookieStore::default();
+        inserted!(store.insert_raw(
+            &RawCookie::parse("cookie1=value1").unwrap(),
+            &test_utils::url("http://example.com/foo/bar"),
+        ));
+        non_rel_scheme!(store.insert_raw(
+            &RawCookie::parse("cookie1=value1").unwrap(),
+            &test_utils::url("data:nonrelativescheme"),
+        ));
+        non_http_scheme!(store.insert_raw(
+            &RawCookie::parse("cookie1=value1; HttpOnly").unwrap(),
+            &test_utils::url("ftp://example.com/"),
+        ));
+        expired_existing!(store.insert_raw(
+            &RawCookie::parse("cookie1=value1; Max-Age=0").unwrap(),
+            &test_utils::url("http://example.com/foo/bar"),
+        ));
+        expired_err!(store.insert_raw(
+            &RawCookie::parse("cookie1=value1; Max-Age=-1").unwrap(),
+            &test_utils::url("http://example.com/foo/bar"),
+        ));
+        updated!(store.insert_raw(
+            &RawCookie::parse("cookie1=value1").unwrap(),
+            &test_utils::url("http://example.com/foo/bar"),
+        ));
+        expired_existing!(store.insert_raw(
+            &RawCookie::parse("cookie1=value1; Max-Age=-1").unwrap(),
+            &test_utils::url("http://example.com/foo/bar"),
+        ));
+        domain_mismatch!(store.insert_raw(
+            &RawCookie::parse("cookie1=value1; Domain=bar.example.com").unwrap(),
+            &test_utils::url("http://example.com/foo/bar"),
+        ));
+    }
+
+    #[test]
+    fn parse() {
+        let mut store = CookieStore::default();
+        inserted!(store.parse(
+            "cookie1=value1",
+            &test_utils::url("http://example.com/foo/bar"),
+        ));
+        non_rel_scheme!(store.parse("cookie1=value1", &test_utils::url("data:nonrelativescheme"),));
+        non_http_scheme!(store.parse(
+            "cookie1=value1; HttpOnly",
+            &test_utils::url("ftp://example.com/"),
+        ));
+        expired_existing!(store.parse(
+            "cookie1=value1; Max-Age=0",
+            &test_utils::url("http://example.com/foo/bar"),
+        ));
+        expired_err!(store.parse(
+            "cookie1=value1; Max-Age=-1",
+            &test_utils::url("http://example.com/foo/bar"),
+        ));
+        updated!(store.parse(
+            "cookie1=value1",
+            &test_utils::url("http://example.com/foo/bar"),
+        ));
+        expired_existing!(store.parse(
+            "cookie1=value1; Max-Age=-1",
+            &test_utils::url("http://example.com/foo/bar"),
+        ));
+        domain_mismatch!(store.parse(
+            "cookie1=value1; Domain=bar.example.com",
+            &test_utils::url("http://example.com/foo/bar"),
+        ));
+    }
+
+    #[test]
+    fn domains() {
+        let mut store = CookieStore::default();
+        //        The user agent will reject cookies unless the Domain attribute
+        // specifies a scope for the cookie that would include the origin
+        // server.  For example, the user agent will accept a cookie with a
+        // Domain attribute of "example.com" or of "foo.example.com" from
+        // foo.example.com, but the user agent will not accept a cookie with a
+        // Domain attribute of "bar.example.com" or of "baz.foo.example.com".
+        fn domain_cookie_from(domain: &str, request_url: &str) -> Cookie<'static> {
+            let cookie_str = format!("cookie1=value1; Domain={}", domain);
+            Cookie::parse(cookie_str, &test_utils::url(request_url)).unwrap()
+        }
+
+        {
+            let request_url = test_utils::url("http://foo.example.com");
+            // foo.example.com can submit cookies for example.com and foo.example.com
+            inserted!(store.insert(
+                domain_cookie_from("example.com", "http://foo.example.com",),
+                &request_url,
+            ));
+            updated!(store.insert(
+                domain_cookie_from(".example.com", "http://foo.example.com",),
+                &request_url,
+            ));
+            inserted!(store.insert(
+                domain_cookie_from("foo.example.com", "http://foo.example.com",),
+                &request_url,
+            ));
+            updated!(store.insert(
+                domain_cookie_from(".foo.example.com", "http://foo.example.com",),
+                &request_url,
+            ));
+            // not for bar.example.com
+            domain_mismatch!(store.insert(
+                domain_cookie_from("bar.example.com", "http://bar.example.com",),
+                &request_url,
+            ));
+            domain_mismatch!(store.insert(
+                domain_cookie_from(".bar.example.com", "http://bar.example.com",),
+                &request_url,
+            ));
+            // not for bar.foo.example.com
+            domain_mismatch!(store.insert(
+                domain_cookie_from("bar.foo.example.com", "http://bar.foo.example.com",),
+                &request_url,
+            ));
+            domain_mismatch!(store.insert(
+                domain_cookie_from(".bar.foo.example.com", "http://bar.foo.example.com",),
+                &request_url,
+            ));
+        }
+
+        {
+            let request_url = test_utils::url("http://bar.example.com");
+            // bar.example.com can submit for example.com and bar.example.com
+            updated!(store.insert(
+                domain_cookie_from("example.com", "http://foo.example.com",),
+                &request_url,
+            ));
+            updated!(store.insert(
+                domain_cookie_from(".example.com", "http://foo.example.com",),
+                &request_url,
+            ));
+            inserted!(store.insert(
+                domain_cookie_from("bar.example.com", "http://bar.example.com",),
+                &request_url,
+            ));
+            updated!(store.insert(
+                domain_cookie_from(".bar.example.com", "http://bar.example.com",),
+                &request_url,
+            ));
+            // bar.example.com cannot submit for foo.example.com
+            domain_mismatch!(store.insert(
+                domain_cookie_from("foo.example.com", "http://foo.example.com",),
+                &request_url,
+            ));
+            domain_mismatch!(store.insert(
+                domain_cookie_from(".foo.example.com", "http://foo.example.com",),
+                &request_url,
+            ));
+        }
+        {
+            let request_url = test_utils::url("http://example.com");
+            // example.com can submit for example.com
+            updated!(store.insert(
+                domain_cookie_from("example.com", "http://foo.example.com",),
+                &request_url,
+            ));
+            updated!(store.insert(
+                domain_cookie_from(".example.com", "http://foo.example.com",),
+                &request_url,
+            ));
+            // example.com cannot submit for foo.example.com or bar.example.com
+            domain_mismatch!(store.insert(
+                domain_cookie_from("foo.example.com", "http://foo.example.com",),
+                &request_url,
+            ));
+            domain_mismatch!(store.insert(
+                domain_cookie_from(".foo.example.com", "http://foo.example.com",),
+                &request_url,
+            ));
+            domain_mismatch!(store.insert(
+                domain_cookie_from("bar.example.com", "http://bar.example.com",),
+                &request_url,
+            ));
+            domain_mismatch!(store.insert(
+                domain_cookie_from(".bar.example.com", "http://bar.example.com",),
+                &request_url,
+            ));
+        }
+    }
+
+    #[test]
+    fn cookie_jar() {
+        let mut store = make_match_store();
+        let url = test_utils::url("http://example.com/foo/bar/bus");
+        let mut jar = store.to_cookie_jar(&url);
+        let mut names = jar.iter().map(|c| c.name().to_owned()).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["cookie1", "cookie5", "cookie6"]);
+        assert_eq!(jar.delta().count(), 0);
+
+        jar.remove(jar.get("cookie5").unwrap().clone());
+        let mut cookie1 = jar.get("cookie1").unwrap().clone();
+        cookie1.set_value("updated");
+        jar.add(cookie1);
+        jar.add(RawCookie::build(("cookie10", "10")).path("/foo").build());
+        store.store_cookie_jar_delta(&jar, &url);
+        assert!(store.get("example.com", "/foo", "cookie5").is_none());
+        assert!(store.get_any("example.com", "/foo", "cookie5").unwrap().is_expired());
+        assert_eq!(store.get("example.com", "/foo", "cookie1").unwrap().value(), "updated");
+        assert_eq!(store.get("example.com", "/foo", "cookie10").unwrap().value(), "10");
+        values_are!(store, "http://example.com/foo/bar/bus", vec!["updated", "6", "10"]);
+    }
+
+    #[test]
+    fn interned_keys() {
+        let mut store = CookieStore::default();
+        inserted!(add_cookie(&mut store, "cookie1=value1; Path=/", "http://example.com/", None, None));
+        inserted!(add_cookie(&mut store, "cookie1=value2; Path=/", "http://example.org/", None, None));
+        let path1 = &*store.get("example.com", "/", "cookie1").unwrap().path;
+        let path2 = &*store.get("example.org", "/", "cookie1").unwrap().path;
+        assert!(std::ptr::eq(path1, path2));
+
+        let footprint = store.memory_footprint();
+        assert!(footprint > 0);
+        let removed = store.remove("example.com", "/", "cookie1").unwrap();
+        let path2 = &*store.get("example.org", "/", "cookie1").unwrap().path;
+        assert!(!std::ptr::eq(&*removed.path, path2));
+        assert!(store.memory_footprint() < footprint);
+    }
+
+    #[test]
+    fn clock() {
+        use std::sync::{Arc, Mutex};
+
+        let now = Arc::new(Mutex::new(test_utils::in_days(-10)));
+        let clock = {
+            let now = Arc::clone(&now);
+            move || *now.lock().unwrap()
+        };
+        let mut store = CookieStore::default().with_clock(clock);
+        let url = test_utils::url("http://example.com/");
+        inserted!(store.parse("cookie1=value1; Max-Age=60", &url));
+        // expired per the system clock, but not per the store's clock
+        inserted!(store.insert_raw(
+            &RawCookie::build(("cookie2", "value2"))
+                .expires(test_utils::in_days(-9))
+                .build(),
+            &url
+        ));
+        // a `Cookie` created relative to the store's clock
+        let cookie = Cookie::try_from_raw_cookie_at(
+            &RawCookie::parse("cookie3=value3; Max-Age=60").unwrap(),
+            &url,
+            store.now_utc(),
+        )
+        .unwrap();
+        inserted!(store.insert(cookie.into_owned(), &url));
+        values_are!(
+            store,
+            "http://example.com/",
+            vec!["value1", "value2", "value3"]
+        );
+        assert_eq!(0, store.stats().expired);
+
+        *now.lock().unwrap() += time::Duration::minutes(2);
+        values_are!(store, "http://example.com/", vec!["value2"]);
+        assert!(store.get("example.com", "/", "cookie1").is_none());
+        assert_eq!(1, store.iter_unexpired().count());
+        assert_eq!(2, store.stats().expired);
+
+        *now.lock().unwrap() += time::Duration::days(2);
+        values_are!(store, "http://example.com/", vec![]);
+    }
+
+    #[test]
+    fn observer() {
+        use super::StoreEvent;
+        use std::sync::{Arc, Mutex};
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut store = CookieStore::default();
+        {
+            let events = Arc::clone(&events);
+            store.set_observer(move |event| {
+                events.lock().unwrap().push(match event {
+                    StoreEvent::Inserted(c) => format!("inserted {}", c.value()),
+                    StoreEvent::Updated { old, new } => {
+                        format!("updated {} -> {}", old.value(), new.value())
+                    }
+                    StoreEvent::Expired(c) => format!("expired {}", c.value()),
+                    StoreEvent::Removed(c) => format!("removed {}", c.value()),
+                })
+            });
+        }
+        let url = test_utils::url("http://example.com/");
+        inserted!(store.parse("cookie1=value1", &url));
+        inserted!(store.parse("cookie2=value2", &url));
+        updated!(store.parse("cookie1=value3", &url));
+        expired_existing!(store.parse("cookie1=value4; Max-Age=0", &url));
+        expired_err!(store.parse("cookie3=value5; Max-Age=0", &url));
+        assert!(store.remove("example.com", "/", "cookie2").is_some());
+        assert!(store.remove("example.com", "/", "cookie2").is_none());
+        inserted!(store.parse("cookie2=value6", &url));
+        store.clear();
+        store.clear_observer();
+        inserted!(store.parse("cookie2=value7", &url));
+        let mut events = events.lock().unwrap().clone();
+        // clear() removes the remaining (including expired) cookies in no particular order
+        events[6..].sort();
+        assert_eq!(
+            events,
+            vec![
+                "inserted value1",
+                "inserted value2",
+                "updated value1 -> value3",
+                "expired value3",
+                "removed value2",
+                "inserted value6",
+                "removed value3",
+                "removed value6",
+            ]
+        );
+    }
+
+    #[test]
+    fn subscribe() {
+        use super::OwnedStoreEvent;
+
+        let mut store = CookieStore::default();
+        let url = test_utils::url("http://example.com/");
+        let receiver = store.subscribe();
+        inserted!(store.parse("cookie1=value1", &url));
+        let dropped = store.subscribe();
+        drop(dropped);
+        store.set_observer(|_| {});
+        store.clear_observer();
+        updated!(store.parse("cookie1=value2", &url));
+        let removed = store.remove("example.com", "/", "cookie1").unwrap();
+        let events = receiver.try_iter().collect::<Vec<_>>();
+        assert_eq!(3, events.len());
+        assert!(matches!(events[0], OwnedStoreEvent::Inserted(ref c) if c.value() == "value1"));
+        assert!(matches!(
+            events[1],
+            OwnedStoreEvent::Updated { ref old, ref new }
+                if old.value() == "value1" && new.value() == "value2"
+        ));
+        assert_eq!(OwnedStoreEvent::Removed(removed), events[2]);
+        // the dropped subscriber has been removed
+        assert_eq!(
+            1,
+            store
+                .observer
+                .as_ref()
+                .unwrap()
+                .subscribers
+                .lock()
+                .unwrap()
+                .len()
+        );
+
+        drop(receiver);
+        inserted!(store.parse("cookie1=value3", &url));
+        store.clear_observer();
+        assert!(store.observer.is_none());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn subscribe_async() {
+        use super::OwnedStoreEvent;
+
+        let mut store = CookieStore::default();
+        let mut receiver = store.subscribe_async();
+        let url = test_utils::url("http://example.com/");
+        inserted!(store.parse("cookie1=value1", &url));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let event = runtime.block_on(receiver.recv()).unwrap();
+        assert!(matches!(event, OwnedStoreEvent::Inserted(ref c) if c.value() == "value1"));
+    }
+
+    #[test]
+    fn generation() {
+        let mut store = CookieStore::default();
+        assert!(!store.is_dirty());
+        store.clear();
+        assert_eq!(0, store.generation());
+        let url = test_utils::url("http://example.com/");
+        inserted!(store.parse("cookie1=value1", &url));
+        assert_eq!(1, store.generation());
+        assert!(store.is_dirty());
+        store.mark_clean();
+        assert!(!store.is_dirty());
+
+        updated!(store.parse("cookie1=value2", &url));
+        expired_existing!(store.parse("cookie1=value3; Max-Age=0", &url));
+        domain_mismatch!(store.parse("cookie2=value4; Domain=example.org", &url));
+        assert!(store.remove("example.com", "/", "cookie2").is_none());
+        assert_eq!(3, store.generation());
+        assert!(store.remove("example.com", "/", "cookie1").is_some());
+        assert_eq!(4, store.generation());
+        assert!(store.is_dirty());
+        store.mark_clean();
+        store.clear();
+        assert!(!store.is_dirty());
+
+        let store = CookieStore::from_cookies(
+            vec![Ok::<_, CookieError>(test_utils::make_cookie(
+                "cookie1=value1",
+                "http://example.com/",
+                None,
+                None,
+            ))],
+            false,
+        )
+        .unwrap();
+        assert!(!store.is_dirty());
+    }
+
+    #[test]
+    fn len() {
+        let mut store = CookieStore::default();
+        assert!(store.is_empty());
+        assert_eq!(0, store.len());
+        let url = test_utils::url("http://www.example.com/foo/bar");
+        inserted!(store.parse("cookie1=value1", &url));
+        inserted!(store.parse("cookie2=value2; Path=/", &url));
+        inserted!(store.parse("cookie1=value1; Domain=example.com", &url));
+        expired_existing!(store.parse("cookie2=value2; Path=/; Max-Age=0", &url));
+        assert!(!store.is_empty());
+        assert_eq!(3, store.len());
+        assert_eq!(2, store.unexpired_len());
+        assert_eq!(2, store.domain_len("www.example.com"));
+        assert_eq!(1, store.domain_len("example.com"));
+        assert_eq!(0, store.domain_len("example.org"));
+        store.remove("example.com", "/foo", "cookie1");
+        assert_eq!(0, store.domain_len("example.com"));
+        store.clear();
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn domains_and_iter_domain() {
+        let mut store = CookieStore::default();
+        assert_eq!(0, store.domains().count());
+        let url = test_utils::url("http://www.example.com/foo/bar");
+        inserted!(store.parse("cookie1=value1", &url));
+        inserted!(store.parse("cookie2=value2; Path=/", &url));
+        inserted!(store.parse("cookie3=value3; Domain=example.com", &url));
+        expired_existing!(store.parse("cookie2=value2; Path=/; Max-Age=0", &url));
+        let mut domains = store.domains().collect::<Vec<_>>();
+        domains.sort();
+        assert_eq!(vec!["example.com", "www.example.com"], domains);
+        let mut names = store
+            .iter_domain("www.example.com")
+            .map(|c| c.name())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(vec!["cookie1", "cookie2"], names);
+        assert_eq!(1, store.iter_domain("example.com").count());
+        assert_eq!(0, store.iter_domain("example.org").count());
+    }
+
+    #[test]
+    fn retain() {
+        let mut store = make_match_store();
+        let len = store.len();
+        let generation = store.generation();
+        store.retain(|_| true);
+        assert_eq!(len, store.len());
+        assert_eq!(generation, store.generation());
+
+        store.retain(|c| String::from(&c.domain) != "example.com");
+        assert_eq!(len - 6, store.len());
+        assert_eq!(generation + 1, store.generation());
+        assert_eq!(0, store.domain_len("example.com"));
+        assert!(!store.domains().any(|d| d == "example.com"));
+        values_are!(store, "http://example.org/foo/bar", vec!["8"]);
+
+        store.retain(|_| false);
+        assert!(store.is_empty());
+        assert_eq!(0, store.domains().count());
+    }
+
+    #[test]
+    fn drain_and_into_iter() {
+        let mut store = make_match_store();
+        let mut expected = store
+            .iter_any()
+            .map(|c| c.value().to_owned())
+            .collect::<Vec<_>>();
+        expected.sort();
+        let generation = store.generation();
+
+        let mut drained = store
+            .clone()
+            .drain()
+            .map(|c| c.value().to_owned())
+            .collect::<Vec<_>>();
+        drained.sort();
+        assert_eq!(expected, drained);
+
+        let mut owned = store
+            .clone()
+            .into_iter()
+            .map(|c| c.value().to_owned())
+            .collect::<Vec<_>>();
+        owned.sort();
+        assert_eq!(expected, owned);
+
+        // dropping the iterator does not restore the drained cookies
+        store.drain().next().unwrap();
+        assert!(store.is_empty());
+        assert_eq!(generation + 1, store.generation());
+        assert_eq!(0, store.drain().count());
+        assert_eq!(generation + 1, store.generation());
+        assert_eq!(0, store.into_iter().count());
+    }
+
+    #[test]
+    fn remove_domain() {
+        fn values(cookies: Vec<Cookie<'static>>) -> Vec<String> {
+            let mut values = cookies
+                .iter()
+                .map(|c| c.value().to_owned())
+                .collect::<Vec<_>>();
+            values.sort();
+            values
+        }
+        let mut store = make_match_store();
+        let len = store.len();
+        let generation = store.generation();
+        assert!(store.remove_domain("unknowndomain.org").is_empty());
+        assert_eq!(generation, store.generation());
+
+        assert_eq!(vec!["8"], values(store.remove_domain("example.org")));
+        assert_eq!(0, store.domain_len("example.org"));
+        assert_eq!(1, store.domain_len("bar.example.org"));
+        assert_eq!(len - 1, store.len());
+        assert_eq!(generation + 1, store.generation());
+
+        inserted!(add_cookie(
+            &mut store,
+            "cookie10=10",
+            "http://fooexample.com/",
+            None,
+            None
+        ));
+        assert_eq!(
+            vec!["1", "2", "3", "4", "5", "6", "7"],
+            values(store.remove_domain_and_subdomains("example.com"))
+        );
+        assert_eq!(1, store.domain_len("fooexample.com"));
+        assert_eq!(vec!["9"], values(store.remove_domain_and_subdomains("example.org")));
+        assert_eq!(1, store.len());
+    }
+
+    #[test]
+    fn get_and_remove_for_url() {
+        let mut store = make_match_store();
+        let url = test_utils::url("http://www.example.com/foo/bar");
+        inserted!(store.parse("cookie1=outer; Path=/", &url));
+        inserted!(store.parse("cookie1=inner; Path=/foo/bar", &url));
+        inserted!(store.parse("cookie1=other; Path=/bus", &url));
+        assert_eq!(
+            "inner",
+            store.get_for_url(&url, "cookie1").unwrap().value()
+        );
+        assert_eq!(
+            "outer",
+            store
+                .get_for_url(&test_utils::url("http://www.example.com/foo"), "cookie1")
+                .unwrap()
+                .value()
+        );
+        assert!(store.get_for_url(&url, "cookie2").is_none());
+        assert!(store
+            .get_for_url(&test_utils::url("http://example.com/sec/"), "cookie2")
+            .is_none());
+        assert_eq!(
+            "2",
+            store
+                .get_for_url(&test_utils::url("https://example.com/sec/"), "cookie2")
+                .unwrap()
+                .value()
+        );
+
+        let mut removed = store
+            .remove_for_url(&url, "cookie1")
+            .iter()
+            .map(|c| c.value().to_owned())
+            .collect::<Vec<_>>();
+        removed.sort();
+        assert_eq!(vec!["inner", "outer"], removed);
+        assert!(store.get_for_url(&url, "cookie1").is_none());
+        assert!(store.contains("www.example.com", "/bus", "cookie1"));
+        assert!(store.remove_for_url(&url, "cookie1").is_empty());
+    }
+
+    #[test]
+    fn query() {
+        fn query(store: &CookieStore, domain: &str, path: Option<&str>) -> Vec<String> {
+            let mut values = store
+                .query(domain, path)
+                .map(|c| c.value().to_owned())
+                .collect::<Vec<_>>();
+            values.sort();
+            values
+        }
+        let store = make_match_store();
+        assert_eq!(vec!["7"], query(&store, "*.example.com", None));
+        assert_eq!(vec!["9"], query(&store, "*.EXAMPLE.org", None));
+        assert_eq!(vec!["8", "9"], query(&store, "*example.org", None));
+        assert_eq!(vec!["1", "5", "8"], query(&store, "example.*", Some("/foo")));
+        assert_eq!(
+            vec!["2", "3", "4"],
+            query(&store, "example.com", Some("/sec*"))
+        );
+        assert!(query(&store, "*.example.net", None).is_empty());
+        assert_eq!(store.len(), store.query("*", None).count());
+    }
+
+    #[test]
+    fn merge() {
+        use super::MergeStrategy;
+
+        fn stores() -> (CookieStore, CookieStore) {
+            let url = test_utils::url("http://example.com/");
+            let mut this = CookieStore::default()
+                .with_clock(|| OffsetDateTime::now_utc() - time::Duration::seconds(1));
+            inserted!(this.parse("shared1=self; Max-Age=60", &url));
+            inserted!(this.parse("shared2=self; Max-Age=600", &url));
+            inserted!(this.parse("shared3=self", &url));
+            inserted!(this.parse("self=self", &url));
+            let mut other = CookieStore::default();
+            inserted!(other.parse("shared1=other; Max-Age=600", &url));
+            inserted!(other.parse("shared2=other; Max-Age=60", &url));
+            inserted!(other.parse("shared3=other; Max-Age=600", &url));
+            inserted!(other.parse("other=other", &url));
+            (this, other)
+        }
+        fn values(store: &CookieStore) -> Vec<String> {
+            let mut values = store
+                .iter_any()
+                .map(|c| format!("{}={}", c.name(), c.value()))
+                .collect::<Vec<_>>();
+            values.sort();
+            values
+        }
+
+        let (mut this, other) = stores();
+        this.merge(other, MergeStrategy::PreferSelf);
+        assert_eq!(
+            values(&this),
+            vec![
+                "other=other",
+                "self=self",
+                "shared1=self",
+                "shared2=self",
+                "shared3=self"
+            ]
+        );
+
+        let (mut this, other) = stores();
+        let created = this
+            .get("example.com", "/", "shared1")
+            .unwrap()
+            .creation_time;
+        assert_ne!(
+            created,
+            other
+                .get("example.com", "/", "shared1")
+                .unwrap()
+                .creation_time
+        );
+        this.merge(other, MergeStrategy::PreferOther);
+        assert_eq!(
+            created,
+            this.get("example.com", "/", "shared1")
+                .unwrap()
+                .creation_time
+        );
+        assert_eq!(
+            values(&this),
+            vec![
+                "other=other",
+                "self=self",
+                "shared1=other",
+                "shared2=other",
+                "shared3=other"
+            ]
+        );
+
+        let (mut this, other) = stores();
+        this.merge(other, MergeStrategy::PreferNewerExpiry);
+        assert_eq!(
+            values(&this),
+            vec![
+                "other=other",
+                "self=self",
+                "shared1=other",
+                "shared2=self",
+                "shared3=self"
+            ]
+        );
+    }
+
+    #[test]
+    fn diff() {
+        let before = make_match_store();
+        assert!(before.diff(&before).is_empty());
+        assert!(before.diff(&before.clone()).is_empty());
+
+        let mut after = before.clone();
+        let url = test_utils::url("http://example.com/foo/bar");
+        updated!(after.parse("cookie1=changed", &url));
+        inserted!(after.parse("cookie10=10", &url));
+        after.remove("example.org", "/foo", "cookie8");
+
+        let diff = before.diff(&after);
+        assert_eq!(
+            vec!["cookie10=10"],
+            diff.added
+                .iter()
+                .map(|c| format!("{}={}", c.name(), c.value()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["cookie8=8"],
+            diff.removed
+                .iter()
+                .map(|c| format!("{}={}", c.name(), c.value()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(1, diff.changed.len());
+        assert_eq!("1", diff.changed[0].0.value());
+        assert_eq!("changed", diff.changed[0].1.value());
+
+        let diff = after.diff(&before);
+        assert_eq!("cookie8", diff.added[0].name());
+        assert_eq!("cookie10", diff.removed[0].name());
+        assert_eq!("changed", diff.changed[0].0.value());
+    }
+
+    #[test]
+    fn domain_lists() {
+        use crate::DomainRule;
+
+        let mut store = CookieStore::default()
+            .with_domain_allowlist(vec![
+                DomainRule::suffix("mycorp.com"),
+                DomainRule::exact("example.com"),
+            ])
+            .with_domain_denylist(vec![DomainRule::suffix("ads.mycorp.com")]);
+        inserted!(store.parse("cookie1=1", &test_utils::url("http://mycorp.com/")));
+        inserted!(store.parse("cookie2=2", &test_utils::url("http://www.mycorp.com/")));
+        inserted!(store.parse("cookie3=3", &test_utils::url("http://example.com/")));
+        assert_eq!(
+            Err(CookieError::DomainNotPermitted(Default::default())),
+            store.parse("cookie4=4", &test_utils::url("http://www.example.com/"))
+        );
+        assert_eq!(
+            Err(CookieError::DomainNotPermitted(Default::default())),
+            store.parse("cookie5=5", &test_utils::url("http://x.ads.mycorp.com/"))
+        );
+        // the cookie domain, rather than the request-host, is checked
+        inserted!(store.parse(
+            "cookie6=6; Domain=mycorp.com",
+            &test_utils::url("http://ads.mycorp.com/")
+        ));
+        inserted!(store.parse(
+            "cookie7=7; Domain=example.com",
+            &test_utils::url("http://www.example.com/")
+        ));
+        assert_eq!(5, store.len());
+
+        let mut store = CookieStore::default()
+            .with_domain_denylist(vec![DomainRule::suffix("doubleclick.net")]);
+        inserted!(store.parse("cookie1=1", &test_utils::url("http://example.com/")));
+        assert_eq!(
+            Err(CookieError::DomainNotPermitted(Default::default())),
+            store.parse("cookie2=2", &test_utils::url("http://doubleclick.net/"))
+        );
+    }
+
+    #[cfg(feature = "psl")]
+    #[test]
+    fn embedded_psl() {
+        let mut store = CookieStore::new_with_public_suffix();
+        assert_eq!(
+            Err(CookieError::PublicSuffix(Default::default())),
+            store.parse(
+                "cookie1=1; Domain=co.uk",
+                &test_utils::url("http://www.example.co.uk/")
+            )
+        );
+        inserted!(store.parse(
+            "cookie2=2; Domain=example.co.uk",
+            &test_utils::url("http://www.example.co.uk/")
+        ));
+        // a public suffix domain-attribute identical to the request-host is treated as HostOnly
+        inserted!(store.parse(
+            "cookie3=3; Domain=github.io",
+            &test_utils::url("http://github.io/")
+        ));
+        assert!(matches!(
+            store
+                .get_for_url(&test_utils::url("http://github.io/"), "cookie3")
+                .unwrap()
+                .domain,
+            crate::CookieDomain::HostOnly(_)
+        ));
+        values_are!(store, "http://foo.github.io/", vec![]);
+    }
+
+    #[cfg(feature = "public_suffix")]
+    #[test]
+    fn update_suffix_list() {
+        let url = test_utils::url("http://www.example.co.uk/");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("cookie1=1; Domain=co.uk", &url));
+        store.update_suffix_list("// ===BEGIN ICANN DOMAINS===\nco.uk\n".parse().unwrap());
+        assert_eq!(
+            Err(CookieError::PublicSuffix(Default::default())),
+            store.parse("cookie2=2; Domain=co.uk", &url)
+        );
+        store.update_suffix_list("// ===BEGIN ICANN DOMAINS===\nuk\n".parse().unwrap());
+        inserted!(store.parse("cookie2=2; Domain=co.uk", &url));
+    }
+
+    #[cfg(feature = "public_suffix")]
+    #[test]
+    fn suffix_list_from_reader_and_path() {
+        let list = "// ===BEGIN ICANN DOMAINS===\nco.uk\n";
+        let url = test_utils::url("http://www.example.co.uk/");
+        let mut store = CookieStore::default()
+            .with_suffix_list_from_reader(list.as_bytes())
+            .unwrap();
+        assert_eq!(
+            Err(CookieError::PublicSuffix(Default::default())),
+            store.parse("cookie1=1; Domain=co.uk", &url)
+        );
+
+        let path = std::env::temp_dir().join(format!(
+            "cookie_store_suffix_list_{}.dat",
+            std::process::id()
+        ));
+        std::fs::write(&path, list).unwrap();
+        let store = CookieStore::default().with_suffix_list_from_path(&path);
+        std::fs::remove_file(&path).unwrap();
+        let mut store = store.unwrap();
+        assert_eq!(
+            Err(CookieError::PublicSuffix(Default::default())),
+            store.parse("cookie1=1; Domain=co.uk", &url)
+        );
+        assert!(CookieStore::default()
+            .with_suffix_list_from_path(path)
+            .is_err());
+    }
+
+    #[test]
+    fn same_site_lax_by_default() {
+        use crate::RequestContext;
+        use cookie::SameSite;
+
+        let url = test_utils::url("http://example.com/");
+        fn names<'a>(cookies: Vec<&'a Cookie<'static>>) -> Vec<&'a str> {
+            let mut names = cookies.into_iter().map(|c| c.name()).collect::<Vec<_>>();
+            names.sort_unstable();
+            names
+        }
+
+        let mut store = CookieStore::default();
+        inserted!(store.parse("unset=1", &url));
+        inserted!(store.parse("none=2; SameSite=None", &url));
+        inserted!(store.parse("lax=3; SameSite=Lax", &url));
+        inserted!(store.parse("strict=4; SameSite=Strict", &url));
+        assert_eq!(None, store.get_for_url(&url, "unset").unwrap().same_site());
+        assert_eq!(
+            vec!["lax", "none", "strict", "unset"],
+            names(store.matches_with_context(&url, &RequestContext::same_site()))
+        );
+        assert_eq!(
+            vec!["lax", "none", "unset"],
+            names(store.matches_with_context(&url, &RequestContext::cross_site_navigation()))
+        );
+        assert_eq!(
+            vec!["none", "unset"],
+            names(store.matches_with_context(&url, &RequestContext::cross_site()))
+        );
+
+        // existing cookies lacking SameSite are treated as Lax
+        let mut store = store.with_same_site_lax_by_default(true);
+        assert_eq!(
+            vec!["lax", "none", "unset"],
+            names(store.matches_with_context(&url, &RequestContext::cross_site_navigation()))
+        );
+        assert_eq!(
+            vec!["none"],
+            names(store.matches_with_context(&url, &RequestContext::cross_site()))
+        );
+        // newly stored cookies lacking SameSite are stored as Lax
+        inserted!(store.parse("unset2=5", &url));
+        assert_eq!(
+            Some(SameSite::Lax),
+            store.get_for_url(&url, "unset2").unwrap().same_site()
+        );
+        assert_eq!(None, store.get_for_url(&url, "unset").unwrap().same_site());
+    }
+
+    #[test]
+    fn scheme_bound() {
+        use crate::SourceScheme;
+
+        let http = test_utils::url("http://example.com/");
+        let https = test_utils::url("https://example.com/");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("unbound=1", &http));
+        let mut store = store.with_scheme_bound(true);
+        inserted!(store.parse("insecure=2", &http));
+        inserted!(store.parse("secure=3", &https));
+        assert_eq!(
+            Some(SourceScheme::NonSecure),
+            store.get_for_url(&http, "insecure").unwrap().source_scheme
+        );
+        values_are!(store, "http://example.com/", vec!["1", "2"]);
+        values_are!(store, "https://example.com/", vec!["1", "3"]);
+
+        let store = store.with_scheme_bound(false);
+        values_are!(store, "http://example.com/", vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    fn port_bound() {
+        let mut store = CookieStore::default().with_port_bound(true);
+        inserted!(store.parse("cookie1=1", &test_utils::url("http://localhost:8080/")));
+        inserted!(store.parse("cookie2=2", &test_utils::url("http://localhost:8081/")));
+        inserted!(store.parse("cookie3=3", &test_utils::url("http://localhost/")));
+        assert_eq!(
+            Some(8080),
+            store
+                .get_for_url(&test_utils::url("http://localhost:8080/"), "cookie1")
+                .unwrap()
+                .source_port
+        );
+        values_are!(store, "http://localhost:8080/", vec!["1"]);
+        values_are!(store, "http://localhost:8081/foo", vec!["2"]);
+        values_are!(store, "http://localhost:80/", vec!["3"]);
+        values_are!(store, "https://localhost/", vec![]);
+
+        let store = store.with_port_bound(false);
+        values_are!(store, "http://localhost:9090/", vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    fn idna_lookup() {
+        let url = test_utils::url("http://bücher.example/foo");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("cookie1=1", &url));
+        inserted!(store.parse("cookie2=2; Domain=Bücher.example", &url));
+        assert!(store.contains("bücher.example", "/", "cookie1"));
+        assert!(store.contains("xn--bcher-kva.example", "/", "cookie1"));
+        assert!(store.contains_any("BÜCHER.example", "/", "cookie2"));
+        assert_eq!(2, store.domain_len("Bücher.Example"));
+        assert_eq!(2, store.iter_domain("bücher.example").count());
+        assert!(store.remove("bücher.example", "/", "cookie2").is_some());
+        assert_eq!(1, store.remove_domain("bücher.example").len());
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn trailing_dot() {
+        let mut store = CookieStore::default();
+        inserted!(store.parse("cookie1=1", &test_utils::url("http://example.com./")));
+        inserted!(store.parse(
+            "cookie2=2; Domain=example.com.",
+            &test_utils::url("http://www.example.com/")
+        ));
+        values_are!(store, "http://example.com/", vec!["1", "2"]);
+        values_are!(store, "http://example.com./", vec!["1", "2"]);
+        values_are!(store, "http://www.example.com./", vec!["2"]);
+        assert!(store.contains("example.com.", "/", "cookie1"));
+    }
+
+    #[test]
+    fn trustworthy_origins() {
+        use crate::TrustworthyOrigins;
+
+        let mut store = CookieStore::default().with_trustworthy_origins(
+            TrustworthyOrigins::https_only()
+                .with_host("*.internal")
+                .with_enforce_on_insert(true),
+        );
+        inserted!(store.parse(
+            "cookie1=1; Secure",
+            &test_utils::url("http://api.internal/")
+        ));
+        inserted!(store.parse("cookie2=2; Secure", &test_utils::url("https://localhost/")));
+        assert_eq!(
+            Err(CookieError::NonSecureOrigin(Default::default())),
+            store.parse("cookie3=3; Secure", &test_utils::url("http://example.com/"))
+        );
+        let err = store
+            .parse("cookie3=3; Secure", &test_utils::url("http://example.com/"))
+            .unwrap_err();
+        let context = err.context().unwrap();
+        assert_eq!(Some("cookie3"), context.name.as_deref());
+        assert_eq!(Some("example.com"), context.domain.as_deref());
+        assert_eq!(Some("example.com"), context.request_host.as_deref());
+        values_are!(store, "http://api.internal/", vec!["1"]);
+        values_are!(store, "https://localhost/", vec!["2"]);
+        values_are!(store, "http://localhost/", vec![]);
+    }
+
+    #[test]
+    fn cookie_header_values() {
+        use super::DEFAULT_MAX_COOKIE_HEADER_LEN;
+
+        let url = test_utils::url("http://example.com/");
+        let mut store = CookieStore::default();
+        assert!(store
+            .cookie_header_values(&url, DEFAULT_MAX_COOKIE_HEADER_LEN)
+            .is_empty());
+        for i in 0..4 {
+            inserted!(store.parse(&format!("cookie{}=value{}", i, i), &url));
+        }
+        let large = format!("large={}", "x".repeat(40));
+        inserted!(store.parse(&large, &url));
+        let values = store.cookie_header_values(&url, DEFAULT_MAX_COOKIE_HEADER_LEN);
+        assert_eq!(1, values.len());
+        assert_eq!(4 * 14 + 46 + 4 * 2, values[0].len());
+
+        let values = store.cookie_header_values(&url, 30);
+        assert!(values.len() >= 3);
+        assert!(values.iter().all(|v| v.len() <= 30 || *v == large));
+        let mut pairs = values
+            .iter()
+            .flat_map(|v| v.split("; "))
+            .collect::<Vec<_>>();
+        pairs.sort_unstable();
+        assert_eq!(
+            vec![
+                "cookie0=value0",
+                "cookie1=value1",
+                "cookie2=value2",
+                "cookie3=value3",
+                &large
+            ],
+            pairs
+        );
+    }
+
+    #[test]
+    fn get_request_values_deduped() {
+        let url = test_utils::url("http://www.example.com/foo/bar");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("cookie1=root; Path=/", &url));
+        inserted!(store.parse("cookie1=foo; Path=/foo", &url));
+        inserted!(store.parse("cookie2=suffix; Domain=example.com; Path=/", &url));
+        inserted!(store.parse("cookie2=hostonly; Path=/", &url));
+        inserted!(store.parse("cookie3=parent; Domain=example.com", &url));
+        inserted!(store.parse("cookie3=child; Domain=www.example.com", &url));
+        inserted!(store.parse("cookie4=only", &url));
+        assert_eq!(7, store.get_request_values(&url).count());
+        let mut values = store.get_request_values_deduped(&url).collect::<Vec<_>>();
+        values.sort_unstable();
+        assert_eq!(
+            vec![
+                ("cookie1", "foo"),
+                ("cookie2", "hostonly"),
+                ("cookie3", "child"),
+                ("cookie4", "only")
+            ],
+            values
+        );
+    }
+
+    #[test]
+    fn percent_encoding() {
+        let url = test_utils::url("http://www.example.com/foo/bar");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("cookie1=hello%20world%3B", &url));
+        inserted!(store.insert_raw(&RawCookie::new("cookie 2", "a;b,c\"d%"), &url));
+        assert_eq!(
+            "hello world;",
+            store
+                .get_for_url(&url, "cookie1")
+                .unwrap()
+                .value_decoded()
+                .unwrap()
+        );
+        let invalid = Cookie::parse("cookie3=%FF", &url).unwrap();
+        assert!(invalid.value_decoded().is_err());
+
+        let mut values = store.get_request_values_encoded(&url).collect::<Vec<_>>();
+        values.sort_unstable();
+        assert_eq!(
+            vec![
+                ("cookie%202".into(), "a%3Bb%2Cc%22d%25".into()),
+                ("cookie1".into(), "hello%2520world%253B".into()),
+            ],
+            values
+        );
+        let decoded = store.get_for_url(&url, "cookie 2").unwrap();
+        assert_eq!("a;b,c\"d%", decoded.value_decoded().unwrap());
+    }
+
+    #[test]
+    fn identity() {
+        use crate::CookieIdentity;
+
+        let url = test_utils::url("http://www.example.com/foo/bar");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("cookie1=1; Domain=example.com", &url));
+        let id = store.get_for_url(&url, "cookie1").unwrap().identity();
+        assert_eq!(CookieIdentity::new("example.com", "/foo", "cookie1"), id);
+
+        // the identity remains valid across updates of the cookie
+        updated!(store.parse("cookie1=2; Domain=example.com", &url));
+        inserted!(store.parse("cookie2=3", &url));
+        assert_eq!("2", store.get_by_id(&id).unwrap().value());
+        assert_eq!("2", store.remove_by_id(&id).unwrap().value());
+        assert!(store.get_by_id(&id).is_none());
+        assert!(store.remove_by_id(&id).is_none());
+        assert_eq!(1, store.len());
+    }
+
+    #[test]
+    fn creation_time() {
+        use std::sync::{Arc, Mutex};
+
+        let created = test_utils::in_days(-1);
+        let now = Arc::new(Mutex::new(created));
+        let clock = {
+            let now = Arc::clone(&now);
+            move || *now.lock().unwrap()
+        };
+        let mut store = CookieStore::default().with_clock(clock);
+        let url = test_utils::url("http://example.com/");
+        assert!(Cookie::parse("cookie1=1", &url)
+            .unwrap()
+            .creation_time
+            .is_none());
+        inserted!(store.parse("cookie1=1", &url));
+        assert_eq!(
+            Some(created),
+            store
+                .get("example.com", "/", "cookie1")
+                .unwrap()
+                .creation_time
+        );
+
+        // an update retains the creation-time of the replaced cookie
+        let updated = created + time::Duration::hours(1);
+        *now.lock().unwrap() = updated;
+        updated!(store.parse("cookie1=2; Max-Age=60", &url));
+        inserted!(store.parse("cookie2=3", &url));
+        let cookie1 = store.get("example.com", "/", "cookie1").unwrap();
+        assert_eq!("2", cookie1.value());
+        assert_eq!(Some(created), cookie1.creation_time);
+        assert_eq!(
+            Some(updated),
+            store
+                .get("example.com", "/", "cookie2")
+                .unwrap()
+                .creation_time
+        );
+
+        // once removed, a cookie is created anew
+        store.remove("example.com", "/", "cookie1");
+        inserted!(store.parse("cookie1=4", &url));
+        assert_eq!(
+            Some(updated),
+            store
+                .get("example.com", "/", "cookie1")
+                .unwrap()
+                .creation_time
+        );
+    }
+
+    #[test]
+    fn iter_expired_and_persistent() {
+        let url = test_utils::url("http://example.com/");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("persistent=1; Max-Age=3600", &url));
+        inserted!(store.parse("session=2", &url));
+        inserted!(store.parse("expired=3; Max-Age=3600", &url));
+        expired_existing!(store.parse("expired=3; Max-Age=0", &url));
+        inserted!(store.parse("expiring=4", &url));
+        expired_existing!(store.parse("expiring=4; Max-Age=0", &url));
+
+        let mut expired = store.iter_expired().map(|c| c.name()).collect::<Vec<_>>();
+        expired.sort_unstable();
+        assert_eq!(vec!["expired", "expiring"], expired);
+        let persistent = store
+            .iter_persistent()
+            .map(|c| c.name())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["persistent"], persistent);
+        assert_eq!(
+            store.iter_any().count(),
+            store.iter_expired().count() + store.iter_unexpired().count()
+        );
+    }
+
+    #[test]
+    fn validate() {
+        use super::{NameMap, StoreViolation};
+        use crate::{CookieDomain, CookieIdentity};
+        use std::sync::Arc;
+
+        let url = test_utils::url("http://www.example.com/foo/bar");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("cookie1=1; Domain=example.com", &url));
+        inserted!(store.parse("cookie2=2; Max-Age=3600", &url));
+        assert!(store.validate().is_empty());
+
+        let mut cookie = Cookie::parse("cookie3=3", &url).unwrap();
+        cookie.domain = CookieDomain::HostOnly("www.example.org".into());
+        let path_map = Arc::make_mut(store.cookies.entry("WWW.Example.org".into()).or_default());
+        path_map.insert("/".into(), NameMap::default());
+        path_map
+            .entry("/foo".into())
+            .or_default()
+            .insert("cookie3".into(), cookie);
+        let mut cookie = Cookie::parse("cookie4=4", &url).unwrap();
+        cookie.domain = CookieDomain::NotPresent;
+        Arc::make_mut(store.cookies.entry("".into()).or_default())
+            .entry("/foo".into())
+            .or_default()
+            .insert("cookie4".into(), cookie);
+        store
+            .cookies
+            .insert("example.net".into(), Arc::default());
+
+        let mut violations = store.validate();
+        violations.sort_by_key(|v| format!("{:?}", v));
+        assert_eq!(
+            vec![
+                StoreViolation::EmptyDomain("example.net".into()),
+                StoreViolation::EmptyPath("WWW.Example.org".into(), "/".into()),
+                StoreViolation::KeyMismatch {
+                    key: CookieIdentity::new("WWW.Example.org", "/foo", "cookie3"),
+                    cookie: CookieIdentity::new("www.example.org", "/foo", "cookie3"),
+                },
+                StoreViolation::UnnormalizedDomain("".into()),
+                StoreViolation::UnnormalizedDomain("WWW.Example.org".into()),
+                StoreViolation::UnspecifiedDomain(CookieIdentity::new("", "/foo", "cookie4")),
+            ],
+            violations
+        );
+    }
+
+    #[test]
+    fn insert_unchecked() {
+        use crate::CookieDomain;
+
+        let url = test_utils::url("http://www.example.com/foo/bar");
+        let mut store = CookieStore::default();
+        let mut cookie = Cookie::parse("cookie1=1; Path=/", &url).unwrap();
+        cookie.domain = CookieDomain::Suffix("Example.ORG.".into());
+        inserted!(store.insert_unchecked(cookie.clone()));
+        assert!(store.validate().is_empty());
+        assert_eq!(
+            CookieDomain::Suffix("example.org".into()),
+            store.get("example.org", "/", "cookie1").unwrap().domain
+        );
+        values_are!(store, "http://sub.example.org/", vec!["1"]);
+        updated!(store.insert_unchecked(cookie.clone()));
+
+        // expired cookies are stored as-is
+        let mut expired = Cookie::parse("cookie2=2; Max-Age=0", &url).unwrap();
+        expired.domain = CookieDomain::HostOnly("example.net".into());
+        inserted!(store.insert_unchecked(expired));
+        assert!(store.get_any("example.net", "/foo", "cookie2").is_some());
+        assert!(store.get("example.net", "/foo", "cookie2").is_none());
+
+        cookie.domain = CookieDomain::NotPresent;
+        assert_eq!(
+            Err(CookieError::UnspecifiedDomain),
+            store.insert_unchecked(cookie)
+        );
+        assert_eq!(2, store.len());
+    }
+
+    #[test]
+    fn store_response_headers_str() {
+        let url = test_utils::url("http://www.example.com/foo/bar");
+        let mut store = CookieStore::default();
+        let headers = [
+            "cookie1=1",
+            "=invalid",
+            "cookie2=2; Domain=example.org",
+            "cookie1=3; Path=/foo",
+        ];
+        assert_eq!(
+            vec![
+                Ok(StoreAction::Inserted),
+                Err(CookieError::Parse),
+                Err(CookieError::DomainMismatch(Default::default())),
+                Ok(StoreAction::UpdatedExisting)
+            ],
+            store.store_response_headers_str(headers.iter().copied(), &url)
+        );
+        values_are!(store, "http://www.example.com/foo/bar", vec!["3"]);
+    }
+
+    #[test]
+    fn overflow_policy() {
+        use std::sync::{Arc, Mutex};
+
+        let now = Arc::new(Mutex::new(test_utils::in_days(-1)));
+        let clock = {
+            let now = Arc::clone(&now);
+            move || *now.lock().unwrap()
+        };
+        let tick = || {
+            let mut now = now.lock().unwrap();
+            *now += time::Duration::minutes(1);
+        };
+        let url = test_utils::url("http://example.com/");
+        let other_url = test_utils::url("http://example.org/");
+        let mut store = CookieStore::default()
+            .with_clock(clock.clone())
+            .with_max_cookies(3)
+            .with_max_cookies_per_domain(2);
+
+        // the oldest cookies are evicted by default: of the same domain if over the per-domain
+        // limit, otherwise of the entire store
+        inserted!(store.parse("cookie1=1", &url));
+        tick();
+        inserted!(store.parse("cookie2=2", &other_url));
+        tick();
+        inserted!(store.parse("cookie3=3", &url));
+        tick();
+        inserted!(store.parse("cookie4=4", &url));
+        assert_eq!(3, store.len());
+        values_are!(store, "http://example.com/", vec!["3", "4"]);
+        tick();
+        inserted!(store.parse("cookie5=5", &other_url));
+        assert_eq!(3, store.len());
+        values_are!(store, "http://example.com/", vec!["3", "4"]);
+        values_are!(store, "http://example.org/", vec!["5"]);
+        // updates are always permitted
+        tick();
+        updated!(store.parse("cookie4=updated", &url));
+        assert_eq!(3, store.len());
+
+        // expired cookies are evicted first
+        let mut store = CookieStore::default()
+            .with_clock(clock)
+            .with_max_cookies(3)
+            .with_overflow_policy(OverflowPolicy::Evict(EvictionPolicy::SoonestExpiring));
+        inserted!(store.parse("cookie1=1; Max-Age=60", &url));
+        inserted!(store.parse("cookie2=2; Max-Age=7200", &url));
+        inserted!(store.parse("cookie3=3", &other_url));
+        tick();
+        tick();
+        inserted!(store.parse("cookie4=4; Max-Age=3600", &other_url));
+        assert_eq!(3, store.len());
+        values_are!(store, "http://example.com/", vec!["2"]);
+        values_are!(store, "http://example.org/", vec!["3", "4"]);
+        // non-persistent cookies are evicted after any persistent cookies
+        inserted!(store.parse("cookie5=5; Max-Age=10800", &url));
+        values_are!(store, "http://example.com/", vec!["2", "5"]);
+        values_are!(store, "http://example.org/", vec!["3"]);
+        let mut store = store.with_max_cookies_per_domain(2);
+        inserted!(store.parse("cookie6=6", &url));
+        values_are!(store, "http://example.com/", vec!["5", "6"]);
+        values_are!(store, "http://example.org/", vec!["3"]);
+
+        let mut store = store.with_overflow_policy(OverflowPolicy::Reject);
+        let err = store.parse("cookie7=7", &url).unwrap_err();
+        assert_eq!(CookieError::StoreFull(Default::default()), err);
+        assert_eq!(
+            Some("cookie7"),
+            err.context().and_then(|c| c.name.as_deref())
+        );
+        updated!(store.parse("cookie6=updated", &url));
+
+        let mut store = store.with_overflow_policy(OverflowPolicy::Ignore);
+        assert_eq!(Ok(StoreAction::Ignored), store.parse("cookie7=7", &url));
+        assert_eq!(3, store.len());
+        assert!(store.get("example.com", "/", "cookie7").is_none());
+
+        // a limit of zero cannot be satisfied by eviction
+        let mut store = CookieStore::default().with_max_cookies(0);
+        assert_eq!(Ok(StoreAction::Ignored), store.parse("cookie1=1", &url));
+    }
+
+    #[test]
+    fn purge_expired() {
+        let mut store = make_match_store();
+        let len = store.len();
+        assert_eq!(0, store.purge_expired());
+        let generation = store.generation();
+        let url = test_utils::url("http://example.com/");
+        inserted!(store.parse("cookie1=1", &url));
+        expired_existing!(store.parse("cookie1=1; Max-Age=0", &url));
+        assert_eq!(len + 1, store.len());
+        assert_eq!(1, store.purge_expired());
+        assert_eq!(len, store.len());
+        assert!(store.generation() > generation);
+    }
+
+    #[test]
+    fn date_parsing() {
+        use crate::{CookieExpiration, DateParsing};
+
+        let url = test_utils::url("http://example.com/");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("cookie1=1; Expires=01-Jan-99 00:00:00", &url));
+        assert!(!store
+            .get("example.com", "/", "cookie1")
+            .unwrap()
+            .is_persistent());
+
+        let mut store = store.with_date_parsing(DateParsing::Lenient);
+        // a 2-digit year of 99 denotes 1999, so the existing cookie is expired
+        expired_existing!(store.parse("cookie1=1; Expires=01-Jan-99 00:00:00", &url));
+        inserted!(store.parse("cookie2=2; expires=Thu 01 Jan 2099 00:00:00", &url));
+        assert_eq!(
+            CookieExpiration::AtUtc(time::macros::datetime!(2099-01-01 00:00:00 UTC)),
+            store.get("example.com", "/", "cookie2").unwrap().expires
+        );
+    }
+
+    #[test]
+    fn max_bytes_per_domain() {
+        use std::sync::{Arc, Mutex};
+
+        let now = Arc::new(Mutex::new(test_utils::in_days(-1)));
+        let clock = {
+            let now = Arc::clone(&now);
+            move || *now.lock().unwrap()
+        };
+        let tick = || {
+            let mut now = now.lock().unwrap();
+            *now += time::Duration::minutes(1);
+        };
+        let url = test_utils::url("http://example.com/");
+        let other_url = test_utils::url("http://example.org/");
+        let mut store = CookieStore::default()
+            .with_clock(clock)
+            .with_max_bytes_per_domain(30);
+
+        inserted!(store.parse("cookie1=12345", &url));
+        tick();
+        inserted!(store.parse("cookie2=12345", &url));
+        inserted!(store.parse("cookie3=12345", &other_url));
+        assert_eq!(24, store.domain_bytes("example.com"));
+        assert_eq!(0, store.domain_bytes("example.net"));
+        // the oldest cookie of the domain is evicted
+        tick();
+        inserted!(store.parse("cookie4=1", &url));
+        values_are!(store, "http://example.com/", vec!["12345", "1"]);
+        assert_eq!(20, store.domain_bytes("example.com"));
+        // updates count the size of the replaced cookie, and never evict it
+        tick();
+        updated!(store.parse("cookie4=123456789012", &url));
+        values_are!(store, "http://example.com/", vec!["123456789012"]);
+        assert_eq!(19, store.domain_bytes("example.com"));
+        values_are!(store, "http://example.org/", vec!["12345"]);
+        // a cookie which alone exceeds the budget is never stored
+        assert_eq!(
+            Ok(StoreAction::Ignored),
+            store.parse("cookie5=123456789012345678901234", &url)
+        );
+        values_are!(store, "http://example.com/", vec!["123456789012"]);
+
+        let mut store = store.with_overflow_policy(OverflowPolicy::Reject);
+        inserted!(store.parse("cookie5=123", &url));
+        assert_eq!(
+            Err(CookieError::StoreFull(Default::default())),
+            store.parse("cookie5=12345", &url)
+        );
+        assert_eq!(29, store.domain_bytes("example.com"));
+    }
+
+    #[test]
+    fn transform() {
+        use crate::{CookieDomain, CookieExpiration};
+        use ::cookie::SameSite;
+
+        let max_expiry = test_utils::in_days(1);
+        let mut store = CookieStore::default().with_transform(
+            move |cookie: &mut Cookie<'static>, request_url: &url::Url| {
+                cookie.domain = CookieDomain::host_only(request_url).unwrap();
+                if cookie.same_site() == Some(SameSite::None) {
+                    cookie.set_same_site(SameSite::Lax);
+                }
+                if !cookie.expires_by(&max_expiry) {
+                    cookie.expires = CookieExpiration::from(max_expiry);
+                }
+            },
+        );
+        let url = test_utils::url("http://www.example.com/");
+        inserted!(store.parse(
+            "cookie1=1; Domain=example.com; SameSite=None; Max-Age=604800",
+            &url
+        ));
+        values_are!(store, "http://www.example.com/", vec!["1"]);
+        values_are!(store, "http://api.example.com/", vec![]);
+        let cookie = store.get("www.example.com", "/", "cookie1").unwrap();
+        assert_eq!(Some(SameSite::Lax), cookie.same_site());
+        assert_eq!(CookieExpiration::AtUtc(max_expiry), cookie.expires);
+        // cookies are validated before being transformed
+        domain_mismatch!(store.parse("cookie2=2; Domain=example.org", &url));
+    }
+
+    #[test]
+    fn shadows_secure() {
+        let mut store = CookieStore::default();
+        let secure_url = test_utils::url("https://www.example.com/foo/bar");
+        let insecure_url = test_utils::url("http://www.example.com/foo/bar");
+        inserted!(store.parse("cookie1=secure; Domain=example.com; Secure", &secure_url));
+        inserted!(store.parse("cookie2=secure; Path=/foo; Secure", &secure_url));
+
+        let shadows = |result: InsertResult| {
+            assert_eq!(Err(CookieError::ShadowsSecure(Default::default())), result)
+        };
+        // may not overwrite the Secure cookie, nor shadow it in a sub-domain or sub-path
+        shadows(store.parse("cookie1=insecure; Domain=example.com", &insecure_url));
+        shadows(store.parse("cookie1=insecure", &insecure_url));
+        shadows(store.parse("cookie2=insecure; Path=/foo/bar", &insecure_url));
+        // nor from a parent domain
+        shadows(store.parse(
+            "cookie2=insecure; Domain=example.com; Path=/foo",
+            &insecure_url,
+        ));
+        shadows(store.parse("cookie2=expired; Path=/foo; Max-Age=0", &insecure_url));
+        // unrelated names, domains, and paths are unaffected
+        inserted!(store.parse("cookie3=insecure", &insecure_url));
+        inserted!(store.parse("cookie2=insecure; Path=/", &insecure_url));
+        inserted!(store.parse(
+            "cookie1=insecure",
+            &test_utils::url("http://www.example.org/")
+        ));
+        // a secure request-uri may overwrite the cookie
+        updated!(store.parse("cookie1=insecure; Domain=example.com", &secure_url));
+        inserted!(store.parse("cookie1=insecure", &insecure_url));
+        values_are!(
+            store,
+            "https://www.example.com/foo/bar",
+            vec!["insecure", "insecure", "insecure", "secure", "insecure"]
+        );
+    }
+
+    #[test]
+    fn snapshot() {
+        use std::sync::Arc;
+
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let mut store = CookieStore::default();
+        let url = test_utils::url("http://example.com/foo/bar");
+        inserted!(store.parse("cookie1=1", &url));
+        inserted!(store.parse("cookie2=2", &test_utils::url("http://example.org/")));
+        let snapshot = store.snapshot();
+        assert_send_sync(&snapshot);
+        assert!(Arc::ptr_eq(
+            &store.cookies["example.com"],
+            &snapshot.cookies["example.com"]
+        ));
+
+        updated!(store.parse("cookie1=updated", &url));
+        inserted!(store.parse("cookie3=3", &url));
+        assert!(store.remove("example.org", "/", "cookie2").is_some());
+        values_are!(store, "http://example.com/foo/bar", vec!["updated", "3"]);
+        values_are!(store, "http://example.org/", vec![]);
+        values_are!(snapshot, "http://example.com/foo/bar", vec!["1"]);
+        values_are!(snapshot, "http://example.org/", vec!["2"]);
+        assert!(!Arc::ptr_eq(
+            &store.cookies["example.com"],
+            &snapshot.cookies["example.com"]
+        ));
+
+        // retained cookies are unaffected by the store being emptied
+        store.clear();
+        store.shrink_to_fit();
+        assert_eq!(2, snapshot.len());
+        assert_eq!(2, snapshot.thaw().into_iter().count());
+    }
+
+    #[test]
+    fn snapshot_purge() {
+        use std::sync::Arc;
+
+        let mut store = CookieStore::default();
+        inserted!(store.parse("cookie1=1", &test_utils::url("http://example.com/")));
+        let url = test_utils::url("http://example.org/");
+        inserted!(store.parse("cookie2=2", &url));
+        inserted!(store.parse("cookie3=3", &url));
+        assert_eq!(1, store.expire_matching(|c| c.name() == "cookie2"));
+        let snapshot = store.snapshot();
+
+        // only the domain a cookie is purged from is copied
+        assert_eq!(1, store.purge_expired());
+        assert!(Arc::ptr_eq(
+            &store.cookies["example.com"],
+            &snapshot.cookies["example.com"]
+        ));
+        assert!(!Arc::ptr_eq(
+            &store.cookies["example.org"],
+            &snapshot.cookies["example.org"]
+        ));
+        assert_eq!(2, store.len());
+        assert_eq!(3, snapshot.len());
+    }
+
+    #[test]
+    fn get_request_cookies_detailed() {
+        use ::cookie::SameSite;
+
+        let url = test_utils::url("https://example.com/foo");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("cookie1=value1; SameSite=Strict", &url));
+        inserted!(store.parse("cookie2=value2", &url));
+        let mut detailed = store
+            .get_request_cookies_detailed(&url)
+            .map(|c| (c.pair, c.cookie.same_site()))
+            .collect::<Vec<_>>();
+        detailed.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            vec![
+                ("cookie1=value1".to_string(), Some(SameSite::Strict)),
+                ("cookie2=value2".to_string(), None),
+            ],
+            detailed
+        );
+        assert_eq!(
+            0,
+            store
+                .get_request_cookies_detailed(&test_utils::url("https://other.com"))
+                .count()
+        );
+    }
+
+    #[test]
+    fn match_report() {
+        use super::MatchExclusion;
+        use crate::RequestContext;
+
+        let set_url = test_utils::url("https://example.com/");
+        let mut store = CookieStore::default();
+        for cookie in [
+            "host=1",
+            "ok=1; Domain=example.com",
+            "path=1; Domain=example.com; Path=/bar",
+            "secure=1; Domain=example.com; Secure",
+            "strict=1; Domain=example.com; SameSite=Strict",
+        ] {
+            inserted!(store.parse(cookie, &set_url));
         }
+        inserted!(store.parse("other=1", &test_utils::url("https://other.com/")));
+
+        let url = test_utils::url("http://www.example.com/foo");
+        let report = |context: Option<&RequestContext>| {
+            let mut report = match context {
+                Some(context) => store.match_report_with_context(&url, context),
+                None => store.match_report(&url),
+            }
+            .into_iter()
+            .map(|m| (m.cookie.name(), m.exclusion))
+            .collect::<Vec<_>>();
+            report.sort_by_key(|&(name, _)| name);
+            report
+        };
+        assert_eq!(
+            vec![
+                ("host", Some(MatchExclusion::DomainMismatch)),
+                ("ok", None),
+                ("path", Some(MatchExclusion::PathMismatch)),
+                ("secure", Some(MatchExclusion::Secure)),
+                ("strict", None),
+            ],
+            report(None)
+        );
+        assert_eq!(
+            Some(MatchExclusion::SameSite),
+            report(Some(&RequestContext::cross_site()))[4].1
+        );
+
+        // the matched cookies of the report are those of matches()
+        let mut matched = store
+            .match_report(&url)
+            .into_iter()
+            .filter(|m| m.is_match())
+            .map(|m| m.cookie.name())
+            .collect::<Vec<_>>();
+        matched.sort_unstable();
+        let mut matches = store
+            .matches(&url)
+            .into_iter()
+            .map(|c| c.name())
+            .collect::<Vec<_>>();
+        matches.sort_unstable();
+        assert_eq!(matches, matched);
     }
 
-    struct CookieStoreVisitor;
+    #[test]
+    fn would_accept() {
+        use std::sync::{Arc, Mutex};
 
-    impl<'de> Visitor<'de> for CookieStoreVisitor {
-        type Value = super::CookieStore;
+        let url = test_utils::url("http://example.com/foo");
+        let mut store = CookieStore::default().with_max_cookies_per_domain(1);
+        let events = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&events);
+        store.set_observer(move |_| *counter.lock().unwrap() += 1);
 
-        fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            write!(formatter, "a sequence of cookies")
-        }
+        assert_eq!(
+            Ok(StoreAction::Inserted),
+            store.would_accept("cookie1=1", &url)
+        );
+        domain_mismatch!(store.would_accept("cookie1=1; Domain=other.com", &url));
+        expired_err!(store.would_accept("cookie1=1; Max-Age=0", &url));
+        assert!(store.is_empty());
+        assert_eq!(0, *events.lock().unwrap());
 
-        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
-        where
-            A: SeqAccess<'de>,
-        {
-            super::CookieStore::from_cookies(std::iter::from_fn(|| seq.next_element().transpose()), false)
-        }
+        inserted!(store.parse("cookie1=1", &url));
+        assert_eq!(
+            Ok(StoreAction::UpdatedExisting),
+            store.would_accept("cookie1=2", &url)
+        );
+        assert_eq!(
+            Ok(StoreAction::ExpiredExisting),
+            store.would_accept("cookie1=2; Max-Age=0", &url)
+        );
+        // evicting to make room for a new cookie is only simulated
+        assert_eq!(
+            Ok(StoreAction::Inserted),
+            store.would_accept("cookie2=2", &url)
+        );
+        values_are!(store, "http://example.com/foo", vec!["1"]);
+        assert_eq!(1, *events.lock().unwrap());
     }
 
-    impl<'de> Deserialize<'de> for super::CookieStore {
-        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-        where
-            D: Deserializer<'de>,
+    #[test]
+    fn clear_for_url() {
+        let url = test_utils::url("http://www.example.com/foo/bar");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("cookie1=1", &url));
+        inserted!(store.parse("cookie2=2; Domain=example.com; Path=/", &url));
+        inserted!(store.parse("cookie3=3; Path=/baz", &url));
+        inserted!(store.parse("cookie4=4", &test_utils::url("http://other.example.com/")));
+
+        let mut removed = store
+            .clear_for_url(&url)
+            .into_iter()
+            .map(|c| c.value().to_owned())
+            .collect::<Vec<_>>();
+        removed.sort_unstable();
+        assert_eq!(vec!["1", "2"], removed);
+        assert!(store.matches(&url).is_empty());
+        assert!(store.contains("www.example.com", "/baz", "cookie3"));
+        assert!(store.contains("other.example.com", "/", "cookie4"));
+        assert!(store.clear_for_url(&url).is_empty());
+    }
+
+    #[test]
+    fn expire_matching() {
+        use super::StoreEvent;
+        use std::sync::{Arc, Mutex};
+
+        let url = test_utils::url("http://example.com/foo");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("session=1; Max-Age=3600", &url));
+        inserted!(store.parse("token=2", &url));
+        inserted!(store.parse("prefs=3; Max-Age=3600", &url));
+        let expired = Arc::new(Mutex::new(Vec::new()));
         {
-            deserializer.deserialize_seq(CookieStoreVisitor)
+            let expired = Arc::clone(&expired);
+            store.set_observer(move |event| {
+                if let StoreEvent::Expired(c) = event {
+                    expired.lock().unwrap().push(c.name().to_owned());
+                }
+            });
         }
+
+        assert_eq!(
+            2,
+            store.expire_matching(|c| c.name() == "session" || c.name() == "token")
+        );
+        values_are!(store, "http://example.com/foo", vec!["3"]);
+        // the expired cookies are retained, to be saved or merged
+        assert_eq!(3, store.iter_any().count());
+        assert!(store
+            .get_any("example.com", "/", "session")
+            .unwrap()
+            .is_expired());
+        let mut expired = expired.lock().unwrap().clone();
+        expired.sort_unstable();
+        assert_eq!(vec!["session", "token"], expired);
+        assert_eq!(0, store.expire_matching(|c| c.name() == "session"));
+        assert_eq!(2, store.purge_expired());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::CookieStore;
-    use super::{InsertResult, StoreAction};
-    use crate::cookie::Cookie;
-    use crate::CookieError;
-    use ::cookie::Cookie as RawCookie;
-    use time::OffsetDateTime;
+    #[test]
+    fn insert_if_absent_and_upsert() {
+        let url = test_utils::url("http://example.com/foo");
+        let cookie = |s| Cookie::parse(s, &url).unwrap().into_owned();
+        let mut store = CookieStore::default();
 
-    use crate::utils::test as test_utils;
+        inserted!(store.insert_if_absent(cookie("session=1"), &url));
+        assert_eq!(
+            Ok(StoreAction::Ignored),
+            store.insert_if_absent(cookie("session=2"), &url)
+        );
+        assert_eq!(
+            Ok(StoreAction::Ignored),
+            store.insert_if_absent(cookie("session=2; Max-Age=0"), &url)
+        );
+        values_are!(store, "http://example.com/foo", vec!["1"]);
 
-    macro_rules! inserted {
-        ($e: expr) => {
-            assert_eq!(Ok(StoreAction::Inserted), $e)
-        };
+        let (action, old) = store.upsert(cookie("session=2"), &url).unwrap();
+        assert_eq!(StoreAction::UpdatedExisting, action);
+        assert_eq!("1", old.unwrap().value());
+        values_are!(store, "http://example.com/foo", vec!["2"]);
+        assert_eq!(
+            Ok((StoreAction::Inserted, None)),
+            store.upsert(cookie("other=3"), &url)
+        );
+
+        // an __expired__ existing cookie may be replaced
+        expired_existing!(store.insert(cookie("session=2; Max-Age=0"), &url));
+        updated!(store.insert_if_absent(cookie("session=4"), &url));
+        values_are!(store, "http://example.com/foo", vec!["3", "4"]);
     }
-    macro_rules! updated {
-        ($e: expr) => {
-            assert_eq!(Ok(StoreAction::UpdatedExisting), $e)
+
+    #[test]
+    fn ttl_policy() {
+        use super::TtlPolicy;
+        use crate::CookieExpiration;
+        use time::Duration;
+
+        let url = test_utils::url("http://example.com/");
+        let expires_within = |store: &CookieStore, name, days| {
+            let cookie = store.get("example.com", "/", name).unwrap();
+            assert!(cookie.is_persistent());
+            assert!(cookie.expires_by(&test_utils::in_days(days)));
         };
+
+        let mut store = CookieStore::default()
+            .with_ttl_policy(TtlPolicy::default().with_max_lifetime(Duration::days(7)));
+        inserted!(store.parse("long=1; Max-Age=31536000", &url));
+        inserted!(store.parse("short=2; Max-Age=3600", &url));
+        inserted!(store.parse("session=3", &url));
+        expires_within(&store, "long", 7);
+        assert!(!store
+            .get("example.com", "/", "long")
+            .unwrap()
+            .expires_by(&test_utils::in_days(6)));
+        assert!(store
+            .get("example.com", "/", "short")
+            .unwrap()
+            .expires_by(&test_utils::in_days(1)));
+        assert_eq!(
+            CookieExpiration::SessionEnd,
+            store.get("example.com", "/", "session").unwrap().expires
+        );
+        // deletions are unaffected
+        expired_existing!(store.parse("long=1; Max-Age=0", &url));
+
+        // a lifetime beyond the representable expiries saturates rather than overflowing
+        let mut store = CookieStore::default().with_ttl_policy(
+            TtlPolicy::default()
+                .with_session_lifetime(Duration::MAX)
+                .with_max_lifetime(Duration::MAX),
+        );
+        inserted!(store.parse("long=1; Max-Age=31536000", &url));
+        inserted!(store.parse("session=3", &url));
+        expires_within(&store, "long", 366);
+        assert!(!store
+            .get("example.com", "/", "session")
+            .unwrap()
+            .expires_by(&test_utils::in_days(365 * 1000)));
+
+        let mut store = CookieStore::default().with_ttl_policy(
+            TtlPolicy::default()
+                .with_session_lifetime(Duration::days(1))
+                .with_max_lifetime(Duration::days(7)),
+        );
+        inserted!(store.parse("session=3", &url));
+        expires_within(&store, "session", 2);
+        assert!(store.iter_saved().any(|c| c.name() == "session"));
     }
-    macro_rules! expired_existing {
-        ($e: expr) => {
-            assert_eq!(Ok(StoreAction::ExpiredExisting), $e)
+
+    #[test]
+    fn first_party_only_persistence() {
+        let url = test_utils::url("http://www.example.com/");
+        let tracker = test_utils::url("http://tracker.test/pixel");
+        let saved = |store: &CookieStore| {
+            let mut names = store
+                .iter_saved()
+                .map(|c| c.name().to_owned())
+                .collect::<Vec<_>>();
+            names.sort();
+            names
         };
+        let mut store = CookieStore::default();
+        inserted!(store.parse("unknown=1; Max-Age=3600", &url));
+        inserted!(store.insert_raw_for_site(
+            &RawCookie::parse("first=1; Max-Age=3600").unwrap(),
+            &url,
+            &url
+        ));
+        inserted!(store.insert_raw_for_site(
+            &RawCookie::parse("parent=1; Domain=example.com; Max-Age=3600").unwrap(),
+            &url,
+            &url
+        ));
+        inserted!(store.insert_raw_for_site(
+            &RawCookie::parse("third=1; Max-Age=3600").unwrap(),
+            &tracker,
+            &url
+        ));
+        assert_eq!(
+            Some("www.example.com"),
+            store
+                .get("tracker.test", "/", "third")
+                .unwrap()
+                .site_for_cookies
+                .as_deref()
+        );
+        assert!(!store
+            .get("tracker.test", "/", "third")
+            .unwrap()
+            .is_first_party());
+        assert_eq!(vec!["first", "parent", "third", "unknown"], saved(&store));
+
+        let store = store.with_first_party_only_persistence(true);
+        assert_eq!(vec!["first", "parent", "unknown"], saved(&store));
+        // third-party cookies are still sent for the session
+        assert_eq!(1, store.matches(&tracker).len());
     }
-    macro_rules! domain_mismatch {
-        ($e: expr) => {
-            assert_eq!(Err(CookieError::DomainMismatch), $e)
-        };
+
+    #[cfg(feature = "psl")]
+    #[test]
+    fn first_party_site_registrable_domain() {
+        let mut store = CookieStore::new_with_public_suffix();
+        let page = test_utils::url("http://www.example.co.uk/");
+        let api = test_utils::url("http://api.example.co.uk/");
+        inserted!(store.insert_raw_for_site(&RawCookie::parse("api=1").unwrap(), &api, &page));
+        let cookie = store.get("api.example.co.uk", "/", "api").unwrap();
+        assert_eq!(Some("example.co.uk"), cookie.site_for_cookies.as_deref());
+        assert!(cookie.is_first_party());
     }
-    macro_rules! non_http_scheme {
-        ($e: expr) => {
-            assert_eq!(Err(CookieError::NonHttpScheme), $e)
+
+    #[test]
+    fn max_idle() {
+        use std::sync::{Arc, Mutex};
+        use time::Duration;
+
+        let start = test_utils::in_days(-60);
+        let now = Arc::new(Mutex::new(start));
+        let clock = {
+            let now = Arc::clone(&now);
+            move || *now.lock().unwrap()
         };
+        let advance = |duration: Duration| *now.lock().unwrap() += duration;
+        let mut store = CookieStore::default()
+            .with_clock(clock)
+            .with_max_idle(Duration::days(30));
+        let url = test_utils::url("http://example.com/foo");
+        let other_url = test_utils::url("http://other.com/");
+        inserted!(store.parse("cookie1=1", &url));
+        inserted!(store.parse("cookie2=2", &other_url));
+        assert_eq!(
+            Some(start),
+            store
+                .get("example.com", "/", "cookie1")
+                .unwrap()
+                .last_access_time
+        );
+
+        advance(Duration::days(20));
+        store.mark_clean();
+        assert_eq!(0, store.touch(&test_utils::url("http://unknown.com/")));
+        assert!(!store.is_dirty());
+        assert_eq!(1, store.touch(&url));
+        assert_eq!(
+            Some(start + Duration::days(20)),
+            store
+                .get("example.com", "/", "cookie1")
+                .unwrap()
+                .last_access_time
+        );
+        assert!(store.is_dirty());
+
+        // cookie2 is idle, and so is purged on insert
+        advance(Duration::days(15));
+        inserted!(store.parse("cookie3=3", &url));
+        values_are!(store, "http://example.com/foo", vec!["1", "3"]);
+        values_are!(store, "http://other.com/", vec![]);
+
+        // purging is not repeated within half of max_idle
+        advance(Duration::days(10));
+        inserted!(store.parse("cookie4=4", &other_url));
+        assert_eq!(3, store.len());
+        assert_eq!(1, store.purge_idle(Duration::days(20)));
+        values_are!(store, "http://example.com/foo", vec!["3"]);
+        assert_eq!(0, store.purge_idle(Duration::days(20)));
+        assert_eq!(0, store.purge_idle(Duration::MAX));
     }
-    macro_rules! non_rel_scheme {
-        ($e: expr) => {
-            assert_eq!(Err(CookieError::NonRelativeScheme), $e)
+
+    #[test]
+    fn audit_log() {
+        use crate::audit::AuditAction;
+        use crate::CookieIdentity;
+
+        // timestamps are serialized with a precision of seconds
+        let now = time::macros::datetime!(2030-01-01 00:00 UTC);
+        let url = test_utils::url("http://example.com/foo");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("cookie1=1", &url));
+        assert!(store.audit_log().is_empty());
+
+        let mut store = store.with_audit_log().with_clock(move || now);
+        updated!(store.parse("cookie1=2", &url));
+        inserted!(store.parse("cookie2=3", &url));
+        expired_existing!(store.parse("cookie2=3; Max-Age=0", &url));
+        assert!(store.remove("example.com", "/", "cookie1").is_some());
+        // snapshots and dry runs are not audited
+        let _ = store.would_accept("cookie3=4", &url);
+
+        let id = |name| CookieIdentity::new("example.com", "/", name);
+        let source_url = Some(url.to_string());
+        let entry = |action, name, source_url| crate::AuditEntry {
+            time: now,
+            action,
+            cookie: id(name),
+            source_url,
         };
+        assert_eq!(
+            vec![
+                entry(AuditAction::Updated, "cookie1", source_url.clone()),
+                entry(AuditAction::Inserted, "cookie2", source_url.clone()),
+                entry(AuditAction::Expired, "cookie2", source_url),
+                entry(AuditAction::Removed, "cookie1", None),
+            ],
+            store.audit_log()
+        );
+        #[cfg(feature = "serde_json")]
+        {
+            let json = serde_json::to_string(&store.audit_log()).unwrap();
+            let log: Vec<crate::AuditEntry> = serde_json::from_str(&json).unwrap();
+            assert_eq!(store.audit_log(), log);
+        }
+        assert_eq!(4, store.take_audit_log().len());
+        assert!(store.audit_log().is_empty());
+        store.clear();
+        assert_eq!(1, store.audit_log().len());
     }
-    macro_rules! expired_err {
-        ($e: expr) => {
-            assert_eq!(Err(CookieError::Expired), $e)
+
+    #[test]
+    fn cookies_expiring_before() {
+        let url = test_utils::url("http://example.com/foo");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("cookie1=1; Max-Age=7200", &url));
+        inserted!(store.parse("cookie2=2; Max-Age=60", &url));
+        inserted!(store.parse("cookie3=3; Max-Age=86400", &url));
+        inserted!(store.parse("session=4", &url));
+        let names = |deadline| {
+            store
+                .cookies_expiring_before(deadline)
+                .iter()
+                .map(|c| c.name().to_owned())
+                .collect::<Vec<_>>()
         };
+        let now = time::OffsetDateTime::now_utc();
+        assert_eq!(
+            vec!["cookie2", "cookie1"],
+            names(now + time::Duration::hours(3))
+        );
+        assert_eq!(vec!["cookie2"], names(now + time::Duration::minutes(5)));
+        assert!(names(now).is_empty());
+        assert_eq!(3, names(test_utils::in_days(365)).len());
     }
-    macro_rules! values_are {
-        ($store: expr, $url: expr, $values: expr) => {{
-            let mut matched_values = $store
-                .matches(&test_utils::url($url))
-                .iter()
-                .map(|c| &c.value()[..])
-                .collect::<Vec<_>>();
-            matched_values.sort();
 
-            let mut values: Vec<&str> = $values;
-            values.sort();
+    #[test]
+    fn export_changes_since() {
+        use crate::CookieIdentity;
 
-            assert!(
-                matched_values == values,
-                "\n{:?}\n!=\n{:?}\n",
-                matched_values,
-                values
-            );
-        }};
-    }
+        let url = test_utils::url("http://example.com/foo");
+        let mut store = CookieStore::default();
+        inserted!(store.parse("cookie1=1", &url));
+        assert!(store.export_changes_since(0).is_none());
 
-    fn add_cookie(
-        store: &mut CookieStore,
-        cookie: &str,
-        url: &str,
-        expires: Option<OffsetDateTime>,
-        max_age: Option<u64>,
-    ) -> InsertResult {
-        store.insert(
-            test_utils::make_cookie(cookie, url, expires, max_age),
-            &test_utils::url(url),
-        )
+        let mut store = store.with_change_tracking();
+        let start = store.generation();
+        assert!(store.export_changes_since(start - 1).is_none());
+        assert!(store.export_changes_since(start).unwrap().is_empty());
+        inserted!(store.parse("cookie2=2", &url));
+        inserted!(store.parse("cookie3=3", &url));
+        let synced = store.generation();
+
+        updated!(store.parse("cookie1=4", &url));
+        assert!(store.remove("example.com", "/", "cookie2").is_some());
+        expired_existing!(store.parse("cookie3=3; Max-Age=0", &url));
+        inserted!(store.parse("cookie5=5", &url));
+        updated!(store.parse("cookie5=6", &url));
+        // a cookie added and removed since is not reported
+        inserted!(store.parse("cookie6=6", &url));
+        assert!(store.remove("example.com", "/", "cookie6").is_some());
+
+        let names = |cookies: &Vec<&Cookie<'static>>| {
+            let mut names = cookies.iter().map(|c| c.name()).collect::<Vec<_>>();
+            names.sort();
+            names.join(",")
+        };
+        let changes = store.export_changes_since(synced).unwrap();
+        assert_eq!(store.generation(), changes.generation);
+        assert_eq!("cookie5", names(&changes.added));
+        assert_eq!("cookie1,cookie3", names(&changes.updated));
+        assert_eq!(
+            vec![CookieIdentity::new("example.com", "/", "cookie2")],
+            changes.removed
+        );
+        let changes = store.export_changes_since(start).unwrap();
+        assert_eq!("cookie3,cookie5", names(&changes.added));
+        assert_eq!("cookie1", names(&changes.updated));
+        assert!(changes.removed.is_empty());
+
+        store.compact_changes(synced);
+        assert!(store.export_changes_since(start).is_none());
+        let generation = store.generation();
+        assert!(store.export_changes_since(synced).is_some());
+        assert!(store.export_changes_since(generation + 1).is_none());
+        store.clear();
+        let changes = store.export_changes_since(generation).unwrap();
+        assert_eq!(3, changes.removed.len());
     }
 
-    fn make_match_store() -> CookieStore {
+    #[test]
+    fn tombstones() {
+        use crate::{CookieIdentity, Tombstone};
+        use std::sync::{Arc, Mutex};
+        use time::Duration;
+
+        let start = test_utils::in_days(-30);
+        let now = Arc::new(Mutex::new(start));
+        let clock = {
+            let now = Arc::clone(&now);
+            move || *now.lock().unwrap()
+        };
+        let advance = |duration: Duration| *now.lock().unwrap() += duration;
+        let url = test_utils::url("http://example.com/foo");
         let mut store = CookieStore::default();
-        inserted!(add_cookie(
-            &mut store,
-            "cookie1=1",
-            "http://example.com/foo/bar",
-            None,
-            Some(60 * 5),
-        ));
-        inserted!(add_cookie(
-            &mut store,
-            "cookie2=2; Secure",
-            "https://example.com/sec/",
-            None,
-            Some(60 * 5),
-        ));
-        inserted!(add_cookie(
-            &mut store,
-            "cookie3=3; HttpOnly",
-            "https://example.com/sec/",
-            None,
-            Some(60 * 5),
-        ));
-        inserted!(add_cookie(
-            &mut store,
-            "cookie4=4; Secure; HttpOnly",
-            "https://example.com/sec/",
-            None,
-            Some(60 * 5),
-        ));
-        inserted!(add_cookie(
-            &mut store,
-            "cookie5=5",
-            "http://example.com/foo/",
-            None,
-            Some(60 * 5),
-        ));
-        inserted!(add_cookie(
-            &mut store,
-            "cookie6=6",
-            "http://example.com/",
-            None,
-            Some(60 * 5),
-        ));
-        inserted!(add_cookie(
-            &mut store,
-            "cookie7=7",
-            "http://bar.example.com/foo/",
-            None,
-            Some(60 * 5),
-        ));
+        inserted!(store.parse("cookie1=1", &url));
+        assert!(store.remove("example.com", "/", "cookie1").is_some());
+        assert!(store.tombstones().is_empty());
 
-        inserted!(add_cookie(
-            &mut store,
-            "cookie8=8",
-            "http://example.org/foo/bar",
-            None,
-            Some(60 * 5),
-        ));
-        inserted!(add_cookie(
-            &mut store,
-            "cookie9=9",
-            "http://bar.example.org/foo/bar",
-            None,
-            Some(60 * 5),
-        ));
-        store
+        let mut store = store.with_clock(clock).with_tombstones(Duration::days(7));
+        inserted!(store.parse("cookie1=1", &url));
+        inserted!(store.parse("cookie2=2", &url));
+        assert!(store.remove("example.com", "/", "cookie1").is_some());
+        advance(Duration::days(1));
+        expired_existing!(store.parse("cookie2=2; Max-Age=0", &url));
+        let tombstone = |name, deleted_at| Tombstone {
+            cookie: CookieIdentity::new("example.com", "/", name),
+            deleted_at,
+        };
+        assert_eq!(
+            vec![
+                tombstone("cookie1", start),
+                tombstone("cookie2", start + Duration::days(1))
+            ],
+            store.tombstones()
+        );
+
+        // re-adding a cookie discards its tombstone
+        inserted!(store.parse("cookie1=3", &url));
+        assert_eq!(
+            vec![tombstone("cookie2", start + Duration::days(1))],
+            store.tombstones()
+        );
+
+        // tombstones lapse after the retention window
+        advance(Duration::days(7));
+        assert!(store.tombstones().is_empty());
     }
 
-    macro_rules! check_matches {
-        ($store: expr) => {{
-            values_are!($store, "http://unknowndomain.org/foo/bar", vec![]);
-            values_are!($store, "http://example.org/foo/bar", vec!["8"]);
-            values_are!($store, "http://example.org/bus/bar", vec![]);
-            values_are!($store, "http://bar.example.org/foo/bar", vec!["9"]);
-            values_are!($store, "http://bar.example.org/bus/bar", vec![]);
-            values_are!(
-                $store,
-                "https://example.com/sec/foo",
-                vec!["6", "4", "3", "2"]
-            );
-            values_are!($store, "http://example.com/sec/foo", vec!["6", "3"]);
-            values_are!($store, "ftp://example.com/sec/foo", vec!["6"]);
-            values_are!($store, "http://bar.example.com/foo/bar/bus", vec!["7"]);
-            values_are!(
-                $store,
-                "http://example.com/foo/bar/bus",
-                vec!["1", "5", "6"]
-            );
-        }};
+    #[test]
+    fn merge_tombstones() {
+        use super::MergeStrategy;
+        use std::sync::{Arc, Mutex};
+        use time::Duration;
+
+        let now = Arc::new(Mutex::new(test_utils::in_days(-30)));
+        let clock = {
+            let now = Arc::clone(&now);
+            move || *now.lock().unwrap()
+        };
+        let advance = |duration: Duration| *now.lock().unwrap() += duration;
+        let url = test_utils::url("http://example.com/foo");
+        let mut store = CookieStore::default()
+            .with_clock(clock)
+            .with_tombstones(Duration::days(7));
+        inserted!(store.parse("cookie1=1", &url));
+        inserted!(store.parse("cookie2=2", &url));
+        inserted!(store.parse("cookie3=3", &url));
+        let mut other = store.clone();
+
+        advance(Duration::days(1));
+        assert!(store.remove("example.com", "/", "cookie1").is_some());
+        assert!(store.remove("example.com", "/", "cookie3").is_some());
+        assert!(other.remove("example.com", "/", "cookie2").is_some());
+        assert!(other.remove("example.com", "/", "cookie3").is_some());
+        // a cookie set after its deletion elsewhere is not a resurrection
+        advance(Duration::days(1));
+        inserted!(store.parse("cookie3=new", &url));
+        inserted!(other.parse("cookie4=4", &url));
+
+        let mut without_tombstones = CookieStore::default();
+        without_tombstones.merge(store.clone(), MergeStrategy::PreferOther);
+        without_tombstones.merge(other.clone(), MergeStrategy::PreferOther);
+        values_are!(
+            without_tombstones,
+            "http://example.com/foo",
+            vec!["1", "2", "4", "new"]
+        );
+
+        store.merge(other, MergeStrategy::PreferOther);
+        values_are!(store, "http://example.com/foo", vec!["4", "new"]);
+        let mut deleted = store
+            .tombstones()
+            .into_iter()
+            .map(|tombstone| tombstone.cookie.name)
+            .collect::<Vec<_>>();
+        deleted.sort();
+        assert_eq!(vec!["cookie1", "cookie2"], deleted);
     }
 
     #[test]
-    fn insert_raw() {
-        let mut store = CookieStore::default();
-        inserted!(store.insert_raw(
-            &RawCookie::parse("cookie1=value1").unwrap(),
-            &test_utils::url("http://example.com/foo/bar"),
-        ));
-        non_rel_scheme!(store.insert_raw(
-            &RawCookie::parse("cookie1=value1").unwrap(),
-            &test_utils::url("data:nonrelativescheme"),
-        ));
-        non_http_scheme!(store.insert_raw(
-            &RawCookie::parse("cookie1=value1; HttpOnly").unwrap(),
-            &test_utils::url("ftp://example.com/"),
-        ));
-        expired_existing!(store.insert_raw(
-            &RawCookie::parse("cookie1=value1; Max-Age=0").unwrap(),
-            &test_utils::url("http://example.com/foo/bar"),
-        ));
-        expired_err!(store.insert_raw(
-            &RawCookie::parse("cookie1=value1; Max-Age=-1").unwrap(),
-            &test_utils::url("http://example.com/foo/bar"),
-        ));
-        updated!(store.insert_raw(
-            &RawCookie::parse("cookie1=value1").unwrap(),
-            &test_utils::url("http://example.com/foo/bar"),
-        ));
-        expired_existing!(store.insert_raw(
-            &RawCookie::parse("cookie1=value1; Max-Age=-1").unwrap(),
-            &test_utils::url("http://example.com/foo/bar"),
-        ));
-        domain_mismatch!(store.insert_raw(
-            &RawCookie::parse("cookie1=value1; Domain=bar.example.com").unwrap(),
-            &test_utils::url("http://example.com/foo/bar"),
-        ));
+    fn merge_tombstones_repeatedly() {
+        use super::MergeStrategy;
+        use std::sync::{Arc, Mutex};
+        use time::Duration;
+
+        let start = test_utils::in_days(-30);
+        let now = Arc::new(Mutex::new(start));
+        let clock = {
+            let now = Arc::clone(&now);
+            move || *now.lock().unwrap()
+        };
+        let advance = |duration: Duration| *now.lock().unwrap() += duration;
+        let url = test_utils::url("http://example.com/foo");
+        let mut store = CookieStore::default()
+            .with_clock(clock.clone())
+            .with_tombstones(Duration::days(7));
+        inserted!(store.parse("cookie1=1", &url));
+        let mut deleter = store.clone();
+        let stale = store.clone();
+
+        advance(Duration::days(1));
+        assert!(deleter.remove("example.com", "/", "cookie1").is_some());
+        advance(Duration::days(1));
+        let mut other = CookieStore::default()
+            .with_clock(clock)
+            .with_tombstones(Duration::days(7));
+        inserted!(other.parse("cookie1=other", &url));
+
+        // the replaced cookie keeps its creation-time...
+        store.merge(other, MergeStrategy::PreferOther);
+        values_are!(store, "http://example.com/foo", vec!["other"]);
+        assert_eq!(
+            Some(start),
+            store
+                .get("example.com", "/", "cookie1")
+                .unwrap()
+                .creation_time
+        );
+        // ...and so was created before its deletion by `deleter`
+        store.merge(deleter, MergeStrategy::PreferOther);
+        values_are!(store, "http://example.com/foo", Vec::<&str>::new());
+        // a stale copy is not resurrected
+        store.merge(stale, MergeStrategy::PreferOther);
+        values_are!(store, "http://example.com/foo", Vec::<&str>::new());
+        assert_eq!(1, store.tombstones().len());
     }
 
     #[test]
-    fn parse() {
+    fn stats_and_shrink_to_fit() {
         let mut store = CookieStore::default();
-        inserted!(store.parse(
+        inserted!(add_cookie(
+            &mut store,
             "cookie1=value1",
-            &test_utils::url("http://example.com/foo/bar"),
-        ));
-        non_rel_scheme!(store.parse("cookie1=value1", &test_utils::url("data:nonrelativescheme"),));
-        non_http_scheme!(store.parse(
-            "cookie1=value1; HttpOnly",
-            &test_utils::url("ftp://example.com/"),
-        ));
-        expired_existing!(store.parse(
-            "cookie1=value1; Max-Age=0",
-            &test_utils::url("http://example.com/foo/bar"),
+            "http://example.com/",
+            None,
+            None
         ));
-        expired_err!(store.parse(
-            "cookie1=value1; Max-Age=-1",
-            &test_utils::url("http://example.com/foo/bar"),
+        inserted!(add_cookie(
+            &mut store,
+            "cookie2=value2",
+            "http://example.com/",
+            None,
+            None
         ));
-        updated!(store.parse(
+        inserted!(add_cookie(
+            &mut store,
             "cookie1=value1",
-            &test_utils::url("http://example.com/foo/bar"),
-        ));
-        expired_existing!(store.parse(
-            "cookie1=value1; Max-Age=-1",
-            &test_utils::url("http://example.com/foo/bar"),
+            "http://example.org/",
+            None,
+            None
         ));
-        domain_mismatch!(store.parse(
-            "cookie1=value1; Domain=bar.example.com",
-            &test_utils::url("http://example.com/foo/bar"),
+        expired_existing!(add_cookie(
+            &mut store,
+            "cookie2=value2",
+            "http://example.com/",
+            Some(test_utils::in_days(-1)),
+            None
         ));
-    }
-
-    #[test]
-    fn domains() {
-        let mut store = CookieStore::default();
-        //        The user agent will reject cookies unless the Domain attribute
-        // specifies a scope for the cookie that would include the origin
-        // server.  For example, the user agent will accept a cookie with a
-        // Domain attribute of "example.com" or of "foo.example.com" from
-        // foo.example.com, but the user agent will not accept a cookie with a
-        // Domain attribute of "bar.example.com" or of "baz.foo.example.com".
-        fn domain_cookie_from(domain: &str, request_url: &str) -> Cookie<'static> {
-            let cookie_str = format!("cookie1=value1; Domain={}", domain);
-            Cookie::parse(cookie_str, &test_utils::url(request_url)).unwrap()
-        }
 
-        {
-            let request_url = test_utils::url("http://foo.example.com");
-            // foo.example.com can submit cookies for example.com and foo.example.com
-            inserted!(store.insert(
-                domain_cookie_from("example.com", "http://foo.example.com",),
-                &request_url,
-            ));
-            updated!(store.insert(
-                domain_cookie_from(".example.com", "http://foo.example.com",),
-                &request_url,
-            ));
-            inserted!(store.insert(
-                domain_cookie_from("foo.example.com", "http://foo.example.com",),
-                &request_url,
-            ));
-            updated!(store.insert(
-                domain_cookie_from(".foo.example.com", "http://foo.example.com",),
-                &request_url,
-            ));
-            // not for bar.example.com
-            domain_mismatch!(store.insert(
-                domain_cookie_from("bar.example.com", "http://bar.example.com",),
-                &request_url,
-            ));
-            domain_mismatch!(store.insert(
-                domain_cookie_from(".bar.example.com", "http://bar.example.com",),
-                &request_url,
-            ));
-            // not for bar.foo.example.com
-            domain_mismatch!(store.insert(
-                domain_cookie_from("bar.foo.example.com", "http://bar.foo.example.com",),
-                &request_url,
-            ));
-            domain_mismatch!(store.insert(
-                domain_cookie_from(".bar.foo.example.com", "http://bar.foo.example.com",),
-                &request_url,
-            ));
-        }
+        let stats = store.stats();
+        assert_eq!(3, stats.cookies);
+        assert_eq!(1, stats.expired);
+        let mut cookies_per_domain = stats.cookies_per_domain;
+        cookies_per_domain.sort();
+        assert_eq!(
+            vec![("example.com".to_owned(), 2), ("example.org".to_owned(), 1)],
+            cookies_per_domain
+        );
+        assert_eq!(store.memory_footprint(), stats.heap_bytes);
 
-        {
-            let request_url = test_utils::url("http://bar.example.com");
-            // bar.example.com can submit for example.com and bar.example.com
-            updated!(store.insert(
-                domain_cookie_from("example.com", "http://foo.example.com",),
-                &request_url,
-            ));
-            updated!(store.insert(
-                domain_cookie_from(".example.com", "http://foo.example.com",),
-                &request_url,
-            ));
-            inserted!(store.insert(
-                domain_cookie_from("bar.example.com", "http://bar.example.com",),
-                &request_url,
-            ));
-            updated!(store.insert(
-                domain_cookie_from(".bar.example.com", "http://bar.example.com",),
-                &request_url,
-            ));
-            // bar.example.com cannot submit for foo.example.com
-            domain_mismatch!(store.insert(
-                domain_cookie_from("foo.example.com", "http://foo.example.com",),
-                &request_url,
-            ));
-            domain_mismatch!(store.insert(
-                domain_cookie_from(".foo.example.com", "http://foo.example.com",),
-                &request_url,
-            ));
-        }
-        {
-            let request_url = test_utils::url("http://example.com");
-            // example.com can submit for example.com
-            updated!(store.insert(
-                domain_cookie_from("example.com", "http://foo.example.com",),
-                &request_url,
-            ));
-            updated!(store.insert(
-                domain_cookie_from(".example.com", "http://foo.example.com",),
-                &request_url,
-            ));
-            // example.com cannot submit for foo.example.com or bar.example.com
-            domain_mismatch!(store.insert(
-                domain_cookie_from("foo.example.com", "http://foo.example.com",),
-                &request_url,
-            ));
-            domain_mismatch!(store.insert(
-                domain_cookie_from(".foo.example.com", "http://foo.example.com",),
-                &request_url,
-            ));
-            domain_mismatch!(store.insert(
-                domain_cookie_from("bar.example.com", "http://bar.example.com",),
-                &request_url,
-            ));
-            domain_mismatch!(store.insert(
-                domain_cookie_from(".bar.example.com", "http://bar.example.com",),
-                &request_url,
-            ));
+        for i in 0..100 {
+            store.interned.intern(&format!("unused{}", i));
         }
+        let footprint = store.memory_footprint();
+        store.shrink_to_fit();
+        assert_eq!(3, store.stats().cookies);
+        assert!(store.memory_footprint() < footprint);
+        values_are!(store, "http://example.com/", vec!["value1"]);
     }
 
     #[test]
@@ -1060,6 +5753,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn matches_iter() {
+        let store = make_match_store();
+        for url in [
+            "http://unknowndomain.org/foo/bar",
+            "https://example.com/sec/foo",
+            "http://example.com/sec/foo",
+            "ftp://example.com/sec/foo",
+            "http://example.com/foo/bar/bus",
+        ] {
+            let url = test_utils::url(url);
+            let matches = store.matches(&url);
+            let matches_iter = store.matches_iter(&url).collect::<Vec<_>>();
+            assert_eq!(matches, matches_iter);
+        }
+    }
+
     #[test]
     fn some_non_https_uris_are_secure() {
         // Matching the list in Firefox's regression test:
@@ -1165,6 +5875,63 @@ mod tests {
         matches_are(&store, "http://foo.example.com/", vec!["cookie2=2b"]);
     }
 
+    #[test]
+    fn deep_subdomains() {
+        let mut store = CookieStore::default();
+        inserted!(add_cookie(
+            &mut store,
+            "cookie1=1; Domain=example.com",
+            "http://a.b.c.example.com/",
+            None,
+            None
+        ));
+        inserted!(add_cookie(
+            &mut store,
+            "cookie2=2; Domain=c.example.com",
+            "http://a.b.c.example.com/",
+            None,
+            None
+        ));
+        inserted!(add_cookie(
+            &mut store,
+            "cookie3=3",
+            "http://b.c.example.com/",
+            None,
+            None
+        ));
+        inserted!(add_cookie(
+            &mut store,
+            "cookie4=4",
+            "http://a.b.c.example.com/",
+            None,
+            None
+        ));
+        inserted!(add_cookie(
+            &mut store,
+            "cookie5=5",
+            "http://b.c.example.org/",
+            None,
+            None
+        ));
+        matches_are(
+            &store,
+            "http://a.b.c.example.com/",
+            vec!["cookie1=1", "cookie2=2", "cookie4=4"],
+        );
+        matches_are(
+            &store,
+            "http://b.c.example.com/",
+            vec!["cookie1=1", "cookie2=2", "cookie3=3"],
+        );
+        matches_are(
+            &store,
+            "http://x.c.example.com/",
+            vec!["cookie1=1", "cookie2=2"],
+        );
+        matches_are(&store, "http://example.com/", vec!["cookie1=1"]);
+        matches_are(&store, "http://c.example.org/", vec![]);
+    }
+
     #[test]
     fn path_collisions() {
         let mut store = CookieStore::default();
@@ -1240,7 +6007,7 @@ mod tests {
         use crate::cookie::Cookie;
         use crate::CookieError;
 
-        use crate::utils::test as test_utils;
+        use crate::test_util as test_utils;
 
         macro_rules! has_str {
             ($e: expr, $i: ident) => {{
@@ -1747,5 +6514,77 @@ mod tests {
         }
 
     }
+
+    #[cfg(feature = "http")]
+    mod http_tests {
+        use super::{make_match_store, CookieStore};
+        use http::header::{HeaderMap, HeaderValue, COOKIE, SET_COOKIE};
+
+        use crate::test_util as test_utils;
+
+        #[test]
+        fn store_response_headers() {
+            let mut store = CookieStore::default();
+            let mut headers = HeaderMap::new();
+            headers.append(SET_COOKIE, HeaderValue::from_static("cookie1=value1"));
+            headers.append(SET_COOKIE, HeaderValue::from_static("cookie2=value2; Path=/"));
+            headers.append(SET_COOKIE, HeaderValue::from_static("=invalid"));
+            headers.append(SET_COOKIE, HeaderValue::from_bytes(b"cookie3=\xff").unwrap());
+            headers.append(COOKIE, HeaderValue::from_static("cookie4=value4"));
+            store.store_response_headers(&headers, &test_utils::url("http://example.com/foo/bar"));
+            assert_eq!(store.get("example.com", "/foo", "cookie1").unwrap().value(), "value1");
+            assert_eq!(store.get("example.com", "/", "cookie2").unwrap().value(), "value2");
+            assert_eq!(store.iter_any().count(), 2);
+        }
+
+        #[test]
+        fn add_cookie_header() {
+            let store = make_match_store();
+            let mut headers = HeaderMap::new();
+            store.add_cookie_header(&mut headers, &test_utils::url("http://unknowndomain.org/foo"));
+            assert!(headers.get(COOKIE).is_none());
+
+            headers.insert(COOKIE, HeaderValue::from_static("stale=value"));
+            store.add_cookie_header(&mut headers, &test_utils::url("http://example.org/foo/bar"));
+            assert_eq!(headers.get_all(COOKIE).iter().count(), 1);
+            assert_eq!(headers.get(COOKIE).unwrap(), "cookie8=8");
+        }
+
+        #[test]
+        fn add_set_cookie_headers() {
+            let mut store = CookieStore::default();
+            let url = test_utils::url("http://example.com/foo/bar");
+            store.parse("cookie1=value1; Path=/", &url).unwrap();
+            store.parse("cookie2=value2; Path=/foo", &url).unwrap();
+            let mut headers = HeaderMap::new();
+            store.add_set_cookie_headers(&mut headers, &url);
+            let mut values = headers
+                .get_all(SET_COOKIE)
+                .iter()
+                .map(|v| v.to_str().unwrap())
+                .collect::<Vec<_>>();
+            values.sort_unstable();
+            assert_eq!(values, vec!["cookie1=value1; Path=/", "cookie2=value2; Path=/foo"]);
+        }
+
+        #[test]
+        fn set_cookies_and_cookies() {
+            let mut store = CookieStore::default();
+            let url = test_utils::url("http://example.com/foo/bar");
+            assert!(store.cookies(&url).is_none());
+            let set_cookies = vec![
+                HeaderValue::from_static("cookie1=value1"),
+                HeaderValue::from_static("cookie2=value2; Secure"),
+                HeaderValue::from_static("cookie3=value3; Path=/bus"),
+            ];
+            store.set_cookies(&set_cookies, &url);
+            assert_eq!(store.iter_any().count(), 3);
+            assert_eq!(store.cookies(&url).unwrap(), "cookie1=value1");
+            let cookies = store.cookies(&test_utils::url("https://example.com/foo/bar")).unwrap();
+            let mut pairs = cookies.to_str().unwrap().split("; ").collect::<Vec<_>>();
+            pairs.sort();
+            assert_eq!(pairs, vec!["cookie1=value1", "cookie2=value2"]);
+        }
+    }
 }
 
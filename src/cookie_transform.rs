@@ -0,0 +1,50 @@
+use std::fmt;
+use std::sync::Arc;
+
+use url::Url;
+
+use crate::cookie::Cookie;
+
+/// A hook rewriting each `Cookie` received by a [`CookieStore`](crate::CookieStore) before it is
+/// stored, allowing policies to be enforced on the contents of the store, e.g. stripping the
+/// Domain attribute to force a host-only `Cookie`, downgrading the SameSite attribute, or capping
+/// the expiry time; see
+/// [`CookieStore::with_transform`](crate::CookieStore::with_transform). The `Cookie` has been
+/// validated against the request-uri it was received from, so the transform may rely on e.g. the
+/// domain of the `Cookie` matching the request-uri; the `Cookie` it produces is not validated
+/// again.
+///
+/// `CookieTransform` is implemented for any `Fn(&mut Cookie<'static>, &Url) + Send + Sync`.
+pub trait CookieTransform: Send + Sync {
+    /// Rewrite `cookie`, received from `request_url`
+    fn transform(&self, cookie: &mut Cookie<'static>, request_url: &Url);
+}
+
+impl<F> CookieTransform for F
+where
+    F: Fn(&mut Cookie<'static>, &Url) + Send + Sync,
+{
+    fn transform(&self, cookie: &mut Cookie<'static>, request_url: &Url) {
+        self(cookie, request_url)
+    }
+}
+
+/// The `CookieTransform` held by a `CookieStore`, allowing the store to remain `Clone` and `Debug`
+#[derive(Clone)]
+pub(crate) struct SharedTransform(Arc<dyn CookieTransform>);
+
+impl SharedTransform {
+    pub(crate) fn new<T: CookieTransform + 'static>(transform: T) -> SharedTransform {
+        SharedTransform(Arc::new(transform))
+    }
+
+    pub(crate) fn transform(&self, cookie: &mut Cookie<'static>, request_url: &Url) {
+        self.0.transform(cookie, request_url)
+    }
+}
+
+impl fmt::Debug for SharedTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CookieTransform").finish()
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::cookie_domain::is_domain_match;
+
+/// An ordered index of the domain keys of a `CookieStore`, keyed by each domain with its
+/// characters reversed (e.g. `example.com` as `moc.elpmaxe`), so that the subdomains of a domain
+/// form a contiguous range of keys and may be found without scanning every domain in the store.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct DomainIndex(BTreeMap<String, Arc<str>>);
+
+fn reversed(domain: &str) -> String {
+    domain.chars().rev().collect()
+}
+
+impl DomainIndex {
+    /// Add `domain` to the index, if not already present
+    pub(crate) fn insert(&mut self, domain: &Arc<str>) {
+        self.0
+            .entry(reversed(domain))
+            .or_insert_with(|| Arc::clone(domain));
+    }
+
+    pub(crate) fn remove(&mut self, domain: &str) {
+        self.0.remove(&reversed(domain));
+    }
+
+    /// Retain only the domains for which `f` returns `true`
+    pub(crate) fn retain<F: FnMut(&str) -> bool>(&mut self, mut f: F) {
+        self.0.retain(|_, domain| f(domain));
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    /// The indexed (strict) subdomains of `domain`
+    pub(crate) fn subdomains<'a>(&'a self, domain: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        let prefix = reversed(domain);
+        // the keys of the subdomains are those prefixed with `prefix` followed by a '.'; '/' is
+        // the character following '.'
+        self.0
+            .range(format!("{}.", prefix)..format!("{}/", prefix))
+            .map(|(_, subdomain)| &**subdomain)
+            .filter(move |subdomain| is_domain_match(subdomain, domain))
+    }
+
+    /// Estimated heap bytes used by the index; the domains are shared with the `CookieStore`
+    pub(crate) fn heap_size(&self) -> usize {
+        self.0
+            .keys()
+            .map(|key| key.len() + std::mem::size_of::<(String, Arc<str>)>())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DomainIndex;
+    use std::sync::Arc;
+
+    #[test]
+    fn subdomains() {
+        let mut index = DomainIndex::default();
+        for domain in [
+            "example.com",
+            "www.example.com",
+            "a.b.example.com",
+            "badexample.com",
+            "example.org",
+            "com",
+        ] {
+            index.insert(&Arc::from(domain));
+        }
+        let subdomains = |index: &DomainIndex, domain| index.subdomains(domain).count();
+        assert_eq!(
+            vec!["a.b.example.com", "www.example.com"],
+            index.subdomains("example.com").collect::<Vec<_>>()
+        );
+        assert_eq!(0, subdomains(&index, "www.example.com"));
+        assert_eq!(4, subdomains(&index, "com"));
+        assert_eq!(1, subdomains(&index, "org"));
+
+        index.remove("www.example.com");
+        index.retain(|domain| domain != "a.b.example.com");
+        assert_eq!(0, subdomains(&index, "example.com"));
+    }
+}
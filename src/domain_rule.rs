@@ -0,0 +1,66 @@
+/// A rule matching the domains under which cookies are stored, used to configure the domain
+/// allowlist and denylist of a [`CookieStore`](crate::CookieStore); see
+/// [`CookieStore::with_domain_allowlist`](crate::CookieStore::with_domain_allowlist) and
+/// [`CookieStore::with_domain_denylist`](crate::CookieStore::with_domain_denylist).
+#[derive(PartialEq, Clone, Debug, Eq, Hash)]
+pub enum DomainRule {
+    /// Matches only the given domain
+    Exact(String),
+    /// Matches the given domain and any of its subdomains
+    Suffix(String),
+}
+
+impl DomainRule {
+    /// A rule matching only `domain`
+    pub fn exact(domain: &str) -> DomainRule {
+        DomainRule::Exact(normalize(domain))
+    }
+
+    /// A rule matching `domain` and any of its subdomains; e.g. `DomainRule::suffix("example.com")`
+    /// matches both `example.com` and `www.example.com`, but not `fooexample.com`
+    pub fn suffix(domain: &str) -> DomainRule {
+        DomainRule::Suffix(normalize(domain))
+    }
+
+    /// Returns true if `domain` is matched by this rule
+    pub fn matches(&self, domain: &str) -> bool {
+        match self {
+            DomainRule::Exact(rule) => rule.eq_ignore_ascii_case(domain),
+            DomainRule::Suffix(rule) => {
+                let split = domain.len().saturating_sub(rule.len());
+                match (domain.get(..split), domain.get(split..)) {
+                    (Some(sub), Some(suffix)) => {
+                        suffix.eq_ignore_ascii_case(rule) && (sub.is_empty() || sub.ends_with('.'))
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+fn normalize(domain: &str) -> String {
+    domain.trim_start_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DomainRule;
+
+    #[test]
+    fn matches() {
+        let exact = DomainRule::exact("Example.com");
+        assert!(exact.matches("example.com"));
+        assert!(exact.matches("EXAMPLE.COM"));
+        assert!(!exact.matches("www.example.com"));
+
+        let suffix = DomainRule::suffix(".example.com");
+        assert_eq!(DomainRule::Suffix("example.com".to_owned()), suffix);
+        assert!(suffix.matches("example.com"));
+        assert!(suffix.matches("www.Example.com"));
+        assert!(suffix.matches("a.b.example.com"));
+        assert!(!suffix.matches("fooexample.com"));
+        assert!(!suffix.matches("example.org"));
+        assert!(!suffix.matches("com"));
+    }
+}
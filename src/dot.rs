@@ -0,0 +1,118 @@
+//! Rendering of the contents of a `CookieStore` as a [Graphviz](https://graphviz.org) DOT graph,
+//! for debugging; see [`CookieStore::to_dot`](crate::CookieStore::to_dot)
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::cookie::Cookie;
+use crate::cookie_domain::CookieDomain;
+use crate::cookie_store::CookieStore;
+
+/// Escape `s` for use within a double-quoted DOT string
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+type Tree<'a> = BTreeMap<String, BTreeMap<String, Vec<&'a Cookie<'static>>>>;
+
+impl CookieStore {
+    /// Render the cookies (including __expired__) in the store as a Graphviz DOT graph of the
+    /// domain → path → name hierarchy under which they are stored: each domain is drawn as a
+    /// cluster, containing a node for each path, linked to a leaf for each cookie name.
+    /// __Expired__ cookies are drawn dashed, and host-only cookies are marked as such, which
+    /// helps in diagnosing unexpected shadowing between cookies of subdomains and paths. The
+    /// output may be rendered with e.g. `dot -Tsvg`.
+    pub fn to_dot(&self) -> String {
+        let now_utc = self.now_utc();
+        let mut tree = Tree::new();
+        for cookie in self.iter_any() {
+            tree.entry(String::from(&cookie.domain))
+                .or_default()
+                .entry(String::from(&cookie.path))
+                .or_default()
+                .push(cookie);
+        }
+
+        let mut dot = String::from("digraph cookie_store {\n    rankdir=LR;\n");
+        for (d, (domain, paths)) in tree.iter_mut().enumerate() {
+            let _ = writeln!(dot, "    subgraph \"cluster_{}\" {{", d);
+            let _ = writeln!(dot, "        label=\"{}\";", escape(domain));
+            for (p, (path, cookies)) in paths.iter_mut().enumerate() {
+                let path_node = format!("d{}p{}", d, p);
+                let _ = writeln!(
+                    dot,
+                    "        \"{}\" [label=\"{}\", shape=folder];",
+                    path_node,
+                    escape(path)
+                );
+                cookies.sort_by_key(|c| c.name());
+                for (c, cookie) in cookies.iter().enumerate() {
+                    let mut label = escape(cookie.name());
+                    if let CookieDomain::HostOnly(_) = cookie.domain {
+                        label.push_str(" (host-only)");
+                    }
+                    let style = if cookie.expires_by(&now_utc) {
+                        "dashed"
+                    } else {
+                        "solid"
+                    };
+                    let cookie_node = format!("{}c{}", path_node, c);
+                    let _ = writeln!(
+                        dot,
+                        "        \"{}\" [label=\"{}\", shape=note, style={}];",
+                        cookie_node, label, style
+                    );
+                    let _ = writeln!(dot, "        \"{}\" -> \"{}\";", path_node, cookie_node);
+                }
+            }
+            dot.push_str("    }\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cookie_store::CookieStore;
+    use crate::test_util;
+
+    #[test]
+    fn to_dot() {
+        assert_eq!(
+            "digraph cookie_store {\n    rankdir=LR;\n}\n",
+            CookieStore::default().to_dot()
+        );
+
+        let url = test_util::url("http://www.example.com/foo/bar");
+        let mut store = CookieStore::default();
+        store
+            .parse("a=1; Domain=example.com; Path=/", &url)
+            .unwrap();
+        store.parse("b=2", &url).unwrap();
+        store.parse("\"q\"=3; Max-Age=60", &url).unwrap();
+        store.parse("\"q\"=3; Max-Age=0", &url).unwrap();
+        let dot = store.to_dot();
+        assert_eq!(
+            "digraph cookie_store {
+    rankdir=LR;
+    subgraph \"cluster_0\" {
+        label=\"example.com\";
+        \"d0p0\" [label=\"/\", shape=folder];
+        \"d0p0c0\" [label=\"a\", shape=note, style=solid];
+        \"d0p0\" -> \"d0p0c0\";
+    }
+    subgraph \"cluster_1\" {
+        label=\"www.example.com\";
+        \"d1p0\" [label=\"/foo\", shape=folder];
+        \"d1p0c0\" [label=\"\\\"q\\\" (host-only)\", shape=note, style=dashed];
+        \"d1p0\" -> \"d1p0c0\";
+        \"d1p0c1\" [label=\"b (host-only)\", shape=note, style=solid];
+        \"d1p0\" -> \"d1p0c1\";
+    }
+}
+",
+            dot
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::cookie_store::CookieStore;
+
+/// A read-only [`CookieStore`], as returned by [`CookieStore::freeze`]. A `FrozenCookieStore`
+/// dereferences to the underlying `CookieStore`, so all non-mutating methods (e.g.
+/// [`CookieStore::matches`]) are available, while any attempt to mutate the store fails to
+/// compile. Clones share the same underlying store, so a "golden" store loaded once may be
+/// cheaply handed out to code which must not modify it.
+#[derive(Debug, Clone)]
+pub struct FrozenCookieStore(Arc<CookieStore>);
+
+impl FrozenCookieStore {
+    pub(crate) fn new(cookie_store: CookieStore) -> FrozenCookieStore {
+        FrozenCookieStore(Arc::new(cookie_store))
+    }
+
+    /// A mutable copy of the store; the `FrozenCookieStore` itself is unaffected by any changes
+    /// made to the copy.
+    pub fn thaw(&self) -> CookieStore {
+        CookieStore::clone(&self.0)
+    }
+}
+
+impl Deref for FrozenCookieStore {
+    type Target = CookieStore;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<CookieStore> for FrozenCookieStore {
+    fn as_ref(&self) -> &CookieStore {
+        &self.0
+    }
+}
+
+impl From<CookieStore> for FrozenCookieStore {
+    fn from(cookie_store: CookieStore) -> Self {
+        FrozenCookieStore::new(cookie_store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cookie_store::CookieStore;
+    use crate::test_util;
+
+    #[test]
+    fn freeze_and_thaw() {
+        let mut store = CookieStore::default();
+        let url = test_util::url("http://example.com/foo/bar");
+        store.parse("cookie1=value1", &url).unwrap();
+        let frozen = store.freeze();
+        let shared = frozen.clone();
+        assert_eq!(1, shared.matches(&url).len());
+        assert_eq!(
+            "value1",
+            shared.get_for_url(&url, "cookie1").unwrap().value()
+        );
+
+        let mut thawed = frozen.thaw();
+        thawed.parse("cookie2=value2", &url).unwrap();
+        assert_eq!(2, thawed.matches(&url).len());
+        assert_eq!(1, frozen.matches(&url).len());
+    }
+}
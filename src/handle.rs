@@ -0,0 +1,766 @@
+use std::fmt;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+use crate::cookie::Cookie;
+use crate::cookie_store::{CookieStore, InsertResult, StoreResult};
+
+type Command = Box<dyn FnOnce(&mut CookieStore) + Send>;
+type AutoSaver = Box<dyn Fn(&CookieStore) -> StoreResult<()> + Send>;
+#[cfg(feature = "tokio")]
+type Persist = Arc<dyn Fn(&CookieStore) -> StoreResult<()> + Send + Sync>;
+
+/// Error returned by a [`Reply`] when the thread owning the [`CookieStore`] terminated before
+/// responding, e.g. due to a panic while persisting a store spawned via
+/// [`CookieStoreHandle::spawn_autosaving`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoreClosed;
+
+impl fmt::Display for StoreClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the thread owning the CookieStore has terminated")
+    }
+}
+
+impl std::error::Error for StoreClosed {}
+
+struct ReplyState<T> {
+    /// The response, or the payload of a panic raised while producing it
+    value: Option<thread::Result<T>>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+struct ReplyShared<T> {
+    state: Mutex<ReplyState<T>>,
+    ready: Condvar,
+}
+
+impl<T> ReplyShared<T> {
+    fn complete(&self, value: Option<thread::Result<T>>) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.value = value;
+        state.closed = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.ready.notify_all();
+    }
+}
+
+/// The sending half of a [`Reply`]; if dropped without responding, the `Reply` resolves to
+/// `Err(StoreClosed)`.
+struct Responder<T>(Option<Arc<ReplyShared<T>>>);
+
+impl<T> Responder<T> {
+    fn respond(mut self, value: thread::Result<T>) {
+        if let Some(shared) = self.0.take() {
+            shared.complete(Some(value));
+        }
+    }
+}
+
+impl<T> Drop for Responder<T> {
+    fn drop(&mut self) {
+        if let Some(shared) = self.0.take() {
+            shared.complete(None);
+        }
+    }
+}
+
+/// The eventual response to a request made through a [`CookieStoreHandle`]. The response may be
+/// retrieved by blocking on [`Reply::wait`], or by `.await`ing the `Reply` from any async
+/// runtime.
+pub struct Reply<T>(Arc<ReplyShared<T>>);
+
+impl<T> Reply<T> {
+    fn new() -> (Responder<T>, Reply<T>) {
+        let shared = Arc::new(ReplyShared {
+            state: Mutex::new(ReplyState {
+                value: None,
+                closed: false,
+                waker: None,
+            }),
+            ready: Condvar::new(),
+        });
+        (Responder(Some(Arc::clone(&shared))), Reply(shared))
+    }
+
+    /// Block the current thread until the response is available
+    pub fn wait(self) -> Result<T, StoreClosed> {
+        let mut state = self.0.state.lock().unwrap_or_else(|e| e.into_inner());
+        while !state.closed {
+            state = self.0.ready.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        Reply::resolve(&mut state)
+    }
+
+    /// Take the response from the completed `state`, resuming any panic raised while producing
+    /// it on the current thread
+    fn resolve(state: &mut ReplyState<T>) -> Result<T, StoreClosed> {
+        match state.value.take() {
+            Some(Ok(value)) => Ok(value),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => Err(StoreClosed),
+        }
+    }
+}
+
+impl<T> Future for Reply<T> {
+    type Output = Result<T, StoreClosed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.0.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.closed {
+            Poll::Ready(Reply::resolve(&mut state))
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> fmt::Debug for Reply<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reply").finish_non_exhaustive()
+    }
+}
+
+/// When a store spawned via [`CookieStoreHandle::spawn_autosaving`] is persisted: once either
+/// limit is reached, whichever comes first, so that a store changing at a high rate is not
+/// persisted on every change, yet at most a bounded number of changes (or, once changes cease, a
+/// bounded time) is lost should the process exit uncleanly. The default policy persists the
+/// store only when the owning thread exits.
+#[derive(PartialEq, Clone, Copy, Debug, Eq, Default)]
+pub struct AutoSave {
+    /// If set, the store is persisted once this many changes (see [`CookieStore::generation`])
+    /// have been made since it was last persisted
+    pub max_changes: Option<u64>,
+    /// If set, the store is persisted once no changes have been made for this long
+    pub quiescence: Option<Duration>,
+}
+
+impl AutoSave {
+    /// Persist the store after `max_changes` changes
+    pub fn with_max_changes(self, max_changes: u64) -> AutoSave {
+        AutoSave {
+            max_changes: Some(max_changes),
+            ..self
+        }
+    }
+
+    /// Persist the store once it has not changed for `quiescence`
+    pub fn with_quiescence(self, quiescence: Duration) -> AutoSave {
+        AutoSave {
+            quiescence: Some(quiescence),
+            ..self
+        }
+    }
+}
+
+/// Persists a store on behalf of its owning thread, per an [`AutoSave`] policy
+struct AutoSaving {
+    policy: AutoSave,
+    persist: AutoSaver,
+    /// The generation of the store when last persisted
+    saved_generation: u64,
+    /// The time of the last change made to the store
+    changed_at: Instant,
+}
+
+impl AutoSaving {
+    /// The time remaining until the store is to be persisted due to quiescence, if dirty
+    fn timeout(&self, cookie_store: &CookieStore) -> Option<Duration> {
+        self.policy
+            .quiescence
+            .filter(|_| cookie_store.is_dirty())
+            .map(|quiescence| quiescence.saturating_sub(self.changed_at.elapsed()))
+    }
+
+    /// Note any change made to the store by the last command, persisting it if the maximum
+    /// number of changes is reached
+    fn changed(&mut self, cookie_store: &mut CookieStore, generation: u64) {
+        if cookie_store.generation() != generation {
+            self.changed_at = Instant::now();
+        }
+        // a command replacing the store (e.g. reloading it) may reset its generation
+        self.saved_generation = self.saved_generation.min(cookie_store.generation());
+        let changes = cookie_store.generation() - self.saved_generation;
+        if self.policy.max_changes.map_or(false, |max| changes >= max) {
+            self.save(cookie_store);
+        }
+    }
+
+    /// Persist the store, if dirty
+    fn save(&mut self, cookie_store: &mut CookieStore) {
+        if !cookie_store.is_dirty() {
+            return;
+        }
+        match (self.persist)(cookie_store) {
+            Ok(()) => {
+                cookie_store.mark_clean();
+                self.saved_generation = cookie_store.generation();
+            }
+            Err(e) => {
+                warn!("failed to persist CookieStore: {}", e);
+                // retry after a further period of quiescence, rather than immediately
+                self.changed_at = Instant::now();
+            }
+        }
+    }
+}
+
+/// A handle to a [`CookieStore`] owned by a dedicated thread. Requests made through the handle
+/// are sent to that thread over a channel and applied to the store in the order received, with
+/// the results delivered through a [`Reply`]. This avoids sharing the store behind a lock, at the
+/// cost of copying cookies out of the store (e.g. [`CookieStoreHandle::matches`] returns owned
+/// cookies).
+///
+/// The handle may be cheaply cloned; the owning thread exits once all handles have been dropped.
+#[derive(Clone)]
+pub struct CookieStoreHandle {
+    sender: mpsc::Sender<Command>,
+}
+
+impl CookieStoreHandle {
+    /// Move `cookie_store` onto a newly spawned thread, returning a handle to it.
+    pub fn spawn(cookie_store: CookieStore) -> CookieStoreHandle {
+        let (sender, receiver) = mpsc::channel::<Command>();
+        thread::spawn(move || {
+            let mut cookie_store = cookie_store;
+            for command in receiver {
+                command(&mut cookie_store);
+            }
+            debug!("all CookieStoreHandles dropped; shutting down");
+        });
+        CookieStoreHandle { sender }
+    }
+
+    /// As [`CookieStoreHandle::spawn`], with the owning thread persisting the store with
+    /// `persist` as dictated by `policy`, and when all handles have been dropped. The store is
+    /// only persisted if dirty (see [`CookieStore::is_dirty`]), and is marked clean once
+    /// `persist` succeeds; failures are logged, and retried once the policy next dictates.
+    pub fn spawn_autosaving<F>(
+        cookie_store: CookieStore,
+        policy: AutoSave,
+        persist: F,
+    ) -> CookieStoreHandle
+    where
+        F: Fn(&CookieStore) -> StoreResult<()> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel::<Command>();
+        thread::spawn(move || {
+            let mut cookie_store = cookie_store;
+            let mut autosaving = AutoSaving {
+                policy,
+                persist: Box::new(persist),
+                saved_generation: cookie_store.generation(),
+                changed_at: Instant::now(),
+            };
+            loop {
+                let command = match autosaving.timeout(&cookie_store) {
+                    Some(timeout) => match receiver.recv_timeout(timeout) {
+                        Ok(command) => command,
+                        Err(mpsc::RecvTimeoutError::Timeout) => {
+                            autosaving.save(&mut cookie_store);
+                            continue;
+                        }
+                        Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    },
+                    None => match receiver.recv() {
+                        Ok(command) => command,
+                        Err(mpsc::RecvError) => break,
+                    },
+                };
+                let generation = cookie_store.generation();
+                command(&mut cookie_store);
+                autosaving.changed(&mut cookie_store, generation);
+            }
+            debug!("all CookieStoreHandles dropped; shutting down");
+            autosaving.save(&mut cookie_store);
+        });
+        CookieStoreHandle { sender }
+    }
+
+    /// Run `f` with exclusive access to the store on the owning thread, replying with its result.
+    /// All other request methods are implemented in terms of this method.
+    ///
+    /// Should `f` panic, the panic is caught on the owning thread and resumed on the thread
+    /// retrieving the `Reply` (via [`Reply::wait`] or `.await`); the store remains available to
+    /// all handles, including any changes `f` made to it before panicking.
+    pub fn with_store<F, T>(&self, f: F) -> Reply<T>
+    where
+        F: FnOnce(&mut CookieStore) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (responder, reply) = Reply::new();
+        // if the owning thread has terminated, the command (and thus the responder) is dropped,
+        // resolving the reply to `Err(StoreClosed)`
+        let _ = self.sender.send(Box::new(move |cookie_store| {
+            responder.respond(panic::catch_unwind(AssertUnwindSafe(|| f(cookie_store))))
+        }));
+        reply
+    }
+
+    /// Insert `cookie`, received from `request_url`, into the store; see [`CookieStore::insert`].
+    pub fn insert(&self, cookie: Cookie<'static>, request_url: Url) -> Reply<InsertResult> {
+        self.with_store(move |cookie_store| cookie_store.insert(cookie, &request_url))
+    }
+
+    /// Insert the `cookie::Cookie` (from the `cookie` crate) `cookie`, received from
+    /// `request_url`, into the store; see [`CookieStore::insert_raw`].
+    pub fn insert_raw(
+        &self,
+        cookie: crate::RawCookie<'static>,
+        request_url: Url,
+    ) -> Reply<InsertResult> {
+        self.with_store(move |cookie_store| cookie_store.insert_raw(&cookie, &request_url))
+    }
+
+    /// Store the `cookies` received from `url`; see [`CookieStore::store_response_cookies`].
+    pub fn store_response_cookies(
+        &self,
+        cookies: Vec<crate::RawCookie<'static>>,
+        url: Url,
+    ) -> Reply<()> {
+        self.with_store(move |cookie_store| {
+            cookie_store.store_response_cookies(cookies.into_iter(), &url)
+        })
+    }
+
+    /// Copies of the cookies matching `request_url`; see [`CookieStore::matches`]. If the store
+    /// purges idle cookies (see [`CookieStore::with_max_idle`]), the cookies are recorded as
+    /// sent via [`CookieStore::touch`].
+    pub fn matches(&self, request_url: Url) -> Reply<Vec<Cookie<'static>>> {
+        self.with_store(move |cookie_store| {
+            if cookie_store.tracks_access() {
+                cookie_store.touch(&request_url);
+            }
+            cookie_store
+                .matches(&request_url)
+                .into_iter()
+                .cloned()
+                .collect()
+        })
+    }
+
+    /// Remove all __expired__ cookies from the store, replying with the number removed; see
+    /// [`CookieStore::purge_expired`].
+    pub fn purge_expired(&self) -> Reply<usize> {
+        self.with_store(|cookie_store| cookie_store.purge_expired())
+    }
+
+    /// Persist the store by running `save` against it on the owning thread, e.g. with one of
+    /// the [`crate::serde`] `save` functions.
+    pub fn save<F>(&self, save: F) -> Reply<StoreResult<()>>
+    where
+        F: FnOnce(&CookieStore) -> StoreResult<()> + Send + 'static,
+    {
+        self.with_store(move |cookie_store| save(cookie_store))
+    }
+
+    /// Persist the store to `writer` in JSON format, as per [`crate::serde::json::save`].
+    /// Requires feature `serde_json`.
+    #[cfg(feature = "serde_json")]
+    pub fn save_json<W>(&self, mut writer: W) -> Reply<StoreResult<()>>
+    where
+        W: std::io::Write + Send + 'static,
+    {
+        self.save(move |cookie_store| crate::serde::json::save(cookie_store, &mut writer))
+    }
+
+    /// Spawn a task on the current [tokio](https://crates.io/crates/tokio) runtime which purges
+    /// __expired__ cookies from the store every `interval`; see [`CookieStore::purge_expired`].
+    /// The task holds a handle to the store, keeping the owning thread alive until the task is
+    /// aborted via the returned `JoinHandle`. Requires feature `tokio`.
+    ///
+    /// # Panics
+    /// If called outside of a tokio runtime, or if `interval` is zero.
+    #[cfg(feature = "tokio")]
+    pub fn spawn_gc(&self, interval: std::time::Duration) -> tokio::task::JoinHandle<()> {
+        self.spawn_gc_inner(interval, None)
+    }
+
+    /// As [`CookieStoreHandle::spawn_gc`], additionally persisting the store with `persist`
+    /// after each purge which leaves the store dirty (see [`CookieStore::is_dirty`]), whether
+    /// due to expired cookies being purged or to any other change since the store was last
+    /// persisted. The store is marked clean once `persist` succeeds; failures are logged, and
+    /// retried after the next interval. Requires feature `tokio`.
+    #[cfg(feature = "tokio")]
+    pub fn spawn_gc_with_persist<F>(
+        &self,
+        interval: std::time::Duration,
+        persist: F,
+    ) -> tokio::task::JoinHandle<()>
+    where
+        F: Fn(&CookieStore) -> StoreResult<()> + Send + Sync + 'static,
+    {
+        self.spawn_gc_inner(interval, Some(Arc::new(persist)))
+    }
+
+    #[cfg(feature = "tokio")]
+    fn spawn_gc_inner(
+        &self,
+        interval: std::time::Duration,
+        persist: Option<Persist>,
+    ) -> tokio::task::JoinHandle<()> {
+        let handle = self.clone();
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        tokio::spawn(async move {
+            // the first tick completes immediately
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match handle.collect_garbage(persist.clone()).await {
+                    Ok(purged) => debug!("purged {} expired cookies", purged),
+                    Err(StoreClosed) => break,
+                }
+            }
+        })
+    }
+
+    /// A single pass of the task spawned by [`CookieStoreHandle::spawn_gc`], resolving to the
+    /// number of cookies purged
+    #[cfg(feature = "tokio")]
+    fn collect_garbage(&self, persist: Option<Persist>) -> Reply<usize> {
+        self.with_store(move |cookie_store| {
+            let purged = cookie_store.purge_expired();
+            if let Some(persist) = persist.filter(|_| cookie_store.is_dirty()) {
+                match persist(cookie_store) {
+                    Ok(()) => cookie_store.mark_clean(),
+                    Err(e) => warn!("failed to persist CookieStore: {}", e),
+                }
+            }
+            purged
+        })
+    }
+}
+
+impl fmt::Debug for CookieStoreHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CookieStoreHandle").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CookieStoreHandle;
+    use crate::cookie_store::{CookieStore, StoreAction};
+    use crate::test_util as test_utils;
+
+    #[test]
+    fn insert_and_matches() {
+        let handle = CookieStoreHandle::spawn(CookieStore::default());
+        let url = test_utils::url("http://example.com/foo/bar");
+        let other = handle.clone();
+        assert_eq!(
+            Ok(Ok(StoreAction::Inserted)),
+            other
+                .insert(
+                    test_utils::make_cookie(
+                        "cookie1=value1",
+                        "http://example.com/foo/bar",
+                        None,
+                        None
+                    ),
+                    url.clone()
+                )
+                .wait()
+        );
+        let mut set_cookies = vec![crate::RawCookie::parse("cookie2=value2").unwrap()];
+        set_cookies.push(crate::RawCookie::parse("cookie3=value3; Path=/bus").unwrap());
+        handle
+            .store_response_cookies(set_cookies, url.clone())
+            .wait()
+            .unwrap();
+
+        let mut values = handle
+            .matches(url)
+            .wait()
+            .unwrap()
+            .iter()
+            .map(|c| c.value().to_owned())
+            .collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec!["value1", "value2"]);
+        assert_eq!(
+            Ok(3),
+            handle.with_store(|store| store.iter_any().count()).wait()
+        );
+    }
+
+    #[test]
+    fn pass_through() {
+        let handle = CookieStoreHandle::spawn(CookieStore::default());
+        let url = test_utils::url("http://example.com/");
+        let raw = |s: &str| crate::RawCookie::parse(s.to_owned()).unwrap();
+        assert_eq!(
+            Ok(Ok(StoreAction::Inserted)),
+            handle
+                .insert_raw(raw("a=1; Max-Age=3600"), url.clone())
+                .wait()
+        );
+        handle
+            .insert_raw(raw("b=2"), url.clone())
+            .wait()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            Ok(Ok(StoreAction::ExpiredExisting)),
+            handle.insert_raw(raw("b=2; Max-Age=0"), url.clone()).wait()
+        );
+        assert_eq!(Ok(1), handle.purge_expired().wait());
+        assert_eq!(Ok(0), handle.purge_expired().wait());
+
+        #[cfg(feature = "serde_json")]
+        {
+            use std::io::Write;
+            use std::sync::{Arc, Mutex};
+
+            #[derive(Clone, Default)]
+            struct Shared(Arc<Mutex<Vec<u8>>>);
+
+            impl Write for Shared {
+                fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                    self.0.lock().unwrap().write(buf)
+                }
+
+                fn flush(&mut self) -> std::io::Result<()> {
+                    Ok(())
+                }
+            }
+
+            let writer = Shared::default();
+            handle.save_json(writer.clone()).wait().unwrap().unwrap();
+            let saved = writer.0.lock().unwrap();
+            let loaded = crate::serde::json::load(&saved[..]).unwrap();
+            assert_eq!("1", loaded.get("example.com", "/", "a").unwrap().value());
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn spawn_gc() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        let now = Arc::new(Mutex::new(time::OffsetDateTime::now_utc()));
+        let clock = {
+            let now = Arc::clone(&now);
+            move || *now.lock().unwrap()
+        };
+        let url = test_utils::url("http://example.com/");
+        let mut store = CookieStore::default().with_clock(clock);
+        store.parse("cookie1=1; Max-Age=60", &url).unwrap();
+        store.parse("cookie2=2", &url).unwrap();
+        store.mark_clean();
+        let handle = CookieStoreHandle::spawn(store);
+
+        let persisted = Arc::new(AtomicUsize::new(0));
+        let notify = Arc::new(tokio::sync::Notify::new());
+        let persist: super::Persist = {
+            let persisted = Arc::clone(&persisted);
+            let notify = Arc::clone(&notify);
+            Arc::new(move |cookie_store: &CookieStore| {
+                persisted.store(cookie_store.len(), Ordering::SeqCst);
+                notify.notify_one();
+                Ok(())
+            })
+        };
+        // nothing has expired, so the store is not persisted
+        assert_eq!(
+            Ok(0),
+            handle.collect_garbage(Some(Arc::clone(&persist))).wait()
+        );
+        assert_eq!(0, persisted.load(Ordering::SeqCst));
+
+        *now.lock().unwrap() += time::Duration::minutes(2);
+        assert_eq!(
+            Ok(1),
+            handle.collect_garbage(Some(Arc::clone(&persist))).wait()
+        );
+        assert_eq!(1, persisted.load(Ordering::SeqCst));
+        assert_eq!(
+            Ok((1, false)),
+            handle
+                .with_store(|store| (store.len(), store.is_dirty()))
+                .wait()
+        );
+
+        // the spawned task persists the store once a change leaves it dirty
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let _runtime = runtime.enter();
+        let gc = handle.spawn_gc_inner(Duration::from_millis(5), Some(persist));
+        handle
+            .with_store(move |store| store.parse("cookie3=3", &url).unwrap())
+            .wait()
+            .unwrap();
+        while persisted.load(Ordering::SeqCst) != 2 {
+            runtime.block_on(notify.notified());
+        }
+        gc.abort();
+    }
+
+    #[test]
+    fn spawn_autosaving() {
+        use super::AutoSave;
+        use std::sync::mpsc::{self, TryRecvError};
+        use std::time::Duration;
+
+        let url = test_utils::url("http://example.com/");
+        // the owning thread sends the number of cookies persisted on each save, and drops the
+        // sender when it exits
+        let spawn = |policy| {
+            let (saves, saved) = mpsc::channel();
+            let handle =
+                CookieStoreHandle::spawn_autosaving(CookieStore::default(), policy, move |store| {
+                    saves.send(store.len()).unwrap();
+                    Ok(())
+                });
+            (handle, saved)
+        };
+        let insert = |handle: &CookieStoreHandle, name: &str| {
+            let cookie = test_utils::make_cookie(name, "http://example.com/", None, None);
+            handle.insert(cookie, url.clone()).wait().unwrap().unwrap();
+        };
+        // commands are applied in order, so a reply to this follows any save due to prior commands
+        let is_dirty = |handle: &CookieStoreHandle| {
+            handle.with_store(|store| store.is_dirty()).wait().unwrap()
+        };
+
+        let (handle, saved) = spawn(AutoSave::default().with_max_changes(2));
+        insert(&handle, "cookie1=1");
+        assert!(is_dirty(&handle));
+        assert_eq!(Err(TryRecvError::Empty), saved.try_recv());
+        insert(&handle, "cookie2=2");
+        assert!(!is_dirty(&handle));
+        assert_eq!(Ok(2), saved.try_recv());
+        insert(&handle, "cookie3=3");
+        // the remaining change is persisted once all handles are dropped
+        drop(handle);
+        assert_eq!(Ok(3), saved.recv());
+        assert!(saved.recv().is_err());
+
+        let (handle, saved) = spawn(AutoSave::default().with_quiescence(Duration::from_millis(20)));
+        let batch_url = url.clone();
+        handle
+            .with_store(move |store| {
+                store.parse("cookie1=1", &batch_url).unwrap();
+                store.parse("cookie2=2", &batch_url).unwrap();
+            })
+            .wait()
+            .unwrap();
+        assert_eq!(Ok(2), saved.recv());
+        assert!(!is_dirty(&handle));
+        // an unchanged store is not persisted
+        drop(handle);
+        assert!(saved.recv().is_err());
+
+        // replacing the store resets its generation below that of the last save
+        let (handle, saved) = spawn(AutoSave::default().with_max_changes(2));
+        insert(&handle, "cookie1=1");
+        insert(&handle, "cookie2=2");
+        assert!(!is_dirty(&handle));
+        assert_eq!(Ok(2), saved.try_recv());
+        handle
+            .with_store(|store| *store = CookieStore::default())
+            .wait()
+            .unwrap();
+        insert(&handle, "cookie3=3");
+        assert!(is_dirty(&handle));
+        insert(&handle, "cookie4=4");
+        assert!(!is_dirty(&handle));
+        assert_eq!(Ok(2), saved.try_recv());
+    }
+
+    #[test]
+    fn matches_records_access() {
+        use std::sync::{Arc, Mutex};
+        use time::Duration;
+
+        let start = time::OffsetDateTime::now_utc();
+        let now = Arc::new(Mutex::new(start));
+        let clock = {
+            let now = Arc::clone(&now);
+            move || *now.lock().unwrap()
+        };
+        let url = test_utils::url("http://example.com/");
+        let mut store = CookieStore::default()
+            .with_clock(clock)
+            .with_max_idle(Duration::days(30));
+        store.parse("cookie1=1", &url).unwrap();
+        let handle = CookieStoreHandle::spawn(store);
+
+        *now.lock().unwrap() += Duration::days(1);
+        assert_eq!(1, handle.matches(url).wait().unwrap().len());
+        assert_eq!(
+            Ok(Some(start + Duration::days(1))),
+            handle
+                .with_store(|store| store
+                    .get("example.com", "/", "cookie1")
+                    .unwrap()
+                    .last_access_time)
+                .wait()
+        );
+    }
+
+    #[test]
+    fn panicking_closure() {
+        let handle = CookieStoreHandle::spawn(CookieStore::default());
+        let url = test_utils::url("http://example.com/");
+        handle
+            .with_store(move |store| store.parse("cookie1=1", &url).unwrap())
+            .wait()
+            .unwrap();
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            handle
+                .with_store(|_| -> () { panic!("intentional panic") })
+                .wait()
+        }));
+        assert_eq!(
+            Some(&"intentional panic"),
+            panicked.unwrap_err().downcast_ref::<&str>()
+        );
+        // the store remains available to the handle and its clones
+        assert_eq!(Ok(1), handle.clone().with_store(|store| store.len()).wait());
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn save() {
+        use std::sync::{Arc, Mutex};
+
+        let handle = CookieStoreHandle::spawn(CookieStore::default());
+        let url = test_utils::url("http://example.com/foo/bar");
+        let cookie = test_utils::make_cookie(
+            "cookie1=value1",
+            "http://example.com/foo/bar",
+            None,
+            Some(60),
+        );
+        handle.insert(cookie, url).wait().unwrap().unwrap();
+
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let writer = Arc::clone(&output);
+        handle
+            .save(move |store| crate::serde::json::save(store, &mut *writer.lock().unwrap()))
+            .wait()
+            .unwrap()
+            .unwrap();
+        let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("cookie1=value1"), "{}", output);
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// A set of shared strings, used to deduplicate the domain, path, and name keys of a
+/// `CookieStore` (e.g. the path "/" is typically repeated across every domain in the store).
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Interner(HashSet<Arc<str>>);
+
+impl Interner {
+    /// Get the shared instance of `s`, adding it to the set if not already present
+    pub(crate) fn intern(&mut self, s: &str) -> Arc<str> {
+        match self.0.get(s) {
+            Some(interned) => Arc::clone(interned),
+            None => {
+                let interned: Arc<str> = Arc::from(s);
+                self.0.insert(Arc::clone(&interned));
+                interned
+            }
+        }
+    }
+
+    /// Drop `s` from the set if the set holds the only remaining reference to it
+    pub(crate) fn release(&mut self, s: &str) {
+        if self
+            .0
+            .get(s)
+            .map_or(false, |interned| Arc::strong_count(interned) == 1)
+        {
+            self.0.remove(s);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    /// Drop any strings no longer referenced outside the set
+    pub(crate) fn prune(&mut self) {
+        self.0.retain(|interned| Arc::strong_count(interned) > 1);
+    }
+
+    /// Drop any strings no longer referenced outside the set, and shrink the set's capacity
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.prune();
+        self.0.shrink_to_fit();
+    }
+
+    /// Estimated heap bytes used by the set and the strings it holds
+    pub(crate) fn heap_size(&self) -> usize {
+        self.0.capacity() * std::mem::size_of::<Arc<str>>()
+            + self
+                .0
+                .iter()
+                // strong + weak reference counts, plus the string itself
+                .map(|s| 2 * std::mem::size_of::<usize>() + s.len())
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interner;
+    use std::sync::Arc;
+
+    #[test]
+    fn intern_and_release() {
+        let mut interner = Interner::default();
+        let a = interner.intern("/");
+        let b = interner.intern("/");
+        assert!(Arc::ptr_eq(&a, &b));
+        drop(a);
+        interner.release("/");
+        assert!(Arc::ptr_eq(&b, &interner.intern("/")));
+        drop(b);
+        interner.release("/");
+        assert_eq!(0, interner.0.len());
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        let mut interner = Interner::default();
+        let a = interner.intern("a");
+        interner.intern("b");
+        interner.shrink_to_fit();
+        assert_eq!(1, interner.0.len());
+        assert!(Arc::ptr_eq(&a, &interner.intern("a")));
+    }
+}
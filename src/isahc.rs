@@ -0,0 +1,167 @@
+//! An interceptor connecting an [isahc](https://crates.io/crates/isahc) `HttpClient` to a
+//! [`CookieStore`]. Requires feature `isahc`
+//!
+//! ```no_run
+//! # fn main() -> Result<(), isahc::Error> {
+//! use std::sync::{Arc, RwLock};
+//! use cookie_store::{isahc::CookieInterceptor, CookieStore};
+//!
+//! let cookie_store = Arc::new(RwLock::new(CookieStore::default()));
+//! let client = isahc::HttpClient::builder()
+//!     .interceptor(CookieInterceptor::new(Arc::clone(&cookie_store)))
+//!     .build()?;
+//! client.get("https://example.com/")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The store may be shared behind any [`StoreLock`](crate::StoreLock), e.g. a `std::sync::Mutex`,
+//! or with feature `parking_lot`, a `parking_lot::RwLock`.
+//!
+//! __NB__: isahc follows redirects without invoking interceptors, so only the cookies of the final
+//! response are stored; disable isahc's redirect policy to store the cookies of each response.
+
+use std::sync::{Arc, RwLock};
+
+use ::isahc::interceptor::{Context, Interceptor, InterceptorFuture};
+use ::isahc::{AsyncBody, ResponseExt};
+use url::Url;
+
+use crate::cookie_store::CookieStore;
+use crate::lock::StoreLock;
+
+/// An isahc [`Interceptor`] adding the cookies of a [`CookieStore`], shared behind the
+/// [`StoreLock`] `L`, to each request, and storing the cookies of each response; see
+/// [`CookieStore::add_cookie_header`] and [`CookieStore::store_response_headers`]. If the store
+/// purges idle cookies (see [`CookieStore::with_max_idle`]), the cookies sent are recorded via
+/// [`CookieStore::touch`] once the response is received.
+#[derive(Debug)]
+pub struct CookieInterceptor<L = RwLock<CookieStore>> {
+    cookie_store: Arc<L>,
+}
+
+impl<L> CookieInterceptor<L> {
+    /// An interceptor sharing `cookie_store`
+    pub fn new(cookie_store: Arc<L>) -> CookieInterceptor<L> {
+        CookieInterceptor { cookie_store }
+    }
+
+    /// The `CookieStore` shared by this interceptor
+    pub fn cookie_store(&self) -> &Arc<L> {
+        &self.cookie_store
+    }
+}
+
+impl<L> Clone for CookieInterceptor<L> {
+    fn clone(&self) -> Self {
+        CookieInterceptor {
+            cookie_store: Arc::clone(&self.cookie_store),
+        }
+    }
+}
+
+impl<L: StoreLock + 'static> Interceptor for CookieInterceptor<L> {
+    type Err = ::isahc::Error;
+
+    fn intercept<'a>(
+        &'a self,
+        mut request: ::isahc::Request<AsyncBody>,
+        ctx: Context<'a>,
+    ) -> InterceptorFuture<'a, Self::Err> {
+        Box::pin(async move {
+            let url = Url::parse(&request.uri().to_string()).ok();
+            match url {
+                Some(ref url) => self.cookie_store.read_store(|cookie_store| {
+                    cookie_store.add_cookie_header(request.headers_mut(), url)
+                }),
+                None => debug!("unable to parse request uri '{}'", request.uri()),
+            }
+            let response = ctx.send(request).await?;
+            let response_url = response
+                .effective_uri()
+                .and_then(|uri| Url::parse(&uri.to_string()).ok())
+                .or_else(|| url.clone());
+            if let Some(response_url) = response_url {
+                self.cookie_store.write_store(|cookie_store| {
+                    // recorded here, as the cookies are added to the request under a shared lock
+                    if let Some(url) = url.filter(|_| cookie_store.tracks_access()) {
+                        cookie_store.touch(&url);
+                    }
+                    cookie_store.store_response_headers(response.headers(), &response_url)
+                });
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use super::CookieInterceptor;
+    use crate::cookie_store::CookieStore;
+    use crate::test_util;
+
+    #[test]
+    fn intercept() {
+        let (url, request) = test_util::serve_once("Set-Cookie: cookie2=value2\r\n");
+        let cookie_store = Arc::new(RwLock::new(CookieStore::default()));
+        cookie_store
+            .write()
+            .unwrap()
+            .parse("cookie1=value1", &url)
+            .unwrap();
+        let client = ::isahc::HttpClient::builder()
+            .interceptor(CookieInterceptor::new(Arc::clone(&cookie_store)))
+            .build()
+            .unwrap();
+        client.get(url.as_str()).unwrap();
+        assert!(request
+            .join()
+            .unwrap()
+            .to_ascii_lowercase()
+            .contains("\r\ncookie: cookie1=value1\r\n"));
+        let cookie_store = cookie_store.read().unwrap();
+        assert_eq!(2, cookie_store.len());
+        assert_eq!(
+            "value2",
+            cookie_store.get_for_url(&url, "cookie2").unwrap().value()
+        );
+    }
+
+    #[test]
+    fn intercept_records_access() {
+        use std::sync::Mutex;
+        use time::Duration;
+
+        let (url, request) = test_util::serve_once("");
+        let start = time::OffsetDateTime::now_utc();
+        let now = Arc::new(Mutex::new(start));
+        let clock = {
+            let now = Arc::clone(&now);
+            move || *now.lock().unwrap()
+        };
+        let mut cookie_store = CookieStore::default()
+            .with_clock(clock)
+            .with_max_idle(Duration::days(30));
+        cookie_store.parse("cookie1=value1", &url).unwrap();
+        let cookie_store = Arc::new(RwLock::new(cookie_store));
+        let client = ::isahc::HttpClient::builder()
+            .interceptor(CookieInterceptor::new(Arc::clone(&cookie_store)))
+            .build()
+            .unwrap();
+        *now.lock().unwrap() += Duration::days(1);
+        client.get(url.as_str()).unwrap();
+        request.join().unwrap();
+        assert_eq!(
+            Some(start + Duration::days(1)),
+            cookie_store
+                .read()
+                .unwrap()
+                .get_for_url(&url, "cookie1")
+                .unwrap()
+                .last_access_time
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! Key material held in the credential manager of the OS (e.g. the macOS Keychain, the Windows
+//! Credential Manager, or the Secret Service on Linux) via
+//! [keyring](https://crates.io/crates/keyring), so that applications persisting a `CookieStore`
+//! may protect it at rest (e.g. by encrypting the output of the [`crate::serde`] `save`
+//! functions) without managing keys themselves. The key may also be used with
+//! [`CookieStore::export_anonymized`](crate::CookieStore::export_anonymized).
+//!
+//! Which credential store is used is determined by the features enabled on `keyring`; without
+//! any, keys are held only in memory, by the `keyring` mock store.
+//! Requires feature `keyring`
+
+use keyring::Entry;
+
+use crate::cookie_store::StoreResult;
+
+/// The length in bytes of the keys managed by this module
+pub const KEY_LEN: usize = 32;
+
+/// The key held by the credential manager for `service` and `user`, generating and storing a new
+/// random key if none is held yet
+pub fn load_or_create_key(service: &str, user: &str) -> StoreResult<[u8; KEY_LEN]> {
+    load_or_create_key_in(&Entry::new(service, user)?)
+}
+
+/// As [`load_or_create_key`], for the credential `entry`, e.g. as created via
+/// [`Entry::new_with_target`]
+pub fn load_or_create_key_in(entry: &Entry) -> StoreResult<[u8; KEY_LEN]> {
+    match entry.get_secret() {
+        Ok(secret) => secret.as_slice().try_into().map_err(|_| {
+            format!(
+                "the credential holds a {} byte secret, rather than a {} byte key",
+                secret.len(),
+                KEY_LEN
+            )
+            .into()
+        }),
+        Err(keyring::Error::NoEntry) => {
+            let mut key = [0u8; KEY_LEN];
+            getrandom::getrandom(&mut key)?;
+            entry.set_secret(&key)?;
+            Ok(key)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Remove the key held by the credential manager for `service` and `user`, if any; data
+/// protected by the key is then unrecoverable.
+pub fn delete_key(service: &str, user: &str) -> StoreResult<()> {
+    match Entry::new(service, user)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use keyring::Entry;
+
+    use super::{load_or_create_key_in, KEY_LEN};
+
+    #[test]
+    fn load_or_create_key() {
+        // without a platform credential store, the mock store retains secrets per `Entry`
+        let entry = Entry::new("cookie_store", "test").unwrap();
+        let key = load_or_create_key_in(&entry).unwrap();
+        assert_ne!([0u8; KEY_LEN], key);
+        assert_eq!(key, load_or_create_key_in(&entry).unwrap());
+
+        entry.set_secret(b"short").unwrap();
+        assert!(load_or_create_key_in(&entry).is_err());
+    }
+}
@@ -10,23 +10,79 @@
 //! ## Feature flags
 #![doc = document_features::document_features!()]
 
-use idna;
-
 pub use ::cookie::{Cookie as RawCookie, ParseError as RawCookieParseError};
 
+#[macro_use]
+mod logging;
+
+#[cfg(feature = "anonymize")]
+mod anonymize;
+#[cfg(feature = "anonymize")]
+pub use crate::anonymize::AnonymizedCookie;
+#[cfg(feature = "async-lock")]
+mod async_store;
+#[cfg(feature = "async-lock")]
+pub use crate::async_store::AsyncCookieStore;
+#[cfg(feature = "attohttpc")]
+pub mod attohttpc;
+mod audit;
+pub use crate::audit::{AuditAction, AuditEntry};
+mod change_log;
+mod clock;
+pub use crate::clock::{Clock, SystemClock};
 mod cookie;
 pub use crate::cookie::Error as CookieError;
-pub use crate::cookie::{Cookie, CookieResult};
+pub use crate::cookie::{Cookie, CookieIdentity, CookieResult, ErrorContext, SourceScheme};
+mod cookie_date;
+pub use crate::cookie_date::DateParsing;
 mod cookie_domain;
 pub use crate::cookie_domain::CookieDomain;
 mod cookie_expiration;
 pub use crate::cookie_expiration::CookieExpiration;
 mod cookie_path;
 pub use crate::cookie_path::CookiePath;
+mod cookie_transform;
+pub use crate::cookie_transform::CookieTransform;
 mod cookie_store;
-pub use crate::cookie_store::{CookieStore, StoreAction};
+#[cfg(feature = "psl_download")]
+pub use crate::cookie_store::PUBLIC_SUFFIX_LIST_URL;
+pub use crate::cookie_store::{
+    CookieMatch, CookieStore, EvictionPolicy, IntoIter, MatchExclusion, MergeStrategy,
+    OverflowPolicy, OwnedStoreEvent, RequestCookie, StoreAction, StoreChanges, StoreDiff,
+    StoreEvent, StoreStats, StoreViolation, TtlPolicy, DEFAULT_MAX_COOKIE_HEADER_LEN,
+};
+mod domain_index;
+mod domain_rule;
+pub use crate::domain_rule::DomainRule;
+mod dot;
+mod frozen;
+pub use crate::frozen::FrozenCookieStore;
+mod handle;
+mod interner;
+pub use crate::handle::{AutoSave, CookieStoreHandle, Reply, StoreClosed};
+#[cfg(feature = "isahc")]
+pub mod isahc;
+#[cfg(feature = "keyring")]
+pub mod keyring;
+mod lock;
+pub use crate::lock::StoreLock;
+mod netscape;
+pub use crate::netscape::NetscapeParsing;
+#[cfg(feature = "proptest")]
+pub mod proptest;
+mod report;
+pub use crate::report::{ExpiryHistogram, StoreReport};
+mod request_context;
+pub use crate::request_context::RequestContext;
 #[cfg(feature = "serde")]
 pub mod serde;
+mod set_cookie_log;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+mod tombstone;
+pub use crate::tombstone::Tombstone;
+mod trustworthy_origins;
+pub use crate::trustworthy_origins::TrustworthyOrigins;
 mod utils;
 
 #[derive(Debug)]
@@ -51,15 +107,44 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 #[cfg(feature = "serde")]
 pub(crate) mod rfc3339_fmt {
+    use std::cell::Cell;
+
+    use crate::serde::TimestampFormat;
 
     pub(crate) const RFC3339_FORMAT: &[time::format_description::FormatItem] =
         time::macros::format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z");
 
+    thread_local! {
+        /// The format in which timestamps are serialized by the current thread
+        static TIMESTAMP_FORMAT: Cell<TimestampFormat> = Cell::new(TimestampFormat::default());
+    }
+
+    /// Restores the previous timestamp format of the current thread on drop, including when
+    /// unwinding from a panic
+    struct RestoreFormat(TimestampFormat);
+
+    impl Drop for RestoreFormat {
+        fn drop(&mut self) {
+            TIMESTAMP_FORMAT.with(|current| current.set(self.0));
+        }
+    }
+
+    /// Call `f`, serializing any timestamps in `format`; `f` must serialize eagerly on the current
+    /// thread, as other threads (or serialization deferred until after `f` returns) use the
+    /// default format
+    pub(crate) fn with_timestamp_format<T, F: FnOnce() -> T>(format: TimestampFormat, f: F) -> T {
+        let _restore = RestoreFormat(TIMESTAMP_FORMAT.with(|current| current.replace(format)));
+        f()
+    }
+
     pub(super) fn serialize<S>(t: &time::OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
         use serde::ser::Error;
+        if TIMESTAMP_FORMAT.with(Cell::get) == TimestampFormat::UnixSeconds {
+            return serializer.serialize_i64(t.unix_timestamp());
+        }
         // An explicit format string is used here, instead of time::format_description::well_known::Rfc3339, to explicitly
         // utilize the 'Z' terminator instead of +00:00 format for Zulu time.
         let s = t.format(&RFC3339_FORMAT).map_err(|e| {
@@ -72,20 +157,133 @@ pub(crate) mod rfc3339_fmt {
         serializer.serialize_str(&s)
     }
 
+    /// Deserializes a timestamp in either of the formats of [`TimestampFormat`]
+    struct TimestampVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for TimestampVisitor {
+        type Value = time::OffsetDateTime;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                formatter,
+                "an RFC3339 UTC datetime string or Unix timestamp"
+            )
+        }
+
+        fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<Self::Value, E> {
+            time::OffsetDateTime::parse(s, &time::format_description::well_known::Rfc3339).map_err(
+                |e| {
+                    E::custom(format!(
+                        "Could not parse string '{}' as RFC3339 UTC format: {}",
+                        s, e
+                    ))
+                },
+            )
+        }
+
+        fn visit_i64<E: serde::de::Error>(self, t: i64) -> Result<Self::Value, E> {
+            time::OffsetDateTime::from_unix_timestamp(t)
+                .map_err(|e| E::custom(format!("Could not parse '{}' as Unix timestamp: {}", t, e)))
+        }
+
+        fn visit_u64<E: serde::de::Error>(self, t: u64) -> Result<Self::Value, E> {
+            let t = i64::try_from(t)
+                .map_err(|_| E::custom(format!("Unix timestamp '{}' is out of range", t)))?;
+            self.visit_i64(t)
+        }
+    }
+
     pub(super) fn deserialize<'de, D>(t: D) -> Result<time::OffsetDateTime, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        use serde::{de::Error, Deserialize};
-
-        let s = String::deserialize(t)?;
-        time::OffsetDateTime::parse(&s, &time::format_description::well_known::Rfc3339).map_err(
-            |e| {
-                D::Error::custom(format!(
-                    "Could not parse string '{}' as RFC3339 UTC format: {}",
-                    s, e
-                ))
-            },
-        )
+        t.deserialize_any(TimestampVisitor)
+    }
+
+    /// The JSON Schema of a timestamp in either of the formats of [`TimestampFormat`]
+    #[cfg(feature = "schemars")]
+    pub(crate) struct Timestamp;
+
+    #[cfg(feature = "schemars")]
+    impl schemars::JsonSchema for Timestamp {
+        fn schema_name() -> String {
+            "Timestamp".to_owned()
+        }
+
+        fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+            use schemars::schema::{InstanceType, Metadata, SchemaObject, SubschemaValidation};
+
+            let format = |instance_type: InstanceType, format: &str| {
+                SchemaObject {
+                    instance_type: Some(instance_type.into()),
+                    format: Some(format.to_owned()),
+                    ..Default::default()
+                }
+                .into()
+            };
+            SchemaObject {
+                metadata: Some(Box::new(Metadata {
+                    description: Some(
+                        "An RFC3339 UTC datetime string or Unix timestamp".to_owned(),
+                    ),
+                    ..Default::default()
+                })),
+                subschemas: Some(Box::new(SubschemaValidation {
+                    any_of: Some(vec![
+                        format(InstanceType::String, "date-time"),
+                        format(InstanceType::Integer, "int64"),
+                    ]),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            }
+            .into()
+        }
+    }
+    /// As the parent module, for an optional datetime
+    pub(crate) mod option {
+        pub(crate) fn serialize<S>(
+            t: &Option<time::OffsetDateTime>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            struct Rfc3339<'a>(&'a time::OffsetDateTime);
+
+            impl serde::Serialize for Rfc3339<'_> {
+                fn serialize<S: serde::Serializer>(
+                    &self,
+                    serializer: S,
+                ) -> Result<S::Ok, S::Error> {
+                    super::serialize(self.0, serializer)
+                }
+            }
+
+            // serialized via `serialize_some`, so that formats distinguishing `Some` from the
+            // value itself (e.g. RON) may deserialize the `Option`
+            match t {
+                Some(t) => serializer.serialize_some(&Rfc3339(t)),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub(crate) fn deserialize<'de, D>(t: D) -> Result<Option<time::OffsetDateTime>, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            use serde::Deserialize;
+
+            /// A timestamp in either of the formats of [`TimestampFormat`]
+            struct Timestamp(time::OffsetDateTime);
+
+            impl<'de> Deserialize<'de> for Timestamp {
+                fn deserialize<D: serde::Deserializer<'de>>(t: D) -> Result<Self, D::Error> {
+                    super::deserialize(t).map(Timestamp)
+                }
+            }
+
+            Ok(Option::<Timestamp>::deserialize(t)?.map(|Timestamp(t)| t))
+        }
     }
 }
@@ -0,0 +1,99 @@
+//! Access to a [`CookieStore`] shared between threads behind a lock
+
+use std::sync::{Mutex, PoisonError, RwLock};
+
+use crate::cookie_store::CookieStore;
+
+/// A lock guarding a [`CookieStore`] shared between threads, e.g. by an
+/// `isahc::CookieInterceptor`. Implemented for the `Mutex` and `RwLock` of `std::sync`,
+/// recovering the store from a poisoned lock, and, with feature `parking_lot`, for the
+/// (non-poisoning) `Mutex` and `RwLock` of [parking_lot](https://crates.io/crates/parking_lot).
+pub trait StoreLock: Send + Sync {
+    /// Run `f` with shared access to the store
+    fn read_store<T, F: FnOnce(&CookieStore) -> T>(&self, f: F) -> T;
+
+    /// Run `f` with exclusive access to the store
+    fn write_store<T, F: FnOnce(&mut CookieStore) -> T>(&self, f: F) -> T;
+}
+
+impl StoreLock for Mutex<CookieStore> {
+    fn read_store<T, F: FnOnce(&CookieStore) -> T>(&self, f: F) -> T {
+        f(&self.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn write_store<T, F: FnOnce(&mut CookieStore) -> T>(&self, f: F) -> T {
+        f(&mut self.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl StoreLock for RwLock<CookieStore> {
+    fn read_store<T, F: FnOnce(&CookieStore) -> T>(&self, f: F) -> T {
+        f(&self.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    fn write_store<T, F: FnOnce(&mut CookieStore) -> T>(&self, f: F) -> T {
+        f(&mut self.write().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+#[cfg(feature = "parking_lot")]
+impl StoreLock for parking_lot::Mutex<CookieStore> {
+    fn read_store<T, F: FnOnce(&CookieStore) -> T>(&self, f: F) -> T {
+        f(&self.lock())
+    }
+
+    fn write_store<T, F: FnOnce(&mut CookieStore) -> T>(&self, f: F) -> T {
+        f(&mut self.lock())
+    }
+}
+
+#[cfg(feature = "parking_lot")]
+impl StoreLock for parking_lot::RwLock<CookieStore> {
+    fn read_store<T, F: FnOnce(&CookieStore) -> T>(&self, f: F) -> T {
+        f(&self.read())
+    }
+
+    fn write_store<T, F: FnOnce(&mut CookieStore) -> T>(&self, f: F) -> T {
+        f(&mut self.write())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex, RwLock};
+
+    use super::StoreLock;
+    use crate::cookie_store::CookieStore;
+    use crate::test_util;
+
+    fn exercise<L: StoreLock>(lock: &L) {
+        let url = test_util::url("http://example.com/");
+        lock.write_store(|store| store.parse("cookie1=1", &url))
+            .unwrap();
+        assert_eq!(1, lock.read_store(|store| store.matches(&url).len()));
+    }
+
+    #[test]
+    fn std_locks() {
+        exercise(&Mutex::new(CookieStore::default()));
+        let lock = Arc::new(RwLock::new(CookieStore::default()));
+        exercise(&*lock);
+
+        // a poisoned lock still yields the store
+        let poisoner = Arc::clone(&lock);
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.write().unwrap();
+            panic!("poison");
+        })
+        .join();
+        assert!(lock.is_poisoned());
+        assert_eq!(1, lock.read_store(|store| store.len()));
+    }
+
+    #[cfg(feature = "parking_lot")]
+    #[test]
+    fn parking_lot_locks() {
+        exercise(&parking_lot::Mutex::new(CookieStore::default()));
+        exercise(&parking_lot::RwLock::new(CookieStore::default()));
+    }
+}
@@ -0,0 +1,21 @@
+//! Logging via [log](https://crates.io/crates/log) with feature `log`; without it, the logging
+//! macros are no-ops, so that minimal builds carry no logging machinery. Arguments are still
+//! type-checked (but not evaluated into a message) in either case.
+
+macro_rules! debug {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::debug!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+macro_rules! warn {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::warn!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
@@ -0,0 +1,301 @@
+//! Import and export of cookies in the Netscape `cookies.txt` format, as used by e.g. curl and
+//! wget
+
+use std::io::{BufRead, Write};
+
+use cookie::Cookie as RawCookie;
+use url::Url;
+
+use crate::cookie::Cookie;
+use crate::cookie_domain::CookieDomain;
+use crate::cookie_expiration::CookieExpiration;
+use crate::cookie_store::{CookieStore, StoreResult};
+use crate::utils::Buffer;
+
+/// The header written by [`CookieStore::save_netscape`]
+const HEADER: &str = "# Netscape HTTP Cookie File";
+
+/// The domain prefix with which curl marks HttpOnly cookies
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+/// How strictly the Netscape `cookies.txt` format is parsed by
+/// [`CookieStore::load_netscape_with`]
+#[derive(PartialEq, Clone, Copy, Debug, Eq, Default)]
+pub enum NetscapeParsing {
+    /// Accept the variants of the format written in practice: a missing header comment (as
+    /// written by wget), fields separated by spaces rather than tabs, lowercase `true`/`false`
+    /// flags, and a missing trailing value field. Lines which still cannot be parsed are skipped.
+    #[default]
+    Lenient,
+    /// Accept only the format as written by [`CookieStore::save_netscape`] or curl: a header
+    /// comment, followed by lines of exactly seven tab-separated fields. Any line which cannot be
+    /// parsed is an error.
+    Strict,
+}
+
+/// Parse a `TRUE`/`FALSE` flag field
+fn parse_flag(flag: &str, parsing: NetscapeParsing) -> Result<bool, String> {
+    match flag {
+        "TRUE" => Ok(true),
+        "FALSE" => Ok(false),
+        _ if parsing == NetscapeParsing::Lenient && flag.eq_ignore_ascii_case("true") => Ok(true),
+        _ if parsing == NetscapeParsing::Lenient && flag.eq_ignore_ascii_case("false") => Ok(false),
+        _ => Err(format!("expected TRUE or FALSE, found '{}'", flag)),
+    }
+}
+
+/// Split `line` into its fields, supplying an empty value if a lenient `line` lacks one
+fn split_fields(line: &str, parsing: NetscapeParsing) -> Result<Vec<&str>, String> {
+    let mut fields = match parsing {
+        NetscapeParsing::Strict => line.split('\t').collect::<Vec<_>>(),
+        NetscapeParsing::Lenient if line.contains('\t') => line.split('\t').collect(),
+        // the value (the last field) may itself contain spaces
+        NetscapeParsing::Lenient => {
+            let mut fields = Vec::new();
+            let mut rest = line.trim_start();
+            while fields.len() < 6 && !rest.is_empty() {
+                let (field, remainder) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                fields.push(field);
+                rest = remainder.trim_start();
+            }
+            if !rest.is_empty() {
+                fields.push(rest);
+            }
+            fields
+        }
+    };
+    if parsing == NetscapeParsing::Lenient && fields.len() == 6 {
+        fields.push("");
+    }
+    if fields.len() != 7 {
+        return Err(format!("expected 7 fields, found {}", fields.len()));
+    }
+    Ok(fields)
+}
+
+/// Parse a single (non-comment) line of a `cookies.txt` file into a `Cookie`
+fn parse_line(line: &str, parsing: NetscapeParsing) -> Result<Cookie<'static>, String> {
+    let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+        Some(line) => (line, true),
+        None => (line, false),
+    };
+    let fields = split_fields(line, parsing)?;
+    let (domain, include_subdomains, path, secure, expires, name, value) = (
+        fields[0], fields[1], fields[2], fields[3], fields[4], fields[5], fields[6],
+    );
+    let include_subdomains = parse_flag(include_subdomains, parsing)?;
+    let secure = parse_flag(secure, parsing)?;
+    let expires = expires
+        .parse::<i64>()
+        .map_err(|e| format!("invalid expiry '{}': {}", expires, e))?;
+
+    let host = domain.trim_start_matches('.');
+    let scheme = if secure { "https" } else { "http" };
+    let url = Url::parse(&format!("{}://{}{}", scheme, host, path))
+        .map_err(|e| format!("invalid domain '{}' or path '{}': {}", domain, path, e))?;
+    let mut raw_cookie = RawCookie::new(name.to_owned(), value.to_owned());
+    raw_cookie.set_path(path.to_owned());
+    raw_cookie.set_secure(secure);
+    raw_cookie.set_http_only(http_only);
+    if include_subdomains {
+        raw_cookie.set_domain(host.to_owned());
+    }
+    if expires != 0 {
+        let expires = time::OffsetDateTime::from_unix_timestamp(expires)
+            .map_err(|e| format!("invalid expiry '{}': {}", expires, e))?;
+        raw_cookie.set_expires(expires);
+    }
+    Cookie::try_from_raw_cookie(&raw_cookie, &url)
+        .map(Cookie::into_owned)
+        .map_err(|e| e.to_string())
+}
+
+/// Render `cookie` as a line of a `cookies.txt` file
+fn to_line(cookie: &Cookie<'_>) -> String {
+    let (domain, include_subdomains) = match cookie.domain {
+        CookieDomain::Suffix(ref suffix) => (format!(".{}", suffix), "TRUE"),
+        _ => (String::from(&cookie.domain), "FALSE"),
+    };
+    let flag = |set: Option<bool>| {
+        if set.unwrap_or(false) {
+            "TRUE"
+        } else {
+            "FALSE"
+        }
+    };
+    let expires = match cookie.expires {
+        CookieExpiration::AtUtc(expires) => expires.unix_timestamp(),
+        CookieExpiration::SessionEnd => 0,
+    };
+    format!(
+        "{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        if cookie.http_only().unwrap_or(false) {
+            HTTP_ONLY_PREFIX
+        } else {
+            ""
+        },
+        domain,
+        include_subdomains,
+        &*cookie.path,
+        flag(cookie.secure()),
+        expires,
+        cookie.name(),
+        cookie.value()
+    )
+}
+
+impl CookieStore {
+    /// Load __unexpired__ cookies from a Netscape `cookies.txt` file, as written by e.g. curl or
+    /// wget, tolerating the variants of the format described by [`NetscapeParsing::Lenient`]
+    pub fn load_netscape<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
+        CookieStore::load_netscape_with(reader, NetscapeParsing::default())
+    }
+
+    /// Load __unexpired__ cookies from a Netscape `cookies.txt` file, parsed per `parsing`. Lines
+    /// beginning with `#HttpOnly_` (as written by curl) are loaded as HttpOnly cookies; other
+    /// lines beginning with `#`, and empty lines, are ignored. An expiry of `0` denotes a
+    /// __non-persistent__ cookie.
+    pub fn load_netscape_with<R: BufRead>(
+        reader: R,
+        parsing: NetscapeParsing,
+    ) -> StoreResult<CookieStore> {
+        let mut cookies = Vec::new();
+        let mut header_seen = false;
+        for (i, line) in reader.lines().enumerate() {
+            let line = Buffer::from(line?);
+            if line.trim().is_empty() {
+                continue;
+            }
+            if line.starts_with('#') && !line.starts_with(HTTP_ONLY_PREFIX) {
+                header_seen = header_seen
+                    || line.eq_ignore_ascii_case(HEADER)
+                    || line.eq_ignore_ascii_case("# HTTP Cookie File");
+                continue;
+            }
+            if parsing == NetscapeParsing::Strict && !header_seen {
+                return Err(format!(
+                    "invalid Netscape cookie file: expected '{}' header before line {}",
+                    HEADER,
+                    i + 1
+                )
+                .into());
+            }
+            match parse_line(&line, parsing) {
+                Ok(cookie) => cookies.push(Ok::<_, crate::Error>(cookie)),
+                Err(e) if parsing == NetscapeParsing::Strict => {
+                    return Err(
+                        format!("invalid Netscape cookie file line {}: {}", i + 1, e).into(),
+                    );
+                }
+                Err(e) => debug!("skipping Netscape cookie file line {}: {}", i + 1, e),
+            }
+        }
+        CookieStore::from_cookies(cookies, false)
+    }
+
+    /// Write the __unexpired__ and __persistent__ cookies in the store to `writer` as a Netscape
+    /// `cookies.txt` file, which may be read by e.g. curl (via `--cookie`) or wget (via
+    /// `--load-cookies`). HttpOnly cookies are written with the `#HttpOnly_` prefix of curl.
+    pub fn save_netscape<W: Write>(&self, writer: &mut W) -> StoreResult<()> {
+        writeln!(writer, "{}", HEADER)?;
+        for cookie in self.iter_saved() {
+            writeln!(writer, "{}", Buffer::from(to_line(cookie)).as_str())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NetscapeParsing;
+    use crate::cookie_store::CookieStore;
+    use crate::test_util;
+
+    const CURL: &str = "# Netscape HTTP Cookie File\n\
+                        # https://curl.se/docs/http-cookies.html\n\
+                        \n\
+                        .example.com\tTRUE\t/\tFALSE\t4120936717\tcookie1\tvalue1\n\
+                        #HttpOnly_www.example.com\tFALSE\t/foo\tTRUE\t4120936717\tcookie2\tvalue2\n\
+                        www.example.com\tFALSE\t/\tFALSE\t946684800\texpired\tvalue3\n";
+
+    #[test]
+    fn load_netscape() {
+        for parsing in [NetscapeParsing::Strict, NetscapeParsing::Lenient] {
+            let store = CookieStore::load_netscape_with(CURL.as_bytes(), parsing).unwrap();
+            assert_eq!(2, store.len());
+            let cookie1 = store.get("example.com", "/", "cookie1").unwrap();
+            assert_eq!("value1", cookie1.value());
+            assert_eq!(
+                time::macros::datetime!(2100-08-03 00:38:37 UTC),
+                match cookie1.expires {
+                    crate::CookieExpiration::AtUtc(expires) => expires,
+                    _ => panic!("expected a persistent cookie"),
+                }
+            );
+            let cookie2 = store.get("www.example.com", "/foo", "cookie2").unwrap();
+            assert_eq!(Some(true), cookie2.http_only());
+            assert_eq!(Some(true), cookie2.secure());
+            assert!(matches!(cookie2.domain, crate::CookieDomain::HostOnly(_)));
+        }
+
+        let url = test_util::url("https://www.example.com/foo/bar");
+        let store = CookieStore::load_netscape(CURL.as_bytes()).unwrap();
+        let mut values = store
+            .matches(&url)
+            .iter()
+            .map(|c| c.value())
+            .collect::<Vec<_>>();
+        values.sort_unstable();
+        assert_eq!(vec!["value1", "value2"], values);
+    }
+
+    #[test]
+    fn load_netscape_variants() {
+        // wget: no header; spaces rather than tabs; lowercase flags; a missing value field
+        let wget = "example.com  true  /  false  4120936717  cookie1  value with spaces\n\
+                    www.example.com\tFALSE\t/\tFALSE\t0\tsession\n\
+                    garbage\n";
+        let store = CookieStore::load_netscape(wget.as_bytes()).unwrap();
+        assert_eq!(2, store.len());
+        assert_eq!(
+            "value with spaces",
+            store.get("example.com", "/", "cookie1").unwrap().value()
+        );
+        let session = store.get("www.example.com", "/", "session").unwrap();
+        assert_eq!("", session.value());
+        assert!(!session.is_persistent());
+
+        assert!(CookieStore::load_netscape_with(wget.as_bytes(), NetscapeParsing::Strict).is_err());
+        let garbage = format!("{}garbage\n", CURL);
+        assert!(
+            CookieStore::load_netscape_with(garbage.as_bytes(), NetscapeParsing::Strict).is_err()
+        );
+        assert_eq!(
+            2,
+            CookieStore::load_netscape(garbage.as_bytes())
+                .unwrap()
+                .len()
+        );
+    }
+
+    #[test]
+    fn save_netscape() {
+        let store = CookieStore::load_netscape(CURL.as_bytes()).unwrap();
+        let mut saved = Vec::new();
+        store.save_netscape(&mut saved).unwrap();
+        let saved = String::from_utf8(saved).unwrap();
+        let mut lines = saved.lines().collect::<Vec<_>>();
+        assert_eq!("# Netscape HTTP Cookie File", lines.remove(0));
+        lines.sort_unstable();
+        assert_eq!(
+            vec![
+                "#HttpOnly_www.example.com\tFALSE\t/foo\tTRUE\t4120936717\tcookie2\tvalue2",
+                ".example.com\tTRUE\t/\tFALSE\t4120936717\tcookie1\tvalue1",
+            ],
+            lines
+        );
+        let reloaded =
+            CookieStore::load_netscape_with(saved.as_bytes(), NetscapeParsing::Strict).unwrap();
+        assert!(store.diff(&reloaded).is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+//! [proptest](https://crates.io/crates/proptest) strategies generating cookies, request-uris, and
+//! [`CookieStore`]s, for property testing of cookie handling. Requires feature `proptest`
+//!
+//! The `*_edge_case` strategies deliberately generate inputs exercising the less common corners
+//! of [RFC6265](https://datatracker.ietf.org/doc/html/rfc6265) (e.g. mixed-case and
+//! fully-qualified domains, IP addresses, empty or malformed attributes), which a `CookieStore`
+//! may reject.
+
+use ::proptest::collection::vec;
+use ::proptest::option;
+use ::proptest::prelude::*;
+use url::Url;
+
+use crate::cookie::Cookie;
+use crate::cookie_store::CookieStore;
+
+/// A host name of between one and three lowercase labels, e.g. `www.example.com`
+pub fn domain() -> impl Strategy<Value = String> {
+    vec("[a-z][a-z0-9]{0,7}", 1..=3).prop_map(|labels| labels.join("."))
+}
+
+/// A host, including edge cases: mixed-case and fully-qualified domains, internationalized
+/// domains, IP addresses, and `localhost`
+pub fn domain_edge_case() -> impl Strategy<Value = String> {
+    prop_oneof![
+        4 => domain(),
+        1 => domain().prop_map(|d| d.to_ascii_uppercase()),
+        1 => domain().prop_map(|d| format!("{}.", d)),
+        1 => domain().prop_map(|d| format!("bücher.{}", d)),
+        1 => Just(String::from("localhost")),
+        1 => any::<[u8; 4]>().prop_map(|ip| std::net::Ipv4Addr::from(ip).to_string()),
+        1 => Just(String::from("[::1]")),
+    ]
+}
+
+/// An absolute path of up to three segments, e.g. `/foo/bar`
+pub fn path() -> impl Strategy<Value = String> {
+    vec("[a-z0-9_~-]{1,8}", 0..=3).prop_map(|segments| format!("/{}", segments.join("/")))
+}
+
+/// An `http` or `https` request-uri on one of `hosts`
+pub fn url_on<S>(hosts: S) -> impl Strategy<Value = Url>
+where
+    S: Strategy<Value = String>,
+{
+    (any::<bool>(), hosts, path(), any::<bool>()).prop_filter_map(
+        "request-uri must be valid",
+        |(https, host, path, trailing_slash)| {
+            let scheme = if https { "https" } else { "http" };
+            let slash = if trailing_slash && path != "/" {
+                "/"
+            } else {
+                ""
+            };
+            Url::parse(&format!("{}://{}{}{}", scheme, host, path, slash)).ok()
+        },
+    )
+}
+
+/// An `http` or `https` request-uri, e.g. `https://www.example.com/foo/bar`
+pub fn url() -> impl Strategy<Value = Url> {
+    url_on(domain())
+}
+
+/// A request-uri on any host of [`domain_edge_case`]
+pub fn url_edge_case() -> impl Strategy<Value = Url> {
+    url_on(domain_edge_case())
+}
+
+/// A valid `Set-Cookie` header value received from `url`, with a Domain attribute (if any)
+/// matching `url`
+pub fn set_cookie_for(url: &Url) -> impl Strategy<Value = String> {
+    let host = url.host_str().unwrap_or_default().to_owned();
+    let suffixes = host
+        .match_indices('.')
+        .map(|(i, _)| host[i + 1..].to_owned())
+        .chain(std::iter::once(host.clone()))
+        .collect::<Vec<_>>();
+    (
+        "[A-Za-z0-9_-]{1,12}",
+        "[A-Za-z0-9!#$%&'()*+./:<=>?@^_`{|}~-]{0,24}",
+        option::of(prop::sample::select(suffixes)),
+        option::of(path()),
+        option::of(0..=86_400i64),
+        any::<(bool, bool)>(),
+        option::of(prop::sample::select(vec!["Strict", "Lax", "None"])),
+    )
+        .prop_map(
+            |(name, value, domain, path, max_age, (secure, http_only), same_site)| {
+                let mut set_cookie = format!("{}={}", name, value);
+                if let Some(domain) = domain {
+                    set_cookie.push_str(&format!("; Domain={}", domain));
+                }
+                if let Some(path) = path {
+                    set_cookie.push_str(&format!("; Path={}", path));
+                }
+                if let Some(max_age) = max_age {
+                    set_cookie.push_str(&format!("; Max-Age={}", max_age + 1));
+                }
+                if secure {
+                    set_cookie.push_str("; Secure");
+                }
+                if http_only {
+                    set_cookie.push_str("; HttpOnly");
+                }
+                if let Some(same_site) = same_site {
+                    set_cookie.push_str(&format!("; SameSite={}", same_site));
+                }
+                set_cookie
+            },
+        )
+}
+
+/// A `Set-Cookie` header value including edge cases: empty, mismatched, or leading-dot Domain
+/// attributes, relative paths, expired or past Expires attributes, and empty values; such
+/// cookies may be rejected by a `CookieStore`
+pub fn set_cookie_edge_case() -> impl Strategy<Value = String> {
+    (
+        "[A-Za-z0-9_-]{0,12}",
+        "[A-Za-z0-9\"%=-]{0,12}",
+        vec(
+            prop_oneof![
+                Just(String::from("; Domain=")),
+                domain_edge_case().prop_map(|d| format!("; Domain={}", d)),
+                domain().prop_map(|d| format!("; domain=.{}", d)),
+                Just(String::from("; Path=")),
+                "[a-z/]{0,8}".prop_map(|p| format!("; Path={}", p)),
+                (-86_400..=86_400i64).prop_map(|max_age| format!("; Max-Age={}", max_age)),
+                Just(String::from("; Expires=Thu, 01 Jan 1970 00:00:00 GMT")),
+                Just(String::from("; Expires=not a date")),
+                Just(String::from("; SECURE")),
+                Just(String::from("; httponly")),
+                Just(String::from("; SameSite=Bogus")),
+                Just(String::from(";;")),
+            ],
+            0..=4,
+        ),
+    )
+        .prop_map(|(name, value, attributes)| format!("{}={}{}", name, value, attributes.concat()))
+}
+
+/// A valid `Cookie`, as received from the paired request-uri
+pub fn cookie() -> impl Strategy<Value = (Cookie<'static>, Url)> {
+    url()
+        .prop_flat_map(|url| (set_cookie_for(&url), Just(url)))
+        .prop_filter_map("cookie must be valid", |(set_cookie, url)| {
+            Cookie::parse(set_cookie, &url)
+                .ok()
+                .map(|cookie| (cookie.into_owned(), url))
+        })
+}
+
+/// A `CookieStore` holding up to `max_cookies` valid cookies, received from request-uris on a
+/// small set of related hosts so that cookies overlap in domain and path
+pub fn store(max_cookies: usize) -> impl Strategy<Value = CookieStore> {
+    vec(domain(), 1..=3)
+        .prop_flat_map(move |hosts| {
+            let hosts = hosts
+                .iter()
+                .flat_map(|host| [host.clone(), format!("www.{}", host)])
+                .collect::<Vec<_>>();
+            vec(
+                url_on(prop::sample::select(hosts))
+                    .prop_flat_map(|url| (set_cookie_for(&url), Just(url))),
+                0..=max_cookies,
+            )
+        })
+        .prop_map(|set_cookies| {
+            let mut store = CookieStore::default();
+            for (set_cookie, url) in set_cookies {
+                let _ = store.parse(&set_cookie, &url);
+            }
+            store
+        })
+}
+
+/// A `CookieStore` into which up to `max_cookies` `Set-Cookie` headers of
+/// [`set_cookie_edge_case`] have been inserted, from request-uris of [`url_edge_case`]
+pub fn store_edge_case(max_cookies: usize) -> impl Strategy<Value = CookieStore> {
+    vec((set_cookie_edge_case(), url_edge_case()), 0..=max_cookies).prop_map(|set_cookies| {
+        let mut store = CookieStore::default();
+        for (set_cookie, url) in set_cookies {
+            let _ = store.parse(&set_cookie, &url);
+        }
+        store
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ::proptest::prelude::*;
+
+    use super::{cookie, store, store_edge_case, url, url_edge_case};
+
+    proptest! {
+        #[test]
+        fn cookies_are_valid((cookie, url) in cookie()) {
+            prop_assert!(cookie.domain.matches(&url));
+            prop_assert!(cookie.path.starts_with('/'));
+            prop_assert!(!cookie.is_expired());
+        }
+
+        #[test]
+        fn stores_are_consistent(store in store(16), url in url()) {
+            prop_assert!(store.validate().is_empty());
+            for cookie in store.matches(&url) {
+                prop_assert!(cookie.domain.matches(&url) && cookie.path.matches(&url));
+            }
+        }
+
+        #[test]
+        fn edge_case_stores_are_consistent(store in store_edge_case(16), url in url_edge_case()) {
+            prop_assert!(store.validate().is_empty());
+            for cookie in store.matches(&url) {
+                prop_assert!(cookie.domain.matches(&url) && cookie.path.matches(&url));
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Aggregate statistics of the contents of a `CookieStore`, e.g. for dashboards monitoring the
+//! health of the cookie jars of a fleet of crawlers; see
+//! [`CookieStore::report`](crate::CookieStore::report)
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "serde")]
+use serde_derive::{Deserialize, Serialize};
+use time::Duration;
+
+use crate::cookie_expiration::CookieExpiration;
+use crate::cookie_store::CookieStore;
+
+/// The distribution of the remaining lifetimes of the cookies of a [`StoreReport`]; each cookie
+/// is counted in exactly one bucket
+#[derive(PartialEq, Clone, Debug, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExpiryHistogram {
+    /// __Expired__ cookies
+    pub expired: usize,
+    /// Cookies expiring within an hour
+    pub within_hour: usize,
+    /// Cookies expiring within a day, but not within an hour
+    pub within_day: usize,
+    /// Cookies expiring within a week, but not within a day
+    pub within_week: usize,
+    /// Cookies expiring within 30 days, but not within a week
+    pub within_month: usize,
+    /// Cookies expiring within 365 days, but not within 30 days
+    pub within_year: usize,
+    /// Cookies expiring after 365 days
+    pub beyond_year: usize,
+    /// __Non-persistent__ cookies, expiring at the end of the session
+    pub session: usize,
+}
+
+impl ExpiryHistogram {
+    fn add(&mut self, expires: &CookieExpiration, now_utc: time::OffsetDateTime) {
+        let expires = match expires {
+            CookieExpiration::SessionEnd => {
+                self.session += 1;
+                return;
+            }
+            CookieExpiration::AtUtc(expires) => *expires,
+        };
+        let remaining = expires - now_utc;
+        let bucket = if remaining <= Duration::ZERO {
+            &mut self.expired
+        } else if remaining <= Duration::HOUR {
+            &mut self.within_hour
+        } else if remaining <= Duration::DAY {
+            &mut self.within_day
+        } else if remaining <= Duration::WEEK {
+            &mut self.within_week
+        } else if remaining <= Duration::days(30) {
+            &mut self.within_month
+        } else if remaining <= Duration::days(365) {
+            &mut self.within_year
+        } else {
+            &mut self.beyond_year
+        };
+        *bucket += 1;
+    }
+}
+
+/// Aggregate statistics of the contents of a [`CookieStore`], as returned by
+/// [`CookieStore::report`]. Unlike [`StoreStats`](crate::StoreStats), which describes the
+/// resource usage of the store, a report describes the cookies it holds.
+#[derive(PartialEq, Clone, Debug, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StoreReport {
+    /// The number of cookies (including __expired__) in the store
+    pub cookies: usize,
+    /// The number of __persistent__ cookies
+    pub persistent: usize,
+    /// The number of __non-persistent__ (session) cookies
+    pub session: usize,
+    /// The number of cookies with the `Secure` attribute
+    pub secure: usize,
+    /// The number of cookies with the `HttpOnly` attribute
+    pub http_only: usize,
+    /// The number of cookies (including __expired__) stored under each domain
+    pub cookies_per_domain: BTreeMap<String, usize>,
+    /// The distribution of the remaining lifetimes of the cookies
+    pub expiry: ExpiryHistogram,
+}
+
+impl StoreReport {
+    /// The fraction of cookies with the `Secure` attribute, or 0 for an empty store
+    pub fn secure_ratio(&self) -> f64 {
+        ratio(self.secure, self.cookies)
+    }
+
+    /// The fraction of cookies with the `HttpOnly` attribute, or 0 for an empty store
+    pub fn http_only_ratio(&self) -> f64 {
+        ratio(self.http_only, self.cookies)
+    }
+}
+
+fn ratio(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+impl CookieStore {
+    /// Aggregate statistics of the cookies (including __expired__) in the store
+    pub fn report(&self) -> StoreReport {
+        let now_utc = self.now_utc();
+        let mut report = StoreReport::default();
+        for cookie in self.iter_any() {
+            report.cookies += 1;
+            if cookie.is_persistent() {
+                report.persistent += 1;
+            } else {
+                report.session += 1;
+            }
+            if cookie.secure().unwrap_or(false) {
+                report.secure += 1;
+            }
+            if cookie.http_only().unwrap_or(false) {
+                report.http_only += 1;
+            }
+            *report
+                .cookies_per_domain
+                .entry(String::from(&cookie.domain))
+                .or_insert(0) += 1;
+            report.expiry.add(&cookie.expires, now_utc);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExpiryHistogram, StoreReport};
+    use crate::cookie_store::CookieStore;
+    use crate::test_util;
+
+    #[test]
+    fn report() {
+        assert_eq!(StoreReport::default(), CookieStore::default().report());
+        assert_eq!(0.0, CookieStore::default().report().secure_ratio());
+
+        let url = test_util::url("https://www.example.com/");
+        let mut store = CookieStore::default();
+        for cookie in [
+            "session=1; Secure; HttpOnly",
+            "hour=2; Max-Age=1800; Secure",
+            "week=3; Max-Age=259200",
+            "forever=4; Domain=example.com; Max-Age=63072000",
+        ] {
+            store.parse(cookie, &url).unwrap();
+        }
+        store
+            .parse("other=5", &test_util::url("http://other.com/"))
+            .unwrap();
+
+        let report = store.report();
+        assert_eq!(5, report.cookies);
+        assert_eq!((3, 2), (report.persistent, report.session));
+        assert_eq!((2, 1), (report.secure, report.http_only));
+        assert_eq!(0.4, report.secure_ratio());
+        assert_eq!(0.2, report.http_only_ratio());
+        assert_eq!(
+            vec![
+                ("example.com".to_owned(), 1),
+                ("other.com".to_owned(), 1),
+                ("www.example.com".to_owned(), 3)
+            ],
+            report.cookies_per_domain.into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            ExpiryHistogram {
+                within_hour: 1,
+                within_week: 1,
+                beyond_year: 1,
+                session: 2,
+                ..ExpiryHistogram::default()
+            },
+            report.expiry
+        );
+        #[cfg(feature = "serde_json")]
+        {
+            let json = serde_json::to_string(&store.report()).unwrap();
+            assert!(json.contains(r#""cookies_per_domain":{"example.com":1,"#));
+            let report: StoreReport = serde_json::from_str(&json).unwrap();
+            assert_eq!(store.report(), report);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+/// Describes the context of a request for which cookies are being retrieved, as needed to apply
+/// the `SameSite` attribute of cookies per
+/// [draft-ietf-httpbis-rfc6265bis](https://datatracker.ietf.org/doc/html/draft-ietf-httpbis-rfc6265bis#section-5.2);
+/// see [`CookieStore::matches_with_context`](crate::CookieStore::matches_with_context).
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub struct RequestContext {
+    /// The request is "same-site", i.e. the site of the request URL is the same as that of the
+    /// document (or other initiator) making the request
+    pub same_site: bool,
+    /// The request is a top-level navigation (e.g. following a link) using a "safe" method, such
+    /// as `GET`
+    pub top_level_safe_navigation: bool,
+}
+
+impl RequestContext {
+    /// A same-site request; cookies with any `SameSite` attribute are included
+    pub fn same_site() -> RequestContext {
+        RequestContext {
+            same_site: true,
+            top_level_safe_navigation: false,
+        }
+    }
+
+    /// A cross-site request which is not a top-level navigation (e.g. a subresource fetch);
+    /// only cookies with `SameSite=None` are included
+    pub fn cross_site() -> RequestContext {
+        RequestContext {
+            same_site: false,
+            top_level_safe_navigation: false,
+        }
+    }
+
+    /// A cross-site, top-level navigation using a "safe" method; cookies with `SameSite=Lax` or
+    /// `SameSite=None` are included
+    pub fn cross_site_navigation() -> RequestContext {
+        RequestContext {
+            same_site: false,
+            top_level_safe_navigation: true,
+        }
+    }
+
+    /// Returns true if a cookie with the `same_site` attribute should be included in a request
+    /// made in this context
+    pub fn allows(&self, same_site: Option<cookie::SameSite>) -> bool {
+        match same_site {
+            None | Some(cookie::SameSite::None) => true,
+            Some(cookie::SameSite::Lax) => self.same_site || self.top_level_safe_navigation,
+            Some(cookie::SameSite::Strict) => self.same_site,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RequestContext;
+    use cookie::SameSite;
+
+    #[test]
+    fn allows() {
+        let same_site = RequestContext::same_site();
+        assert!(same_site.allows(None));
+        assert!(same_site.allows(Some(SameSite::None)));
+        assert!(same_site.allows(Some(SameSite::Lax)));
+        assert!(same_site.allows(Some(SameSite::Strict)));
+
+        let navigation = RequestContext::cross_site_navigation();
+        assert!(navigation.allows(None));
+        assert!(navigation.allows(Some(SameSite::None)));
+        assert!(navigation.allows(Some(SameSite::Lax)));
+        assert!(!navigation.allows(Some(SameSite::Strict)));
+
+        let cross_site = RequestContext::cross_site();
+        assert!(cross_site.allows(None));
+        assert!(cross_site.allows(Some(SameSite::None)));
+        assert!(!cross_site.allows(Some(SameSite::Lax)));
+        assert!(!cross_site.allows(Some(SameSite::Strict)));
+    }
+}
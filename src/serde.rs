@@ -1,14 +1,311 @@
 //! De/serialization functionality
 //! Requires feature `serde`
+//!
+//! The canonical serialized form of a `CookieStore` is a sequence of cookies (or, as written by
+//! the `save_with_config` functions, the cookies enveloped with the [`StoreConfig`] of the
+//! store), as saved by e.g. [`json::save`]; the `save_with_options` functions may instead nest the
+//! cookies under their domain (see [`Layout`]). For compatibility, the `load` functions of the
+//! [`json`] module also accept the legacy line-oriented layout, with one cookie per line, as
+//! written by the deprecated `CookieStore::save_json`.
 
+use std::collections::BTreeMap;
 use std::io::{BufRead, Write};
 
-use crate::{Cookie, cookie_store::StoreResult, CookieStore};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    cookie_store::StoreResult, utils::Buffer, Cookie, CookieIdentity, CookieStore, DateParsing,
+    OverflowPolicy, Tombstone,
+};
 
 #[cfg(feature = "serde_json")]
 pub mod json;
+#[cfg(feature = "serde_json")]
+pub mod ndjson;
 #[cfg(feature = "serde_ron")]
 pub mod ron;
+#[cfg(feature = "dirs")]
+pub mod data_dir;
+#[cfg(feature = "local_storage")]
+pub mod local_storage;
+#[cfg(feature = "signed")]
+pub mod signed;
+
+/// Options controlling the output of the `save_with_options` functions of the format-specific
+/// modules, e.g. [`json::save_with_options`]. The default options match the output of the
+/// plain `save` functions: pretty-printed with the default indentation of the format, followed
+/// by a newline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveOptions {
+    /// Whether to pretty-print the output
+    pretty: bool,
+    /// The number of spaces to indent by when pretty-printing, if not the format default
+    indent: Option<usize>,
+    /// Whether to end the output with a newline
+    trailing_newline: bool,
+    /// The format of timestamps, e.g. the expiry of a cookie
+    timestamps: TimestampFormat,
+    /// The layout of the serialized cookies
+    layout: Layout,
+}
+
+/// The format in which the timestamps of cookies (e.g. their expiry) are serialized; see
+/// [`SaveOptions::with_timestamp_format`]. Timestamps in either format are accepted when
+/// loading cookies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampFormat {
+    /// An RFC3339 UTC datetime string, e.g. `"2030-08-03T00:38:37Z"`
+    #[default]
+    Rfc3339,
+    /// The number of seconds since the Unix epoch, e.g. `1912034317`
+    UnixSeconds,
+}
+
+/// The layout in which cookies are serialized; see [`SaveOptions::with_layout`]. Cookies in either
+/// layout are accepted when loading cookies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// A flat sequence of cookies, e.g. `[{...}, {...}]`
+    #[default]
+    Sequence,
+    /// A map from each domain to the sequence of cookies stored under it, ordered by domain, e.g.
+    /// `{"example.com": [{...}], "www.example.org": [{...}]}`
+    ByDomain,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        SaveOptions::pretty()
+    }
+}
+
+impl SaveOptions {
+    /// Pretty-printed output, with the default indentation of the format
+    pub fn pretty() -> SaveOptions {
+        SaveOptions {
+            pretty: true,
+            indent: None,
+            trailing_newline: true,
+            timestamps: TimestampFormat::Rfc3339,
+            layout: Layout::Sequence,
+        }
+    }
+
+    /// Compact output, without any insignificant whitespace; typically around half the size of
+    /// pretty-printed output
+    pub fn compact() -> SaveOptions {
+        SaveOptions {
+            pretty: false,
+            ..SaveOptions::pretty()
+        }
+    }
+
+    /// Indent pretty-printed output by `indent` spaces per level; ignored for compact output
+    pub fn with_indent(self, indent: usize) -> SaveOptions {
+        SaveOptions {
+            indent: Some(indent),
+            ..self
+        }
+    }
+
+    /// Specify whether to end the output with a newline
+    pub fn with_trailing_newline(self, trailing_newline: bool) -> SaveOptions {
+        SaveOptions {
+            trailing_newline,
+            ..self
+        }
+    }
+
+    /// Specify the format of timestamps, e.g. to write Unix timestamps for consumers which do not
+    /// parse RFC3339 datetimes
+    pub fn with_timestamp_format(self, timestamps: TimestampFormat) -> SaveOptions {
+        SaveOptions { timestamps, ..self }
+    }
+
+    /// Specify the layout of the serialized cookies, e.g. to nest cookies under their domain for
+    /// consumers which inspect cookies per site
+    pub fn with_layout(self, layout: Layout) -> SaveOptions {
+        SaveOptions { layout, ..self }
+    }
+}
+
+/// `cookies` nested under their domain, in the form serialized for [`Layout::ByDomain`]
+fn by_domain<'c>(cookies: &'c [Cookie<'static>]) -> BTreeMap<&'c str, Vec<&'c Cookie<'static>>> {
+    let mut domains = BTreeMap::<_, Vec<_>>::new();
+    for cookie in cookies {
+        domains
+            .entry(cookie.domain.as_str().unwrap_or_default())
+            .or_default()
+            .push(cookie);
+    }
+    domains
+}
+
+/// The settings of a [`CookieStore`] which may be persisted alongside its cookies, via e.g.
+/// [`json::save_with_config`], and are applied to the store when loaded. Settings which cannot be
+/// persisted (e.g. a custom public suffix list, clock, or observer) are not included. Settings
+/// missing from the persisted data take their default values.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct StoreConfig {
+    /// Whether [public suffix](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3)
+    /// rejection is enabled; see [`CookieStore::with_config`]
+    pub public_suffix: bool,
+    /// See [`CookieStore::with_max_cookies`]
+    pub max_cookies: Option<usize>,
+    /// See [`CookieStore::with_max_cookies_per_domain`]
+    pub max_cookies_per_domain: Option<usize>,
+    /// See [`CookieStore::with_max_bytes_per_domain`]
+    pub max_bytes_per_domain: Option<usize>,
+    /// See [`CookieStore::with_overflow_policy`]
+    pub overflow_policy: OverflowPolicy,
+    /// See [`CookieStore::with_persist_session_cookies`]
+    pub persist_session_cookies: bool,
+    /// See [`CookieStore::with_first_party_only_persistence`]
+    pub first_party_only_persistence: bool,
+    /// See [`CookieStore::with_same_site_lax_by_default`]
+    pub same_site_lax_by_default: bool,
+    /// See [`CookieStore::with_scheme_bound`]
+    pub scheme_bound: bool,
+    /// See [`CookieStore::with_port_bound`]
+    pub port_bound: bool,
+    /// See [`CookieStore::with_date_parsing`]
+    pub date_parsing: DateParsing,
+    /// The retention of tombstones in seconds, if enabled; see [`CookieStore::with_tombstones`]
+    pub tombstone_retention: Option<u64>,
+}
+
+/// The cookies of a `CookieStore` enveloped with its [`StoreConfig`], as saved by the
+/// `save_with_config` functions of the format-specific modules, e.g. [`json::save_with_config`]
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+struct Envelope {
+    #[serde(default)]
+    config: StoreConfig,
+    cookies: Vec<Cookie<'static>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tombstones: Vec<Tombstone>,
+}
+
+/// The [JSON Schema](https://json-schema.org/) of the data saved by the `save` functions of the
+/// format-specific modules, e.g. [`json::save`]: a sequence of cookies, cookies nested under their
+/// domain (see [`Layout::ByDomain`]), or cookies enveloped with the [`StoreConfig`] of the store
+/// (see [`json::save_with_config`]). Requires feature `schemars`.
+#[cfg(feature = "schemars")]
+pub fn json_schema() -> schemars::schema::RootSchema {
+    use schemars::schema::{Metadata, SchemaObject, SubschemaValidation};
+
+    let mut gen = schemars::gen::SchemaSettings::draft07().into_generator();
+    let any_of = vec![
+        gen.subschema_for::<Vec<Cookie<'static>>>(),
+        gen.subschema_for::<BTreeMap<String, Vec<Cookie<'static>>>>(),
+        gen.subschema_for::<Envelope>(),
+    ];
+    schemars::schema::RootSchema {
+        meta_schema: gen.settings().meta_schema.clone(),
+        schema: SchemaObject {
+            metadata: Some(Box::new(Metadata {
+                title: Some("CookieStore".to_owned()),
+                ..Default::default()
+            })),
+            subschemas: Some(Box::new(SubschemaValidation {
+                any_of: Some(any_of),
+                ..Default::default()
+            })),
+            ..Default::default()
+        },
+        definitions: gen.take_definitions(),
+    }
+}
+
+/// A self-describing format in which the format-specific modules persist a `CookieStore`
+trait Format {
+    type Error;
+
+    /// Whether data in the format may be in the legacy line-oriented layout, with one cookie per
+    /// line
+    const LEGACY_LAYOUT: bool;
+
+    fn from_str<T: serde::de::DeserializeOwned>(s: &str) -> Result<T, Self::Error>;
+}
+
+/// The serialized form of a `CookieStore`, as loaded by the format-specific modules: either a
+/// bare sequence of cookies (in either [`Layout`]), or an [`Envelope`]
+enum Persisted {
+    Cookies(Vec<Cookie<'static>>),
+    Enveloped(Envelope),
+}
+
+impl Persisted {
+    /// Deserialize `persisted` in format `F`, as a bare sequence of cookies, an `Envelope`
+    /// (identified by its required `cookies` field), or a map of cookies by domain, whichever
+    /// deserializes successfully; failing those, and only for formats with a legacy layout, as
+    /// the legacy layout of one cookie per line. Fails with the error of deserializing a bare
+    /// sequence of cookies, the canonical layout.
+    fn from_str<F: Format>(persisted: &str) -> Result<Self, F::Error> {
+        let error = match F::from_str(persisted) {
+            Ok(cookies) => return Ok(Persisted::Cookies(cookies)),
+            Err(e) => e,
+        };
+        if let Ok(envelope) = F::from_str(persisted) {
+            return Ok(Persisted::Enveloped(envelope));
+        }
+        if let Ok(domains) = F::from_str::<BTreeMap<String, Vec<Cookie<'static>>>>(persisted) {
+            return Ok(Persisted::Cookies(
+                domains.into_values().flatten().collect(),
+            ));
+        }
+        if F::LEGACY_LAYOUT {
+            let legacy = persisted
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(F::from_str)
+                .collect::<Result<Vec<_>, _>>();
+            if let Ok(cookies) = legacy {
+                if !cookies.is_empty() {
+                    return Ok(Persisted::Cookies(cookies));
+                }
+            }
+        }
+        Err(error)
+    }
+}
+
+impl From<Vec<Cookie<'static>>> for Persisted {
+    fn from(cookies: Vec<Cookie<'static>>) -> Self {
+        Persisted::Cookies(cookies)
+    }
+}
+
+/// A data-quality issue encountered while loading cookies, as reported by the
+/// `load_with_warnings` and `load_all_with_warnings` functions of the format-specific modules,
+/// e.g. [`json::load_with_warnings`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadWarning {
+    /// An __expired__ cookie was skipped
+    Expired(CookieIdentity),
+    /// A cookie was replaced by a later cookie of the same identity
+    Duplicate(CookieIdentity),
+    /// The expiry of a cookie was clamped to the latest representable time, the end of the year
+    /// 9999
+    ClampedExpiry(CookieIdentity),
+}
+
+impl std::fmt::Display for LoadWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (warning, id) = match self {
+            LoadWarning::Expired(id) => ("skipped expired cookie", id),
+            LoadWarning::Duplicate(id) => ("replaced duplicate cookie", id),
+            LoadWarning::ClampedExpiry(id) => ("clamped expiry of cookie", id),
+        };
+        write!(
+            f,
+            "{} '{}' (domain '{}', path '{}')",
+            warning, id.name, id.domain, id.path
+        )
+    }
+}
 
 /// Load cookies from `reader`, deserializing with `cookie_from_str`, skipping any __expired__
 /// cookies
@@ -33,7 +330,7 @@ pub fn load_all<R, E, F>(reader: R, cookies_from_str: F) -> StoreResult<CookieSt
 }
 
 fn load_from<R, E, F>(
-    mut reader: R,
+    reader: R,
     cookies_from_str: F,
     include_expired: bool,
 ) -> StoreResult<CookieStore>
@@ -42,13 +339,92 @@ fn load_from<R, E, F>(
     F: Fn(&str) -> Result<Vec<Cookie<'static>>, E>,
     crate::Error: From<E>,
 {
-    let mut cookie_store = String::new();
+    load_from_with_warnings(reader, cookies_from_str, include_expired)
+        .map(|(cookie_store, _)| cookie_store)
+}
+
+/// Load cookies from `reader`, deserializing with `cookie_from_str`, skipping any __expired__
+/// cookies, and returning a [`LoadWarning`] for each data-quality issue encountered
+pub fn load_with_warnings<R, E, F>(
+    reader: R,
+    cookies_from_str: F,
+) -> StoreResult<(CookieStore, Vec<LoadWarning>)>
+where
+    R: BufRead,
+    F: Fn(&str) -> Result<Vec<Cookie<'static>>, E>,
+    crate::Error: From<E>,
+{
+    load_from_with_warnings(reader, cookies_from_str, false)
+}
+
+/// Load cookies from `reader`, deserializing with `cookie_from_str`, loading both __unexpired__
+/// and __expired__ cookies, and returning a [`LoadWarning`] for each data-quality issue
+/// encountered
+pub fn load_all_with_warnings<R, E, F>(
+    reader: R,
+    cookies_from_str: F,
+) -> StoreResult<(CookieStore, Vec<LoadWarning>)>
+where
+    R: BufRead,
+    F: Fn(&str) -> Result<Vec<Cookie<'static>>, E>,
+    crate::Error: From<E>,
+{
+    load_from_with_warnings(reader, cookies_from_str, true)
+}
+
+fn load_from_with_warnings<R, E, F, T>(
+    mut reader: R,
+    cookies_from_str: F,
+    include_expired: bool,
+) -> StoreResult<(CookieStore, Vec<LoadWarning>)>
+where
+    R: BufRead,
+    F: Fn(&str) -> Result<T, E>,
+    T: Into<Persisted>,
+    crate::Error: From<E>,
+{
+    let mut cookie_store = Buffer::default();
     reader.read_to_string(&mut cookie_store)?;
-    let cookies = cookies_from_str(&cookie_store)?;
-    CookieStore::from_cookies(
-        cookies.into_iter().map(|cookies| Ok(cookies)),
-        include_expired,
-    )
+    let (config, cookies, tombstones) = match cookies_from_str(&cookie_store)?.into() {
+        Persisted::Cookies(cookies) => (None, cookies, Vec::new()),
+        Persisted::Enveloped(Envelope {
+            config,
+            cookies,
+            tombstones,
+        }) => (Some(config), cookies, tombstones),
+    };
+    let (cookie_store, warnings) =
+        CookieStore::from_cookies_with_warnings(cookies.into_iter().map(Ok), include_expired)?;
+    match config {
+        Some(config) => {
+            let mut cookie_store = cookie_store.with_config(&config);
+            cookie_store.restore_tombstones(tombstones);
+            Ok((cookie_store, warnings))
+        }
+        None => Ok((cookie_store, warnings)),
+    }
+}
+
+/// Serialize any __unexpired__ and __persistent__ cookies in the store, enveloped with the
+/// [`StoreConfig`] of the store, with `envelope_to_string` and write them to `writer`
+fn save_with_config<W, E, F>(
+    cookie_store: &CookieStore,
+    writer: &mut W,
+    envelope_to_string: F,
+) -> StoreResult<()>
+where
+    W: Write,
+    F: Fn(&Envelope) -> Result<String, E>,
+    crate::Error: From<E>,
+{
+    let envelope = Envelope {
+        config: cookie_store.config(),
+        cookies: cookie_store.iter_saved().cloned().collect(),
+        tombstones: cookie_store.tombstones(),
+    };
+    let envelope = Buffer::from(envelope_to_string(&envelope)?);
+    writeln!(writer, "{}", envelope.as_str())?;
+    Ok(())
 }
 
 /// Serialize any __unexpired__ and __persistent__ cookies in the store with `cookie_to_string`
@@ -63,17 +439,77 @@ pub fn save<W, E, F>(
     F: Fn(&Vec<Cookie<'static>>) -> Result<String, E>,
     crate::Error: From<E>,
 {
-    let mut cookies = Vec::new();
-    for cookie in cookie_store.iter_unexpired() {
-        if cookie.is_persistent() {
-            cookies.push(cookie.clone());
-        }
+    save_cookies(cookie_store, writer, cookies_to_string, false, true)
+}
+
+/// Serialize the cookies in the store with `cookies_to_string` and write them to `writer`,
+/// including __expired__ and __non-persistent__ cookies if `include_all`, followed by a newline
+/// if `trailing_newline`
+fn save_cookies<W, E, F>(
+    cookie_store: &CookieStore,
+    writer: &mut W,
+    cookies_to_string: F,
+    include_all: bool,
+    trailing_newline: bool,
+) -> StoreResult<()>
+where
+    W: Write,
+    F: Fn(&Vec<Cookie<'static>>) -> Result<String, E>,
+    crate::Error: From<E>,
+{
+    let cookies = if include_all {
+        cookie_store.iter_any().cloned().collect()
+    } else {
+        cookie_store.iter_saved().cloned().collect()
+    };
+    write_cookies(&cookies, writer, cookies_to_string, trailing_newline)
+}
+
+/// Serialize `cookies` with `cookies_to_string` and write them to `writer`, followed by a newline
+/// if `trailing_newline`
+fn write_cookies<W, E, F>(
+    cookies: &Vec<Cookie<'static>>,
+    writer: &mut W,
+    cookies_to_string: F,
+    trailing_newline: bool,
+) -> StoreResult<()>
+where
+    W: Write,
+    F: Fn(&Vec<Cookie<'static>>) -> Result<String, E>,
+    crate::Error: From<E>,
+{
+    let cookies = Buffer::from(cookies_to_string(cookies)?);
+    if trailing_newline {
+        writeln!(writer, "{}", cookies.as_str())?;
+    } else {
+        write!(writer, "{}", cookies.as_str())?;
     }
-    let cookies = cookies_to_string(&cookies);
-    writeln!(writer, "{}", cookies?)?;
     Ok(())
 }
 
+/// Serialize the __unexpired__ and __persistent__ cookies in the store for which `predicate`
+/// returns `true` with `cookies_to_string` and write them to `writer`, e.g. to exclude HttpOnly
+/// cookies or the cookies of a sensitive domain from persistence
+pub fn save_filtered<W, E, F, P>(
+    cookie_store: &CookieStore,
+    writer: &mut W,
+    cookies_to_string: F,
+    mut predicate: P,
+) -> StoreResult<()>
+where
+    W: Write,
+    F: Fn(&Vec<Cookie<'static>>) -> Result<String, E>,
+    P: FnMut(&Cookie<'static>) -> bool,
+    crate::Error: From<E>,
+{
+    let cookies = cookie_store
+        .iter_saved()
+        .filter(|c| predicate(c))
+        .cloned()
+        .collect();
+    write_cookies(&cookies, writer, cookies_to_string, true)
+}
+
 /// Serialize all (including __expired__ and __non-persistent__) cookies in the store with `cookie_to_string` and write them to `writer`
 pub fn save_incl_expired_and_nonpersistent<W, E, F>(
     cookie_store: &CookieStore,
@@ -85,11 +521,126 @@ pub fn save_incl_expired_and_nonpersistent<W, E, F>(
     F: Fn(&Vec<Cookie<'static>>) -> Result<String, E>,
     crate::Error: From<E>,
 {
-    let mut cookies = Vec::new();
-    for cookie in cookie_store.iter_any() {
-        cookies.push(cookie.clone());
+    save_cookies(cookie_store, writer, cookies_to_string, true, true)
+}
+
+/// The canonical format written by [`convert_legacy`]
+#[cfg(feature = "serde_json")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetFormat {
+    /// The format of [`json::save`]
+    Json,
+    /// The format of [`ron::save`]. Requires feature `serde_ron`
+    #[cfg(feature = "serde_ron")]
+    Ron,
+}
+
+/// Read cookies in the legacy line-oriented layout written by the deprecated
+/// `CookieStore::save_json` and `CookieStore::save_incl_expired_and_nonpersistent_json` from
+/// `reader`, and write them to `writer` in the canonical `target_format`, e.g. to migrate
+/// persisted cookies in one call. All cookies are converted, including any __expired__ or
+/// __non-persistent__ cookies. Data already in the canonical JSON format is also accepted.
+#[cfg(feature = "serde_json")]
+pub fn convert_legacy<R, W>(
+    reader: R,
+    writer: &mut W,
+    target_format: TargetFormat,
+) -> StoreResult<()>
+where
+    R: BufRead,
+    W: Write,
+{
+    let cookie_store = json::load_all(reader)?;
+    match target_format {
+        TargetFormat::Json => json::save_incl_expired_and_nonpersistent(&cookie_store, writer),
+        #[cfg(feature = "serde_ron")]
+        TargetFormat::Ron => ron::save_incl_expired_and_nonpersistent(&cookie_store, writer),
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::{convert_legacy, json, TargetFormat};
+    use crate::test_util;
+    use crate::CookieStore;
+
+    #[test]
+    #[allow(deprecated)]
+    fn convert_legacy_to_json() {
+        let url = test_util::url("http://example.com/foo/bar");
+        let mut store = CookieStore::default();
+        store.parse("cookie1=1; Max-Age=3600", &url).unwrap();
+        store.parse("session=2", &url).unwrap();
+        let mut legacy = Vec::new();
+        store
+            .save_incl_expired_and_nonpersistent_json(&mut legacy)
+            .unwrap();
+
+        let mut converted = Vec::new();
+        convert_legacy(&legacy[..], &mut converted, TargetFormat::Json).unwrap();
+        let converted = String::from_utf8(converted).unwrap();
+        assert!(converted.starts_with('['));
+        let loaded = json::load_all(converted.as_bytes()).unwrap();
+        assert_eq!(2, loaded.len());
+        for cookie in store.iter_any() {
+            assert_eq!(
+                cookie.value(),
+                loaded
+                    .get_any("example.com", "/foo", cookie.name())
+                    .unwrap()
+                    .value()
+            );
+        }
+
+        assert!(convert_legacy(&b"not a cookie"[..], &mut Vec::new(), TargetFormat::Json).is_err());
+    }
+
+    #[cfg(feature = "serde_ron")]
+    #[test]
+    #[allow(deprecated)]
+    fn convert_legacy_to_ron() {
+        let url = test_util::url("http://example.com/foo/bar");
+        let mut store = CookieStore::default();
+        store.parse("cookie1=1; Max-Age=3600", &url).unwrap();
+        let mut legacy = Vec::new();
+        store.save_json(&mut legacy).unwrap();
+
+        let mut converted = Vec::new();
+        convert_legacy(&legacy[..], &mut converted, TargetFormat::Ron).unwrap();
+        let loaded = super::ron::load(&converted[..]).unwrap();
+        assert_eq!(1, loaded.len());
+        assert!(loaded.get("example.com", "/foo", "cookie1").is_some());
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    fn json_schema() {
+        let schema = serde_json::to_value(super::json_schema()).unwrap();
+        assert_eq!(3, schema["anyOf"].as_array().unwrap().len());
+        let definitions = schema["definitions"].as_object().unwrap();
+        for definition in [
+            "Cookie",
+            "CookieDomain",
+            "CookieExpiration",
+            "CookiePath",
+            "Envelope",
+            "StoreConfig",
+            "Timestamp",
+            "Tombstone",
+        ] {
+            assert!(definitions.contains_key(definition), "{}", definition);
+        }
+        let cookie = &definitions["Cookie"];
+        assert_eq!("string", cookie["properties"]["raw_cookie"]["type"]);
+        assert_eq!(vec!["domain", "expires", "path", "raw_cookie"], {
+            let mut required = cookie["required"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|r| r.as_str().unwrap())
+                .collect::<Vec<_>>();
+            required.sort();
+            required
+        });
     }
-    let cookies = cookies_to_string(&cookies);
-    writeln!(writer, "{}", cookies?)?;
-    Ok(())
 }
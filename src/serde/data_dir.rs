@@ -0,0 +1,109 @@
+//! Persistence to a file in the per-platform user data directory, as resolved by
+//! [dirs](https://crates.io/crates/dirs) (e.g. `$XDG_DATA_HOME` on Linux, `%APPDATA%` on
+//! Windows, and `~/Library/Application Support` on macOS), storing the store under a
+//! subdirectory named for the application using the same JSON format as [crate::serde::json].
+//! Requires feature `dirs`
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cookie_store::{CookieStore, StoreResult};
+
+/// The name of the file holding the store within the application data directory
+const FILE_NAME: &str = "cookies.json";
+
+/// The path of the file in which the store of `app_name` is persisted, or `None` if the user
+/// data directory of the platform cannot be determined
+pub fn path(app_name: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|data_dir| data_dir.join(app_name).join(FILE_NAME))
+}
+
+fn resolve(app_name: &str) -> io::Result<PathBuf> {
+    path(app_name).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "the user data directory could not be determined",
+        )
+    })
+}
+
+fn load_from(path: &Path) -> StoreResult<CookieStore> {
+    match File::open(path) {
+        Ok(file) => super::json::load(BufReader::new(file)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(CookieStore::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn save_to(cookie_store: &CookieStore, path: &Path) -> StoreResult<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // write to a temporary file first, so that a failed save does not corrupt the store
+    let temp_path = path.with_extension("json.tmp");
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    super::json::save(cookie_store, &mut writer)?;
+    writer.flush()?;
+    drop(writer);
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Load the JSON-formatted cookies persisted for `app_name`, skipping any __expired__ cookies,
+/// creating the application data directory if needed. If nothing has been persisted for
+/// `app_name`, an empty `CookieStore` is returned.
+pub fn open_default(app_name: &str) -> StoreResult<CookieStore> {
+    let path = resolve(app_name)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    load_from(&path)
+}
+
+/// Serialize any __unexpired__ and __persistent__ cookies in the store to JSON format and
+/// persist them for `app_name`, replacing any previously persisted cookies.
+pub fn save_default(cookie_store: &CookieStore, app_name: &str) -> StoreResult<()> {
+    save_to(cookie_store, &resolve(app_name)?)
+}
+
+/// Remove any cookies persisted for `app_name`.
+pub fn remove(app_name: &str) -> StoreResult<()> {
+    match fs::remove_file(resolve(app_name)?) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_from, save_to};
+    use crate::cookie_store::CookieStore;
+    use crate::test_util;
+
+    #[test]
+    fn save_and_load() {
+        let dir =
+            std::env::temp_dir().join(format!("cookie_store-data_dir-{}", std::process::id()));
+        let path = dir.join("app").join(super::FILE_NAME);
+        assert!(load_from(&path).unwrap().is_empty());
+
+        let url = test_util::url("http://example.com/");
+        let mut store = CookieStore::default();
+        store.parse("cookie1=1; Max-Age=3600", &url).unwrap();
+        store.parse("session=2", &url).unwrap();
+        save_to(&store, &path).unwrap();
+        let loaded = load_from(&path).unwrap();
+        assert_eq!(1, loaded.len());
+        assert!(loaded.get("example.com", "/", "cookie1").is_some());
+        assert!(!path.with_extension("json.tmp").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn path() {
+        if let Some(path) = super::path("app") {
+            assert!(path.ends_with("app/cookies.json"));
+        }
+    }
+}
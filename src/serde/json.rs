@@ -3,33 +3,65 @@
 
 use std::io::{BufRead, Write};
 
+use super::{Layout, LoadWarning, SaveOptions};
+use crate::cookie::Cookie;
 use crate::cookie_store::{StoreResult, CookieStore};
 
-/// Load JSON-formatted cookies from `reader`, skipping any __expired__ cookies.
-/// __NB__: This function is not compatible with data produced by [CookieStore::save_json] or
-/// [CookieStore::save_incl_expired_and_nonpersistent_json].
+/// Load JSON-formatted cookies from `reader`, skipping any __expired__ cookies. Data written by
+/// [`save_with_config`] is loaded with its persisted configuration applied to the store.
+/// Data in the legacy line-oriented layout of [CookieStore::save_json] is also accepted.
 pub fn load<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
-    super::load(reader, |cookies| serde_json::from_str(cookies))
+    super::load_from_with_warnings(reader, super::Persisted::from_str::<Json>, false)
+        .map(|(cookie_store, _)| cookie_store)
 }
 
 /// Load JSON-formatted cookies from `reader`, loading both __expired__ and __unexpired__ cookies.
-/// __NB__: This function is not compatible with data produced by [CookieStore::save_json] or
-/// [CookieStore::save_incl_expired_and_nonpersistent_json].
+/// Data in the legacy line-oriented layout of [CookieStore::save_json] is also accepted.
 pub fn load_all<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
-    super::load_all(reader, |cookies| serde_json::from_str(cookies))
+    super::load_from_with_warnings(reader, super::Persisted::from_str::<Json>, true)
+        .map(|(cookie_store, _)| cookie_store)
+}
+
+/// Load JSON-formatted cookies from `reader`, skipping any __expired__ cookies, and returning a
+/// [`LoadWarning`] for each data-quality issue encountered, e.g. a duplicated cookie
+pub fn load_with_warnings<R: BufRead>(reader: R) -> StoreResult<(CookieStore, Vec<LoadWarning>)> {
+    super::load_from_with_warnings(reader, super::Persisted::from_str::<Json>, false)
+}
+
+/// Load JSON-formatted cookies from `reader`, loading both __expired__ and __unexpired__ cookies,
+/// and returning a [`LoadWarning`] for each data-quality issue encountered
+pub fn load_all_with_warnings<R: BufRead>(
+    reader: R,
+) -> StoreResult<(CookieStore, Vec<LoadWarning>)> {
+    super::load_from_with_warnings(reader, super::Persisted::from_str::<Json>, true)
 }
 
 /// Serialize any __unexpired__ and __persistent__ cookies in the store to JSON format and
-/// write them to `writer`.
-/// __NB__: This function does not produce data compatible with [CookieStore::load_json] or
-/// [CookieStore::load_json_all].
+/// write them to `writer`
 pub fn save<W: Write>(cookie_store: &CookieStore, writer: &mut W) -> StoreResult<()> {
     super::save(cookie_store, writer, ::serde_json::to_string_pretty)
 }
 
-/// Serialize all (including __expired__ and __non-persistent__) cookies in the store to JSON format and write them to `writer`.
-/// __NB__: This function does not produce data compatible with [CookieStore::load_json] or
-/// [CookieStore::load_json_all].
+/// Serialize the __unexpired__ and __persistent__ cookies in the store for which `predicate`
+/// returns `true` to JSON format and write them to `writer`
+pub fn save_filtered<W, P>(
+    cookie_store: &CookieStore,
+    writer: &mut W,
+    predicate: P,
+) -> StoreResult<()>
+where
+    W: Write,
+    P: FnMut(&Cookie<'static>) -> bool,
+{
+    super::save_filtered(
+        cookie_store,
+        writer,
+        ::serde_json::to_string_pretty,
+        predicate,
+    )
+}
+
+/// Serialize all (including __expired__ and __non-persistent__) cookies in the store to JSON format and write them to `writer`
 pub fn save_incl_expired_and_nonpersistent<W: Write>(
     cookie_store: &CookieStore,
     writer: &mut W,
@@ -37,6 +69,79 @@ pub fn save_incl_expired_and_nonpersistent<W: Write>(
     super::save_incl_expired_and_nonpersistent(cookie_store, writer, ::serde_json::to_string_pretty)
 }
 
+/// Serialize any __unexpired__ and __persistent__ cookies in the store to JSON format, per
+/// `options`, and write them to `writer`
+pub fn save_with_options<W: Write>(
+    cookie_store: &CookieStore,
+    writer: &mut W,
+    options: &SaveOptions,
+) -> StoreResult<()> {
+    super::save_cookies(
+        cookie_store,
+        writer,
+        |cookies| to_string(cookies, options),
+        false,
+        options.trailing_newline,
+    )
+}
+
+/// Serialize all (including __expired__ and __non-persistent__) cookies in the store to JSON
+/// format, per `options`, and write them to `writer`
+pub fn save_incl_expired_and_nonpersistent_with_options<W: Write>(
+    cookie_store: &CookieStore,
+    writer: &mut W,
+    options: &SaveOptions,
+) -> StoreResult<()> {
+    super::save_cookies(
+        cookie_store,
+        writer,
+        |cookies| to_string(cookies, options),
+        true,
+        options.trailing_newline,
+    )
+}
+
+/// Serialize any __unexpired__ and __persistent__ cookies in the store to JSON format, along with
+/// the [`StoreConfig`](super::StoreConfig) of the store, and write them to `writer`. The output is a JSON object with
+/// `config` and `cookies` fields, which may be loaded by any of the `load` functions of this
+/// module.
+pub fn save_with_config<W: Write>(cookie_store: &CookieStore, writer: &mut W) -> StoreResult<()> {
+    super::save_with_config(cookie_store, writer, ::serde_json::to_string_pretty)
+}
+
+struct Json;
+
+impl super::Format for Json {
+    type Error = serde_json::Error;
+
+    const LEGACY_LAYOUT: bool = true;
+
+    fn from_str<T: serde::de::DeserializeOwned>(s: &str) -> serde_json::Result<T> {
+        serde_json::from_str(s)
+    }
+}
+
+fn to_string(cookies: &Vec<Cookie<'static>>, options: &SaveOptions) -> StoreResult<String> {
+    crate::rfc3339_fmt::with_timestamp_format(options.timestamps, || match options.layout {
+        Layout::Sequence => serialize(cookies, options),
+        Layout::ByDomain => serialize(&super::by_domain(cookies), options),
+    })
+}
+
+fn serialize<T: serde::Serialize>(value: &T, options: &SaveOptions) -> StoreResult<String> {
+    if !options.pretty {
+        return Ok(serde_json::to_string(value)?);
+    }
+    let indent = " ".repeat(options.indent.unwrap_or(2));
+    let mut writer = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    value.serialize(&mut serde_json::Serializer::with_formatter(
+        &mut writer,
+        formatter,
+    ))?;
+    Ok(String::from_utf8(writer)?)
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::BufWriter;
@@ -150,4 +255,331 @@ mod tests {
         let string = String::from_utf8(writer.into_inner().unwrap()).unwrap();
         assert_eq!(cookie, string);
     }
+
+    #[test]
+    fn creation_time() {
+        let url = crate::test_util::url("http://example.com/");
+        let mut store = crate::CookieStore::default();
+        store.parse("cookie1=1; Max-Age=3600", &url).unwrap();
+        let created = store.iter_any().next().unwrap().creation_time.unwrap();
+
+        let mut writer = Vec::new();
+        save(&store, &mut writer).unwrap();
+        assert!(String::from_utf8(writer.clone())
+            .unwrap()
+            .contains("\"creation_time\": "));
+        let loaded = load(&writer[..]).unwrap();
+        let loaded = loaded.iter_any().next().unwrap().creation_time.unwrap();
+        assert_eq!(created.unix_timestamp(), loaded.unix_timestamp());
+
+        // cookies saved without a creation-time continue to load without one
+        let loaded = load(cookie().as_bytes()).unwrap();
+        assert!(loaded.iter_any().next().unwrap().creation_time.is_none());
+    }
+
+    #[test]
+    fn persist_session_cookies() {
+        let url = crate::test_util::url("http://example.com/");
+        let mut store = crate::CookieStore::default();
+        store.parse("cookie1=1; Max-Age=3600", &url).unwrap();
+        store.parse("session=2", &url).unwrap();
+        store.parse("cookie3=3; Max-Age=3600", &url).unwrap();
+        store.parse("cookie3=3; Max-Age=0", &url).unwrap();
+
+        let mut writer = Vec::new();
+        save(&store, &mut writer).unwrap();
+        assert_eq!(1, load(&writer[..]).unwrap().len());
+
+        let store = store.with_persist_session_cookies(true);
+        let mut writer = Vec::new();
+        save(&store, &mut writer).unwrap();
+        let loaded = load_all(&writer[..]).unwrap();
+        assert_eq!(2, loaded.len());
+        assert!(!loaded
+            .get("example.com", "/", "session")
+            .unwrap()
+            .is_persistent());
+    }
+
+    #[test]
+    fn save_filtered() {
+        let url = crate::test_util::url("http://example.com/");
+        let mut store = crate::CookieStore::default();
+        store.parse("cookie1=1; Max-Age=3600", &url).unwrap();
+        store
+            .parse("cookie2=2; Max-Age=3600; HttpOnly", &url)
+            .unwrap();
+        store.parse("session=3", &url).unwrap();
+
+        let mut writer = Vec::new();
+        super::save_filtered(&store, &mut writer, |c| !c.http_only().unwrap_or(false)).unwrap();
+        let loaded = load(&writer[..]).unwrap();
+        assert_eq!(1, loaded.len());
+        assert!(loaded.get("example.com", "/", "cookie1").is_some());
+        // the predicate applies only to the cookies `save` would persist
+        let mut writer = Vec::new();
+        super::save_filtered(&store, &mut writer, |_| true).unwrap();
+        assert_eq!(2, load(&writer[..]).unwrap().len());
+    }
+
+    #[test]
+    fn load_with_warnings() {
+        use super::super::LoadWarning;
+        use super::{load_all_with_warnings, load_with_warnings};
+        use crate::CookieIdentity;
+
+        let record = |raw_cookie: &str, expires: &str| {
+            format!(
+                r#"{{"raw_cookie": "{}", "path": ["/", true], "domain": {{"HostOnly": "test.com"}}, "expires": {{"AtUtc": "{}"}}}}"#,
+                raw_cookie, expires
+            )
+        };
+        let cookies = format!(
+            "[{}, {}, {}, {}]",
+            record("1=one", "2000-08-03T00:38:37Z"),
+            record("2=two", "2100-08-03T00:38:37Z"),
+            record("2=updated", "2100-08-03T00:38:37Z"),
+            record("3=three", "9999-12-31T23:59:59Z"),
+        );
+        let id = |name| CookieIdentity::new("test.com", "/", name);
+
+        let (cookie_store, warnings) = load_with_warnings(cookies.as_bytes()).unwrap();
+        assert_eq!(2, cookie_store.len());
+        assert!(!cookie_store.is_dirty());
+        assert_eq!(
+            "updated",
+            cookie_store.get("test.com", "/", "2").unwrap().value()
+        );
+        assert_eq!(
+            vec![
+                LoadWarning::Expired(id("1")),
+                LoadWarning::Duplicate(id("2")),
+                LoadWarning::ClampedExpiry(id("3")),
+            ],
+            warnings
+        );
+        assert_eq!(
+            "skipped expired cookie '1' (domain 'test.com', path '/')",
+            warnings[0].to_string()
+        );
+
+        let (cookie_store, warnings) = load_all_with_warnings(cookies.as_bytes()).unwrap();
+        assert_eq!(3, cookie_store.len());
+        assert_eq!(
+            vec![
+                LoadWarning::Duplicate(id("2")),
+                LoadWarning::ClampedExpiry(id("3")),
+            ],
+            warnings
+        );
+
+        let (_, warnings) = load_with_warnings(cookie().as_bytes()).unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn save_with_options() {
+        use super::super::SaveOptions;
+        use super::save_with_options;
+
+        let cookie_store = load(Into::<&[u8]>::into(cookie().as_bytes())).unwrap();
+
+        let mut writer = Vec::new();
+        save_with_options(&cookie_store, &mut writer, &SaveOptions::default()).unwrap();
+        assert_eq!(cookie(), String::from_utf8(writer).unwrap());
+
+        let mut writer = Vec::new();
+        save_with_options(
+            &cookie_store,
+            &mut writer,
+            &SaveOptions::compact().with_trailing_newline(false),
+        )
+        .unwrap();
+        let string = String::from_utf8(writer).unwrap();
+        assert!(string.starts_with(r#"[{"raw_cookie":"2=two;"#));
+        assert!(string.ends_with(r#""expires":{"AtUtc":"2100-08-03T00:38:37Z"}}]"#));
+
+        let mut writer = Vec::new();
+        save_with_options(
+            &cookie_store,
+            &mut writer,
+            &SaveOptions::pretty().with_indent(4),
+        )
+        .unwrap();
+        let string = String::from_utf8(writer).unwrap();
+        assert!(string.starts_with("[\n    {\n        \"raw_cookie\": "));
+        assert!(string.ends_with("}\n]\n"));
+        assert_eq!(1, load(string.as_bytes()).unwrap().len());
+    }
+
+    #[test]
+    fn save_with_timestamp_format() {
+        use super::super::{SaveOptions, TimestampFormat};
+        use super::save_with_options;
+
+        let cookie_store = load(cookie().as_bytes()).unwrap();
+        let mut writer = Vec::new();
+        save_with_options(
+            &cookie_store,
+            &mut writer,
+            &SaveOptions::compact().with_timestamp_format(TimestampFormat::UnixSeconds),
+        )
+        .unwrap();
+        let string = String::from_utf8(writer).unwrap();
+        assert!(string.contains(r#""expires":{"AtUtc":4120936717}"#));
+
+        // timestamps in either format are loaded
+        let loaded = load(string.as_bytes()).unwrap();
+        assert_eq!(1, loaded.len());
+        assert_eq!(
+            cookie_store.get("test.com", "/", "2").unwrap().expires,
+            loaded.get("test.com", "/", "2").unwrap().expires
+        );
+        let mut writer = Vec::new();
+        save_with_options(&loaded, &mut writer, &SaveOptions::default()).unwrap();
+        assert_eq!(cookie(), String::from_utf8(writer).unwrap());
+        assert!(load(&br#"[{"raw_cookie": "1=one", "path": ["/", true], "domain": {"HostOnly": "test.com"}, "expires": {"AtUtc": true}}]"#[..]).is_err());
+
+        // the format is restored when unwinding
+        let unwound = std::panic::catch_unwind(|| {
+            crate::rfc3339_fmt::with_timestamp_format(TimestampFormat::UnixSeconds, || panic!())
+        });
+        assert!(unwound.is_err());
+        let mut writer = Vec::new();
+        save_with_options(&loaded, &mut writer, &SaveOptions::default()).unwrap();
+        assert_eq!(cookie(), String::from_utf8(writer).unwrap());
+    }
+
+    #[test]
+    fn save_by_domain() {
+        use super::super::{Layout, SaveOptions};
+        use super::save_with_options;
+        use crate::test_util;
+
+        let mut cookie_store = load(cookie().as_bytes()).unwrap();
+        let url = test_util::url("http://www.example.com/");
+        cookie_store
+            .parse("a=1; Domain=example.com; Max-Age=3600", &url)
+            .unwrap();
+        cookie_store.parse("b=2; Max-Age=3600", &url).unwrap();
+        let mut writer = Vec::new();
+        save_with_options(
+            &cookie_store,
+            &mut writer,
+            &SaveOptions::compact().with_layout(Layout::ByDomain),
+        )
+        .unwrap();
+        let string = String::from_utf8(writer).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&string).unwrap();
+        let domains = value.as_object().unwrap();
+        assert_eq!(
+            vec!["example.com", "test.com", "www.example.com"],
+            domains.keys().collect::<Vec<_>>()
+        );
+        assert!(domains
+            .values()
+            .all(|cookies| cookies.as_array().unwrap().len() == 1));
+
+        let loaded = load(string.as_bytes()).unwrap();
+        assert_eq!(3, loaded.len());
+        assert_eq!("1", loaded.get("example.com", "/", "a").unwrap().value());
+        assert!(load(&b"{\"example.com\": 1}"[..]).is_err());
+    }
+
+    #[test]
+    fn save_with_config() {
+        use super::super::StoreConfig;
+        use super::save_with_config;
+        use crate::{CookieStore, DateParsing, EvictionPolicy, OverflowPolicy};
+
+        let store = load(cookie().as_bytes())
+            .unwrap()
+            .with_max_cookies(10)
+            .with_overflow_policy(OverflowPolicy::Evict(EvictionPolicy::SoonestExpiring))
+            .with_date_parsing(DateParsing::Lenient)
+            .with_port_bound(true);
+        let mut writer = Vec::new();
+        save_with_config(&store, &mut writer).unwrap();
+        let string = String::from_utf8(writer).unwrap();
+        assert!(string.starts_with("{\n  \"config\": {"));
+        assert!(string.contains("\"max_cookies\": 10"));
+
+        let loaded = load(string.as_bytes()).unwrap();
+        assert_eq!(1, loaded.len());
+        assert_eq!(store.config(), loaded.config());
+        assert_eq!(Some(10), loaded.config().max_cookies);
+        // data lacking a config, or with a partial config, loads with default settings
+        assert_eq!(
+            CookieStore::default().config(),
+            load(cookie().as_bytes()).unwrap().config()
+        );
+        let partial = format!(
+            r#"{{"config": {{"port_bound": true}}, "cookies": {}}}"#,
+            cookie()
+        );
+        let loaded = load(partial.as_bytes()).unwrap();
+        assert_eq!(
+            StoreConfig {
+                port_bound: true,
+                ..StoreConfig::default()
+            },
+            loaded.config()
+        );
+        assert_eq!(1, loaded.len());
+    }
+
+    #[test]
+    fn save_with_tombstones() {
+        use super::save_with_config;
+        use crate::MergeStrategy;
+
+        let original = load(cookie().as_bytes()).unwrap();
+        let mut store = original.clone().with_tombstones(time::Duration::days(30));
+        let id = store.iter_any().next().unwrap().identity();
+        assert!(store.remove_by_id(&id).is_some());
+        let mut writer = Vec::new();
+        save_with_config(&store, &mut writer).unwrap();
+        let string = String::from_utf8(writer).unwrap();
+        assert!(string.contains("\"tombstones\": ["));
+
+        let mut loaded = load(string.as_bytes()).unwrap();
+        assert_eq!(store.config(), loaded.config());
+        let tombstones = loaded.tombstones();
+        assert_eq!(1, tombstones.len());
+        assert_eq!(id, tombstones[0].cookie);
+        assert_eq!(
+            store.tombstones()[0].deleted_at.unix_timestamp(),
+            tombstones[0].deleted_at.unix_timestamp()
+        );
+        // the loaded tombstone prevents the deleted cookie being merged back in
+        loaded.merge(original, MergeStrategy::PreferOther);
+        assert!(loaded.is_empty());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn load_legacy_layout() {
+        let store = load(cookie().as_bytes()).unwrap();
+        let mut legacy = Vec::new();
+        store.save_json(&mut legacy).unwrap();
+        legacy.extend_from_slice(b"\n");
+        let expired = load_all(cookie_expired().as_bytes()).unwrap();
+        expired
+            .save_incl_expired_and_nonpersistent_json(&mut legacy)
+            .unwrap();
+
+        assert_eq!(1, load(&legacy[..]).unwrap().len());
+        assert_eq!(2, load_all(&legacy[..]).unwrap().len());
+        let mut writer = Vec::new();
+        save(&load(&legacy[..]).unwrap(), &mut writer).unwrap();
+        assert_eq!(cookie(), String::from_utf8(writer).unwrap());
+        // the legacy methods load the canonical format
+        assert_eq!(
+            1,
+            crate::CookieStore::load_json(cookie().as_bytes())
+                .unwrap()
+                .len()
+        );
+        assert!(load(&b"{\"raw_cookie\": \"1=one\"}"[..]).is_err());
+    }
 }
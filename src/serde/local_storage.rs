@@ -0,0 +1,92 @@
+//! Persistence to the browser's
+//! [`window.localStorage`](https://developer.mozilla.org/en-US/docs/Web/API/Window/localStorage),
+//! storing the store under a given key using the same JSON format as [crate::serde::json].
+//! Requires feature `local_storage`
+
+use web_sys::wasm_bindgen::JsValue;
+
+use crate::cookie_store::{CookieStore, StoreResult};
+
+/// An error returned by the browser when accessing `localStorage`
+#[derive(Debug)]
+pub struct LocalStorageError(String);
+
+impl std::fmt::Display for LocalStorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "localStorage error: {}", self.0)
+    }
+}
+
+impl std::error::Error for LocalStorageError {}
+
+impl From<JsValue> for LocalStorageError {
+    fn from(e: JsValue) -> Self {
+        LocalStorageError(format!("{:?}", e))
+    }
+}
+
+fn local_storage() -> Result<web_sys::Storage, LocalStorageError> {
+    web_sys::window()
+        .ok_or_else(|| LocalStorageError("no global `window` exists".to_owned()))?
+        .local_storage()?
+        .ok_or_else(|| LocalStorageError("`window.localStorage` is not available".to_owned()))
+}
+
+fn load_from(key: &str, include_expired: bool) -> StoreResult<CookieStore> {
+    match local_storage()?
+        .get_item(key)
+        .map_err(LocalStorageError::from)?
+    {
+        Some(cookies) if include_expired => super::json::load_all(cookies.as_bytes()),
+        Some(cookies) => super::json::load(cookies.as_bytes()),
+        None => Ok(CookieStore::default()),
+    }
+}
+
+/// Load JSON-formatted cookies stored under `key` in `localStorage`, skipping any __expired__
+/// cookies. If nothing is stored under `key`, an empty `CookieStore` is returned.
+pub fn load(key: &str) -> StoreResult<CookieStore> {
+    load_from(key, false)
+}
+
+/// Load JSON-formatted cookies stored under `key` in `localStorage`, loading both __expired__
+/// and __unexpired__ cookies. If nothing is stored under `key`, an empty `CookieStore` is
+/// returned.
+pub fn load_all(key: &str) -> StoreResult<CookieStore> {
+    load_from(key, true)
+}
+
+/// Serialize any __unexpired__ and __persistent__ cookies in the store to JSON format and
+/// store them under `key` in `localStorage`, replacing any existing value.
+pub fn save(cookie_store: &CookieStore, key: &str) -> StoreResult<()> {
+    let mut cookies = Vec::new();
+    super::json::save(cookie_store, &mut cookies)?;
+    set_item(key, cookies)
+}
+
+/// Serialize all (including __expired__ and __non-persistent__) cookies in the store to JSON
+/// format and store them under `key` in `localStorage`, replacing any existing value.
+pub fn save_incl_expired_and_nonpersistent(
+    cookie_store: &CookieStore,
+    key: &str,
+) -> StoreResult<()> {
+    let mut cookies = Vec::new();
+    super::json::save_incl_expired_and_nonpersistent(cookie_store, &mut cookies)?;
+    set_item(key, cookies)
+}
+
+/// Remove any cookies stored under `key` in `localStorage`.
+pub fn remove(key: &str) -> StoreResult<()> {
+    local_storage()?
+        .remove_item(key)
+        .map_err(LocalStorageError::from)?;
+    Ok(())
+}
+
+fn set_item(key: &str, cookies: Vec<u8>) -> StoreResult<()> {
+    let cookies = String::from_utf8(cookies)?;
+    local_storage()?
+        .set_item(key, &cookies)
+        .map_err(LocalStorageError::from)?;
+    Ok(())
+}
@@ -0,0 +1,171 @@
+//! De/serialization via the NDJSON ([JSON Lines](https://jsonlines.org/)) format, in which each
+//! line holds a single cookie in the same format as the cookies of [`super::json`]. Cookies are
+//! read and written one at a time, so large stores may be streamed without holding the entire
+//! serialization in memory.
+//! Requires feature `serde_json`
+
+use std::io::{BufRead, Write};
+
+use super::LoadWarning;
+use crate::cookie::Cookie;
+use crate::cookie_store::{CookieStore, StoreResult};
+use crate::utils::Buffer;
+
+/// Load NDJSON-formatted cookies from `reader`, skipping any __expired__ cookies. If a cookie
+/// appears more than once (e.g. after [`append`]ing an updated or expired cookie), the last
+/// occurrence is loaded.
+pub fn load<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
+    load_from(reader, false).map(|(cookie_store, _)| cookie_store)
+}
+
+/// Load NDJSON-formatted cookies from `reader`, loading both __expired__ and __unexpired__
+/// cookies. If a cookie appears more than once, the last occurrence is loaded.
+pub fn load_all<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
+    load_from(reader, true).map(|(cookie_store, _)| cookie_store)
+}
+
+/// Load NDJSON-formatted cookies from `reader`, skipping any __expired__ cookies, and returning a
+/// [`LoadWarning`] for each data-quality issue encountered. As a cookie appearing more than once
+/// is expected of a file which has been [`append`]ed to, each earlier occurrence is reported as a
+/// [`LoadWarning::Duplicate`].
+pub fn load_with_warnings<R: BufRead>(reader: R) -> StoreResult<(CookieStore, Vec<LoadWarning>)> {
+    load_from(reader, false)
+}
+
+/// Load NDJSON-formatted cookies from `reader`, loading both __expired__ and __unexpired__
+/// cookies, and returning a [`LoadWarning`] for each data-quality issue encountered
+pub fn load_all_with_warnings<R: BufRead>(
+    reader: R,
+) -> StoreResult<(CookieStore, Vec<LoadWarning>)> {
+    load_from(reader, true)
+}
+
+fn load_from<R: BufRead>(
+    reader: R,
+    include_expired: bool,
+) -> StoreResult<(CookieStore, Vec<LoadWarning>)> {
+    let cookies = reader.lines().filter_map(|line| match line {
+        Ok(line) if line.trim().is_empty() => None,
+        Ok(line) => {
+            let line = Buffer::from(line);
+            Some(serde_json::from_str::<Cookie<'static>>(&line).map_err(Into::into))
+        }
+        Err(e) => Some(Err(crate::Error::from(e))),
+    });
+    // the last occurrence of a cookie is loaded even if expired, so __expired__ cookies are only
+    // removed once all cookies are loaded
+    let (mut cookie_store, mut warnings) = CookieStore::from_cookies_with_warnings(cookies, true)?;
+    if !include_expired {
+        let now_utc = cookie_store.now_utc();
+        cookie_store.retain(|c| {
+            let expired = c.expires_by(&now_utc);
+            if expired {
+                warnings.push(LoadWarning::Expired(c.identity()));
+            }
+            !expired
+        });
+        cookie_store.mark_clean();
+    }
+    Ok((cookie_store, warnings))
+}
+
+/// Serialize any __unexpired__ and __persistent__ cookies in the store to NDJSON format and
+/// write them to `writer`
+pub fn save<W: Write>(cookie_store: &CookieStore, writer: &mut W) -> StoreResult<()> {
+    append(cookie_store.iter_saved(), writer)
+}
+
+/// Serialize all (including __expired__ and __non-persistent__) cookies in the store to NDJSON
+/// format and write them to `writer`
+pub fn save_incl_expired_and_nonpersistent<W: Write>(
+    cookie_store: &CookieStore,
+    writer: &mut W,
+) -> StoreResult<()> {
+    append(cookie_store.iter_any(), writer)
+}
+
+/// Serialize `cookies` to NDJSON format and write them to `writer`, e.g. to append newly
+/// inserted or updated cookies to a file previously written by [`save`]
+pub fn append<'a, I, W>(cookies: I, writer: &mut W) -> StoreResult<()>
+where
+    I: IntoIterator<Item = &'a Cookie<'static>>,
+    W: Write,
+{
+    for cookie in cookies {
+        serde_json::to_writer(&mut *writer, cookie)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        append, load, load_all, load_all_with_warnings, load_with_warnings, save,
+        save_incl_expired_and_nonpersistent,
+    };
+    use crate::cookie_store::CookieStore;
+    use crate::serde::LoadWarning;
+    use crate::test_util;
+    use crate::CookieIdentity;
+
+    #[test]
+    fn save_load() {
+        let url = test_util::url("http://example.com/foo/bar");
+        let mut store = CookieStore::default();
+        store.parse("cookie1=1; Max-Age=3600", &url).unwrap();
+        store.parse("cookie2=2; Max-Age=3600", &url).unwrap();
+        store.parse("session=3", &url).unwrap();
+
+        let mut saved = Vec::new();
+        save(&store, &mut saved).unwrap();
+        let lines = String::from_utf8(saved.clone()).unwrap();
+        assert_eq!(2, lines.lines().count());
+        assert!(lines
+            .lines()
+            .all(|line| line.starts_with("{\"raw_cookie\":")));
+        let loaded = load(&saved[..]).unwrap();
+        assert_eq!(2, loaded.len());
+        assert!(store
+            .diff(&loaded)
+            .added
+            .iter()
+            .all(|c| c.name() == "session"));
+
+        let mut saved = Vec::new();
+        save_incl_expired_and_nonpersistent(&store, &mut saved).unwrap();
+        assert_eq!(3, load_all(&saved[..]).unwrap().len());
+
+        // appended cookies take precedence over those previously saved
+        let mut saved = Vec::new();
+        save(&store, &mut saved).unwrap();
+        saved.extend_from_slice(b"\n");
+        store.parse("cookie1=updated; Max-Age=3600", &url).unwrap();
+        store.parse("cookie2=expired; Max-Age=0", &url).unwrap();
+        append(
+            store.iter_any().filter(|c| c.name() != "session"),
+            &mut saved,
+        )
+        .unwrap();
+        let loaded = load(&saved[..]).unwrap();
+        assert_eq!(1, loaded.len());
+        assert_eq!(
+            "updated",
+            loaded.get_for_url(&url, "cookie1").unwrap().value()
+        );
+        assert_eq!(2, load_all(&saved[..]).unwrap().len());
+
+        let (loaded, warnings) = load_with_warnings(&saved[..]).unwrap();
+        assert_eq!(1, loaded.len());
+        let id = |name| CookieIdentity::new("example.com", "/foo", name);
+        assert_eq!(3, warnings.len());
+        assert!(warnings.contains(&LoadWarning::Duplicate(id("cookie1"))));
+        assert!(warnings.contains(&LoadWarning::Duplicate(id("cookie2"))));
+        assert!(warnings.contains(&LoadWarning::Expired(id("cookie2"))));
+        let (loaded, warnings) = load_all_with_warnings(&saved[..]).unwrap();
+        assert_eq!(2, loaded.len());
+        assert_eq!(2, warnings.len());
+
+        assert!(load(&b"{\"raw_cookie\":"[..]).is_err());
+    }
+}
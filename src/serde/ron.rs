@@ -3,16 +3,35 @@
 
 use std::io::{BufRead, Write};
 
+use super::{Layout, LoadWarning, SaveOptions};
+use crate::cookie::Cookie;
 use crate::cookie_store::{StoreResult, CookieStore};
 
-/// Load RON-formatted cookies from `reader`, skipping any __expired__ cookies
+/// Load RON-formatted cookies from `reader`, skipping any __expired__ cookies. Data written by
+/// [`save_with_config`] is loaded with its persisted configuration applied to the store.
 pub fn load<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
-    super::load(reader, |cookies| ron::from_str(cookies))
+    super::load_from_with_warnings(reader, super::Persisted::from_str::<Ron>, false)
+        .map(|(cookie_store, _)| cookie_store)
 }
 
 /// Load RON-formatted cookies from `reader`, loading both __expired__ and __unexpired__ cookies
 pub fn load_all<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
-    super::load_all(reader, |cookies| ron::from_str(cookies))
+    super::load_from_with_warnings(reader, super::Persisted::from_str::<Ron>, true)
+        .map(|(cookie_store, _)| cookie_store)
+}
+
+/// Load RON-formatted cookies from `reader`, skipping any __expired__ cookies, and returning a
+/// [`LoadWarning`] for each data-quality issue encountered, e.g. a duplicated cookie
+pub fn load_with_warnings<R: BufRead>(reader: R) -> StoreResult<(CookieStore, Vec<LoadWarning>)> {
+    super::load_from_with_warnings(reader, super::Persisted::from_str::<Ron>, false)
+}
+
+/// Load RON-formatted cookies from `reader`, loading both __expired__ and __unexpired__ cookies,
+/// and returning a [`LoadWarning`] for each data-quality issue encountered
+pub fn load_all_with_warnings<R: BufRead>(
+    reader: R,
+) -> StoreResult<(CookieStore, Vec<LoadWarning>)> {
+    super::load_from_with_warnings(reader, super::Persisted::from_str::<Ron>, true)
 }
 
 /// Serialize any __unexpired__ and __persistent__ cookies in the store to JSON format and
@@ -23,6 +42,25 @@ pub fn save<W: Write>(cookie_store: &CookieStore, writer: &mut W) -> StoreResult
     })
 }
 
+/// Serialize the __unexpired__ and __persistent__ cookies in the store for which `predicate`
+/// returns `true` to RON format and write them to `writer`
+pub fn save_filtered<W, P>(
+    cookie_store: &CookieStore,
+    writer: &mut W,
+    predicate: P,
+) -> StoreResult<()>
+where
+    W: Write,
+    P: FnMut(&Cookie<'static>) -> bool,
+{
+    super::save_filtered(
+        cookie_store,
+        writer,
+        |string| ::ron::ser::to_string_pretty(string, ron::ser::PrettyConfig::default()),
+        predicate,
+    )
+}
+
 /// Serialize all (including __expired__ and __non-persistent__) cookies in the store to RON format and write them to `writer`
 pub fn save_incl_expired_and_nonpersistent<W: Write>(
     cookie_store: &CookieStore,
@@ -33,6 +71,78 @@ pub fn save_incl_expired_and_nonpersistent<W: Write>(
     })
 }
 
+/// Serialize any __unexpired__ and __persistent__ cookies in the store to RON format, per
+/// `options`, and write them to `writer`
+pub fn save_with_options<W: Write>(
+    cookie_store: &CookieStore,
+    writer: &mut W,
+    options: &SaveOptions,
+) -> StoreResult<()> {
+    super::save_cookies(
+        cookie_store,
+        writer,
+        |cookies| to_string(cookies, options),
+        false,
+        options.trailing_newline,
+    )
+}
+
+/// Serialize all (including __expired__ and __non-persistent__) cookies in the store to RON
+/// format, per `options`, and write them to `writer`
+pub fn save_incl_expired_and_nonpersistent_with_options<W: Write>(
+    cookie_store: &CookieStore,
+    writer: &mut W,
+    options: &SaveOptions,
+) -> StoreResult<()> {
+    super::save_cookies(
+        cookie_store,
+        writer,
+        |cookies| to_string(cookies, options),
+        true,
+        options.trailing_newline,
+    )
+}
+
+/// Serialize any __unexpired__ and __persistent__ cookies in the store to RON format, along with
+/// the [`StoreConfig`](super::StoreConfig) of the store, and write them to `writer`. The output is a RON struct with
+/// `config` and `cookies` fields, which may be loaded by any of the `load` functions of this
+/// module.
+pub fn save_with_config<W: Write>(cookie_store: &CookieStore, writer: &mut W) -> StoreResult<()> {
+    super::save_with_config(cookie_store, writer, |envelope| {
+        ::ron::ser::to_string_pretty(envelope, ron::ser::PrettyConfig::default())
+    })
+}
+
+struct Ron;
+
+impl super::Format for Ron {
+    type Error = ron::error::SpannedError;
+
+    const LEGACY_LAYOUT: bool = false;
+
+    fn from_str<T: serde::de::DeserializeOwned>(s: &str) -> ron::error::SpannedResult<T> {
+        ron::from_str(s)
+    }
+}
+
+fn to_string(cookies: &Vec<Cookie<'static>>, options: &SaveOptions) -> ron::Result<String> {
+    crate::rfc3339_fmt::with_timestamp_format(options.timestamps, || match options.layout {
+        Layout::Sequence => serialize(cookies, options),
+        Layout::ByDomain => serialize(&super::by_domain(cookies), options),
+    })
+}
+
+fn serialize<T: serde::Serialize>(value: &T, options: &SaveOptions) -> ron::Result<String> {
+    if !options.pretty {
+        return ron::ser::to_string(value);
+    }
+    let mut config = ron::ser::PrettyConfig::default();
+    if let Some(indent) = options.indent {
+        config = config.indentor(" ".repeat(indent));
+    }
+    ron::ser::to_string_pretty(value, config)
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::BufWriter;
@@ -129,4 +239,76 @@ mod tests {
         let string = String::from_utf8(writer.into_inner().unwrap()).unwrap();
         assert_eq!(cookie, string);
     }
+
+    #[test]
+    fn save_with_options() {
+        use super::super::{Layout, SaveOptions};
+        use super::save_with_options;
+
+        let cookie_store = load(Into::<&[u8]>::into(cookie().as_bytes())).unwrap();
+
+        let mut writer = Vec::new();
+        save_with_options(&cookie_store, &mut writer, &SaveOptions::default()).unwrap();
+        assert_eq!(cookie(), String::from_utf8(writer).unwrap());
+
+        let mut writer = Vec::new();
+        save_with_options(
+            &cookie_store,
+            &mut writer,
+            &SaveOptions::compact().with_trailing_newline(false),
+        )
+        .unwrap();
+        let string = String::from_utf8(writer).unwrap();
+        assert!(string.starts_with(r#"[(raw_cookie:"2=two;"#));
+        assert!(string.ends_with(r#"expires:AtUtc("2100-08-03T00:38:37Z"))]"#));
+
+        let mut writer = Vec::new();
+        save_with_options(
+            &cookie_store,
+            &mut writer,
+            &SaveOptions::pretty().with_indent(2),
+        )
+        .unwrap();
+        let string = String::from_utf8(writer).unwrap();
+        assert!(string.starts_with("[\n  (\n    raw_cookie: "));
+        assert_eq!(1, load(string.as_bytes()).unwrap().len());
+
+        let mut writer = Vec::new();
+        save_with_options(
+            &cookie_store,
+            &mut writer,
+            &SaveOptions::compact().with_layout(Layout::ByDomain),
+        )
+        .unwrap();
+        let string = String::from_utf8(writer).unwrap();
+        assert!(string.starts_with(r#"{"test.com":[(raw_cookie:"2=two;"#));
+        assert_eq!(1, load(string.as_bytes()).unwrap().len());
+    }
+
+    #[test]
+    fn save_with_config() {
+        use super::save_with_config;
+
+        let store = load(cookie().as_bytes())
+            .unwrap()
+            .with_max_cookies_per_domain(5)
+            .with_persist_session_cookies(true);
+        let mut writer = Vec::new();
+        save_with_config(&store, &mut writer).unwrap();
+        let string = String::from_utf8(writer).unwrap();
+        assert!(string.starts_with("(\n    config: ("));
+
+        let loaded = load(string.as_bytes()).unwrap();
+        assert_eq!(1, loaded.len());
+        assert_eq!(store.config(), loaded.config());
+        assert_eq!(Some(5), loaded.config().max_cookies_per_domain);
+    }
+
+    #[test]
+    fn no_legacy_layout() {
+        // a single cookie per line is not a layout of the RON format
+        let line = r#"(raw_cookie: "2=two", path: ("/", true), domain: HostOnly("test.com"), expires: SessionEnd)"#;
+        assert!(load(line.as_bytes()).is_err());
+        assert!(load(format!("[{}]", line).as_bytes()).is_ok());
+    }
 }
@@ -0,0 +1,213 @@
+//! Integrity protection for persisted cookies: the data written by any of the `save` functions of
+//! the format-specific modules (e.g. [`crate::serde::json::save`]) is followed by a trailing line
+//! holding its HMAC-SHA256 under a caller-provided key, which is verified before the cookies are
+//! loaded, so that tampering with (or corruption of) a persisted store is detected.
+//! Requires feature `signed`
+//!
+//! ```
+//! # fn main() -> cookie_store::Result<()> {
+//! use cookie_store::serde::{json, signed};
+//!
+//! let key = b"an application secret";
+//! let store = cookie_store::CookieStore::default();
+//! let mut writer = Vec::new();
+//! signed::save(&store, &mut writer, key, json::save)?;
+//! let store = signed::load(&writer[..], key, json::load)?;
+//! # assert!(signed::load(&writer[..], b"another secret", json::load).is_err());
+//! # let _ = store;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{self, BufRead, Cursor, Read, Write};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::cookie_store::{CookieStore, StoreResult};
+use crate::utils::Buffer;
+
+/// The prefix of the trailing line holding the hex-encoded HMAC of the data
+const TRAILER_PREFIX: &str = "hmac-sha256:";
+
+/// The failure to verify the integrity of signed data loaded by [`load`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureError {
+    /// The data does not end with a well-formed HMAC line, e.g. as it was not saved by [`save`]
+    Missing,
+    /// The HMAC of the data does not match, as the data was modified or signed with another key
+    Mismatch,
+}
+
+impl std::fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureError::Missing => write!(f, "signed cookie data has no HMAC"),
+            SignatureError::Mismatch => write!(f, "HMAC of signed cookie data does not match"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// The verified data of a store saved by [`save`], as read by the `load` function passed to
+/// [`load`]
+pub struct Payload(Cursor<Buffer>);
+
+impl Read for Payload {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl BufRead for Payload {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.0.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.0.consume(amt)
+    }
+}
+
+fn mac(key: &[u8], payload: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// Decode the hex-encoded `hex`, or `None` if not well-formed
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+        })
+        .collect()
+}
+
+/// Save the store with `save` (e.g. [`crate::serde::json::save`]), writing the saved data to
+/// `writer` followed by a line holding its HMAC-SHA256 under `key`
+pub fn save<W, F>(
+    cookie_store: &CookieStore,
+    writer: &mut W,
+    key: &[u8],
+    save: F,
+) -> StoreResult<()>
+where
+    W: Write,
+    F: FnOnce(&CookieStore, &mut Vec<u8>) -> StoreResult<()>,
+{
+    let mut payload = Vec::new();
+    save(cookie_store, &mut payload)?;
+    let mut payload = Buffer::from(String::from_utf8(payload)?);
+    if !payload.is_empty() && !payload.ends_with('\n') {
+        payload.push('\n');
+    }
+    let digest = mac(key, &payload).finalize().into_bytes();
+    write!(writer, "{}{}", payload.as_str(), TRAILER_PREFIX)?;
+    for b in digest {
+        write!(writer, "{:02x}", b)?;
+    }
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Load a store saved by [`save`] from `reader` with `load` (e.g. [`crate::serde::json::load`]),
+/// after verifying the HMAC-SHA256 of the saved data under `key`. Fails with a [`SignatureError`]
+/// if the data is not signed, or was modified or signed with another key.
+pub fn load<R, F>(mut reader: R, key: &[u8], load: F) -> StoreResult<CookieStore>
+where
+    R: BufRead,
+    F: FnOnce(Payload) -> StoreResult<CookieStore>,
+{
+    let mut signed = Buffer::default();
+    reader.read_to_string(&mut signed)?;
+    let payload_len = {
+        let content = signed.strip_suffix('\n').unwrap_or(signed.as_str());
+        let (payload, trailer) = match content.rfind('\n') {
+            Some(i) => content.split_at(i + 1),
+            None => ("", content),
+        };
+        let digest = trailer
+            .strip_prefix(TRAILER_PREFIX)
+            .and_then(decode_hex)
+            .ok_or(SignatureError::Missing)?;
+        mac(key, payload)
+            .verify_slice(&digest)
+            .map_err(|_| SignatureError::Mismatch)?;
+        payload.len()
+    };
+    signed.truncate(payload_len);
+    load(Payload(Cursor::new(signed)))
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use super::{load, save, SignatureError};
+    use crate::serde::json;
+    use crate::test_util;
+    use crate::CookieStore;
+
+    const KEY: &[u8] = b"secret";
+
+    fn signed() -> String {
+        let mut store = CookieStore::default();
+        store
+            .parse(
+                "cookie1=1; Max-Age=3600",
+                &test_util::url("http://example.com/"),
+            )
+            .unwrap();
+        let mut writer = Vec::new();
+        save(&store, &mut writer, KEY, json::save).unwrap();
+        String::from_utf8(writer).unwrap()
+    }
+
+    fn error(signed: &str, key: &[u8]) -> SignatureError {
+        *load(signed.as_bytes(), key, json::load)
+            .unwrap_err()
+            .downcast::<SignatureError>()
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let signed = signed();
+        assert!(signed.starts_with('['));
+        let trailer = signed.lines().last().unwrap();
+        assert_eq!(12 + 64, trailer.len());
+        assert!(trailer.starts_with("hmac-sha256:"));
+
+        let loaded = load(signed.as_bytes(), KEY, json::load).unwrap();
+        assert_eq!(
+            "1",
+            loaded.get("example.com", "/", "cookie1").unwrap().value()
+        );
+
+        let mut writer = Vec::new();
+        save(&loaded, &mut writer, KEY, json::save_with_config).unwrap();
+        assert_eq!(1, load(&writer[..], KEY, json::load).unwrap().len());
+    }
+
+    #[test]
+    fn tampered() {
+        let signed = signed();
+        assert_eq!(SignatureError::Mismatch, error(&signed, b"another secret"));
+        assert_eq!(
+            SignatureError::Mismatch,
+            error(&signed.replace("cookie1=1", "cookie1=2"), KEY)
+        );
+        let unsigned = signed.rsplit_once("hmac-sha256:").unwrap().0;
+        assert_eq!(SignatureError::Missing, error(unsigned, KEY));
+        assert_eq!(
+            SignatureError::Missing,
+            error(&signed.replace("hmac-sha256:", "hmac-sha256:zz"), KEY)
+        );
+        assert_eq!(SignatureError::Missing, error("", KEY));
+    }
+}
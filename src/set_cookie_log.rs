@@ -0,0 +1,250 @@
+//! Import of cookies from a log of `Set-Cookie` headers, e.g. as captured from HTTP traffic, and
+//! export of cookies as `Set-Cookie` headers
+
+use std::borrow::Cow;
+use std::io::{BufRead, Write};
+use std::ops::Deref;
+
+use url::Url;
+
+use crate::cookie::Cookie;
+use crate::cookie_domain::CookieDomain;
+use crate::cookie_expiration::CookieExpiration;
+use crate::cookie_store::{CookieStore, StoreResult};
+
+/// A record of a JSON-formatted `Set-Cookie` log
+#[cfg(feature = "serde_json")]
+#[derive(serde_derive::Deserialize)]
+struct Record {
+    url: String,
+    set_cookie: String,
+}
+
+/// Parse a single record of a `Set-Cookie` log into the request URL and the `Set-Cookie` header
+/// value.
+fn parse_record(record: &str) -> StoreResult<(Url, Cow<'_, str>)> {
+    #[cfg(feature = "serde_json")]
+    if record.starts_with('{') {
+        let record: Record = serde_json::from_str(record)?;
+        return Ok((
+            Url::parse(&record.url)?,
+            Cow::Owned(strip_header_name(&record.set_cookie).to_owned()),
+        ));
+    }
+    let (url, set_cookie) = record
+        .split_once('\t')
+        .ok_or("expected a tab-separated request URL and Set-Cookie header")?;
+    Ok((
+        Url::parse(url.trim())?,
+        Cow::Borrowed(strip_header_name(set_cookie)),
+    ))
+}
+
+/// Strip any leading `Set-Cookie:` header name from `header`
+fn strip_header_name(header: &str) -> &str {
+    let header = header.trim();
+    match header.split_once(':') {
+        Some((name, value)) if name.trim().eq_ignore_ascii_case("set-cookie") => value.trim(),
+        _ => header,
+    }
+}
+
+/// Render `cookie` as the value of a `Set-Cookie` header, with the normalized domain, path, and
+/// expiry of `cookie` in lieu of the attributes as originally received
+fn to_set_cookie(cookie: &Cookie<'_>) -> String {
+    let mut raw_cookie = cookie.deref().clone();
+    raw_cookie.set_path(String::from(&cookie.path));
+    match cookie.domain {
+        CookieDomain::Suffix(ref domain) => raw_cookie.set_domain(domain.clone()),
+        _ => raw_cookie.unset_domain(),
+    }
+    raw_cookie.set_max_age(None);
+    match cookie.expires {
+        CookieExpiration::AtUtc(expires) => raw_cookie.set_expires(expires),
+        CookieExpiration::SessionEnd => raw_cookie.unset_expires(),
+    }
+    raw_cookie.to_string()
+}
+
+impl CookieStore {
+    /// Load a `CookieStore` from a log of `Set-Cookie` headers; see
+    /// [`CookieStore::replay_set_cookie_log`] for the format of the log.
+    pub fn load_set_cookie_log<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
+        let mut cookie_store = CookieStore::default();
+        cookie_store.replay_set_cookie_log(reader)?;
+        cookie_store.mark_clean();
+        Ok(cookie_store)
+    }
+
+    /// Write each __unexpired__ cookie in the store to `writer` as a complete `Set-Cookie` header
+    /// line (e.g. `Set-Cookie: name=value; Path=/; Domain=example.com`), for use with HTTP tooling
+    /// which understands headers. The normalized domain, path, and expiry of each `Cookie` are
+    /// used; a host-only `Cookie` is rendered without a Domain attribute, and a Max-Age attribute
+    /// is rendered as the equivalent Expires attribute.
+    pub fn save_as_set_cookie_lines<W: Write>(&self, writer: &mut W) -> StoreResult<()> {
+        for cookie in self.iter_unexpired() {
+            writeln!(writer, "Set-Cookie: {}", to_set_cookie(cookie))?;
+        }
+        Ok(())
+    }
+
+    /// Render the cookies for `url` in the store (i.e. those returned by [`CookieStore::matches`])
+    /// as `Set-Cookie` header values, with attributes, e.g. for a test server or proxy to replay
+    /// the state of the store to a client; the attributes are rendered as for
+    /// [`CookieStore::save_as_set_cookie_lines`].
+    pub fn set_cookie_headers_for(&self, url: &Url) -> Vec<String> {
+        self.matches_iter(url).map(to_set_cookie).collect()
+    }
+
+    /// Insert the cookies of a log of `Set-Cookie` headers, in order, as if each header had been
+    /// received in response to a request to the paired URL. Each line of the log is a record of
+    /// either:
+    /// * the request URL and the `Set-Cookie` header, separated by a tab, e.g.
+    ///   `https://example.com/login<TAB>Set-Cookie: session=abc; Path=/`
+    /// * (with feature `serde_json`) a JSON object with `url` and `set_cookie` fields, e.g.
+    ///   `{"url": "https://example.com/login", "set_cookie": "session=abc; Path=/"}`
+    ///
+    /// The `Set-Cookie:` header name is optional. Empty lines and lines beginning with `#` are
+    /// ignored. As with [`CookieStore::store_response_cookies`], headers which fail to parse or
+    /// are rejected by [`CookieStore::insert`] are skipped; a malformed record is an error.
+    /// Returns the number of cookies inserted, updated, or expired.
+    pub fn replay_set_cookie_log<R: BufRead>(&mut self, reader: R) -> StoreResult<usize> {
+        let mut stored = 0;
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let record = line.trim();
+            if record.is_empty() || record.starts_with('#') {
+                continue;
+            }
+            let (url, set_cookie) = parse_record(record)
+                .map_err(|e| format!("invalid Set-Cookie log record on line {}: {}", i + 1, e))?;
+            if self.parse(&set_cookie, &url).is_ok() {
+                stored += 1;
+            }
+        }
+        Ok(stored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cookie_store::CookieStore;
+    use crate::test_util;
+
+    #[test]
+    fn load_set_cookie_log() {
+        let log = "# captured session\n\
+                   http://example.com/login\tSet-Cookie: session=abc; Path=/\n\
+                   \n\
+                   http://example.com/foo/bar\tcookie1=value1\n\
+                   http://example.com/\tset-cookie:cookie2=value2; Domain=example.org\n\
+                   http://example.com/\tSet-Cookie: session=def; Path=/\n";
+        let store = CookieStore::load_set_cookie_log(log.as_bytes()).unwrap();
+        assert!(!store.is_dirty());
+        assert_eq!(2, store.len());
+        let url = test_util::url("http://example.com/foo/bar");
+        assert_eq!("def", store.get_for_url(&url, "session").unwrap().value());
+        assert_eq!(
+            "value1",
+            store.get_for_url(&url, "cookie1").unwrap().value()
+        );
+
+        let mut store = CookieStore::default();
+        assert_eq!(
+            1,
+            store
+                .replay_set_cookie_log("http://example.com/\tcookie1=value1".as_bytes())
+                .unwrap()
+        );
+        assert!(store
+            .replay_set_cookie_log("cookie1=value1".as_bytes())
+            .is_err());
+        assert!(store
+            .replay_set_cookie_log("not a url\tcookie1=value1".as_bytes())
+            .is_err());
+    }
+
+    #[test]
+    fn set_cookie_headers_for() {
+        let url = test_util::url("https://www.example.com/foo/bar");
+        let mut store = CookieStore::default();
+        store
+            .parse("cookie1=value1; Domain=example.com; Path=/; Secure", &url)
+            .unwrap();
+        store.parse("cookie2=value2; Path=/baz", &url).unwrap();
+        store
+            .parse("cookie3=value3", &test_util::url("https://other.com/"))
+            .unwrap();
+        assert_eq!(
+            vec!["cookie1=value1; Secure; Path=/; Domain=example.com"],
+            store.set_cookie_headers_for(&url)
+        );
+        assert!(store
+            .set_cookie_headers_for(&test_util::url("http://www.example.com/"))
+            .is_empty());
+    }
+
+    #[test]
+    fn save_as_set_cookie_lines() {
+        let url = test_util::url("http://www.example.com/foo/bar");
+        let mut store = CookieStore::default();
+        store
+            .parse("cookie1=value1; Max-Age=3600; Secure; HttpOnly", &url)
+            .unwrap();
+        store
+            .parse(
+                "cookie2=value2; Domain=.Example.com; Path=/; SameSite=Lax",
+                &url,
+            )
+            .unwrap();
+        store.parse("cookie3=value3; Max-Age=0", &url).unwrap_err();
+        let mut lines = Vec::new();
+        store.save_as_set_cookie_lines(&mut lines).unwrap();
+        let lines = String::from_utf8(lines).unwrap();
+        let mut lines = lines.lines().collect::<Vec<_>>();
+        lines.sort_unstable();
+        assert_eq!(2, lines.len());
+        assert!(lines[0]
+            .starts_with("Set-Cookie: cookie1=value1; HttpOnly; Secure; Path=/foo; Expires="));
+        assert!(!lines[0].contains("Domain"));
+        assert!(!lines[0].contains("Max-Age"));
+        assert_eq!(
+            "Set-Cookie: cookie2=value2; SameSite=Lax; Path=/; Domain=example.com",
+            lines[1]
+        );
+
+        // the lines may be replayed into an equivalent store
+        let replayed = CookieStore::load_set_cookie_log(
+            lines
+                .iter()
+                .map(|line| format!("{}\t{}\n", url, line))
+                .collect::<String>()
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(2, replayed.len());
+        for cookie in store.iter_unexpired() {
+            let domain = String::from(&cookie.domain);
+            let replayed = replayed.get(&domain, &cookie.path, cookie.name()).unwrap();
+            assert_eq!(cookie.value(), replayed.value());
+            assert_eq!(cookie.domain, replayed.domain);
+            assert_eq!(&*cookie.path, &*replayed.path);
+            assert_eq!(cookie.expires, replayed.expires);
+        }
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn load_set_cookie_log_json() {
+        let log = r#"{"url": "http://example.com/", "set_cookie": "Set-Cookie: cookie1=value1"}
+http://example.com/	cookie2=value2
+{"url": "http://example.com/", "set_cookie": "cookie3=value3"}
+"#;
+        let store = CookieStore::load_set_cookie_log(log.as_bytes()).unwrap();
+        assert_eq!(3, store.len());
+        assert!(
+            CookieStore::load_set_cookie_log(r#"{"url": "http://example.com/"}"#.as_bytes())
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! Helpers for building [`Cookie`] and [`Url`] fixtures in tests. Requires feature `test-util`
+//!
+//! These helpers `panic!` on invalid input, and so are not intended for use outside of tests.
+
+use crate::cookie::Cookie;
+use time::{Duration, OffsetDateTime};
+use url::Url;
+
+/// Parse `url` as a `Url`
+#[inline]
+pub fn url(url: &str) -> Url {
+    Url::parse(url).unwrap()
+}
+
+/// Parse `cookie` as a `Cookie` received from `url_str`, appending an Expires attribute of
+/// `expires` and a Max-Age attribute of `max_age` (in seconds), if provided
+#[inline]
+pub fn make_cookie<'a>(
+    cookie: &str,
+    url_str: &str,
+    expires: Option<OffsetDateTime>,
+    max_age: Option<u64>,
+) -> Cookie<'a> {
+    Cookie::parse(
+        format!(
+            "{}{}{}",
+            cookie,
+            expires.map_or(String::from(""), |e| format!(
+                "; Expires={}",
+                e.format(time::macros::format_description!("[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT")).unwrap()
+            )),
+            max_age.map_or(String::from(""), |m| format!("; Max-Age={}", m))
+        ),
+        &url(url_str),
+    )
+    .unwrap()
+}
+
+/// The time `days` days from now (or ago, if negative)
+#[inline]
+pub fn in_days(days: i64) -> OffsetDateTime {
+    OffsetDateTime::now_utc() + Duration::days(days)
+}
+
+/// The time `mins` minutes from now (or ago, if negative)
+#[inline]
+pub fn in_minutes(mins: i64) -> OffsetDateTime {
+    OffsetDateTime::now_utc() + Duration::minutes(mins)
+}
+
+/// Serve a single HTTP request on a loopback port, responding with an empty `200 OK` response
+/// with the additional `headers` (each terminated by `\r\n`). Returns the `Url` being served and
+/// a handle resolving to the head of the request received.
+#[cfg(all(test, any(feature = "isahc", feature = "attohttpc")))]
+pub(crate) fn serve_once(headers: &str) -> (Url, std::thread::JoinHandle<String>) {
+    use std::io::{BufRead, BufReader, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = url(&format!("http://{}/", listener.local_addr().unwrap()));
+    let response = format!(
+        "HTTP/1.1 200 OK\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+        headers
+    );
+    let handle = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request = String::new();
+        while reader.read_line(&mut request).unwrap() > 2 {}
+        reader.get_mut().write_all(response.as_bytes()).unwrap();
+        request
+    });
+    (url, handle)
+}
@@ -0,0 +1,128 @@
+//! Records of the cookies deleted from a `CookieStore`, so that deletions may be propagated when
+//! stores are merged; see [`CookieStore::with_tombstones`](crate::CookieStore::with_tombstones)
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+#[cfg(feature = "serde")]
+use serde_derive::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+
+use crate::clock::SharedClock;
+use crate::cookie::CookieIdentity;
+use crate::cookie_store::StoreEvent;
+
+/// A record of a `Cookie` expired or removed from a `CookieStore`; see
+/// [`CookieStore::tombstones`](crate::CookieStore::tombstones)
+#[derive(PartialEq, Clone, Debug, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Tombstone {
+    /// The identity of the deleted `Cookie`
+    pub cookie: CookieIdentity,
+    /// The time of the deletion, per the [`Clock`](crate::Clock) of the store
+    #[cfg_attr(feature = "serde", serde(with = "crate::rfc3339_fmt"))]
+    #[cfg_attr(feature = "schemars", schemars(with = "crate::rfc3339_fmt::Timestamp"))]
+    pub deleted_at: OffsetDateTime,
+}
+
+/// The tombstones held by a `CookieStore`
+pub(crate) struct Tombstones {
+    clock: SharedClock,
+    retention: Duration,
+    entries: Mutex<HashMap<CookieIdentity, OffsetDateTime>>,
+}
+
+impl Tombstones {
+    pub(crate) fn new(clock: SharedClock, retention: Duration) -> Tombstones {
+        Tombstones {
+            clock,
+            retention,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn retention(&self) -> Duration {
+        self.retention
+    }
+
+    pub(crate) fn set_retention(&mut self, retention: Duration) {
+        self.retention = retention;
+    }
+
+    /// Record a tombstone for a `Cookie` expired or removed by `event`, or discard the tombstone
+    /// of a `Cookie` (re-)added by `event`
+    pub(crate) fn record(&self, event: &StoreEvent<'_>) {
+        let mut entries = self.lock();
+        match *event {
+            StoreEvent::Inserted(cookie) | StoreEvent::Updated { new: cookie, .. } => {
+                entries.remove(&cookie.identity());
+            }
+            StoreEvent::Expired(cookie) | StoreEvent::Removed(cookie) => {
+                let now = self.clock.now_utc();
+                let cutoff = now - self.retention;
+                entries.retain(|_, deleted_at| *deleted_at > cutoff);
+                entries.insert(cookie.identity(), now);
+            }
+        }
+    }
+
+    /// Add `tombstone`, unless a later tombstone of the same identity is already held
+    pub(crate) fn insert(&self, tombstone: Tombstone) {
+        let mut entries = self.lock();
+        let deleted_at = entries
+            .entry(tombstone.cookie)
+            .or_insert(tombstone.deleted_at);
+        if *deleted_at < tombstone.deleted_at {
+            *deleted_at = tombstone.deleted_at;
+        }
+    }
+
+    /// The time of the deletion of the `Cookie` identified by `id`, if within the retention window
+    pub(crate) fn get(&self, id: &CookieIdentity) -> Option<OffsetDateTime> {
+        let cutoff = self.clock.now_utc() - self.retention;
+        self.lock()
+            .get(id)
+            .copied()
+            .filter(|deleted_at| *deleted_at > cutoff)
+    }
+
+    /// The tombstones within the retention window, oldest first
+    pub(crate) fn entries(&self) -> Vec<Tombstone> {
+        let cutoff = self.clock.now_utc() - self.retention;
+        let mut tombstones = self
+            .lock()
+            .iter()
+            .filter(|(_, deleted_at)| **deleted_at > cutoff)
+            .map(|(cookie, deleted_at)| Tombstone {
+                cookie: cookie.clone(),
+                deleted_at: *deleted_at,
+            })
+            .collect::<Vec<_>>();
+        tombstones.sort_by(|a, b| {
+            a.deleted_at
+                .cmp(&b.deleted_at)
+                .then_with(|| a.cookie.cmp(&b.cookie))
+        });
+        tombstones
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<CookieIdentity, OffsetDateTime>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clone for Tombstones {
+    fn clone(&self) -> Self {
+        Tombstones {
+            clock: self.clock.clone(),
+            retention: self.retention,
+            entries: Mutex::new(self.lock().clone()),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use url::Url;
+
+use crate::utils::{is_glob_match, is_loopback};
+
+/// The policy deciding which request-uris are "trustworthy", i.e. to which cookies with the
+/// Secure attribute may be sent, and (if enabled via [`TrustworthyOrigins::with_enforce_on_insert`])
+/// from which they may be received. `https` request-uris are always trustworthy; by default, so
+/// are loopback hosts (`localhost`, `127.0.0.1`, `[::1]`, etc.) over any scheme. See
+/// [`CookieStore::with_trustworthy_origins`](crate::CookieStore::with_trustworthy_origins).
+#[derive(PartialEq, Clone, Debug, Eq)]
+pub struct TrustworthyOrigins {
+    /// Whether loopback hosts are trustworthy
+    loopback: bool,
+    /// Glob patterns of additional trustworthy hosts
+    hosts: Vec<String>,
+    /// Whether Secure cookies received from untrustworthy request-uris are rejected
+    enforce_on_insert: bool,
+}
+
+impl Default for TrustworthyOrigins {
+    fn default() -> Self {
+        TrustworthyOrigins {
+            loopback: true,
+            hosts: Vec::new(),
+            enforce_on_insert: false,
+        }
+    }
+}
+
+impl TrustworthyOrigins {
+    /// The default policy, under which `https` request-uris and loopback hosts are trustworthy
+    pub fn new() -> TrustworthyOrigins {
+        TrustworthyOrigins::default()
+    }
+
+    /// Only `https` request-uris are trustworthy
+    pub fn https_only() -> TrustworthyOrigins {
+        TrustworthyOrigins::new().with_loopback(false)
+    }
+
+    /// Specify whether loopback hosts are trustworthy
+    pub fn with_loopback(self, loopback: bool) -> TrustworthyOrigins {
+        TrustworthyOrigins { loopback, ..self }
+    }
+
+    /// Additionally trust hosts matching the glob `pattern`, in which `*` matches any (possibly
+    /// empty) sequence of characters and `?` any single character; e.g. `*.internal`
+    pub fn with_host(mut self, pattern: &str) -> TrustworthyOrigins {
+        self.hosts.push(pattern.to_ascii_lowercase());
+        self
+    }
+
+    /// Specify whether cookies with the Secure attribute received from request-uris which are not
+    /// trustworthy are rejected, per
+    /// [draft-ietf-httpbis-rfc6265bis](https://datatracker.ietf.org/doc/html/draft-ietf-httpbis-rfc6265bis#section-5.7).
+    /// Defaults to `false`, accepting such cookies as RFC6265 does.
+    pub fn with_enforce_on_insert(self, enforce_on_insert: bool) -> TrustworthyOrigins {
+        TrustworthyOrigins {
+            enforce_on_insert,
+            ..self
+        }
+    }
+
+    /// Returns true if a cookie with the Secure attribute received from `url` should be rejected
+    pub(crate) fn rejects_secure_from(&self, url: &Url) -> bool {
+        self.enforce_on_insert && !self.is_trustworthy(url)
+    }
+
+    /// Returns true if `url` is trustworthy under this policy
+    pub fn is_trustworthy(&self, url: &Url) -> bool {
+        url.scheme() == "https"
+            || (self.loopback && is_loopback(url))
+            || url.host_str().map_or(false, |host| {
+                self.hosts
+                    .iter()
+                    .any(|pattern| is_glob_match(pattern, host))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrustworthyOrigins;
+    use crate::test_util::url;
+
+    #[test]
+    fn is_trustworthy() {
+        let default = TrustworthyOrigins::default();
+        assert!(default.is_trustworthy(&url("https://example.com")));
+        assert!(default.is_trustworthy(&url("http://localhost:8080")));
+        assert!(default.is_trustworthy(&url("http://127.0.0.1")));
+        assert!(!default.is_trustworthy(&url("http://example.com")));
+        assert!(!default.is_trustworthy(&url("http://api.internal")));
+
+        let https_only = TrustworthyOrigins::https_only().with_host("*.Internal");
+        assert!(https_only.is_trustworthy(&url("https://example.com")));
+        assert!(!https_only.is_trustworthy(&url("http://localhost:8080")));
+        assert!(!https_only.is_trustworthy(&url("http://[::1]")));
+        assert!(https_only.is_trustworthy(&url("http://api.internal")));
+        assert!(!https_only.is_trustworthy(&url("http://internal")));
+        assert!(!https_only.rejects_secure_from(&url("http://example.com")));
+        let enforced = https_only.with_enforce_on_insert(true);
+        assert!(enforced.rejects_secure_from(&url("http://example.com")));
+        assert!(!enforced.rejects_secure_from(&url("http://api.internal")));
+    }
+}
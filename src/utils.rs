@@ -1,28 +1,29 @@
+use percent_encoding::{AsciiSet, CONTROLS};
+use std::borrow::Cow;
 use std::net::{Ipv4Addr, Ipv6Addr};
-use url::Url;
-use url::{Host, ParseError as UrlError};
+use url::{Host, Url};
 
-pub trait IntoUrl {
-    fn into_url(self) -> Result<Url, UrlError>;
-}
+/// The characters percent-encoded in cookie names and values; those outside of the `cookie-octet`
+/// production of [RFC6265](https://tools.ietf.org/html/rfc6265#section-4.1.1), plus `%` itself
+const COOKIE_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'%')
+    .add(b',')
+    .add(b';')
+    .add(b'\\');
 
-impl IntoUrl for Url {
-    fn into_url(self) -> Result<Url, UrlError> {
-        Ok(self)
-    }
+/// Percent-encode `s` for use as a cookie name or value
+pub fn percent_encode_cookie(s: &str) -> Cow<'_, str> {
+    percent_encoding::utf8_percent_encode(s, COOKIE_ENCODE_SET).into()
 }
 
-impl<'a> IntoUrl for &'a str {
-    fn into_url(self) -> Result<Url, UrlError> {
-        Url::parse(self)
-    }
-}
-
-impl<'a> IntoUrl for &'a String {
-    fn into_url(self) -> Result<Url, UrlError> {
-        Url::parse(self)
-    }
-}
+/// A temporary buffer of (potentially sensitive) serialized cookies; with feature `zeroize`, its
+/// contents are wiped on drop
+#[cfg(feature = "zeroize")]
+pub(crate) type Buffer = zeroize::Zeroizing<String>;
+#[cfg(not(feature = "zeroize"))]
+pub(crate) type Buffer = String;
 
 pub fn is_http_scheme(url: &Url) -> bool {
     url.scheme().starts_with("http")
@@ -33,9 +34,10 @@ pub fn is_host_name(host: &str) -> bool {
 }
 
 pub fn is_secure(url: &Url) -> bool {
-    if url.scheme() == "https" {
-        return true;
-    }
+    url.scheme() == "https" || is_loopback(url)
+}
+
+pub fn is_loopback(url: &Url) -> bool {
     if let Some(u) = url.host() {
         match u {
             Host::Domain(d) => d == "localhost",
@@ -47,42 +49,72 @@ pub fn is_secure(url: &Url) -> bool {
     }
 }
 
-#[cfg(test)]
-pub mod test {
-    use crate::cookie::Cookie;
-    use time::{Duration, OffsetDateTime};
-    use url::Url;
-    #[inline]
-    pub fn url(url: &str) -> Url {
-        Url::parse(url).unwrap()
-    }
-    #[inline]
-    pub fn make_cookie<'a>(
-        cookie: &str,
-        url_str: &str,
-        expires: Option<OffsetDateTime>,
-        max_age: Option<u64>,
-    ) -> Cookie<'a> {
-        Cookie::parse(
-            format!(
-                "{}{}{}",
-                cookie,
-                expires.map_or(String::from(""), |e| format!(
-                    "; Expires={}",
-                    e.format(time::macros::format_description!("[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT")).unwrap()
-                )),
-                max_age.map_or(String::from(""), |m| format!("; Max-Age={}", m))
-            ),
-            &url(url_str),
-        )
-        .unwrap()
-    }
-    #[inline]
-    pub fn in_days(days: i64) -> OffsetDateTime {
-        OffsetDateTime::now_utc() + Duration::days(days)
+/// Returns true if `s` matches the glob `pattern`, in which `*` matches any (possibly empty)
+/// sequence of characters and `?` matches any single character.
+pub fn is_glob_match(pattern: &str, s: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let s = s.chars().collect::<Vec<_>>();
+    let (mut p, mut i) = (0, 0);
+    // the position of the last `*` seen in `pattern`, and the position in `s` it was matched at
+    let mut backtrack = None;
+    while i < s.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, i));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == s[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match backtrack {
+                // let the last `*` consume one more character
+                Some((star, matched)) => {
+                    p = star + 1;
+                    i = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
     }
-    #[inline]
-    pub fn in_minutes(mins: i64) -> OffsetDateTime {
-        OffsetDateTime::now_utc() + Duration::minutes(mins)
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Generate an arbitrary string of `len` characters drawn from `alphabet`
+#[cfg(feature = "arbitrary")]
+pub fn arbitrary_string(
+    u: &mut arbitrary::Unstructured<'_>,
+    alphabet: &[u8],
+    len: std::ops::RangeInclusive<usize>,
+) -> arbitrary::Result<String> {
+    let len = u.int_in_range(len)?;
+    (0..len)
+        .map(|_| u.choose(alphabet).map(|&c| char::from(c)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_glob_match;
+
+    #[test]
+    fn glob_match() {
+        assert!(is_glob_match("example.com", "example.com"));
+        assert!(!is_glob_match("example.com", "www.example.com"));
+        assert!(is_glob_match("*.example.com", "www.example.com"));
+        assert!(is_glob_match("*.example.com", "a.b.example.com"));
+        assert!(!is_glob_match("*.example.com", "example.com"));
+        assert!(!is_glob_match("*.example.com", "fooexample.com"));
+        assert!(is_glob_match("*example.com", "fooexample.com"));
+        assert!(is_glob_match("www.example.*", "www.example.org"));
+        assert!(is_glob_match("*", ""));
+        assert!(is_glob_match("/foo/*", "/foo/bar/bus"));
+        assert!(is_glob_match("/foo/*/bus", "/foo/bar/bus"));
+        assert!(!is_glob_match("/foo/*/bus", "/foo/bar/bar"));
+        assert!(is_glob_match("/f?o", "/foo"));
+        assert!(!is_glob_match("/f?o", "/fo"));
+        assert!(is_glob_match("**a*a", "aaaa"));
     }
 }
+